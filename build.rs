@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+
+#[path = "src/blue_noise.rs"]
+mod blue_noise;
+#[allow(dead_code)]
+#[path = "src/hash.rs"]
+mod hash;
+
+use blue_noise::{blue_noise_mask, BLUE_NOISE_SIZE};
+
+// Building the blue noise mask takes seconds, too slow for every Ctx. The ranks are stored
+// as little endian u16 and included by src/sampler.rs.
+fn main() {
+    println!("cargo:rerun-if-changed=src/blue_noise.rs");
+    println!("cargo:rerun-if-changed=src/hash.rs");
+    let count = (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as f32;
+    let ranks: Vec<u8> = blue_noise_mask(BLUE_NOISE_SIZE, 0)
+        .iter()
+        .flat_map(|value| ((value * count) as u16).to_le_bytes())
+        .collect();
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("blue_noise.bin"), ranks).unwrap();
+}
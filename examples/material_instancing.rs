@@ -8,6 +8,7 @@ use renderer::{
     ctx::Ctx,
     math::{Vec2, Vec3},
     mesh_resource::MeshResource,
+    render_settings::RenderSettings,
     scene::Scene,
 };
 use vk_utils::vulkan::Vulkan;
@@ -111,7 +112,8 @@ pub fn create_cube() -> MeshResource {
         vertices,
         normals,
         tangents,
-        tex_coords,
+        tex_coords: vec![tex_coords],
+        colors: None,
     }
 }
 
@@ -176,7 +178,7 @@ fn main() {
     scene.add_instance(floor_instance);
 
     let frame = ctx.build_frame_resources(&framebuffer, &scene);
-    ctx.render_frame(&mut framebuffer, &frame, 128, 4);
+    ctx.render_frame(&mut framebuffer, &frame, 128, 4, &RenderSettings::default());
     let image_data = framebuffer.download_output();
     image::save_buffer(
         "Instance Cubes Materials.png",
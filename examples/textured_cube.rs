@@ -10,6 +10,7 @@ use renderer::{
     image_resource::TextureImageData,
     math::{Vec2, Vec3, Vec4},
     mesh_resource::MeshResource,
+    render_settings::RenderSettings,
     scene::Scene,
     vk::Format,
 };
@@ -114,7 +115,8 @@ pub fn create_cube() -> MeshResource {
         vertices,
         normals,
         tangents,
-        tex_coords,
+        tex_coords: vec![tex_coords],
+        colors: None,
     }
 }
 
@@ -176,7 +178,7 @@ fn main() {
     scene.add_instance(floor_instance);
 
     let frame = ctx.build_frame_resources(&framebuffer, &scene);
-    ctx.render_frame(&mut framebuffer, &frame, 128, 4);
+    ctx.render_frame(&mut framebuffer, &frame, 128, 4, &RenderSettings::default());
     let image_data = framebuffer.download_output();
     image::save_buffer(
         "Textured Cube.png",
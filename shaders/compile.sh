@@ -0,0 +1,9 @@
+#!/bin/sh
+# Regenerates the SPIR-V next to every shader source. The pipelines load the .spv files
+# at runtime, so run this and commit the results after editing a shader.
+set -e
+cd "$(dirname "$0")"
+for shader in simple_pipeline/*.rgen simple_pipeline/*.rchit simple_pipeline/*.rmiss \
+    simple_pipeline/*.comp; do
+    glslangValidator --target-env vulkan1.2 -V "$shader" -o "$shader.spv"
+done
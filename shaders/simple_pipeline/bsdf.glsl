@@ -37,38 +37,59 @@ float G_Smith(float NoV, float NoL, float roughness) {
   return g1_l * g1_v;
 }
 
-// vec3 microfacetBRDF(in vec3 L, in vec3 V, in vec3 N, 
-//               in vec3 baseColor, in float metallicness, 
-//               in float fresnelReflect, in float roughness,
-//               in float transmission, in float ior) {
+// Reflection only, used for explicit light sampling
+vec3 evalMicrofacetBRDF(in vec3 L, in vec3 V, in vec3 N, 
+              in vec3 baseColor, in float metallicness, 
+              in float fresnelReflect, in float roughness,
+              in float transmission) {
      
-//   vec3 H = normalize(V + L); // half vector
+  vec3 H = normalize(V + L); // half vector
 
-//   // all required dot products
-//   float NoV = clamp(dot(N, V), 0.0, 1.0);
-//   float NoL = clamp(dot(N, L), 0.0, 1.0);
-//   float NoH = clamp(dot(N, H), 0.0, 1.0);
-//   float VoH = clamp(dot(V, H), 0.0, 1.0);     
+  // all required dot products
+  float NoV = clamp(dot(N, V), 0.0, 1.0);
+  float NoL = clamp(dot(N, L), 0.0, 1.0);
+  float NoH = clamp(dot(N, H), 0.0, 1.0);
+  float VoH = clamp(dot(V, H), 0.0, 1.0);     
   
-//   // F0 for dielectics in range [0.0, 0.16] 
-//   // default FO is (0.16 * 0.5^2) = 0.04
-//   vec3 f0 = vec3(0.16 * (fresnelReflect * fresnelReflect)); 
-//   // in case of metals, baseColor contains F0
-//   f0 = mix(f0, baseColor, metallicness);
+  // F0 for dielectics in range [0.0, 0.16] 
+  // default FO is (0.16 * 0.5^2) = 0.04
+  vec3 f0 = vec3(0.16 * (fresnelReflect * fresnelReflect)); 
+  // in case of metals, baseColor contains F0
+  f0 = mix(f0, baseColor, metallicness);
 
-//   // specular microfacet (cook-torrance) BRDF
-//   vec3 F = fresnelSchlick(VoH, f0);
-//   float D = D_GGX(NoH, roughness);
-//   float G = G_Smith(NoV, NoL, roughness);
-//   vec3 spec = (D * G * F) / max(4.0 * NoV * NoL, 0.001);
+  // specular microfacet (cook-torrance) BRDF
+  vec3 F = fresnelSchlick(VoH, f0);
+  float D = D_GGX(NoH, roughness);
+  float G = G_Smith(NoV, NoL, roughness);
+  vec3 spec = (D * G * F) / max(4.0 * NoV * NoL, 0.001);
   
-//   // diffuse
-//   vec3 notSpec = vec3(1.0) - F; // if not specular, use as diffuse
-//   notSpec *= (1.0 - metallicness) * (1.0 - transmission); // no diffuse for metals
-//   vec3 diff = notSpec * baseColor / M_PI; 
+  // diffuse
+  vec3 notSpec = vec3(1.0) - F; // if not specular, use as diffuse
+  notSpec *= (1.0 - metallicness) * (1.0 - transmission); // no diffuse for metals
+  vec3 diff = notSpec * baseColor / M_PI; 
   
-//   return diff + spec;
-// }
+  return diff + spec;
+}
+
+// Pdf of sampleMicrofacetBRDF for reflected directions
+float pdfMicrofacetBRDF(in vec3 L, in vec3 V, in vec3 N, 
+              in float roughness, in float transmission) {
+  float NoL = dot(N, L);
+  if(NoL <= 0.0) {
+    return 0.0;
+  }
+
+  vec3 H = normalize(V + L);
+  float NoH = clamp(dot(N, H), 0.0, 1.0);
+  float VoH = clamp(dot(V, H), 0.0, 1.0);
+
+  // cosine weighted diffuse and GGX distributed half vectors
+  float diffusePdf = NoL / M_PI;
+  float specularPdf = D_GGX(NoH, roughness) * NoH / max(4.0 * VoH, 0.001);
+
+  // same lobe selection as below, transmission never reflects
+  return 0.5 * (1.0 - transmission) * diffusePdf + 0.5 * specularPdf;
+}
 
 vec3 sampleMicrofacetBRDF(in vec3 V, in vec3 N, in vec3 baseColor, in float metallicness, 
               in float fresnelReflect, in float roughness, in float transmission, 
@@ -0,0 +1,15 @@
+// Scene buffers, uploaded per frame by Ctx::upload_frame
+layout(set = 1, binding = 1) uniform BufferAddressBuffer {
+    uint64_t material_address;
+    uint64_t instance_properties_address;
+    uint64_t light_address;
+    uint64_t light_count;
+    uint64_t emissive_triangle_address;
+    uint64_t emissive_triangle_count;
+    uint64_t environment_address;
+    uint64_t environment_width;
+    uint64_t environment_height;
+    // Tables of the low discrepancy samplers, see sampler.glsl
+    uint64_t sobol_address;
+    uint64_t blue_noise_address;
+};
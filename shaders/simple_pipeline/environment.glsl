@@ -0,0 +1,91 @@
+// Environment map importance sampling, the data is built by EnvironmentDistribution:
+// marginal CDF (height + 1), conditional CDFs (height * (width + 1)), pdf table (width * height)
+layout(buffer_reference, scalar) readonly buffer EnvironmentData { float data[]; };
+
+layout(set = 1, binding = 4) uniform sampler2D skybox_image;
+layout(set = 1, binding = 5) uniform SkyBoxProperties {
+    mat4 skybox_rotation; // skybox to world
+    float skybox_intensity;
+    uint skybox_background_visible;
+    uint skybox_gamma_encoded;
+    uint skybox_cube_map; // sample skybox_cube instead of skybox_image
+};
+layout(set = 1, binding = 6) uniform samplerCube skybox_cube;
+
+vec2 direction_to_spherical(vec3 dir){
+	float s = fract(1.0 / (2.0*M_PI) * atan(dir.y, -dir.x));
+  	float t = 1.0 / (M_PI) * acos(-dir.z);
+  	return vec2(s, t);
+}
+
+vec3 spherical_to_direction(vec2 st){
+	float phi = st.x * 2.0 * M_PI;
+	float theta = st.y * M_PI;
+	return vec3(-sin(theta) * cos(phi), sin(theta) * sin(phi), -cos(theta));
+}
+
+vec3 world_to_skybox(vec3 dir){
+	return transpose(mat3(skybox_rotation)) * dir;
+}
+
+vec3 skybox_radiance(vec3 dir){
+	vec3 c;
+	if(skybox_cube_map != 0)
+		c = texture(skybox_cube, world_to_skybox(dir)).rgb;
+	else
+		c = texture(skybox_image, direction_to_spherical(world_to_skybox(dir))).rgb;
+	if(skybox_gamma_encoded != 0)
+		c = pow(c, vec3(2.2));
+	return c * skybox_intensity;
+}
+
+// Finds the interval of the count + 1 entry CDF at offset that contains u
+uint sample_cdf(EnvironmentData env, uint offset, uint count, float u, out float du)
+{
+    uint lo = 0;
+    uint hi = count;
+    while(hi - lo > 1)
+    {
+        uint mid = (lo + hi) / 2;
+        if(env.data[offset + mid] <= u)
+            lo = mid;
+        else
+            hi = mid;
+    }
+
+    const float a = env.data[offset + lo];
+    const float b = env.data[offset + lo + 1];
+    du = b > a ? clamp((u - a) / (b - a), 0.0, 1.0) : 0.0;
+    return lo;
+}
+
+float environment_pdf_texel(EnvironmentData env, uint width, uint height, uint x, uint y, float sin_theta)
+{
+    if(sin_theta <= 0.0)
+        return 0.0;
+
+    const uint pdf_offset = (height + 1) + height * (width + 1);
+    return env.data[pdf_offset + y * width + x] / (2.0 * M_PI * M_PI * sin_theta);
+}
+
+// Solid angle pdf of sampling dir
+float environment_pdf(uint64_t address, uint width, uint height, vec3 dir)
+{
+    EnvironmentData env = EnvironmentData(address);
+    const vec2 st = direction_to_spherical(world_to_skybox(dir));
+    const uint x = min(uint(st.x * width), width - 1);
+    const uint y = min(uint(st.y * height), height - 1);
+    return environment_pdf_texel(env, width, height, x, y, sin(st.y * M_PI));
+}
+
+vec3 sample_environment(uint64_t address, uint width, uint height, vec2 xi, out float pdf)
+{
+    EnvironmentData env = EnvironmentData(address);
+    float dy;
+    const uint y = sample_cdf(env, 0, height, xi.y, dy);
+    float dx;
+    const uint x = sample_cdf(env, height + 1 + y * (width + 1), width, xi.x, dx);
+    const vec2 st = vec2((float(x) + dx) / float(width), (float(y) + dy) / float(height));
+    pdf = environment_pdf_texel(env, width, height, x, y, sin(st.y * M_PI));
+    return mat3(skybox_rotation) * spherical_to_direction(st);
+}
@@ -0,0 +1,80 @@
+const uint LIGHT_POINT = 0;
+const uint LIGHT_SPOT = 1;
+const uint LIGHT_DIRECTIONAL = 2;
+const uint LIGHT_AREA = 3;
+
+struct Light
+{
+    vec3 position; // corner of the quad for area lights
+    uint type;
+    vec3 direction;
+    float cos_inner; // cone radius for directional lights
+    vec3 radiance;
+    float cos_outer;
+    vec3 edge_u;
+    float area;
+    vec3 edge_v;
+    float padding;
+};
+
+struct EmissiveTriangle
+{
+    vec3 v0; // world space
+    uint instance;
+    vec3 v1;
+    uint primitive;
+    vec3 v2;
+    float pdf; // probability of picking this triangle
+    float threshold; // alias table
+    uint alias;
+};
+
+// Samples a direction towards the light as seen from P.
+// Returns false if the light can't contribute, otherwise
+// radiance holds the incident radiance divided by the sampling pdf.
+bool sample_light(Light light, vec3 P, vec2 xi, out vec3 L, out float dist, out vec3 radiance)
+{
+    if(light.type == LIGHT_POINT || light.type == LIGHT_SPOT)
+    {
+        vec3 d = light.position - P;
+        float dist2 = max(dot(d, d), 1e-8);
+        dist = sqrt(dist2);
+        L = d / dist;
+        radiance = light.radiance / dist2;
+        if(light.type == LIGHT_SPOT)
+        {
+            radiance *= smoothstep(light.cos_outer, light.cos_inner, dot(-L, light.direction));
+        }
+    }
+    else if(light.type == LIGHT_DIRECTIONAL)
+    {
+        dist = 1e30;
+        L = -light.direction;
+        if(light.cos_inner < 1.0)
+        {
+            // Uniform cone sampling, the pdf cancels against the solid angle
+            float cos_theta = 1.0 - xi.x * (1.0 - light.cos_inner);
+            float sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+            float phi = 2.0 * M_PI * xi.y;
+            L = getNormalSpace(L) * vec3(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+        }
+        radiance = light.radiance;
+    }
+    else
+    {
+        vec3 point = light.position + xi.x * light.edge_u + xi.y * light.edge_v;
+        vec3 d = point - P;
+        float dist2 = max(dot(d, d), 1e-8);
+        dist = sqrt(dist2);
+        L = d / dist;
+        float cos_light = dot(-L, light.direction);
+        if(cos_light <= 0.0)
+        {
+            return false;
+        }
+        // Area to solid angle pdf conversion
+        radiance = light.radiance * cos_light * light.area / dist2;
+    }
+
+    return dot(radiance, radiance) > 0.0;
+}
@@ -1,3 +1,22 @@
+const int NORMAL_MAP_OPENGL = 0;
+const int NORMAL_MAP_DIRECTX = 1;
+
+const int BASE_COLOR_SLOT = 0;
+const int METALLIC_ROUGHNESS_SLOT = 1;
+const int NORMAL_SLOT = 2;
+const int EMISSION_SLOT = 3;
+const int OCCLUSION_SLOT = 4;
+
+const int MAX_TEX_COORD_SETS = 2;
+
+struct TextureTransform
+{
+    vec2 offset;
+    vec2 scale;
+    float rotation;
+    uint tex_coord;
+};
+
 struct Material
 {
     vec4 base_color;
@@ -7,10 +26,27 @@ struct Material
     // float sheen;
     // float clear_coat;
     vec4 properties;
+    // float ior;
+    // float transmission;
+    vec2 transmission;
     // int base_color_texture;
     // int metallic_roughness_texture;
     // int normal_texture;
     // int emission_texture;
-    vec2 transmission;
     ivec4 maps;
-};
\ No newline at end of file
+    int occlusion_map;
+    float normal_scale;
+    float occlusion_strength;
+    int normal_map_convention;
+    TextureTransform transforms[5];
+};
+
+// KHR_texture_transform: scale, rotate, then translate
+vec2 texture_slot_uv(Material material, int slot, vec2 uv_sets[MAX_TEX_COORD_SETS])
+{
+    TextureTransform t = material.transforms[slot];
+    vec2 uv = uv_sets[min(int(t.tex_coord), MAX_TEX_COORD_SETS - 1)] * t.scale;
+    float c = cos(t.rotation);
+    float s = sin(t.rotation);
+    return vec2(c * uv.x + s * uv.y, -s * uv.x + c * uv.y) + t.offset;
+}
@@ -0,0 +1,7 @@
+// Multiple importance sampling weight of strategy a against strategy b, one sample each
+float power_heuristic(float a, float b)
+{
+    float a2 = a * a;
+    float b2 = b * b;
+    return a2 / max(a2 + b2, 1e-20);
+}
@@ -11,8 +11,15 @@ float radicalInverse(uint bits) {
   return float(bits) * 2.3283064365386963e-10; // / 0x100000000
 }
 
-vec2 hammersley(uint n, uint N) {
-  return vec2((float(n) + 0.5) / float(N), radicalInverse(n + 1u));
+// First two dimensions of the Sobol sequence, a (0, 2)-sequence: every power of two
+// prefix is stratified, without a fixed sample count like Hammersley needs
+vec2 sobol2d(uint n) {
+  uint y = 0u;
+  for(uint i = n, v = 1u << 31; i != 0u; i >>= 1u, v ^= v >> 1u) {
+    if((i & 1u) != 0u)
+      y ^= v;
+  }
+  return vec2(radicalInverse(n), float(y) * 2.3283064365386963e-10);
 }
 
 // Hash Functions for GPU Rendering, Jarzynski et al.
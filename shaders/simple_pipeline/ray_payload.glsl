@@ -1,4 +1,9 @@
 
+// Lobe sampled for the next bounce
+const uint RAY_LOBE_DIFFUSE = 0;
+const uint RAY_LOBE_SPECULAR = 1;
+const uint RAY_LOBE_TRANSMISSION = 2;
+
 struct RayPayload
 {
     vec4 color;
@@ -8,5 +13,12 @@ struct RayPayload
     vec3 w_out;
     vec3 normal;
     bool hit;
+    float pdf; // solid angle pdf of the ray direction, 0 for camera rays and non MIS lobes
     uint seed;
+    uint sample_index; // of the path in its pixel, see sampler.glsl
+    uint depth; // number of bounces before this ray, 0 for camera rays
+    vec3 albedo; // first hit data for the aov images
+    uint instance_id;
+    uint material_id;
+    uint lobe; // RAY_LOBE_* of w_out
 };
\ No newline at end of file
@@ -0,0 +1,32 @@
+// Push constants of the ray tracing pipeline, shared by the ray generation and closest
+// hit shaders. The settings half mirrors RenderSettings in src/render_settings.rs.
+layout( push_constant ) uniform constants
+{
+  int32_t spp;
+  int32_t current_batch;
+  uint32_t aov_mask; // bit per AovKind
+  float noise_threshold; // 0 disables adaptive sampling
+  uint32_t min_samples;
+  // Region of the full image covered by this launch, see FrameBuffer::set_region
+  uint32_t offset_x;
+  uint32_t offset_y;
+  uint32_t image_width;
+  uint32_t image_height;
+
+  uint32_t max_depth;
+  uint32_t max_diffuse_depth;
+  uint32_t max_specular_depth;
+  uint32_t max_transmission_depth;
+  uint32_t russian_roulette_depth;
+  float firefly_clamp; // 0 disables clamping
+  float ray_epsilon;
+  float far_distance;
+  uint32_t seed;
+  uint32_t sampler_kind; // SAMPLER_* in sampler.glsl
+} Batch;
+
+// Distance new rays start away from the surface they leave
+float surface_offset()
+{
+  return Batch.ray_epsilon * 0.1;
+}
@@ -0,0 +1,100 @@
+// Sample generators, mirrors src/sampler.rs. Needs render_settings.glsl, random.glsl and
+// buffer_addresses.glsl included before it.
+
+// RenderSettings::sampler
+const uint SAMPLER_INDEPENDENT = 0;
+const uint SAMPLER_SOBOL = 1;
+const uint SAMPLER_BLUE_NOISE_SOBOL = 2;
+
+const uint SOBOL_DIMENSIONS = 16;
+const uint SOBOL_BITS = 32;
+const uint BLUE_NOISE_SIZE = 64;
+
+// Dimensions of a path, the pixel position first and a fixed block per bounce after it
+const uint DIMENSION_PIXEL = 0;
+const uint DIMENSION_BOUNCE = 2;
+const uint DIMENSIONS_PER_BOUNCE = 12;
+// Offsets inside the block of a bounce
+const uint DIMENSION_BSDF = 0;
+const uint DIMENSION_LIGHT = 3;
+const uint DIMENSION_EMITTER = 6;
+const uint DIMENSION_ENVIRONMENT = 9;
+const uint DIMENSION_ROULETTE = 11;
+
+layout(buffer_reference, scalar) readonly buffer SobolMatrices { uint data[]; };
+layout(buffer_reference, scalar) readonly buffer BlueNoiseMask { float data[]; };
+
+uint sampler_hash(uint x)
+{
+  x ^= x >> 16;
+  x *= 0x7feb352du;
+  x ^= x >> 15;
+  x *= 0x846ca68bu;
+  x ^= x >> 16;
+  return x;
+}
+
+uint hash_combine(uint seed, uint v)
+{
+  return seed ^ (v + (seed << 6) + (seed >> 2));
+}
+
+uint owen_scramble(uint x, uint seed)
+{
+  x = bitfieldReverse(x);
+  x += seed;
+  x ^= x * 0x6c50b47cu;
+  x ^= x * 0xb82f1e52u;
+  x ^= x * 0xc7afe638u;
+  x ^= x * 0x8d22f6e6u;
+  return bitfieldReverse(x);
+}
+
+uint sobol(uint index, uint dimension)
+{
+  SobolMatrices matrices = SobolMatrices(sobol_address);
+  uint result = 0;
+  for(uint bit = 0; index != 0; ++bit, index >>= 1)
+  {
+    if((index & 1) != 0)
+      result ^= matrices.data[dimension * SOBOL_BITS + bit];
+  }
+  return result;
+}
+
+float to_unit_float(uint x)
+{
+  return float(x >> 8) / 16777216.0;
+}
+
+// Pixel in the full image, tiles and crop windows only launch part of it
+uvec2 image_pixel()
+{
+  return gl_LaunchIDEXT.xy + uvec2(Batch.offset_x, Batch.offset_y);
+}
+
+// One dimension of a sample in [0, 1). The independent sampler ignores the index and
+// dimension and draws from seed instead, the others leave seed alone.
+float sample_dimension(uint sample_index, uint dimension, inout uint seed)
+{
+  if(Batch.sampler_kind == SAMPLER_INDEPENDENT)
+    return rand_float(seed);
+
+  const uvec2 pixel = image_pixel();
+  const bool blue_noise = Batch.sampler_kind == SAMPLER_BLUE_NOISE_SOBOL;
+  const uint scramble = blue_noise
+    ? sampler_hash(Batch.seed)
+    : sampler_hash(hash_combine(hash_combine(sampler_hash(Batch.seed), pixel.x), pixel.y));
+  const uint shuffled = owen_scramble(sample_index, hash_combine(scramble, dimension / SOBOL_DIMENSIONS));
+  const uint value = owen_scramble(sobol(shuffled, dimension % SOBOL_DIMENSIONS), hash_combine(scramble, dimension + 1));
+  float u = to_unit_float(value);
+  if(blue_noise)
+  {
+    BlueNoiseMask mask = BlueNoiseMask(blue_noise_address);
+    const uint offset = sampler_hash(dimension);
+    const uint x = (pixel.x + (offset & 0xff)) % BLUE_NOISE_SIZE;
+    const uint y = (pixel.y + ((offset >> 8) & 0xff)) % BLUE_NOISE_SIZE;
+    u = fract(u + mask.data[y * BLUE_NOISE_SIZE + x]);
+  }
+  return u;
+}
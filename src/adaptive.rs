@@ -0,0 +1,109 @@
+use crate::emissive::luminance;
+use crate::math::Vec3;
+
+// Stops sampling a pixel once the standard error of its mean luminance drops below
+// noise_threshold times the mean. Checked at the start of every pass in ray_gen.rgen.
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveSampling {
+    // Relative standard error, 0.01 is about 1% noise. 0 samples every pixel every pass.
+    pub noise_threshold: f32,
+    // Samples every pixel takes before it can be considered converged
+    pub min_samples: u32,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            noise_threshold: 0.01,
+            min_samples: 16,
+        }
+    }
+}
+
+impl AdaptiveSampling {
+    // Samples every pixel on every pass
+    pub fn disabled() -> Self {
+        Self {
+            noise_threshold: 0.0,
+            min_samples: 0,
+        }
+    }
+
+    pub fn new(noise_threshold: f32) -> Self {
+        Self {
+            noise_threshold,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_min_samples(mut self, min_samples: u32) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.noise_threshold > 0.0
+    }
+
+    // From the summed radiance, the summed squared luminance and the sample count
+    pub fn is_converged(&self, sum: Vec3, sum_squares: f32, count: u32) -> bool {
+        if !self.is_enabled() || count < self.min_samples.max(2) {
+            return false;
+        }
+
+        relative_error(sum, sum_squares, count) <= self.noise_threshold
+    }
+}
+
+// Standard error of the mean luminance relative to that mean, the noise measure of
+// adaptive sampling. Dark pixels are measured against a mean of at least 1e-3.
+pub fn relative_error(sum: Vec3, sum_squares: f32, count: u32) -> f32 {
+    let mean = luminance(sum) / count as f32;
+    standard_error(mean, sum_squares, count) / mean.max(1e-3)
+}
+
+// Average relative error over the pixels with at least two samples, from the raw rgba
+// accumulation image and the moment image. None before any pixel has two samples.
+pub fn mean_relative_error(accumulation: &[f32], moments: &[f32]) -> Option<f32> {
+    let (sum, count) = accumulation
+        .chunks_exact(4)
+        .zip(moments)
+        .filter(|(p, _)| p[3] >= 2.0)
+        .fold((0.0, 0), |(sum, count), (p, moment)| {
+            let error = relative_error(Vec3::new(p[0], p[1], p[2]), *moment, p[3] as u32);
+            (sum + error, count + 1)
+        });
+    if count > 0 {
+        Some(sum / count as f32)
+    } else {
+        None
+    }
+}
+
+// Standard error of the mean luminance, with the unbiased sample variance
+pub fn standard_error(mean: f32, sum_squares: f32, count: u32) -> f32 {
+    let n = count as f32;
+    let variance = (sum_squares / n - mean * mean).max(0.0) * n / (n - 1.0);
+    (variance / n).sqrt()
+}
+
+// Summary of FrameBuffer::download_sample_counts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampleCountStats {
+    pub min: u32,
+    pub max: u32,
+    pub average: f32,
+    pub total: u64,
+}
+
+impl SampleCountStats {
+    pub fn new(counts: &[u32]) -> Self {
+        let total: u64 = counts.iter().map(|c| *c as u64).sum();
+        Self {
+            min: counts.iter().copied().min().unwrap_or(0),
+            max: counts.iter().copied().max().unwrap_or(0),
+            average: total as f32 / counts.len().max(1) as f32,
+            total,
+        }
+    }
+}
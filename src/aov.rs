@@ -0,0 +1,101 @@
+use ash::vk::Format;
+
+use crate::math::Vec3;
+
+pub const AOV_COUNT: usize = 6;
+
+// First hit data written next to the beauty pass. Float AOVs are averaged over all
+// samples, the ids come from the first sample of the first pass.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AovKind {
+    // Linear base color, including textures and vertex colors
+    Albedo,
+    // World space shading normal
+    Normal,
+    // Distance along the camera's viewing direction
+    Depth,
+    // World space position
+    Position,
+    // Index into Scene::instances
+    InstanceId,
+    // Index into CpuResources::material_handles
+    MaterialId,
+}
+
+impl AovKind {
+    pub const ALL: [AovKind; AOV_COUNT] = [
+        AovKind::Albedo,
+        AovKind::Normal,
+        AovKind::Depth,
+        AovKind::Position,
+        AovKind::InstanceId,
+        AovKind::MaterialId,
+    ];
+
+    pub fn format(&self) -> Format {
+        match self {
+            AovKind::Albedo | AovKind::Normal | AovKind::Position => Format::R32G32B32A32_SFLOAT,
+            AovKind::Depth => Format::R32_SFLOAT,
+            AovKind::InstanceId | AovKind::MaterialId => Format::R32_UINT,
+        }
+    }
+
+    pub fn texel_size(&self) -> usize {
+        match self.format() {
+            Format::R32G32B32A32_SFLOAT => 16,
+            _ => 4,
+        }
+    }
+
+    // Channel names used by HdrImage::write_exr
+    pub fn exr_channels(&self) -> &'static [&'static str] {
+        match self {
+            AovKind::Albedo => &["albedo.R", "albedo.G", "albedo.B"],
+            AovKind::Normal => &["normal.X", "normal.Y", "normal.Z"],
+            AovKind::Depth => &["Z"],
+            AovKind::Position => &["position.X", "position.Y", "position.Z"],
+            AovKind::InstanceId => &["instance_id"],
+            AovKind::MaterialId => &["material_id"],
+        }
+    }
+
+    // Bit in the aov mask push constant
+    pub fn mask(&self) -> u32 {
+        1 << (*self as u32)
+    }
+}
+
+// Pixels in row major order, ids are u32::MAX where the camera ray missed
+pub enum AovData {
+    Scalar(Vec<f32>),
+    Vector(Vec<Vec3>),
+    Id(Vec<u32>),
+}
+
+impl AovData {
+    pub(crate) fn from_bytes(kind: AovKind, bytes: &[u8]) -> Self {
+        let floats = || {
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        match kind {
+            AovKind::Albedo | AovKind::Normal | AovKind::Position => {
+                let values: Vec<f32> = floats().collect();
+                AovData::Vector(
+                    values
+                        .chunks_exact(4)
+                        .map(|v| Vec3::new(v[0], v[1], v[2]))
+                        .collect(),
+                )
+            }
+            AovKind::Depth => AovData::Scalar(floats().collect()),
+            AovKind::InstanceId | AovKind::MaterialId => AovData::Id(
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+// Blue noise mask of the BlueNoiseSobol sampler. build.rs includes this file and hash.rs
+// to build the mask at compile time, so it can't depend on the rest of the crate.
+
+use crate::hash::hash;
+
+// Width and height of the tiling blue noise mask
+pub const BLUE_NOISE_SIZE: u32 = 64;
+
+// Blue noise dither mask in [0, 1), void and cluster method by Ulichney 1993
+pub fn blue_noise_mask(size: u32, seed: u32) -> Vec<f32> {
+    let size = size as usize;
+    let count = size * size;
+    const SIGMA: f32 = 1.5;
+
+    // Gaussian energy of a point at every toroidal offset
+    let kernel: Vec<f32> = (0..count)
+        .map(|i| {
+            let wrap = |d: usize| d.min(size - d) as f32;
+            let (dx, dy) = (wrap(i % size), wrap(i / size));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let update = |energy: &mut [f32], point: usize, sign: f32| {
+        let (px, py) = (point % size, point / size);
+        for y in 0..size {
+            let dy = (y + size - py) % size;
+            for x in 0..size {
+                let dx = (x + size - px) % size;
+                energy[y * size + x] += sign * kernel[dy * size + dx];
+            }
+        }
+    };
+    // Tightest cluster among the set pixels or largest void among the empty ones
+    let extreme = |energy: &[f32], pattern: &[bool], set: bool| -> usize {
+        let candidates = (0..count).filter(|i| pattern[*i] == set);
+        if set {
+            candidates
+                .max_by(|a, b| energy[*a].total_cmp(&energy[*b]))
+                .unwrap()
+        } else {
+            candidates
+                .min_by(|a, b| energy[*a].total_cmp(&energy[*b]))
+                .unwrap()
+        }
+    };
+
+    // Random initial pattern with a tenth of the pixels set, relaxed by moving the point
+    // of the tightest cluster into the largest void until that stops changing anything
+    let mut pattern = vec![false; count];
+    let mut energy = vec![0.0; count];
+    let mut state = seed;
+    let initial = (count / 10).max(1);
+    let mut placed = 0;
+    while placed < initial {
+        state = state.wrapping_mul(747796405).wrapping_add(2891336453);
+        let point = hash(state) as usize % count;
+        if !pattern[point] {
+            pattern[point] = true;
+            update(&mut energy, point, 1.0);
+            placed += 1;
+        }
+    }
+    for _ in 0..count {
+        let cluster = extreme(&energy, &pattern, true);
+        pattern[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+        let void = extreme(&energy, &pattern, false);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+    // Ranks below the initial pattern, removing the tightest clusters first
+    let mut remaining = pattern.clone();
+    let mut remaining_energy = energy.clone();
+    for rank in (0..initial).rev() {
+        let cluster = extreme(&remaining_energy, &remaining, true);
+        remaining[cluster] = false;
+        update(&mut remaining_energy, cluster, -1.0);
+        ranks[cluster] = rank;
+    }
+    // Up to half, filling the largest voids
+    for rank in initial..count / 2 {
+        let void = extreme(&energy, &pattern, false);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        ranks[void] = rank;
+    }
+    // Past half the empty pixels are the minority, fill their tightest clusters
+    let mut empty = vec![0.0; count];
+    for point in (0..count).filter(|i| !pattern[*i]) {
+        update(&mut empty, point, 1.0);
+    }
+    for rank in count / 2..count {
+        let cluster = (0..count)
+            .filter(|i| !pattern[*i])
+            .max_by(|a, b| empty[*a].total_cmp(&empty[*b]))
+            .unwrap();
+        pattern[cluster] = true;
+        update(&mut empty, cluster, -1.0);
+        ranks[cluster] = rank;
+    }
+
+    ranks
+        .iter()
+        .map(|rank| (*rank as f32 + 0.5) / count as f32)
+        .collect()
+}
@@ -1,7 +1,17 @@
 use cgmath::{perspective, Deg, SquareMatrix};
 
+use crate::filter::{GpuFilterEntry, FILTER_TABLE_SIZE};
 use crate::math::{Mat4, Real, Vec3};
 
+// Layout of the CameraProperties uniform in ray_gen.rgen
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GpuCamera {
+    pub _view: Mat4,
+    pub _projection: Mat4,
+    pub _filter_table: [GpuFilterEntry; FILTER_TABLE_SIZE],
+}
+
 #[derive(Clone, Copy)]
 pub struct Camera {
     fov: Real,
@@ -28,6 +38,24 @@ impl Camera {
         self.transform = self.transform * Mat4::from_translation(t)
     }
 
+    // Vertical field of view in degrees
+    pub fn fov(&self) -> Real {
+        self.fov
+    }
+
+    pub fn z_near(&self) -> Real {
+        self.z_near
+    }
+
+    pub fn z_far(&self) -> Real {
+        self.z_far
+    }
+
+    // World to camera, the inverse of view_matrix, which places the camera in the world
+    pub fn world_transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
     pub fn view_matrix(&self) -> Mat4 {
         self.transform.invert().unwrap()
     }
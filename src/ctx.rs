@@ -1,23 +1,44 @@
 use ash::extensions::khr::AccelerationStructure;
 use ash::extensions::khr::DeferredHostOperations;
 use ash::extensions::khr::RayTracingPipeline;
+use ash::vk::AccessFlags;
+use ash::vk::BufferImageCopy;
 use ash::vk::BufferUsageFlags;
+use ash::vk::DependencyFlags;
+use ash::vk::Extent3D;
 use ash::vk::Filter;
 use ash::vk::Format;
 use ash::vk::GeometryInstanceFlagsKHR;
+use ash::vk::ImageAspectFlags;
+use ash::vk::ImageCreateFlags;
+use ash::vk::ImageCreateInfo;
 use ash::vk::ImageLayout;
+use ash::vk::ImageMemoryBarrier;
+use ash::vk::ImageSubresourceLayers;
+use ash::vk::ImageSubresourceRange;
+use ash::vk::ImageTiling;
+use ash::vk::ImageType;
+use ash::vk::ImageUsageFlags;
 use ash::vk::ImageView;
+use ash::vk::ImageViewCreateInfo;
+use ash::vk::ImageViewType;
 use ash::vk::KhrPortabilitySubsetFn;
+use ash::vk::MemoryAllocateInfo;
 use ash::vk::MemoryPropertyFlags;
 use ash::vk::PhysicalDeviceFeatures2KHR;
 use ash::vk::PipelineBindPoint;
+use ash::vk::PipelineStageFlags;
 use ash::vk::QueueFlags;
+use ash::vk::SampleCountFlags;
 use ash::vk::Sampler;
 use ash::vk::SamplerCreateInfo;
 use ash::vk::ShaderStageFlags;
+use ash::vk::SharingMode;
 use slotmap::DefaultKey;
+use slotmap::SecondaryMap;
 use slotmap::SlotMap;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use vk_utils::buffer_resource::BufferResource;
 use vk_utils::command_buffer::CommandBuffer;
@@ -27,24 +48,44 @@ use vk_utils::image2d_resource::Image2DResource;
 use vk_utils::image_resource::ImageResource;
 use vk_utils::queue::CommandQueue;
 
+use crate::aov::AovKind;
+use crate::aov::AOV_COUNT;
 use crate::camera::Camera;
+use crate::camera::GpuCamera;
+use crate::cubemap::CubeMap;
+use crate::cubemap::FACE_COUNT;
+use crate::denoise_pipeline::DenoisePipeline;
 use crate::descriptor_sets::FrameDescriptors;
+use crate::emissive::EmissiveTriangles;
+use crate::emissive::GpuEmissiveTriangle;
+use crate::environment::EnvironmentDistribution;
 use crate::framebuffer::FrameBuffer;
 use crate::geometry::GeometryInstance;
 use crate::geometry::TopLevelAccelerationStructure;
+use crate::gpu_scene::GpuCubeTexture;
+use crate::gpu_scene::GpuEnvironment;
 use crate::gpu_scene::GpuTexture;
+use crate::hdr_image::HdrImage;
 use crate::image_resource::TextureImageData;
+use crate::light::GpuLight;
+use crate::light::Light;
 use crate::material::GpuMaterial;
 use crate::material::Material;
-use crate::math::Mat4;
+use crate::math::Vec4;
 use crate::mesh::Mesh;
 use crate::mesh::MeshAddress;
 use crate::mesh_instance::MeshInstance;
 use crate::mesh_resource::MeshResource;
+use crate::region::Region;
+use crate::render_settings::RenderSettings;
 use crate::rtx_extensions::RtxExtensions;
 use crate::rtx_pipeline::RtxPipeline;
+use crate::sampler::SamplerTables;
 use crate::scene::Scene;
+use crate::skybox::GpuSkyBox;
 use crate::skybox::SkyBox;
+use crate::skybox::SkyBoxImage;
+use crate::tonemap_pipeline::ToneMapPipeline;
 
 pub type Handle = DefaultKey;
 type Map<V> = SlotMap<Handle, V>;
@@ -53,11 +94,23 @@ pub struct FrameResources {
     pub cpu_resources: CpuResources,
     pub gpu_resources: GpuResources,
     descriptors: Rc<FrameDescriptors>,
+    // FrameBuffer::binding of the images in the descriptors
+    framebuffer: (u64, u64),
+}
+
+impl FrameResources {
+    pub fn is_bound_to(&self, framebuffer: &FrameBuffer) -> bool {
+        self.framebuffer == framebuffer.binding()
+    }
 }
 
 pub struct CpuResources {
     pub gpu_materials: Vec<GpuMaterial>,
+    // Material of every gpu material index, resolves AovKind::MaterialId
+    pub material_handles: Vec<Handle>,
     pub gpu_instances: Vec<GeometryInstance>,
+    pub gpu_lights: Vec<GpuLight>,
+    pub gpu_emissive_triangles: Vec<GpuEmissiveTriangle>,
     pub instance_properties: Vec<InstanceProperties>,
     pub geometry_addresses: Vec<MeshAddress>,
     pub camera: Camera,
@@ -76,6 +129,16 @@ impl CpuResources {
     fn geometry_addresses_size(&self) -> u64 {
         std::mem::size_of::<MeshAddress>() as u64 * self.geometry_addresses.len() as u64
     }
+
+    fn light_size(&self) -> u64 {
+        // Never allocate an empty buffer, the shader skips it when the count is zero
+        std::mem::size_of::<GpuLight>() as u64 * self.gpu_lights.len().max(1) as u64
+    }
+
+    fn emissive_triangle_size(&self) -> u64 {
+        std::mem::size_of::<GpuEmissiveTriangle>() as u64
+            * self.gpu_emissive_triangles.len().max(1) as u64
+    }
 }
 pub struct GpuResources {
     pub image_views: Vec<ImageView>,
@@ -83,16 +146,24 @@ pub struct GpuResources {
     pub acceleration_structure: TopLevelAccelerationStructure,
     pub instance_property_buffer: BufferResource,
     pub material_buffer: BufferResource,
+    pub light_buffer: BufferResource,
+    pub emissive_triangle_buffer: BufferResource,
     pub geometry_address_buffer: BufferResource,
     pub buffer_address_buffer: BufferResource,
     pub camera_buffer: BufferResource,
-    pub output_image_views: [ImageView; 2],
+    pub accumulation_image_view: ImageView,
+    pub aov_image_views: [ImageView; AOV_COUNT],
+    pub moment_image_view: ImageView,
     pub skybox_image_view: ImageView,
+    pub skybox_cube_image_view: ImageView,
+    pub skybox_buffer: BufferResource,
 }
 
 pub struct InstanceProperties {
-    geometry_index: u32,
-    material_index: u32,
+    _geometry_index: u32,
+    _material_index: u32,
+    // First entry in the emissive triangle buffer, -1 if the instance doesn't emit
+    _emissive_offset: i32,
 }
 
 pub struct Ctx {
@@ -100,13 +171,31 @@ pub struct Ctx {
     rtx: Rc<RtxExtensions>,
     queue: Rc<CommandQueue>,
     pipeline: RtxPipeline,
+    tone_map_pipeline: ToneMapPipeline,
+    denoise_pipeline: DenoisePipeline,
     textures: Map<GpuTexture>,
+    // Importance sampling tables of the skybox textures
+    environments: SecondaryMap<Handle, GpuEnvironment>,
+    cube_textures: Map<GpuCubeTexture>,
+    // CPU copies of the cube map skyboxes for save_scene
+    cube_maps: SecondaryMap<Handle, CubeMap>,
+    // Bound whenever the skybox isn't a cube map
+    default_cube_texture: Handle,
     meshes: Map<Mesh>,
+    mesh_resources: SecondaryMap<Handle, MeshResource>,
+    texture_data: SecondaryMap<Handle, TextureImageData>,
     instances: Map<MeshInstance>,
+    lights: Map<Light>,
     default_material: Handle,
     materials: Map<Material>,
     default_sampler: Sampler,
     default_skybox: SkyBox,
+    // Tables of the low discrepancy samplers, see RenderSettings::sampler
+    sobol_buffer: BufferResource,
+    blue_noise_buffer: BufferResource,
+    // Files the meshes and textures were loaded from, referenced by saved scenes
+    mesh_sources: SecondaryMap<Handle, PathBuf>,
+    texture_sources: SecondaryMap<Handle, PathBuf>,
 }
 
 impl Ctx {
@@ -122,28 +211,43 @@ impl Ctx {
                 .create_sampler(&sampler_info, None)
                 .expect("Sampler creation failed")
         };
+        let sampler_tables = SamplerTables::new();
+        let sobol_buffer =
+            Self::create_sampler_buffer(device.clone(), &sampler_tables.sobol_matrices);
+        let blue_noise_buffer =
+            Self::create_sampler_buffer(device.clone(), &sampler_tables.blue_noise);
         let mut instance = Self {
             device: device.clone(),
             rtx: rtx.clone(),
-            pipeline: RtxPipeline::new(device, rtx, max_frames_in_flight),
+            pipeline: RtxPipeline::new(device.clone(), rtx, max_frames_in_flight),
+            tone_map_pipeline: ToneMapPipeline::new(device.clone()),
+            denoise_pipeline: DenoisePipeline::new(device),
             textures: Map::new(),
+            environments: SecondaryMap::new(),
+            cube_textures: Map::new(),
+            cube_maps: SecondaryMap::new(),
+            default_cube_texture: Handle::default(),
             meshes: Map::new(),
+            mesh_resources: SecondaryMap::new(),
+            texture_data: SecondaryMap::new(),
             instances: Map::new(),
+            lights: Map::new(),
             default_material: Handle::default(),
             materials: Map::new(),
             queue,
             default_sampler,
-            default_skybox: SkyBox {
-                gpu_texture_handle: Handle::default(),
-            },
+            default_skybox: SkyBox::new(Handle::default()),
+            sobol_buffer,
+            blue_noise_buffer,
+            mesh_sources: SecondaryMap::new(),
+            texture_sources: SecondaryMap::new(),
         };
 
         let skybox_image =
             TextureImageData::new(Format::R8G8B8A8_UNORM, 1, 1, &[228, 246, 248, 255]);
-        let skybox_image_handle = instance.create_texture(&skybox_image);
-        instance.default_skybox = SkyBox {
-            gpu_texture_handle: skybox_image_handle,
-        };
+        instance.default_skybox = instance.create_skybox(&skybox_image);
+        instance.default_cube_texture =
+            instance.create_cube_texture(&CubeMap::new(1, Vec4::new(0.0, 0.0, 0.0, 1.0)));
         let default_material = instance.create_material();
         instance.default_material = default_material;
         instance
@@ -200,39 +304,308 @@ impl Ctx {
         })
     }
 
-    pub fn create_skybox(&mut self, data: &TextureImageData) -> SkyBox {
-        let gpu_texture_handle = self.create_texture(data);
-        SkyBox { gpu_texture_handle }
+    pub fn create_skybox<'a>(&mut self, image: impl Into<SkyBoxImage<'a>>) -> SkyBox {
+        match image.into() {
+            SkyBoxImage::Equirectangular(data) => {
+                let gpu_texture_handle = self.upload_texture(data);
+                let environment = self.create_environment(data);
+                self.environments.insert(gpu_texture_handle, environment);
+                self.texture_data.insert(gpu_texture_handle, data.clone());
+                SkyBox::new(gpu_texture_handle)
+            }
+            SkyBoxImage::CubeFaces(faces) => self.create_cube_map_skybox(
+                &CubeMap::from_faces(faces).expect("Cube map faces must be equally sized squares"),
+            ),
+            SkyBoxImage::Cross(data) => self.create_cube_map_skybox(
+                &CubeMap::from_cross(data).expect("Cube map cross must have a 4x3 or 3x4 layout"),
+            ),
+        }
+    }
+
+    pub fn create_cube_map_skybox(&mut self, cube_map: &CubeMap) -> SkyBox {
+        let handle = self.create_cube_texture(cube_map);
+        self.cube_maps.insert(handle, cube_map.clone());
+        SkyBox::new_cube_map(handle)
+    }
+
+    // Equirectangular skybox from an image file
+    pub fn load_skybox(&mut self, path: impl AsRef<Path>) -> std::io::Result<SkyBox> {
+        let skybox = self.create_skybox(&TextureImageData::load(&path)?);
+        self.texture_sources
+            .insert(skybox.gpu_texture_handle, path.as_ref().to_path_buf());
+        Ok(skybox)
+    }
+
+    fn create_sampler_buffer<T>(device: Rc<DeviceContext>, data: &[T]) -> BufferResource {
+        let mut buffer = BufferResource::new(
+            device,
+            std::mem::size_of_val(data) as u64,
+            MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        );
+        buffer.upload(data);
+        buffer
+    }
+
+    fn create_environment(&self, data: &TextureImageData) -> GpuEnvironment {
+        let distribution = EnvironmentDistribution::new(data);
+        let gpu_data = distribution.to_gpu();
+        let mut buffer = BufferResource::new(
+            self.device.clone(),
+            (gpu_data.len() * std::mem::size_of::<f32>()) as u64,
+            MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        );
+        buffer.upload(&gpu_data);
+        GpuEnvironment {
+            buffer,
+            width: distribution.width(),
+            height: distribution.height(),
+            gamma_encoded: data.is_gamma_encoded(),
+        }
+    }
+
+    fn memory_type_index(&self, type_bits: u32, flags: MemoryPropertyFlags) -> u32 {
+        let properties = &self.rtx.memory_properties().memory_properties;
+        (0..properties.memory_type_count)
+            .find(|i| {
+                type_bits & (1 << i) != 0
+                    && properties.memory_types[*i as usize]
+                        .property_flags
+                        .contains(flags)
+            })
+            .expect("No suitable memory type found")
+    }
+
+    fn create_cube_texture(&mut self, cube_map: &CubeMap) -> Handle {
+        let size = cube_map.size();
+        let pixels = cube_map.to_bytes();
+        let mut buffer = BufferResource::new(
+            self.device.clone(),
+            pixels.len() as u64,
+            MemoryPropertyFlags::HOST_VISIBLE,
+            BufferUsageFlags::TRANSFER_SRC,
+        );
+        buffer.upload(&pixels);
+
+        let format = Format::R32G32B32A32_SFLOAT;
+        let image_info = *ImageCreateInfo::builder()
+            .flags(ImageCreateFlags::CUBE_COMPATIBLE)
+            .image_type(ImageType::TYPE_2D)
+            .format(format)
+            .extent(Extent3D {
+                width: size,
+                height: size,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(FACE_COUNT as u32)
+            .samples(SampleCountFlags::TYPE_1)
+            .tiling(ImageTiling::OPTIMAL)
+            .usage(ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::SAMPLED)
+            .sharing_mode(SharingMode::EXCLUSIVE)
+            .initial_layout(ImageLayout::UNDEFINED);
+
+        let (image, memory) = unsafe {
+            let image = self
+                .device
+                .handle()
+                .create_image(&image_info, None)
+                .expect("Cube image creation failed");
+            let requirements = self.device.handle().get_image_memory_requirements(image);
+            let allocate_info = *MemoryAllocateInfo::builder()
+                .allocation_size(requirements.size)
+                .memory_type_index(self.memory_type_index(
+                    requirements.memory_type_bits,
+                    MemoryPropertyFlags::DEVICE_LOCAL,
+                ));
+            let memory = self
+                .device
+                .handle()
+                .allocate_memory(&allocate_info, None)
+                .expect("Cube image memory allocation failed");
+            self.device
+                .handle()
+                .bind_image_memory(image, memory, 0)
+                .expect("Cube image memory binding failed");
+            (image, memory)
+        };
+
+        let subresource_range = *ImageSubresourceRange::builder()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .level_count(1)
+            .layer_count(FACE_COUNT as u32);
+        let face_size = (size * size * 16) as u64;
+        let regions: Vec<BufferImageCopy> = (0..FACE_COUNT as u32)
+            .map(|face| {
+                *BufferImageCopy::builder()
+                    .buffer_offset(face as u64 * face_size)
+                    .image_subresource(
+                        *ImageSubresourceLayers::builder()
+                            .aspect_mask(ImageAspectFlags::COLOR)
+                            .base_array_layer(face)
+                            .layer_count(1),
+                    )
+                    .image_extent(Extent3D {
+                        width: size,
+                        height: size,
+                        depth: 1,
+                    })
+            })
+            .collect();
+
+        let mut command_buffer = CommandBuffer::new(self.device.clone(), self.queue.clone());
+        command_buffer.begin();
+        unsafe {
+            command_buffer.record_handle(|handle| {
+                let to_transfer = [*ImageMemoryBarrier::builder()
+                    .image(image)
+                    .old_layout(ImageLayout::UNDEFINED)
+                    .new_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .dst_access_mask(AccessFlags::TRANSFER_WRITE)
+                    .subresource_range(subresource_range)];
+                self.device.handle().cmd_pipeline_barrier(
+                    handle,
+                    PipelineStageFlags::TOP_OF_PIPE,
+                    PipelineStageFlags::TRANSFER,
+                    DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &to_transfer,
+                );
+                self.device.handle().cmd_copy_buffer_to_image(
+                    handle,
+                    buffer.buffer,
+                    image,
+                    ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &regions,
+                );
+                let to_shader = [*ImageMemoryBarrier::builder()
+                    .image(image)
+                    .old_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .new_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .src_access_mask(AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(AccessFlags::SHADER_READ)
+                    .subresource_range(subresource_range)];
+                self.device.handle().cmd_pipeline_barrier(
+                    handle,
+                    PipelineStageFlags::TRANSFER,
+                    PipelineStageFlags::RAY_TRACING_SHADER_KHR,
+                    DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &to_shader,
+                );
+                handle
+            });
+        }
+        command_buffer.submit();
+
+        let view_info = *ImageViewCreateInfo::builder()
+            .format(format)
+            .view_type(ImageViewType::CUBE)
+            .image(image)
+            .subresource_range(subresource_range);
+
+        let image_view = unsafe {
+            self.device
+                .handle()
+                .create_image_view(&view_info, None)
+                .expect("Image view creation failed")
+        };
+
+        // The importance sampling tables are built from an equirectangular projection
+        let width = (size * 4).clamp(4, 2048);
+        let environment = self.create_environment(&cube_map.to_equirect(width, width / 2));
+
+        self.cube_textures.insert(GpuCubeTexture {
+            device: self.device.clone(),
+            image,
+            memory,
+            image_view,
+            environment,
+        })
     }
 
     pub fn create_framebuffer(&self, width: u32, height: u32) -> FrameBuffer {
         FrameBuffer::new(self.device.clone(), self.queue.clone(), width, height)
     }
 
+    pub fn create_framebuffer_with_aovs(
+        &self,
+        width: u32,
+        height: u32,
+        aovs: &[AovKind],
+    ) -> FrameBuffer {
+        FrameBuffer::with_aovs(self.device.clone(), self.queue.clone(), width, height, aovs)
+    }
+
     pub fn create_material(&mut self) -> Handle {
         self.materials.insert(Material::new())
     }
 
+    pub fn material(&self, material: Handle) -> Option<&Material> {
+        self.materials.get(material)
+    }
+
     pub fn material_mut(&mut self, material: Handle) -> Option<&mut Material> {
         self.materials.get_mut(material)
     }
 
     pub fn create_mesh(&mut self, mesh: &MeshResource) -> Handle {
-        let m = Mesh::new(
-            self.device.clone(),
-            &self.rtx,
-            self.queue.clone(),
-            &mesh.indices,
-            &mesh.vertices,
-            &mesh.normals,
-            &mesh.tangents,
-            &mesh.tex_coords,
-        );
+        let m = Mesh::new(self.device.clone(), &self.rtx, self.queue.clone(), mesh);
 
-        self.meshes.insert(m)
+        let handle = self.meshes.insert(m);
+        // Kept on the CPU to build the emissive triangle list
+        self.mesh_resources.insert(handle, mesh.clone());
+        handle
+    }
+
+    // Native mesh file, see MeshResource::save
+    pub fn load_mesh(&mut self, path: impl AsRef<Path>) -> std::io::Result<Handle> {
+        let handle = self.create_mesh(&MeshResource::load(&path)?);
+        self.mesh_sources
+            .insert(handle, path.as_ref().to_path_buf());
+        Ok(handle)
+    }
+
+    pub fn mesh_resource(&self, mesh: Handle) -> Option<&MeshResource> {
+        self.mesh_resources.get(mesh)
+    }
+
+    pub fn mesh_source(&self, mesh: Handle) -> Option<&Path> {
+        self.mesh_sources.get(mesh).map(PathBuf::as_path)
+    }
+
+    pub fn load_texture(&mut self, path: impl AsRef<Path>) -> std::io::Result<Handle> {
+        let handle = self.create_texture(&TextureImageData::load(&path)?);
+        self.texture_sources
+            .insert(handle, path.as_ref().to_path_buf());
+        Ok(handle)
+    }
+
+    // Also holds for the texture of a skybox made by load_skybox
+    pub fn texture_source(&self, texture: Handle) -> Option<&Path> {
+        self.texture_sources.get(texture).map(PathBuf::as_path)
     }
 
     pub fn create_texture(&mut self, data: &TextureImageData) -> Handle {
+        let handle = self.upload_texture(data);
+        // Kept on the CPU for export::collect and save_scene, like the skybox images
+        self.texture_data.insert(handle, data.clone());
+        handle
+    }
+
+    pub fn texture_data(&self, texture: Handle) -> Option<&TextureImageData> {
+        self.texture_data.get(texture)
+    }
+
+    // The cube map of a skybox made by create_cube_map_skybox or create_skybox
+    pub fn cube_map(&self, cube_texture: Handle) -> Option<&CubeMap> {
+        self.cube_maps.get(cube_texture)
+    }
+
+    fn upload_texture(&mut self, data: &TextureImageData) -> Handle {
         let (mut image, buffer, format) = if data.format == Format::R8G8B8_UINT {
             let mut pixels = Vec::new();
 
@@ -367,14 +740,70 @@ impl Ctx {
         instance_property_buffer.upload(&frame.instance_properties);
         let instance_property_buffer_address = instance_property_buffer.device_address();
 
+        let mut light_buffer = BufferResource::new(
+            self.device.clone(),
+            frame.light_size(),
+            MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        );
+
+        light_buffer.upload(&frame.gpu_lights);
+        let light_buffer_address = light_buffer.device_address();
+
+        let mut emissive_triangle_buffer = BufferResource::new(
+            self.device.clone(),
+            frame.emissive_triangle_size(),
+            MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        );
+
+        emissive_triangle_buffer.upload(&frame.gpu_emissive_triangles);
+        let emissive_triangle_buffer_address = emissive_triangle_buffer.device_address();
+
+        let skybox = frame.skybox.unwrap_or(self.default_skybox);
+        let (skybox_image_view, skybox_cube_image_view, environment) = if skybox.is_cube_map() {
+            let cube = &self.cube_textures[skybox.gpu_texture_handle];
+            (
+                self.textures[self.default_skybox.gpu_texture_handle].image_view,
+                cube.image_view,
+                Some(&cube.environment),
+            )
+        } else {
+            (
+                self.textures[skybox.gpu_texture_handle].image_view,
+                self.cube_textures[self.default_cube_texture].image_view,
+                self.environments.get(skybox.gpu_texture_handle),
+            )
+        };
+        let (environment_address, environment_width, environment_height) = match environment {
+            Some(environment) => (
+                environment.buffer.device_address(),
+                environment.width as u64,
+                environment.height as u64,
+            ),
+            None => (0, 0, 0),
+        };
+
         let mut buffer_address_buffer = BufferResource::new(
             self.device.clone(),
-            std::mem::size_of::<u64>() as u64 * 2,
+            std::mem::size_of::<u64>() as u64 * 11,
             MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
             BufferUsageFlags::UNIFORM_BUFFER | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
         );
 
-        buffer_address_buffer.upload(&[material_address, instance_property_buffer_address]);
+        buffer_address_buffer.upload(&[
+            material_address,
+            instance_property_buffer_address,
+            light_buffer_address,
+            frame.gpu_lights.len() as u64,
+            emissive_triangle_buffer_address,
+            frame.gpu_emissive_triangles.len() as u64,
+            environment_address,
+            environment_width,
+            environment_height,
+            self.sobol_buffer.device_address(),
+            self.blue_noise_buffer.device_address(),
+        ]);
 
         let mut geometry_address_buffer = BufferResource::new(
             self.device.clone(),
@@ -392,55 +821,103 @@ impl Ctx {
             &frame.gpu_instances,
         );
 
-        let camera_matrices = [
-            frame.camera.view_matrix(),
-            frame.camera.projection_matrix(framebuffer.aspect_ratio()),
-        ];
+        let camera_buffer = self.create_camera_buffer(&frame.camera, framebuffer);
 
-        let camera_size = std::mem::size_of::<Mat4>() * camera_matrices.len();
-        let mut camera_buffer = BufferResource::new(
+        let skybox_properties = GpuSkyBox {
+            _rotation: skybox.rotation_matrix(),
+            _intensity: skybox.intensity,
+            _background_visible: skybox.background_visible as u32,
+            _gamma_encoded: environment
+                .map(|environment| environment.gamma_encoded)
+                .unwrap_or(true) as u32,
+            _cube_map: skybox.is_cube_map() as u32,
+        };
+        let mut skybox_buffer = BufferResource::new(
             self.device.clone(),
-            camera_size as u64,
+            std::mem::size_of::<GpuSkyBox>() as u64,
             MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
             BufferUsageFlags::UNIFORM_BUFFER,
         );
-
-        camera_buffer.upload(&camera_matrices);
-
-        let output_image_views = [
-            framebuffer.final_image_view,
-            framebuffer.accumulation_image_view,
-        ];
-
-        let skybox_image_view = if let Some(skybox) = &frame.skybox {
-            self.textures[skybox.gpu_texture_handle].image_view
-        } else {
-            self.textures[self.default_skybox.gpu_texture_handle].image_view
-        };
+        skybox_buffer.upload(&[skybox_properties]);
 
         GpuResources {
             acceleration_structure,
             material_buffer,
+            light_buffer,
+            emissive_triangle_buffer,
             geometry_address_buffer,
             instance_property_buffer,
             image_views,
             buffer_address_buffer,
             camera_buffer,
-            output_image_views,
+            accumulation_image_view: framebuffer.accumulation_image_view,
+            aov_image_views: framebuffer.aov_image_views(),
+            moment_image_view: framebuffer.moment_image.image_view,
             sampler: *sampler,
             skybox_image_view,
+            skybox_cube_image_view,
+            skybox_buffer,
         }
     }
 
+    fn create_camera_buffer(&self, camera: &Camera, framebuffer: &FrameBuffer) -> BufferResource {
+        let gpu_camera = GpuCamera {
+            _view: camera.view_matrix(),
+            _projection: camera.projection_matrix(framebuffer.aspect_ratio()),
+            _filter_table: framebuffer.pixel_filter.sample_table(),
+        };
+
+        let mut camera_buffer = BufferResource::new(
+            self.device.clone(),
+            std::mem::size_of::<GpuCamera>() as u64,
+            MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
+            BufferUsageFlags::UNIFORM_BUFFER,
+        );
+
+        camera_buffer.upload(&[gpu_camera]);
+        camera_buffer
+    }
+
+    // Points existing frame resources at another framebuffer, or at the same one after a
+    // resize, without uploading the scene again
+    pub fn bind_framebuffer(&self, frame: &mut FrameResources, framebuffer: &FrameBuffer) {
+        // The descriptors may still be in use by earlier passes
+        self.device.wait();
+        let resources = &mut frame.gpu_resources;
+        resources.camera_buffer =
+            self.create_camera_buffer(&frame.cpu_resources.camera, framebuffer);
+        resources.accumulation_image_view = framebuffer.accumulation_image_view;
+        resources.aov_image_views = framebuffer.aov_image_views();
+        resources.moment_image_view = framebuffer.moment_image.image_view;
+        frame.descriptors.update(resources);
+        frame.framebuffer = framebuffer.binding();
+    }
+
     pub fn create_instance(&mut self, mesh: Handle) -> Handle {
         self.instances
             .insert(MeshInstance::new(mesh, self.default_material))
     }
 
+    pub fn instance(&self, handle: Handle) -> Option<&MeshInstance> {
+        self.instances.get(handle)
+    }
+
     pub fn instance_mut(&mut self, handle: Handle) -> Option<&mut MeshInstance> {
         self.instances.get_mut(handle)
     }
 
+    pub fn create_light(&mut self, light: Light) -> Handle {
+        self.lights.insert(light)
+    }
+
+    pub fn light(&self, handle: Handle) -> Option<&Light> {
+        self.lights.get(handle)
+    }
+
+    pub fn light_mut(&mut self, handle: Handle) -> Option<&mut Light> {
+        self.lights.get_mut(handle)
+    }
+
     pub fn build_frame_resources(
         &mut self,
         framebuffer: &FrameBuffer,
@@ -465,10 +942,12 @@ impl Ctx {
         }
 
         let mut material_map = HashMap::new();
+        let mut material_handles = Vec::new();
         let mut materials = Vec::new();
         let mut gpu_materials = Vec::new();
         for (index, (key, material)) in self.materials.iter().enumerate() {
             material_map.insert(key, index);
+            material_handles.push(key);
             materials.push(material);
             let base_color_id = if let Some(texture_id) = material.base_color_texture {
                 *texture_map.get(&texture_id).unwrap() as i32
@@ -494,6 +973,12 @@ impl Ctx {
                 -1
             };
 
+            let occlusion_id = if let Some(texture_id) = material.occlusion_texture {
+                *texture_map.get(&texture_id).unwrap() as i32
+            } else {
+                -1
+            };
+
             gpu_materials.push(GpuMaterial {
                 _base_color: material.base_color,
                 _emission: material.emission,
@@ -507,14 +992,28 @@ impl Ctx {
                 _emission_texture: emission_id,
                 _metallic_roughness_texture: metal_roughness_id,
                 _normal_texture: normal_id,
+                _occlusion_texture: occlusion_id,
+                _normal_scale: material.normal_scale,
+                _occlusion_strength: material.occlusion_strength,
+                _normal_map_convention: material.normal_map_convention as u32,
+                _texture_transforms: material.gpu_texture_transforms(),
             });
         }
 
         let mut gpu_instances = Vec::new();
         let mut instance_properties = Vec::new();
+        let mut emissive_triangles = EmissiveTriangles::new();
         for (instance_id, key) in scene.instances().iter().enumerate() {
             if let Some(instance) = self.instances.get(*key) {
                 let geometry_index = *geometry_map.get(&instance.mesh()).unwrap();
+                let emissive_offset = emissive_triangles
+                    .add_instance(
+                        instance_id as u32,
+                        instance.transform(),
+                        &self.mesh_resources[instance.mesh()],
+                        &self.materials[instance.material()],
+                    )
+                    .map_or(-1, |offset| offset as i32);
                 let mesh = &geometries[geometry_index];
                 gpu_instances.push(GeometryInstance::new(
                     instance_id as u32,
@@ -527,15 +1026,26 @@ impl Ctx {
                 ));
 
                 instance_properties.push(InstanceProperties {
-                    geometry_index: geometry_index as u32,
-                    material_index: *material_map.get(&instance.material()).unwrap() as u32,
+                    _geometry_index: geometry_index as u32,
+                    _material_index: *material_map.get(&instance.material()).unwrap() as u32,
+                    _emissive_offset: emissive_offset,
                 });
             }
         }
 
+        let gpu_lights = scene
+            .lights()
+            .iter()
+            .filter_map(|key| self.lights.get(*key))
+            .map(|light| light.to_gpu())
+            .collect();
+
         let cpu_resources = CpuResources {
             gpu_materials,
+            material_handles,
             gpu_instances,
+            gpu_lights,
+            gpu_emissive_triangles: emissive_triangles.build(),
             geometry_addresses,
             instance_properties,
             camera: *scene.camera(),
@@ -554,6 +1064,7 @@ impl Ctx {
             cpu_resources,
             gpu_resources,
             descriptors,
+            framebuffer: framebuffer.binding(),
         }
     }
 
@@ -563,63 +1074,189 @@ impl Ctx {
         frame: &FrameResources,
         pass_count: u32,
         samples_per_pass: u32,
+        settings: &RenderSettings,
     ) {
         for pass in 0..pass_count {
-            let mut command_buffer = CommandBuffer::new(self.device.clone(), self.queue.clone());
-            command_buffer.begin();
-            if framebuffer.final_image.layout() != ImageLayout::GENERAL {
-                command_buffer
-                    .image_resource_transition(&mut framebuffer.final_image, ImageLayout::GENERAL);
+            self.render_pass(framebuffer, frame, pass, samples_per_pass, settings);
+        }
+
+        self.tone_map(framebuffer);
+    }
+
+    // Adds one pass to the accumulation image without resolving it, pass 0 restarts the
+    // accumulation. Call tone_map to update the final image.
+    pub fn render_pass(
+        &self,
+        framebuffer: &mut FrameBuffer,
+        frame: &FrameResources,
+        pass: u32,
+        samples_per_pass: u32,
+        settings: &RenderSettings,
+    ) {
+        assert!(
+            frame.is_bound_to(framebuffer),
+            "Frame resources built for another framebuffer or before a resize, see Ctx::bind_framebuffer"
+        );
+        let mut command_buffer = CommandBuffer::new(self.device.clone(), self.queue.clone());
+        command_buffer.begin();
+        if framebuffer.final_image.layout() != ImageLayout::GENERAL {
+            command_buffer
+                .image_resource_transition(&mut framebuffer.final_image, ImageLayout::GENERAL);
+        }
+        if framebuffer.accumulation_image.layout() != ImageLayout::GENERAL {
+            command_buffer.image_resource_transition(
+                &mut framebuffer.accumulation_image,
+                ImageLayout::GENERAL,
+            );
+        }
+        for image in framebuffer
+            .aov_images
+            .iter_mut()
+            .chain(std::iter::once(&mut framebuffer.moment_image))
+        {
+            if image.image.layout() != ImageLayout::GENERAL {
+                command_buffer.image_resource_transition(&mut image.image, ImageLayout::GENERAL);
             }
-            if framebuffer.accumulation_image.layout() != ImageLayout::GENERAL {
-                command_buffer.image_resource_transition(
-                    &mut framebuffer.accumulation_image,
-                    ImageLayout::GENERAL,
+        }
+        command_buffer.bind_descriptor_sets(
+            &self.pipeline.descriptor_sets.pipeline_layout,
+            PipelineBindPoint::RAY_TRACING_KHR,
+            &frame.descriptors.sets,
+        );
+        command_buffer.bind_pipeline(PipelineBindPoint::RAY_TRACING_KHR, &self.pipeline.pipeline);
+        unsafe {
+            command_buffer.record_handle(|handle| {
+                self.device.handle().cmd_bind_pipeline(
+                    handle,
+                    PipelineBindPoint::RAY_TRACING_KHR,
+                    self.pipeline.pipeline,
+                );
+                let adaptive = &framebuffer.adaptive_sampling;
+                let region = framebuffer.region();
+                let (image_width, image_height) = framebuffer.image_size();
+                let constants: Vec<u8> = [
+                    samples_per_pass,
+                    pass,
+                    framebuffer.aov_mask(),
+                    adaptive.noise_threshold.to_bits(),
+                    adaptive.min_samples,
+                    region.x,
+                    region.y,
+                    image_width,
+                    image_height,
+                ]
+                .iter()
+                .chain(&settings.push_constants())
+                .flat_map(|val| {
+                    let i: u32 = *val;
+                    i.to_le_bytes()
+                })
+                .collect();
+                self.device.handle().cmd_push_constants(
+                    handle,
+                    self.pipeline.descriptor_sets.pipeline_layout,
+                    ShaderStageFlags::RAYGEN_KHR | ShaderStageFlags::CLOSEST_HIT_KHR,
+                    0,
+                    &constants,
                 );
+                self.rtx.pipeline_ext().cmd_trace_rays(
+                    handle,
+                    &self.pipeline.stride_addresses[0],
+                    &self.pipeline.stride_addresses[1],
+                    &self.pipeline.stride_addresses[2],
+                    &self.pipeline.stride_addresses[3],
+                    region.width,
+                    region.height,
+                    1,
+                );
+                handle
+            });
+        }
+
+        command_buffer.submit();
+    }
+
+    // Renders an image of any size in tiles of the framebuffer size and stitches the
+    // averaged radiance on the CPU. Every tile is a separate launch, which keeps large
+    // renders under the driver timeouts. The result is neither denoised nor tone mapped.
+    pub fn render_tiled(
+        &mut self,
+        framebuffer: &mut FrameBuffer,
+        scene: &Scene,
+        (image_width, image_height): (u32, u32),
+        pass_count: u32,
+        samples_per_pass: u32,
+        settings: &RenderSettings,
+    ) -> HdrImage {
+        let tiles = Region::tiles(
+            image_width,
+            image_height,
+            framebuffer.width,
+            framebuffer.height,
+        );
+        // The camera only depends on the image size, one set of resources serves every tile
+        framebuffer.set_region(image_width, image_height, tiles[0]);
+        let frame = self.build_frame_resources(framebuffer, scene);
+
+        let mut image = HdrImage::black(image_width, image_height);
+        for tile in tiles {
+            framebuffer.set_region(image_width, image_height, tile);
+            for pass in 0..pass_count {
+                self.render_pass(framebuffer, &frame, pass, samples_per_pass, settings);
             }
-            command_buffer.bind_descriptor_sets(
-                &self.pipeline.descriptor_sets.pipeline_layout,
-                PipelineBindPoint::RAY_TRACING_KHR,
-                &frame.descriptors.sets,
-            );
+            image.blit(&framebuffer.download_accumulation(), tile);
+        }
+
+        framebuffer.reset_region();
+        image
+    }
+
+    // Blocks until the submitted passes have finished
+    pub fn wait(&self) {
+        self.device.wait();
+    }
+
+    // Resolves the accumulated samples into the final image, denoised first when the
+    // framebuffer has a denoiser. Runs after every render_frame, call it again after
+    // changing the tone mapping or the denoiser.
+    pub fn tone_map(&self, framebuffer: &mut FrameBuffer) {
+        // The tone map descriptor set may still be in use by the previous dispatch
+        self.device.wait();
+        let mut command_buffer = CommandBuffer::new(self.device.clone(), self.queue.clone());
+        command_buffer.begin();
+        if framebuffer.final_image.layout() != ImageLayout::GENERAL {
             command_buffer
-                .bind_pipeline(PipelineBindPoint::RAY_TRACING_KHR, &self.pipeline.pipeline);
-            unsafe {
-                command_buffer.record_handle(|handle| {
-                    self.device.handle().cmd_bind_pipeline(
-                        handle,
-                        PipelineBindPoint::RAY_TRACING_KHR,
-                        self.pipeline.pipeline,
-                    );
-                    let constants: Vec<u8> = [samples_per_pass, pass]
-                        .iter()
-                        .flat_map(|val| {
-                            let i: u32 = *val;
-                            i.to_le_bytes()
-                        })
-                        .collect();
-                    self.device.handle().cmd_push_constants(
-                        handle,
-                        self.pipeline.descriptor_sets.pipeline_layout,
-                        ShaderStageFlags::RAYGEN_KHR,
-                        0,
-                        &constants,
-                    );
-                    self.rtx.pipeline_ext().cmd_trace_rays(
-                        handle,
-                        &self.pipeline.stride_addresses[0],
-                        &self.pipeline.stride_addresses[1],
-                        &self.pipeline.stride_addresses[2],
-                        &self.pipeline.stride_addresses[3],
-                        framebuffer.width,
-                        framebuffer.height,
-                        1,
-                    );
-                    handle
-                });
+                .image_resource_transition(&mut framebuffer.final_image, ImageLayout::GENERAL);
+        }
+        if framebuffer.accumulation_image.layout() != ImageLayout::GENERAL {
+            command_buffer.image_resource_transition(
+                &mut framebuffer.accumulation_image,
+                ImageLayout::GENERAL,
+            );
+        }
+        for image in framebuffer
+            .aov_images
+            .iter_mut()
+            .chain(framebuffer.denoise_images.iter_mut())
+        {
+            if image.image.layout() != ImageLayout::GENERAL {
+                command_buffer.image_resource_transition(&mut image.image, ImageLayout::GENERAL);
             }
-
-            command_buffer.submit();
         }
+
+        let source = match framebuffer.denoiser() {
+            Some(denoiser) if denoiser.iterations > 0 => {
+                self.denoise_pipeline
+                    .record(&mut command_buffer, framebuffer, denoiser)
+            }
+            _ => framebuffer.accumulation_image_view,
+        };
+        self.tone_map_pipeline.record(
+            &mut command_buffer,
+            source,
+            framebuffer,
+            &framebuffer.tone_mapping,
+        );
+        command_buffer.submit();
     }
 }
@@ -0,0 +1,299 @@
+use ash::vk::Format;
+use cgmath::InnerSpace;
+
+use crate::environment::{direction_to_spherical, spherical_to_direction};
+use crate::image_resource::TextureImageData;
+use crate::math::{Vec2, Vec3, Vec4};
+
+pub const FACE_COUNT: usize = 6;
+
+// Faces in Vulkan layer order. Face directions and orientations follow the samplerCube
+// conventions, so a CubeMap uploaded as is matches what the shaders sample.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; FACE_COUNT] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    // Direction through the face at normalized texel coordinates, t runs down the image
+    pub fn direction(self, s: f32, t: f32) -> Vec3 {
+        let a = 2.0 * s - 1.0;
+        let b = 2.0 * t - 1.0;
+        let d = match self {
+            CubeFace::PositiveX => Vec3::new(1.0, -b, -a),
+            CubeFace::NegativeX => Vec3::new(-1.0, -b, a),
+            CubeFace::PositiveY => Vec3::new(a, 1.0, b),
+            CubeFace::NegativeY => Vec3::new(a, -1.0, -b),
+            CubeFace::PositiveZ => Vec3::new(a, -b, 1.0),
+            CubeFace::NegativeZ => Vec3::new(-a, -b, -1.0),
+        };
+        d.normalize()
+    }
+
+    // Face hit by dir and the normalized texel coordinates on that face
+    pub fn from_direction(dir: Vec3) -> (Self, Vec2) {
+        let abs = Vec3::new(dir.x.abs(), dir.y.abs(), dir.z.abs());
+        let (face, sc, tc, ma) = if abs.x >= abs.y && abs.x >= abs.z {
+            if dir.x > 0.0 {
+                (CubeFace::PositiveX, -dir.z, -dir.y, abs.x)
+            } else {
+                (CubeFace::NegativeX, dir.z, -dir.y, abs.x)
+            }
+        } else if abs.y >= abs.z {
+            if dir.y > 0.0 {
+                (CubeFace::PositiveY, dir.x, dir.z, abs.y)
+            } else {
+                (CubeFace::NegativeY, dir.x, -dir.z, abs.y)
+            }
+        } else if dir.z > 0.0 {
+            (CubeFace::PositiveZ, dir.x, -dir.y, abs.z)
+        } else {
+            (CubeFace::NegativeZ, -dir.x, -dir.y, abs.z)
+        };
+
+        let ma = ma.max(f32::MIN_POSITIVE);
+        (
+            face,
+            Vec2::new(0.5 * (sc / ma + 1.0), 0.5 * (tc / ma + 1.0)),
+        )
+    }
+}
+
+// Linear RGBA cube map on the CPU
+#[derive(Clone)]
+pub struct CubeMap {
+    size: u32,
+    faces: [Vec<Vec4>; FACE_COUNT],
+}
+
+fn linear_texels(image: &TextureImageData) -> Option<Vec<Vec4>> {
+    let gamma = if image.is_gamma_encoded() { 2.2 } else { 1.0 };
+    let mut texels = Vec::with_capacity((image.width * image.height) as usize);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let t = image.texel(x, y)?;
+            texels.push(Vec4::new(
+                t.x.max(0.0).powf(gamma),
+                t.y.max(0.0).powf(gamma),
+                t.z.max(0.0).powf(gamma),
+                t.w,
+            ));
+        }
+    }
+    Some(texels)
+}
+
+impl CubeMap {
+    pub fn new(size: u32, color: Vec4) -> Self {
+        let face = vec![color; (size * size) as usize];
+        Self {
+            size,
+            faces: [
+                face.clone(),
+                face.clone(),
+                face.clone(),
+                face.clone(),
+                face.clone(),
+                face,
+            ],
+        }
+    }
+
+    // Faces ordered +X, -X, +Y, -Y, +Z, -Z. Returns None if the faces aren't square,
+    // differ in size or use a format that can't be read on the CPU.
+    pub fn from_faces(faces: [&TextureImageData; FACE_COUNT]) -> Option<Self> {
+        let size = faces[0].width;
+        if size == 0 || faces.iter().any(|f| f.width != size || f.height != size) {
+            return None;
+        }
+
+        let mut cube = Self::new(size, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        for (index, face) in faces.iter().enumerate() {
+            cube.faces[index] = linear_texels(face)?;
+        }
+        Some(cube)
+    }
+
+    // Horizontal (4x3) or vertical (3x4) cross:
+    //
+    //     +Y              +Y
+    //  -X +Z +X -Z     -X +Z +X
+    //     -Y              -Y
+    //                     -Z (upside down)
+    pub fn from_cross(image: &TextureImageData) -> Option<Self> {
+        let texels = linear_texels(image)?;
+        let (size, layout, flip_negative_z) = if image.width * 3 == image.height * 4 {
+            (
+                image.width / 4,
+                [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)],
+                false,
+            )
+        } else if image.width * 4 == image.height * 3 {
+            (
+                image.width / 3,
+                [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)],
+                true,
+            )
+        } else {
+            return None;
+        };
+
+        if size == 0 {
+            return None;
+        }
+
+        let mut cube = Self::new(size, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        for (index, (column, row)) in layout.iter().enumerate() {
+            let flip = flip_negative_z && CubeFace::ALL[index] == CubeFace::NegativeZ;
+            for y in 0..size {
+                for x in 0..size {
+                    let (sx, sy) = if flip {
+                        (size - 1 - x, size - 1 - y)
+                    } else {
+                        (x, y)
+                    };
+                    let px = column * size + sx;
+                    let py = row * size + sy;
+                    cube.faces[index][(y * size + x) as usize] =
+                        texels[(py * image.width + px) as usize];
+                }
+            }
+        }
+        Some(cube)
+    }
+
+    pub fn from_equirect(image: &TextureImageData, size: u32) -> Option<Self> {
+        let texels = linear_texels(image)?;
+        let mut cube = Self::new(size, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        for (index, face) in CubeFace::ALL.iter().enumerate() {
+            for y in 0..size {
+                for x in 0..size {
+                    let dir = face.direction(
+                        (x as f32 + 0.5) / size as f32,
+                        (y as f32 + 0.5) / size as f32,
+                    );
+                    let st = direction_to_spherical(dir);
+                    cube.faces[index][(y * size + x) as usize] =
+                        bilinear(&texels, image.width, image.height, st, true);
+                }
+            }
+        }
+        Some(cube)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn face(&self, face: CubeFace) -> &[Vec4] {
+        &self.faces[face as usize]
+    }
+
+    // Bilinear lookup, filtering doesn't cross face edges
+    pub fn sample(&self, dir: Vec3) -> Vec4 {
+        let (face, st) = CubeFace::from_direction(dir);
+        bilinear(&self.faces[face as usize], self.size, self.size, st, false)
+    }
+
+    pub fn to_equirect(&self, width: u32, height: u32) -> TextureImageData {
+        let mut pixels = Vec::with_capacity((width * height * 16) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let st = Vec2::new(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                );
+                let c = self.sample(spherical_to_direction(st));
+                for v in [c.x, c.y, c.z, c.w] {
+                    pixels.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+
+        TextureImageData::new(Format::R32G32B32A32_SFLOAT, width, height, &pixels)
+    }
+
+    // Linear R32G32B32A32_SFLOAT images in layer order
+    pub fn to_faces(&self) -> Vec<TextureImageData> {
+        self.faces
+            .iter()
+            .map(|face| {
+                let pixels: Vec<u8> = face
+                    .iter()
+                    .flat_map(|c| [c.x, c.y, c.z, c.w])
+                    .flat_map(f32::to_le_bytes)
+                    .collect();
+                TextureImageData::new(Format::R32G32B32A32_SFLOAT, self.size, self.size, &pixels)
+            })
+            .collect()
+    }
+
+    // Linear R32G32B32A32_SFLOAT horizontal cross as read by from_cross, the unused cells
+    // are transparent black
+    pub fn to_cross(&self) -> TextureImageData {
+        let (width, height) = (self.size * 4, self.size * 3);
+        let mut texels = vec![Vec4::new(0.0, 0.0, 0.0, 0.0); (width * height) as usize];
+        let layout = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+        for (face, (column, row)) in self.faces.iter().zip(layout) {
+            for y in 0..self.size {
+                for x in 0..self.size {
+                    let (px, py) = (column * self.size + x, row * self.size + y);
+                    texels[(py * width + px) as usize] = face[(y * self.size + x) as usize];
+                }
+            }
+        }
+        let pixels: Vec<u8> = texels
+            .iter()
+            .flat_map(|c| [c.x, c.y, c.z, c.w])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        TextureImageData::new(Format::R32G32B32A32_SFLOAT, width, height, &pixels)
+    }
+
+    // All faces as tightly packed RGBA floats, the staging layout of the cube image
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.faces
+            .iter()
+            .flatten()
+            .flat_map(|c| [c.x, c.y, c.z, c.w])
+            .flat_map(f32::to_le_bytes)
+            .collect()
+    }
+}
+
+fn bilinear(texels: &[Vec4], width: u32, height: u32, st: Vec2, wrap_s: bool) -> Vec4 {
+    let x = st.x * width as f32 - 0.5;
+    let y = st.y * height as f32 - 0.5;
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+    let column = |c: i64| {
+        if wrap_s {
+            c.rem_euclid(width as i64) as u32
+        } else {
+            c.clamp(0, width as i64 - 1) as u32
+        }
+    };
+    let row = |r: i64| r.clamp(0, height as i64 - 1) as u32;
+    let texel = |c: i64, r: i64| texels[(row(r) * width + column(c)) as usize];
+
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1, y0) * fx;
+    let bottom = texel(x0, y0 + 1) * (1.0 - fx) + texel(x0 + 1, y0 + 1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
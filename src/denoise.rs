@@ -0,0 +1,214 @@
+use cgmath::InnerSpace;
+
+use crate::hdr_image::HdrImage;
+use crate::math::Vec3;
+
+// B3 spline, the 5 tap kernel of every a-trous iteration
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+// Keeps black albedo from blowing up the demodulated irradiance
+const ALBEDO_EPSILON: f32 = 1e-3;
+
+// Radiance and the optional guides, all in row major order with one entry per pixel
+pub struct DenoiseInput<'a> {
+    pub color: &'a HdrImage,
+    pub albedo: Option<&'a [Vec3]>,
+    pub normal: Option<&'a [Vec3]>,
+    pub depth: Option<&'a [f32]>,
+}
+
+impl<'a> DenoiseInput<'a> {
+    pub fn new(color: &'a HdrImage) -> Self {
+        Self {
+            color,
+            albedo: None,
+            normal: None,
+            depth: None,
+        }
+    }
+
+    pub fn with_albedo(mut self, albedo: &'a [Vec3]) -> Self {
+        self.albedo = Some(albedo);
+        self
+    }
+
+    pub fn with_normal(mut self, normal: &'a [Vec3]) -> Self {
+        self.normal = Some(normal);
+        self
+    }
+
+    pub fn with_depth(mut self, depth: &'a [f32]) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+}
+
+// Hook for external denoisers, see FrameBuffer::download_denoised
+pub trait Denoiser {
+    fn denoise(&self, input: &DenoiseInput) -> HdrImage;
+}
+
+// Edge avoiding a-trous wavelet filter, "Edge-Avoiding A-Trous Wavelet Transform for
+// fast Global Illumination Filtering", Dammertz et al. 2010. Runs on the GPU when set on
+// a FrameBuffer, this CPU version produces the same result.
+#[derive(Clone, Copy, Debug)]
+pub struct ATrousDenoiser {
+    // Each iteration doubles the filter footprint, 5 covers 125 pixels
+    pub iterations: u32,
+    // Edge stopping functions, smaller values preserve more detail. The color sigma
+    // is halved after every iteration.
+    pub color_sigma: f32,
+    pub normal_sigma: f32,
+    // Relative to the depth of the center pixel
+    pub depth_sigma: f32,
+}
+
+impl Default for ATrousDenoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            color_sigma: 0.6,
+            normal_sigma: 0.3,
+            depth_sigma: 0.05,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GpuATrousSettings {
+    pub _step_width: u32,
+    pub _flags: u32,
+    pub _color_sigma: f32,
+    pub _normal_sigma: f32,
+    pub _depth_sigma: f32,
+}
+
+impl ATrousDenoiser {
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    fn color_sigma(&self, iteration: u32) -> f32 {
+        self.color_sigma * 2.0f32.powi(-(iteration as i32))
+    }
+
+    // Push constants of one iteration, flags as in denoise.comp
+    pub(crate) fn gpu_settings(&self, iteration: u32, flags: u32) -> GpuATrousSettings {
+        GpuATrousSettings {
+            _step_width: 1 << iteration,
+            _flags: flags,
+            _color_sigma: self.color_sigma(iteration),
+            _normal_sigma: self.normal_sigma,
+            _depth_sigma: self.depth_sigma,
+        }
+    }
+
+    fn edge_weight(
+        &self,
+        input: &DenoiseInput,
+        irradiance: &[Vec3],
+        p: usize,
+        q: usize,
+        step: f32,
+        color_sigma: f32,
+    ) -> f32 {
+        let color = (irradiance[p] - irradiance[q]).magnitude2();
+        let mut weight = (-color / (color_sigma * color_sigma)).exp();
+        if let Some(normal) = input.normal {
+            let distance = (normal[p] - normal[q]).magnitude2();
+            weight *= (-distance / (self.normal_sigma * self.normal_sigma)).exp();
+        }
+        if let Some(depth) = input.depth {
+            let distance = (depth[p] - depth[q]).abs();
+            weight *= (-distance / (self.depth_sigma * step * depth[p].abs() + 1e-4)).exp();
+        }
+        weight
+    }
+}
+
+fn demodulate(color: Vec3, albedo: Vec3) -> Vec3 {
+    Vec3::new(
+        color.x / albedo.x.max(ALBEDO_EPSILON),
+        color.y / albedo.y.max(ALBEDO_EPSILON),
+        color.z / albedo.z.max(ALBEDO_EPSILON),
+    )
+}
+
+fn remodulate(irradiance: Vec3, albedo: Vec3) -> Vec3 {
+    Vec3::new(
+        irradiance.x * albedo.x.max(ALBEDO_EPSILON),
+        irradiance.y * albedo.y.max(ALBEDO_EPSILON),
+        irradiance.z * albedo.z.max(ALBEDO_EPSILON),
+    )
+}
+
+impl Denoiser for ATrousDenoiser {
+    fn denoise(&self, input: &DenoiseInput) -> HdrImage {
+        let width = input.color.width as i64;
+        let height = input.color.height as i64;
+
+        // Filter the lighting only so textures stay sharp, the albedo needs no edge
+        // stopping function after that
+        let mut irradiance: Vec<Vec3> = match input.albedo {
+            Some(albedo) => input
+                .color
+                .pixels
+                .iter()
+                .zip(albedo)
+                .map(|(c, a)| demodulate(*c, *a))
+                .collect(),
+            None => input.color.pixels.clone(),
+        };
+
+        for iteration in 0..self.iterations {
+            let step = 1i64 << iteration;
+            let color_sigma = self.color_sigma(iteration);
+            let mut filtered = Vec::with_capacity(irradiance.len());
+            for y in 0..height {
+                for x in 0..width {
+                    let p = (y * width + x) as usize;
+                    let mut sum = Vec3::new(0.0, 0.0, 0.0);
+                    let mut weight_sum = 0.0;
+                    for (j, ky) in KERNEL.iter().enumerate() {
+                        let qy = y + (j as i64 - 2) * step;
+                        if qy < 0 || qy >= height {
+                            continue;
+                        }
+                        for (i, kx) in KERNEL.iter().enumerate() {
+                            let qx = x + (i as i64 - 2) * step;
+                            if qx < 0 || qx >= width {
+                                continue;
+                            }
+                            let q = (qy * width + qx) as usize;
+                            let weight = kx
+                                * ky
+                                * self.edge_weight(
+                                    input,
+                                    &irradiance,
+                                    p,
+                                    q,
+                                    step as f32,
+                                    color_sigma,
+                                );
+                            sum += irradiance[q] * weight;
+                            weight_sum += weight;
+                        }
+                    }
+                    filtered.push(sum / weight_sum);
+                }
+            }
+            irradiance = filtered;
+        }
+
+        let pixels = match input.albedo {
+            Some(albedo) => irradiance
+                .iter()
+                .zip(albedo)
+                .map(|(i, a)| remodulate(*i, *a))
+                .collect(),
+            None => irradiance,
+        };
+        HdrImage::new(input.color.width, input.color.height, pixels)
+    }
+}
@@ -0,0 +1,332 @@
+use std::rc::Rc;
+
+use ash::vk::{
+    AccessFlags, ComputePipelineCreateInfo, DependencyFlags, DescriptorImageInfo, DescriptorPool,
+    DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo,
+    DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType,
+    ImageLayout, ImageView, MemoryBarrier, Pipeline, PipelineBindPoint, PipelineCache,
+    PipelineLayout, PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags,
+    PushConstantRange, ShaderModuleCreateInfo, ShaderStageFlags, WriteDescriptorSet,
+};
+use vk_utils::{
+    command_buffer::CommandBuffer, device_context::DeviceContext, shader_library::load_spirv,
+};
+
+use crate::{
+    aov::AovKind,
+    denoise::{ATrousDenoiser, GpuATrousSettings},
+    framebuffer::FrameBuffer,
+    rtx_pipeline::shader_directory,
+};
+
+pub const DENOISE_ACCUMULATION_IMAGE_LOCATION: (u32, u32) = (0, 0);
+pub const DENOISE_INPUT_IMAGE_LOCATION: (u32, u32) = (0, 1);
+pub const DENOISE_OUTPUT_IMAGE_LOCATION: (u32, u32) = (0, 2);
+pub const DENOISE_ALBEDO_IMAGE_LOCATION: (u32, u32) = (0, 3);
+pub const DENOISE_NORMAL_IMAGE_LOCATION: (u32, u32) = (0, 4);
+pub const DENOISE_DEPTH_IMAGE_LOCATION: (u32, u32) = (0, 5);
+
+const DENOISE_FIRST: u32 = 1;
+const DENOISE_LAST: u32 = 2;
+const DENOISE_ALBEDO: u32 = 4;
+const DENOISE_NORMAL: u32 = 8;
+const DENOISE_DEPTH: u32 = 16;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+// Accumulation image to the first denoise image, then back and forth between both
+const SET_COUNT: usize = 3;
+
+// GPU version of ATrousDenoiser, one dispatch per iteration
+pub struct DenoisePipeline {
+    device: Rc<DeviceContext>,
+    descriptor_set_layout: DescriptorSetLayout,
+    descriptor_pool: DescriptorPool,
+    // Shared by every dispatch, only update them when the previous frame has finished
+    descriptor_sets: Vec<DescriptorSet>,
+    pipeline_layout: PipelineLayout,
+    pipeline: Pipeline,
+}
+
+impl DenoisePipeline {
+    pub fn new(device: Rc<DeviceContext>) -> Self {
+        unsafe {
+            let bindings: Vec<DescriptorSetLayoutBinding> = [
+                DENOISE_ACCUMULATION_IMAGE_LOCATION,
+                DENOISE_INPUT_IMAGE_LOCATION,
+                DENOISE_OUTPUT_IMAGE_LOCATION,
+                DENOISE_ALBEDO_IMAGE_LOCATION,
+                DENOISE_NORMAL_IMAGE_LOCATION,
+                DENOISE_DEPTH_IMAGE_LOCATION,
+            ]
+            .iter()
+            .map(|location| {
+                *DescriptorSetLayoutBinding::builder()
+                    .descriptor_count(1)
+                    .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                    .stage_flags(ShaderStageFlags::COMPUTE)
+                    .binding(location.1)
+            })
+            .collect();
+            let descriptor_set_layout = device
+                .handle()
+                .create_descriptor_set_layout(
+                    &DescriptorSetLayoutCreateInfo::builder().bindings(&bindings),
+                    None,
+                )
+                .expect("Descriptor set layout creation failed");
+
+            let constant_ranges = [*PushConstantRange::builder()
+                .stage_flags(ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(std::mem::size_of::<GpuATrousSettings>() as u32)];
+            let set_layouts = [descriptor_set_layout];
+            let pipeline_layout = device
+                .handle()
+                .create_pipeline_layout(
+                    &PipelineLayoutCreateInfo::builder()
+                        .set_layouts(&set_layouts)
+                        .push_constant_ranges(&constant_ranges),
+                    None,
+                )
+                .expect("Pipeline layout creation failed");
+
+            let sizes = [DescriptorPoolSize {
+                ty: DescriptorType::STORAGE_IMAGE,
+                descriptor_count: (bindings.len() * SET_COUNT) as u32,
+            }];
+            let descriptor_pool = device
+                .handle()
+                .create_descriptor_pool(
+                    &DescriptorPoolCreateInfo::builder()
+                        .pool_sizes(&sizes)
+                        .max_sets(SET_COUNT as u32),
+                    None,
+                )
+                .expect("Descriptor pool creation failed");
+            let set_layouts = [descriptor_set_layout; SET_COUNT];
+            let descriptor_sets = device
+                .handle()
+                .allocate_descriptor_sets(
+                    &DescriptorSetAllocateInfo::builder()
+                        .descriptor_pool(descriptor_pool)
+                        .set_layouts(&set_layouts),
+                )
+                .expect("Descriptor set allocation failed");
+
+            let code = load_spirv(
+                shader_directory()
+                    .join("denoise.comp.spv")
+                    .to_str()
+                    .unwrap(),
+            );
+            let shader_module_info = ShaderModuleCreateInfo::builder().code(&code);
+            let module = device
+                .handle()
+                .create_shader_module(&shader_module_info, None)
+                .expect("Denoise shader compilation failed");
+            let stage = *PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::COMPUTE)
+                .module(module)
+                .name(c"main");
+            let infos = [*ComputePipelineCreateInfo::builder()
+                .stage(stage)
+                .layout(pipeline_layout)];
+            let pipeline = device
+                .handle()
+                .create_compute_pipelines(PipelineCache::null(), &infos, None)
+                .expect("Denoise pipeline creation failed")[0];
+            device.handle().destroy_shader_module(module, None);
+
+            Self {
+                device,
+                descriptor_set_layout,
+                descriptor_pool,
+                descriptor_sets,
+                pipeline_layout,
+                pipeline,
+            }
+        }
+    }
+
+    fn update_set(
+        &self,
+        set: DescriptorSet,
+        framebuffer: &FrameBuffer,
+        input: ImageView,
+        output: ImageView,
+    ) {
+        let views = [
+            (
+                DENOISE_ACCUMULATION_IMAGE_LOCATION,
+                framebuffer.accumulation_image_view,
+            ),
+            (DENOISE_INPUT_IMAGE_LOCATION, input),
+            (DENOISE_OUTPUT_IMAGE_LOCATION, output),
+            (
+                DENOISE_ALBEDO_IMAGE_LOCATION,
+                framebuffer.aov_images[AovKind::Albedo as usize].image_view,
+            ),
+            (
+                DENOISE_NORMAL_IMAGE_LOCATION,
+                framebuffer.aov_images[AovKind::Normal as usize].image_view,
+            ),
+            (
+                DENOISE_DEPTH_IMAGE_LOCATION,
+                framebuffer.aov_images[AovKind::Depth as usize].image_view,
+            ),
+        ];
+        let infos: Vec<[DescriptorImageInfo; 1]> = views
+            .iter()
+            .map(|(_, view)| {
+                [*DescriptorImageInfo::builder()
+                    .image_view(*view)
+                    .image_layout(ImageLayout::GENERAL)]
+            })
+            .collect();
+        let writes: Vec<WriteDescriptorSet> = views
+            .iter()
+            .zip(&infos)
+            .map(|((location, _), info)| {
+                *WriteDescriptorSet::builder()
+                    .image_info(info)
+                    .dst_set(set)
+                    .dst_binding(location.1)
+                    .descriptor_type(DescriptorType::STORAGE_IMAGE)
+            })
+            .collect();
+
+        unsafe {
+            self.device.handle().update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    // Records every iteration and returns the view holding the result. The framebuffer
+    // images have to be in the GENERAL layout and the denoise images allocated.
+    pub fn record(
+        &self,
+        command_buffer: &mut CommandBuffer,
+        framebuffer: &FrameBuffer,
+        denoiser: &ATrousDenoiser,
+    ) -> ImageView {
+        let ping = framebuffer.denoise_images[0].image_view;
+        let pong = framebuffer.denoise_images[1].image_view;
+        // The first iteration doesn't read the input image, any valid view will do
+        self.update_set(
+            self.descriptor_sets[0],
+            framebuffer,
+            framebuffer.accumulation_image_view,
+            ping,
+        );
+        self.update_set(self.descriptor_sets[1], framebuffer, ping, pong);
+        self.update_set(self.descriptor_sets[2], framebuffer, pong, ping);
+
+        let mut guides = 0;
+        for (kind, flag) in [
+            (AovKind::Albedo, DENOISE_ALBEDO),
+            (AovKind::Normal, DENOISE_NORMAL),
+            (AovKind::Depth, DENOISE_DEPTH),
+        ] {
+            if framebuffer.has_aov(kind) {
+                guides |= flag;
+            }
+        }
+
+        for iteration in 0..denoiser.iterations {
+            let mut flags = guides;
+            if iteration == 0 {
+                flags |= DENOISE_FIRST;
+            }
+            if iteration + 1 == denoiser.iterations {
+                flags |= DENOISE_LAST;
+            }
+            let set = if iteration == 0 {
+                self.descriptor_sets[0]
+            } else {
+                self.descriptor_sets[1 + (iteration as usize + 1) % 2]
+            };
+            let settings = denoiser.gpu_settings(iteration, flags);
+            let constants: Vec<u8> = [
+                settings._step_width.to_le_bytes(),
+                settings._flags.to_le_bytes(),
+                settings._color_sigma.to_le_bytes(),
+                settings._normal_sigma.to_le_bytes(),
+                settings._depth_sigma.to_le_bytes(),
+            ]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+
+            unsafe {
+                command_buffer.record_handle(|handle| {
+                    // Wait for the ray tracing passes or the previous iteration
+                    let barriers = [*MemoryBarrier::builder()
+                        .src_access_mask(AccessFlags::SHADER_WRITE)
+                        .dst_access_mask(AccessFlags::SHADER_READ)];
+                    self.device.handle().cmd_pipeline_barrier(
+                        handle,
+                        PipelineStageFlags::RAY_TRACING_SHADER_KHR
+                            | PipelineStageFlags::COMPUTE_SHADER,
+                        PipelineStageFlags::COMPUTE_SHADER,
+                        DependencyFlags::empty(),
+                        &barriers,
+                        &[],
+                        &[],
+                    );
+                    self.device.handle().cmd_bind_pipeline(
+                        handle,
+                        PipelineBindPoint::COMPUTE,
+                        self.pipeline,
+                    );
+                    self.device.handle().cmd_bind_descriptor_sets(
+                        handle,
+                        PipelineBindPoint::COMPUTE,
+                        self.pipeline_layout,
+                        0,
+                        &[set],
+                        &[],
+                    );
+                    self.device.handle().cmd_push_constants(
+                        handle,
+                        self.pipeline_layout,
+                        ShaderStageFlags::COMPUTE,
+                        0,
+                        &constants,
+                    );
+                    self.device.handle().cmd_dispatch(
+                        handle,
+                        framebuffer.width.div_ceil(WORKGROUP_SIZE),
+                        framebuffer.height.div_ceil(WORKGROUP_SIZE),
+                        1,
+                    );
+                    handle
+                });
+            }
+        }
+
+        // Even iterations write the first image
+        if denoiser.iterations % 2 == 1 {
+            ping
+        } else {
+            pong
+        }
+    }
+}
+
+impl Drop for DenoisePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle().destroy_pipeline(self.pipeline, None);
+            self.device
+                .handle()
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .handle()
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .handle()
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
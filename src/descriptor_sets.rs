@@ -2,29 +2,36 @@ use std::rc::Rc;
 
 use ash::vk::{
     DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool, DescriptorPoolCreateInfo,
-    DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayoutBinding,
-    DescriptorSetLayoutCreateInfo, DescriptorType, ImageLayout, ImageView, PipelineLayout,
-    PipelineLayoutCreateInfo, PushConstantRange, Sampler, ShaderStageFlags, WriteDescriptorSet,
-    WriteDescriptorSetAccelerationStructureKHR,
+    DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout,
+    DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, ImageLayout,
+    ImageView, PipelineLayout, PipelineLayoutCreateInfo, PushConstantRange, Sampler,
+    ShaderStageFlags, WriteDescriptorSet, WriteDescriptorSetAccelerationStructureKHR,
 };
 
 use vk_utils::{buffer_resource::BufferResource, device_context::DeviceContext};
 
-use crate::{ctx::GpuResources, geometry::TopLevelAccelerationStructure};
+use crate::{aov::AOV_COUNT, ctx::GpuResources, geometry::TopLevelAccelerationStructure};
 pub const ACCELERATION_STRUCTURE_LOCATION: (u32, u32) = (0, 0);
-pub const OUTPUT_IMAGE_LOCATION: (u32, u32) = (0, 1);
 pub const ACCUMULATION_IMAGE_LOCATION: (u32, u32) = (0, 2);
+// First of AOV_COUNT consecutive bindings, in AovKind::ALL order
+pub const AOV_IMAGE_LOCATION: (u32, u32) = (0, 3);
+pub const MOMENT_IMAGE_LOCATION: (u32, u32) = (0, 9);
 
 pub const CAMERA_BUFFER_LOCATION: (u32, u32) = (1, 0);
 pub const BUFFER_ADDRESS_LOCATION: (u32, u32) = (1, 1);
 pub const MESH_BUFFERS_LOCATION: (u32, u32) = (1, 2);
 pub const MATERIAL_TEXTURE_LOCATION: (u32, u32) = (1, 3);
 pub const SKYBOX_TEXTURE_LOCATION: (u32, u32) = (1, 4);
+pub const SKYBOX_PROPERTIES_LOCATION: (u32, u32) = (1, 5);
+pub const SKYBOX_CUBE_TEXTURE_LOCATION: (u32, u32) = (1, 6);
 
 pub struct RTXDescriptorSets {
+    device: Rc<DeviceContext>,
+    // Sets allocated per pool, more pools are added when every set is in use
     pub max_sets: u32,
     pub next_set: u32,
-    pub descriptor_pool: DescriptorPool,
+    pub descriptor_pools: Vec<DescriptorPool>,
+    pub descriptor_set_layouts: Vec<DescriptorSetLayout>,
     pub pipeline_layout: PipelineLayout,
     pub frame_descriptors: Vec<Rc<FrameDescriptors>>,
 }
@@ -32,26 +39,38 @@ pub struct RTXDescriptorSets {
 impl RTXDescriptorSets {
     pub fn new(device: Rc<DeviceContext>, max_sets: u32) -> Self {
         unsafe {
-            let set_0_bindings = [
+            let mut set_0_bindings = vec![
                 // acceleration structure
                 *DescriptorSetLayoutBinding::builder()
                     .descriptor_count(1)
                     .descriptor_type(DescriptorType::ACCELERATION_STRUCTURE_KHR)
                     .stage_flags(ShaderStageFlags::RAYGEN_KHR | ShaderStageFlags::CLOSEST_HIT_KHR)
                     .binding(ACCELERATION_STRUCTURE_LOCATION.1),
-                // final image
+                // accumulation image
                 *DescriptorSetLayoutBinding::builder()
                     .descriptor_count(1)
                     .descriptor_type(DescriptorType::STORAGE_IMAGE)
                     .stage_flags(ShaderStageFlags::RAYGEN_KHR)
-                    .binding(OUTPUT_IMAGE_LOCATION.1),
-                // accumulation image
+                    .binding(ACCUMULATION_IMAGE_LOCATION.1),
+            ];
+            // aov images
+            for index in 0..AOV_COUNT as u32 {
+                set_0_bindings.push(
+                    *DescriptorSetLayoutBinding::builder()
+                        .descriptor_count(1)
+                        .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                        .stage_flags(ShaderStageFlags::RAYGEN_KHR)
+                        .binding(AOV_IMAGE_LOCATION.1 + index),
+                );
+            }
+            // moment image
+            set_0_bindings.push(
                 *DescriptorSetLayoutBinding::builder()
                     .descriptor_count(1)
                     .descriptor_type(DescriptorType::STORAGE_IMAGE)
                     .stage_flags(ShaderStageFlags::RAYGEN_KHR)
-                    .binding(ACCUMULATION_IMAGE_LOCATION.1),
-            ];
+                    .binding(MOMENT_IMAGE_LOCATION.1),
+            );
 
             let set_0 = *DescriptorSetLayoutCreateInfo::builder().bindings(&set_0_bindings);
 
@@ -64,7 +83,11 @@ impl RTXDescriptorSets {
                 *DescriptorSetLayoutBinding::builder()
                     .descriptor_count(1)
                     .descriptor_type(DescriptorType::UNIFORM_BUFFER)
-                    .stage_flags(ShaderStageFlags::CLOSEST_HIT_KHR)
+                    .stage_flags(
+                        ShaderStageFlags::RAYGEN_KHR
+                            | ShaderStageFlags::CLOSEST_HIT_KHR
+                            | ShaderStageFlags::MISS_KHR,
+                    )
                     .binding(BUFFER_ADDRESS_LOCATION.1),
                 *DescriptorSetLayoutBinding::builder()
                     .descriptor_count(1)
@@ -79,8 +102,18 @@ impl RTXDescriptorSets {
                 *DescriptorSetLayoutBinding::builder()
                     .descriptor_count(1)
                     .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .stage_flags(ShaderStageFlags::MISS_KHR)
+                    .stage_flags(ShaderStageFlags::MISS_KHR | ShaderStageFlags::CLOSEST_HIT_KHR)
                     .binding(SKYBOX_TEXTURE_LOCATION.1),
+                *DescriptorSetLayoutBinding::builder()
+                    .descriptor_count(1)
+                    .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                    .stage_flags(ShaderStageFlags::MISS_KHR | ShaderStageFlags::CLOSEST_HIT_KHR)
+                    .binding(SKYBOX_PROPERTIES_LOCATION.1),
+                *DescriptorSetLayoutBinding::builder()
+                    .descriptor_count(1)
+                    .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(ShaderStageFlags::MISS_KHR | ShaderStageFlags::CLOSEST_HIT_KHR)
+                    .binding(SKYBOX_CUBE_TEXTURE_LOCATION.1),
             ];
 
             let set_1 = *DescriptorSetLayoutCreateInfo::builder().bindings(&set_1_bindings);
@@ -97,8 +130,8 @@ impl RTXDescriptorSets {
             ];
 
             let constant_ranges = [*PushConstantRange::builder()
-                .size(8)
-                .stage_flags(ShaderStageFlags::RAYGEN_KHR)];
+                .size(76)
+                .stage_flags(ShaderStageFlags::RAYGEN_KHR | ShaderStageFlags::CLOSEST_HIT_KHR)];
 
             let pipeline_layout = device
                 .handle()
@@ -110,81 +143,97 @@ impl RTXDescriptorSets {
                 )
                 .expect("Pipeline layout creation failed");
 
-            let sizes = [
-                // scene
-                DescriptorPoolSize {
-                    ty: DescriptorType::ACCELERATION_STRUCTURE_KHR,
-                    descriptor_count: 1,
-                },
-                // accumulation + output image
-                DescriptorPoolSize {
-                    ty: DescriptorType::STORAGE_IMAGE,
-                    descriptor_count: 2,
-                },
-                // camera
-                DescriptorPoolSize {
-                    ty: DescriptorType::UNIFORM_BUFFER,
-                    descriptor_count: 2,
-                },
-                DescriptorPoolSize {
-                    ty: DescriptorType::STORAGE_BUFFER,
-                    descriptor_count: 1,
-                },
-                DescriptorPoolSize {
-                    ty: DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    descriptor_count: 1024,
-                },
-                DescriptorPoolSize {
-                    ty: DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    descriptor_count: 1,
-                },
-            ];
+            let mut descriptor_sets = Self {
+                device,
+                max_sets,
+                next_set: 0,
+                descriptor_pools: Vec::new(),
+                descriptor_set_layouts,
+                pipeline_layout,
+                frame_descriptors: Vec::new(),
+            };
+            descriptor_sets.grow();
+            descriptor_sets
+        }
+    }
+
+    fn grow(&mut self) {
+        // Every pool size covers max_sets frames
+        let sizes = [
+            // scene
+            DescriptorPoolSize {
+                ty: DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                descriptor_count: 1,
+            },
+            // accumulation + aov + moment images
+            DescriptorPoolSize {
+                ty: DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 2 + AOV_COUNT as u32,
+            },
+            // camera + buffer addresses + skybox properties
+            DescriptorPoolSize {
+                ty: DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 3,
+            },
+            DescriptorPoolSize {
+                ty: DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+            },
+            // material textures + equirectangular and cube skybox
+            DescriptorPoolSize {
+                ty: DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1024 + 2,
+            },
+        ]
+        .map(|size| DescriptorPoolSize {
+            descriptor_count: size.descriptor_count * self.max_sets,
+            ..size
+        });
+
+        unsafe {
             let descriptor_pool_create_info = DescriptorPoolCreateInfo::builder()
-                .max_sets(max_sets * 2)
+                .max_sets(self.max_sets * self.descriptor_set_layouts.len() as u32)
                 .pool_sizes(&sizes);
-            let descriptor_pool = device
+            let descriptor_pool = self
+                .device
                 .handle()
                 .create_descriptor_pool(&descriptor_pool_create_info, None)
                 .expect("Descriptor pool creation failed");
 
             let descriptor_set_create_info = DescriptorSetAllocateInfo::builder()
-                .set_layouts(&descriptor_set_layouts)
+                .set_layouts(&self.descriptor_set_layouts)
                 .descriptor_pool(descriptor_pool);
 
-            let frame_descriptors = (0..max_sets)
-                .into_iter()
-                .map(|_| {
-                    device
-                        .handle()
-                        .allocate_descriptor_sets(&descriptor_set_create_info)
-                        .expect("Descriptor set allocation failed")
-                })
-                .map(|sets| {
-                    Rc::new(FrameDescriptors {
-                        device: device.clone(),
-                        sets,
-                    })
-                })
-                .collect();
-
-            Self {
-                max_sets,
-                next_set: 0,
-                pipeline_layout,
-                descriptor_pool,
-                frame_descriptors,
+            for _ in 0..self.max_sets {
+                let sets = self
+                    .device
+                    .handle()
+                    .allocate_descriptor_sets(&descriptor_set_create_info)
+                    .expect("Descriptor set allocation failed");
+                self.frame_descriptors
+                    .push(Rc::new(FrameDescriptors::new(self.device.clone(), sets)));
             }
+            self.descriptor_pools.push(descriptor_pool);
         }
     }
 
+    // Takes a set that no FrameResources holds anymore. Allocates more when they are all
+    // taken, for example by the frames of several framebuffers.
     pub fn next(&mut self, resources: &GpuResources) -> Rc<FrameDescriptors> {
-        let next = self.next_set;
-        self.next_set += 1;
-        self.next_set %= self.max_sets;
-        Rc::get_mut(&mut self.frame_descriptors[next as usize])
-            .unwrap()
-            .update(resources);
-        self.frame_descriptors[next as usize].clone()
+        let count = self.frame_descriptors.len();
+        let free = (0..count)
+            .map(|offset| (self.next_set as usize + offset) % count)
+            .find(|index| Rc::strong_count(&self.frame_descriptors[*index]) == 1);
+        let next = match free {
+            Some(index) => index,
+            None => {
+                self.grow();
+                count
+            }
+        };
+        self.next_set = ((next + 1) % self.frame_descriptors.len()) as u32;
+        self.frame_descriptors[next].update(resources);
+        self.frame_descriptors[next].clone()
     }
 }
 
@@ -198,14 +247,21 @@ impl FrameDescriptors {
         Self { sets, device }
     }
 
-    pub fn update(&mut self, resources: &GpuResources) {
+    pub fn update(&self, resources: &GpuResources) {
         self.update_acceleration_structure(&resources.acceleration_structure);
         self.update_images(&resources.image_views, &resources.sampler);
         self.update_buffer_address_buffer(&resources.buffer_address_buffer);
         self.update_geometry_address_buffer(&resources.geometry_address_buffer);
         self.update_camera_buffer(&resources.camera_buffer);
-        self.update_output_images(&resources.output_image_views);
-        self.update_skybox(&resources.skybox_image_view, &resources.sampler);
+        self.update_accumulation_image(&resources.accumulation_image_view);
+        self.update_aov_images(&resources.aov_image_views);
+        self.update_moment_image(&resources.moment_image_view);
+        self.update_skybox(
+            &resources.skybox_image_view,
+            &resources.skybox_cube_image_view,
+            &resources.sampler,
+        );
+        self.update_skybox_properties(&resources.skybox_buffer);
     }
 
     fn update_acceleration_structure(&self, acc_structure: &TopLevelAccelerationStructure) {
@@ -303,43 +359,108 @@ impl FrameDescriptors {
         }
     }
 
-    fn update_output_images(&self, image_views: &[ImageView; 2]) {
-        let image_writes = [*DescriptorImageInfo::builder()
-            .image_view(image_views[0])
-            .image_layout(ImageLayout::GENERAL)];
+    fn update_accumulation_image(&self, image_view: &ImageView) {
         let accumulation_image_writes = [*DescriptorImageInfo::builder()
-            .image_view(image_views[1])
+            .image_view(*image_view)
             .image_layout(ImageLayout::GENERAL)];
 
-        let writes = [
-            *WriteDescriptorSet::builder()
-                .image_info(&image_writes)
-                .dst_set(self.sets[OUTPUT_IMAGE_LOCATION.0 as usize])
-                .dst_binding(OUTPUT_IMAGE_LOCATION.1)
-                .descriptor_type(DescriptorType::STORAGE_IMAGE),
-            *WriteDescriptorSet::builder()
-                .image_info(&accumulation_image_writes)
-                .dst_set(self.sets[ACCUMULATION_IMAGE_LOCATION.0 as usize])
-                .dst_binding(ACCUMULATION_IMAGE_LOCATION.1)
-                .descriptor_type(DescriptorType::STORAGE_IMAGE),
-        ];
+        let writes = [*WriteDescriptorSet::builder()
+            .image_info(&accumulation_image_writes)
+            .dst_set(self.sets[ACCUMULATION_IMAGE_LOCATION.0 as usize])
+            .dst_binding(ACCUMULATION_IMAGE_LOCATION.1)
+            .descriptor_type(DescriptorType::STORAGE_IMAGE)];
 
         unsafe {
             self.device.handle().update_descriptor_sets(&writes, &[]);
         }
     }
 
-    fn update_skybox(&self, image_view: &ImageView, sampler: &Sampler) {
+    fn update_moment_image(&self, image_view: &ImageView) {
+        let infos = [*DescriptorImageInfo::builder()
+            .image_view(*image_view)
+            .image_layout(ImageLayout::GENERAL)];
+
+        let writes = [*WriteDescriptorSet::builder()
+            .image_info(&infos)
+            .dst_set(self.sets[MOMENT_IMAGE_LOCATION.0 as usize])
+            .dst_binding(MOMENT_IMAGE_LOCATION.1)
+            .descriptor_type(DescriptorType::STORAGE_IMAGE)];
+
+        unsafe {
+            self.device.handle().update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    fn update_aov_images(&self, image_views: &[ImageView; AOV_COUNT]) {
+        let infos: Vec<[DescriptorImageInfo; 1]> = image_views
+            .iter()
+            .map(|view| {
+                [*DescriptorImageInfo::builder()
+                    .image_view(*view)
+                    .image_layout(ImageLayout::GENERAL)]
+            })
+            .collect();
+
+        let writes: Vec<WriteDescriptorSet> = infos
+            .iter()
+            .enumerate()
+            .map(|(index, info)| {
+                *WriteDescriptorSet::builder()
+                    .image_info(info)
+                    .dst_set(self.sets[AOV_IMAGE_LOCATION.0 as usize])
+                    .dst_binding(AOV_IMAGE_LOCATION.1 + index as u32)
+                    .descriptor_type(DescriptorType::STORAGE_IMAGE)
+            })
+            .collect();
+
+        unsafe {
+            self.device.handle().update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    fn update_skybox_properties(&self, buffer: &BufferResource) {
+        let info = [*DescriptorBufferInfo::builder()
+            .buffer(buffer.buffer)
+            .range(buffer.content_size())];
+
+        let writes = [*WriteDescriptorSet::builder()
+            .buffer_info(&info)
+            .dst_set(self.sets[SKYBOX_PROPERTIES_LOCATION.0 as usize])
+            .dst_binding(SKYBOX_PROPERTIES_LOCATION.1)
+            .descriptor_type(DescriptorType::UNIFORM_BUFFER)];
+
+        unsafe {
+            self.device.handle().update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    fn update_skybox(
+        &self,
+        image_view: &ImageView,
+        cube_image_view: &ImageView,
+        sampler: &Sampler,
+    ) {
         let image_writes = [*DescriptorImageInfo::builder()
             .image_view(*image_view)
             .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .sampler(*sampler)];
+        let cube_image_writes = [*DescriptorImageInfo::builder()
+            .image_view(*cube_image_view)
+            .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*sampler)];
 
-        let writes = [*WriteDescriptorSet::builder()
-            .image_info(&image_writes)
-            .dst_set(self.sets[SKYBOX_TEXTURE_LOCATION.0 as usize])
-            .dst_binding(SKYBOX_TEXTURE_LOCATION.1)
-            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)];
+        let writes = [
+            *WriteDescriptorSet::builder()
+                .image_info(&image_writes)
+                .dst_set(self.sets[SKYBOX_TEXTURE_LOCATION.0 as usize])
+                .dst_binding(SKYBOX_TEXTURE_LOCATION.1)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER),
+            *WriteDescriptorSet::builder()
+                .image_info(&cube_image_writes)
+                .dst_set(self.sets[SKYBOX_CUBE_TEXTURE_LOCATION.0 as usize])
+                .dst_binding(SKYBOX_CUBE_TEXTURE_LOCATION.1)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER),
+        ];
 
         unsafe {
             self.device.handle().update_descriptor_sets(&writes, &[]);
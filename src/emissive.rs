@@ -0,0 +1,105 @@
+use cgmath::{InnerSpace, Transform};
+
+use crate::material::Material;
+use crate::math::{Mat4, Point, Vec3};
+use crate::mesh_resource::MeshResource;
+use crate::sampling::AliasTable;
+
+pub fn luminance(c: Vec3) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+// Relative radiance used to distribute light samples, zero if the material doesn't emit.
+// Emission textures scale the factor and a unorm texel is at most one, so the factor
+// bounds textured emitters too.
+pub fn emissive_weight(material: &Material) -> f32 {
+    luminance(material.emission.truncate()) * material.emission.w
+}
+
+// World space triangles of every emissive instance in the scene. The triangles of an
+// instance are stored consecutively, so the closest hit shader finds the triangle it hit
+// at emissive offset + primitive index.
+#[derive(Default)]
+pub struct EmissiveTriangles {
+    triangles: Vec<GpuEmissiveTriangle>,
+    weights: Vec<f32>,
+}
+
+impl EmissiveTriangles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the emissive offset of the instance, None if its material doesn't emit
+    pub fn add_instance(
+        &mut self,
+        instance_index: u32,
+        transform: &Mat4,
+        mesh: &MeshResource,
+        material: &Material,
+    ) -> Option<u32> {
+        let radiance = emissive_weight(material);
+        if radiance <= 0.0 {
+            return None;
+        }
+
+        let offset = self.triangles.len() as u32;
+        for (primitive, triangle) in mesh.indices.chunks_exact(3).enumerate() {
+            let [v0, v1, v2] = [0, 1, 2].map(|i| {
+                let p = mesh.vertices[triangle[i] as usize];
+                let p = transform.transform_point(Point::new(p.x, p.y, p.z));
+                Vec3::new(p.x, p.y, p.z)
+            });
+            let area = 0.5 * (v1 - v0).cross(v2 - v0).magnitude();
+            self.weights.push(area * radiance);
+            self.triangles.push(GpuEmissiveTriangle {
+                _v0: v0,
+                _instance: instance_index,
+                _v1: v1,
+                _primitive: primitive as u32,
+                _v2: v2,
+                _pdf: 0.0,
+                _threshold: 1.0,
+                _alias: 0,
+            });
+        }
+
+        Some(offset)
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    // Fills in the power weighted alias table
+    pub fn build(mut self) -> Vec<GpuEmissiveTriangle> {
+        if self.triangles.is_empty() {
+            return self.triangles;
+        }
+
+        let table = AliasTable::new(&self.weights);
+        for (triangle, entry) in self.triangles.iter_mut().zip(table.entries()) {
+            triangle._pdf = entry.pdf;
+            triangle._threshold = entry.threshold;
+            triangle._alias = entry.alias;
+        }
+
+        self.triangles
+    }
+}
+
+#[repr(C)]
+pub struct GpuEmissiveTriangle {
+    pub _v0: Vec3,
+    pub _instance: u32,
+    pub _v1: Vec3,
+    pub _primitive: u32,
+    pub _v2: Vec3,
+    pub _pdf: f32,
+    pub _threshold: f32,
+    pub _alias: u32,
+}
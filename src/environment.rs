@@ -0,0 +1,202 @@
+use std::f32::consts::PI;
+
+use crate::emissive::luminance;
+use crate::image_resource::TextureImageData;
+use crate::math::{Vec2, Vec3};
+
+// Same mapping as direction_to_spherical in ray_miss.rmiss
+pub fn direction_to_spherical(dir: Vec3) -> Vec2 {
+    let s = (dir.y.atan2(-dir.x) / (2.0 * PI)).rem_euclid(1.0);
+    let t = (-dir.z).clamp(-1.0, 1.0).acos() / PI;
+    Vec2::new(s, t)
+}
+
+pub fn spherical_to_direction(uv: Vec2) -> Vec3 {
+    let phi = uv.x * 2.0 * PI;
+    let theta = uv.y * PI;
+    Vec3::new(
+        -theta.sin() * phi.cos(),
+        theta.sin() * phi.sin(),
+        -theta.cos(),
+    )
+}
+
+// Piecewise constant distribution over an equirectangular image, sampled by first
+// picking a row from the marginal CDF and then a column from that row's conditional CDF.
+// Every pixel is weighted by its luminance and by sin(theta) to account for the
+// compression of the rows towards the poles.
+pub struct EnvironmentDistribution {
+    width: u32,
+    height: u32,
+    // height + 1 entries, starting at 0 and ending at 1
+    marginal_cdf: Vec<f32>,
+    // height rows of width + 1 entries
+    conditional_cdf: Vec<f32>,
+    // Density with respect to the image uv
+    pdf: Vec<f32>,
+}
+
+impl EnvironmentDistribution {
+    pub fn new(image: &TextureImageData) -> Self {
+        let gamma = if image.is_gamma_encoded() { 2.2 } else { 1.0 };
+        let mut luminances = Vec::with_capacity((image.width * image.height) as usize);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let texel = image
+                    .texel(x, y)
+                    .unwrap_or_else(|| cgmath::vec4(1.0, 1.0, 1.0, 1.0));
+                let linear = Vec3::new(
+                    texel.x.max(0.0).powf(gamma),
+                    texel.y.max(0.0).powf(gamma),
+                    texel.z.max(0.0).powf(gamma),
+                );
+                luminances.push(luminance(linear));
+            }
+        }
+
+        Self::from_luminance(image.width, image.height, &luminances)
+    }
+
+    pub fn from_luminance(width: u32, height: u32, luminances: &[f32]) -> Self {
+        assert!(width > 0 && height > 0);
+        assert_eq!(luminances.len(), (width * height) as usize);
+
+        let w = width as usize;
+        let h = height as usize;
+        let mut weights: Vec<f32> = Vec::with_capacity(w * h);
+        for y in 0..h {
+            let sin_theta = (PI * (y as f32 + 0.5) / h as f32).sin();
+            for x in 0..w {
+                let l = luminances[y * w + x];
+                weights.push(if l.is_finite() {
+                    l.max(0.0) * sin_theta
+                } else {
+                    0.0
+                });
+            }
+        }
+
+        let total: f64 = weights.iter().map(|w| *w as f64).sum();
+        if total <= 0.0 {
+            // Nothing to importance sample, fall back to sampling by solid angle
+            for y in 0..h {
+                let sin_theta = (PI * (y as f32 + 0.5) / h as f32).sin();
+                weights[y * w..(y + 1) * w].fill(sin_theta);
+            }
+        }
+
+        let mut conditional_cdf = Vec::with_capacity(h * (w + 1));
+        let mut row_sums = Vec::with_capacity(h);
+        for row in weights.chunks_exact(w) {
+            let sum: f64 = row.iter().map(|w| *w as f64).sum();
+            row_sums.push(sum);
+            let mut running = 0.0;
+            conditional_cdf.push(0.0);
+            for (x, weight) in row.iter().enumerate() {
+                running += *weight as f64;
+                conditional_cdf.push(if sum > 0.0 {
+                    (running / sum) as f32
+                } else {
+                    (x + 1) as f32 / w as f32
+                });
+            }
+            // Guard against rounding errors
+            let last = conditional_cdf.len() - 1;
+            conditional_cdf[last] = 1.0;
+        }
+
+        let total: f64 = row_sums.iter().sum();
+        let mut marginal_cdf = Vec::with_capacity(h + 1);
+        let mut running = 0.0;
+        marginal_cdf.push(0.0);
+        for sum in &row_sums {
+            running += sum;
+            marginal_cdf.push((running / total) as f32);
+        }
+        marginal_cdf[h] = 1.0;
+
+        let mean = total / (w * h) as f64;
+        let pdf = weights.iter().map(|w| (*w as f64 / mean) as f32).collect();
+
+        Self {
+            width,
+            height,
+            marginal_cdf,
+            conditional_cdf,
+            pdf,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn marginal_cdf(&self) -> &[f32] {
+        &self.marginal_cdf
+    }
+
+    pub fn conditional_cdf(&self, row: u32) -> &[f32] {
+        let stride = self.width as usize + 1;
+        &self.conditional_cdf[row as usize * stride..(row as usize + 1) * stride]
+    }
+
+    // Density of uv with respect to the image area
+    pub fn pdf_uv(&self, uv: Vec2) -> f32 {
+        let x = ((uv.x * self.width as f32) as u32).min(self.width - 1);
+        let y = ((uv.y * self.height as f32) as u32).min(self.height - 1);
+        self.pdf[(y * self.width + x) as usize]
+    }
+
+    // Density with respect to solid angle
+    pub fn pdf(&self, dir: Vec3) -> f32 {
+        let uv = direction_to_spherical(dir);
+        let sin_theta = (uv.y * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.pdf_uv(uv) / (2.0 * PI * PI * sin_theta)
+    }
+
+    // Maps two uniform numbers in [0, 1) to an image uv and its pdf with respect to the image area
+    pub fn sample_uv(&self, u: Vec2) -> (Vec2, f32) {
+        let (y, dy) = sample_cdf(&self.marginal_cdf, u.y);
+        let (x, dx) = sample_cdf(self.conditional_cdf(y as u32), u.x);
+        let uv = Vec2::new(
+            (x as f32 + dx) / self.width as f32,
+            (y as f32 + dy) / self.height as f32,
+        );
+        (uv, self.pdf[y * self.width as usize + x])
+    }
+
+    // Marginal CDF, conditional CDFs and the pdf table, in that order, as read by environment.glsl
+    pub fn to_gpu(&self) -> Vec<f32> {
+        let mut data = Vec::with_capacity(
+            self.marginal_cdf.len() + self.conditional_cdf.len() + self.pdf.len(),
+        );
+        data.extend_from_slice(&self.marginal_cdf);
+        data.extend_from_slice(&self.conditional_cdf);
+        data.extend_from_slice(&self.pdf);
+        data
+    }
+}
+
+// Returns the interval containing u and the offset of u within that interval
+fn sample_cdf(cdf: &[f32], u: f32) -> (usize, f32) {
+    let count = cdf.len() - 1;
+    // First entry greater than u, minus one
+    let index = cdf
+        .partition_point(|c| *c <= u)
+        .saturating_sub(1)
+        .min(count - 1);
+    let width = cdf[index + 1] - cdf[index];
+    let offset = if width > 0.0 {
+        ((u - cdf[index]) / width).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (index, offset)
+}
@@ -0,0 +1,436 @@
+// glTF 2.0 writer. A .glb holds everything in one file, a .gltf gets its binary buffer in
+// a .bin file next to it. Textures are stored as 8 bit RGBA PNG images in the buffer.
+// Sheen, clear coat, ior and transmission have no counterpart the importer reads and are
+// left out.
+use std::collections::{BTreeSet, HashMap};
+use std::io::Cursor;
+use std::path::Path;
+
+use ash::vk::Format;
+use cgmath::SquareMatrix;
+use serde_json::{json, Value};
+
+use crate::image_resource::TextureImageData;
+use crate::import::{ImportedMaterial, ImportedScene};
+use crate::material::{NormalMapConvention, TextureTransform};
+use crate::math::Mat4;
+use crate::mesh_resource::MeshResource;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON_CHUNK: u32 = 0x4E4F534A;
+const GLB_BIN_CHUNK: u32 = 0x004E4942;
+
+pub fn save(imported: &ImportedScene, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let binary = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("glb"));
+
+    let mut builder = Builder::default();
+    let mut root = builder.document(imported)?;
+    let data = builder.data;
+
+    if binary {
+        if !data.is_empty() {
+            root["buffers"] = json!([{ "byteLength": data.len() }]);
+        }
+        std::fs::write(path, glb(&root, &data))
+    } else {
+        if !data.is_empty() {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "scene".to_string());
+            let file = format!("{}.bin", stem);
+            std::fs::write(path.with_file_name(&file), &data)?;
+            root["buffers"] = json!([{ "uri": percent_encode(&file), "byteLength": data.len() }]);
+        }
+        let json = serde_json::to_string_pretty(&root).expect("glTF documents always serialize");
+        std::fs::write(path, json)
+    }
+}
+
+// Collects the binary buffer and the objects that refer into it
+#[derive(Default)]
+struct Builder {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+    images: Vec<Value>,
+    textures: Vec<Value>,
+    extensions: BTreeSet<&'static str>,
+    // glTF texture of every imported texture, and whether its green channel is flipped
+    written_textures: HashMap<(usize, bool), usize>,
+}
+
+impl Builder {
+    fn document(&mut self, imported: &ImportedScene) -> std::io::Result<Value> {
+        let materials = imported
+            .materials
+            .iter()
+            .map(|material| self.material(material, &imported.textures))
+            .collect::<std::io::Result<Vec<Value>>>()?;
+
+        // A glTF mesh has its material in the primitive, so every combination of mesh and
+        // material an instance uses is a mesh. They share the accessors of the mesh.
+        let mut attributes = HashMap::new();
+        let mut meshes = Vec::new();
+        let mut mesh_indices = HashMap::new();
+        let mut nodes = Vec::new();
+        for instance in &imported.instances {
+            let key = (instance.mesh, instance.material);
+            let mesh = match mesh_indices.get(&key) {
+                Some(index) => *index,
+                None => {
+                    let exported = &imported.meshes[instance.mesh];
+                    let (indices, attributes) = attributes
+                        .entry(instance.mesh)
+                        .or_insert_with(|| self.mesh(&exported.resource))
+                        .clone();
+                    let mut primitive = json!({ "attributes": attributes, "indices": indices });
+                    if let Some(material) = instance.material {
+                        primitive["material"] = json!(material);
+                    }
+                    meshes.push(json!({ "name": exported.name, "primitives": [primitive] }));
+                    mesh_indices.insert(key, meshes.len() - 1);
+                    meshes.len() - 1
+                }
+            };
+            let mut node = json!({ "mesh": mesh });
+            if instance.transform != Mat4::identity() {
+                node["matrix"] = matrix(&instance.transform);
+            }
+            nodes.push(node);
+        }
+
+        let mut cameras = Vec::new();
+        if let Some(camera) = &imported.camera {
+            cameras.push(json!({
+                "type": "perspective",
+                "perspective": {
+                    "yfov": camera.fov().to_radians(),
+                    "znear": camera.z_near(),
+                    "zfar": camera.z_far(),
+                }
+            }));
+            // The renderer places a camera at its view_matrix, the inverse of its transform
+            nodes.push(json!({ "camera": 0, "matrix": matrix(&camera.view_matrix()) }));
+        }
+
+        let mut root = json!({
+            "asset": { "version": "2.0", "generator": "renderer" },
+            "scene": 0,
+            "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<usize>>() }],
+            "nodes": nodes,
+        });
+        let arrays = [
+            ("meshes", meshes),
+            ("materials", materials),
+            ("cameras", cameras),
+            ("accessors", std::mem::take(&mut self.accessors)),
+            ("bufferViews", std::mem::take(&mut self.views)),
+            ("images", std::mem::take(&mut self.images)),
+            ("textures", std::mem::take(&mut self.textures)),
+        ];
+        for (name, values) in arrays {
+            if !values.is_empty() {
+                root[name] = Value::Array(values);
+            }
+        }
+        if !self.extensions.is_empty() {
+            root["extensionsUsed"] = json!(self.extensions);
+        }
+        Ok(root)
+    }
+
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        // Views start 4 byte aligned so every accessor type can read them
+        self.data.resize(self.data.len().next_multiple_of(4), 0);
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.data.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.data.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    // POSITION accessors need their bounds
+    fn float_accessor<const N: usize>(&mut self, values: &[[f32; N]], bounds: bool) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let view = self.view(&bytes, Some(ARRAY_BUFFER));
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": format!("VEC{}", N),
+        });
+        if bounds {
+            let component = |i: usize| values.iter().map(move |value| value[i]);
+            let min: Vec<f32> = (0..N)
+                .map(|i| component(i).fold(f32::INFINITY, f32::min))
+                .collect();
+            let max: Vec<f32> = (0..N)
+                .map(|i| component(i).fold(f32::NEG_INFINITY, f32::max))
+                .collect();
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    // Index accessor and attributes of the primitives using the mesh
+    fn mesh(&mut self, mesh: &MeshResource) -> (usize, Value) {
+        let bytes: Vec<u8> = mesh
+            .indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect();
+        let view = self.view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": mesh.indices.len(),
+            "type": "SCALAR",
+        }));
+        let indices = self.accessors.len() - 1;
+
+        let vertices: Vec<[f32; 3]> = mesh.vertices.iter().map(|&v| v.into()).collect();
+        let mut attributes = json!({ "POSITION": self.float_accessor(&vertices, true) });
+        if mesh.normals.len() == mesh.vertices.len() {
+            let normals: Vec<[f32; 3]> = mesh.normals.iter().map(|&n| n.into()).collect();
+            attributes["NORMAL"] = json!(self.float_accessor(&normals, false));
+        }
+        // Tangents are kept without their handedness, glTF requires one
+        if mesh.tangents.len() == mesh.vertices.len() {
+            let tangents: Vec<[f32; 4]> = mesh
+                .tangents
+                .iter()
+                .map(|&t| t.extend(1.0).into())
+                .collect();
+            attributes["TANGENT"] = json!(self.float_accessor(&tangents, false));
+        }
+        for (set, tex_coords) in mesh.tex_coords.iter().enumerate() {
+            let tex_coords: Vec<[f32; 2]> = tex_coords.iter().map(|&uv| uv.into()).collect();
+            attributes[format!("TEXCOORD_{}", set)] =
+                json!(self.float_accessor(&tex_coords, false));
+        }
+        if let Some(colors) = &mesh.colors {
+            let colors: Vec<[f32; 4]> = colors.iter().map(|&c| c.into()).collect();
+            attributes["COLOR_0"] = json!(self.float_accessor(&colors, false));
+        }
+        (indices, attributes)
+    }
+
+    // 8 bit textures are written to PNGs texel for texel. Half and float textures are
+    // rejected, PNGs would clamp them to 8 bit.
+    fn texture(
+        &mut self,
+        textures: &[TextureImageData],
+        index: usize,
+        flip_green: bool,
+    ) -> std::io::Result<usize> {
+        if let Some(written) = self.written_textures.get(&(index, flip_green)) {
+            return Ok(*written);
+        }
+        let texture = &textures[index];
+        let eight_bit = matches!(
+            texture.format,
+            Format::R8_UINT
+                | Format::R8_UNORM
+                | Format::R8G8_UINT
+                | Format::R8G8_UNORM
+                | Format::R8G8B8_UINT
+                | Format::R8G8B8_UNORM
+                | Format::R8G8B8A8_UINT
+                | Format::R8G8B8A8_UNORM
+                | Format::B8G8R8A8_UINT
+                | Format::B8G8R8A8_UNORM
+        );
+        if !eight_bit {
+            return Err(invalid_input(format!(
+                "Texture {} isn't an 8 bit texture, glTF can't store {:?}",
+                index, texture.format
+            )));
+        }
+        let mut pixels = Vec::with_capacity((texture.width * texture.height * 4) as usize);
+        for y in 0..texture.height {
+            for x in 0..texture.width {
+                let mut texel = texture
+                    .texel(x, y)
+                    .ok_or_else(|| invalid_input(format!("Texture {} is too small", index)))?;
+                if flip_green {
+                    texel.y = 1.0 - texel.y;
+                }
+                let texel: [f32; 4] = texel.into();
+                pixels.extend(texel.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+            }
+        }
+        let image = image::RgbaImage::from_raw(texture.width, texture.height, pixels)
+            .ok_or_else(|| invalid_input(format!("Texture {} is too small", index)))?;
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+        let view = self.view(&png, None);
+        self.images
+            .push(json!({ "bufferView": view, "mimeType": "image/png" }));
+        self.textures
+            .push(json!({ "source": self.images.len() - 1 }));
+        self.written_textures
+            .insert((index, flip_green), self.textures.len() - 1);
+        Ok(self.textures.len() - 1)
+    }
+
+    fn texture_info(&mut self, texture: usize, transform: &TextureTransform) -> Value {
+        let mut info = json!({ "index": texture });
+        if transform.tex_coord != 0 {
+            info["texCoord"] = json!(transform.tex_coord);
+        }
+        let untransformed = TextureTransform {
+            tex_coord: transform.tex_coord,
+            ..TextureTransform::new()
+        };
+        if *transform != untransformed {
+            self.extensions.insert("KHR_texture_transform");
+            let offset: [f32; 2] = transform.offset.into();
+            let scale: [f32; 2] = transform.scale.into();
+            info["extensions"] = json!({
+                "KHR_texture_transform": {
+                    "offset": offset,
+                    "rotation": transform.rotation,
+                    "scale": scale,
+                }
+            });
+        }
+        info
+    }
+
+    fn material(
+        &mut self,
+        imported: &ImportedMaterial,
+        textures: &[TextureImageData],
+    ) -> std::io::Result<Value> {
+        let material = &imported.material;
+        let base_color: [f32; 4] = material.base_color.into();
+        let mut pbr = json!({
+            "baseColorFactor": base_color,
+            "metallicFactor": material.metallic,
+            "roughnessFactor": material.roughness,
+        });
+        if let Some(texture) = imported.base_color_texture {
+            let texture = self.texture(textures, texture, false)?;
+            pbr["baseColorTexture"] =
+                self.texture_info(texture, &material.base_color_texture_transform);
+        }
+        if let Some(texture) = imported.metallic_roughness_texture {
+            let texture = self.texture(textures, texture, false)?;
+            pbr["metallicRoughnessTexture"] =
+                self.texture_info(texture, &material.metallic_roughness_texture_transform);
+        }
+
+        let mut exported = json!({ "name": imported.name, "pbrMetallicRoughness": pbr });
+        if let Some(texture) = imported.normal_texture {
+            // glTF normal maps use the OpenGL convention
+            let flip_green = material.normal_map_convention == NormalMapConvention::DirectX;
+            let texture = self.texture(textures, texture, flip_green)?;
+            let mut info = self.texture_info(texture, &material.normal_texture_transform);
+            info["scale"] = json!(material.normal_scale);
+            exported["normalTexture"] = info;
+        }
+        if let Some(texture) = imported.occlusion_texture {
+            let texture = self.texture(textures, texture, false)?;
+            let mut info = self.texture_info(texture, &material.occlusion_texture_transform);
+            info["strength"] = json!(material.occlusion_strength);
+            exported["occlusionTexture"] = info;
+        }
+
+        // The emissive factor is limited to 1, brighter emission needs a strength. w scales
+        // the radiance like in the shader, glTF has nothing else to keep it in.
+        let emission = material.emission.truncate() * material.emission.w;
+        let strength = emission.x.max(emission.y).max(emission.z);
+        let emissive_factor: [f32; 3] = if strength > 1.0 {
+            self.extensions.insert("KHR_materials_emissive_strength");
+            exported["extensions"] = json!({
+                "KHR_materials_emissive_strength": { "emissiveStrength": strength }
+            });
+            (emission / strength).into()
+        } else {
+            emission.into()
+        };
+        exported["emissiveFactor"] = json!(emissive_factor);
+        if let Some(texture) = imported.emission_texture {
+            let texture = self.texture(textures, texture, false)?;
+            exported["emissiveTexture"] =
+                self.texture_info(texture, &material.emission_texture_transform);
+        }
+        Ok(exported)
+    }
+}
+
+// Column major, like Mat4
+fn matrix(matrix: &Mat4) -> Value {
+    let columns: [[f32; 4]; 4] = (*matrix).into();
+    json!(columns.concat())
+}
+
+fn glb(root: &Value, data: &[u8]) -> Vec<u8> {
+    let mut json = serde_json::to_vec(root).expect("glTF documents always serialize");
+    // Chunks are 4 byte aligned, the JSON chunk is padded with spaces
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let mut bin = data.to_vec();
+    bin.resize(bin.len().next_multiple_of(4), 0);
+
+    let mut chunks = vec![(GLB_JSON_CHUNK, json)];
+    if !bin.is_empty() {
+        chunks.push((GLB_BIN_CHUNK, bin));
+    }
+    let length = 12
+        + chunks
+            .iter()
+            .map(|(_, chunk)| 8 + chunk.len())
+            .sum::<usize>();
+    let mut glb = Vec::with_capacity(length);
+    glb.extend_from_slice(GLB_MAGIC);
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(length as u32).to_le_bytes());
+    for (kind, chunk) in chunks {
+        glb.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&kind.to_le_bytes());
+        glb.extend_from_slice(&chunk);
+    }
+    glb
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
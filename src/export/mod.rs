@@ -0,0 +1,106 @@
+// Exporters for interchange formats. They write an ImportedScene, the same CPU side form
+// the importers produce, which collect gathers from a Ctx.
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::ctx::{Ctx, Handle};
+use crate::import::{ImportedInstance, ImportedMaterial, ImportedMesh, ImportedScene};
+use crate::scene::Scene;
+
+pub mod gltf;
+
+// The camera of the scene and its instances with the meshes, materials and textures they
+// use. Lights and the skybox are left out.
+pub fn collect(ctx: &Ctx, scene: &Scene) -> std::io::Result<ImportedScene> {
+    let mut imported = ImportedScene::new();
+    imported.camera = Some(*scene.camera());
+    let mut meshes = HashMap::new();
+    let mut materials = HashMap::new();
+    let mut textures = HashMap::new();
+
+    for handle in scene.instances() {
+        let instance = ctx
+            .instance(*handle)
+            .ok_or_else(|| invalid_input("The scene refers to a removed instance".to_string()))?;
+
+        let mesh = match meshes.get(&instance.mesh()) {
+            Some(index) => *index,
+            None => {
+                let resource = ctx.mesh_resource(instance.mesh()).ok_or_else(|| {
+                    invalid_input("An instance refers to a removed mesh".to_string())
+                })?;
+                imported.meshes.push(ImportedMesh {
+                    name: format!("mesh {}", imported.meshes.len()),
+                    resource: resource.clone(),
+                });
+                meshes.insert(instance.mesh(), imported.meshes.len() - 1);
+                imported.meshes.len() - 1
+            }
+        };
+
+        let material = match materials.get(&instance.material()) {
+            Some(index) => *index,
+            None => {
+                let material = ctx.material(instance.material()).ok_or_else(|| {
+                    invalid_input("An instance refers to a removed material".to_string())
+                })?;
+                let mut texture = |texture: Option<Handle>| -> std::io::Result<Option<usize>> {
+                    let texture = match texture {
+                        Some(texture) => texture,
+                        None => return Ok(None),
+                    };
+                    if let Some(index) = textures.get(&texture) {
+                        return Ok(Some(*index));
+                    }
+                    let data = ctx.texture_data(texture).ok_or_else(|| {
+                        invalid_input(
+                            "A material uses a texture that wasn't made by Ctx::create_texture"
+                                .to_string(),
+                        )
+                    })?;
+                    imported.textures.push(data.clone());
+                    textures.insert(texture, imported.textures.len() - 1);
+                    Ok(Some(imported.textures.len() - 1))
+                };
+                let name = format!("material {}", imported.materials.len());
+                let mut exported = ImportedMaterial::new(&name, material.clone());
+                exported.base_color_texture = texture(material.base_color_texture)?;
+                exported.metallic_roughness_texture = texture(material.metallic_roughness_texture)?;
+                exported.normal_texture = texture(material.normal_texture)?;
+                exported.emission_texture = texture(material.emission_texture)?;
+                exported.occlusion_texture = texture(material.occlusion_texture)?;
+                imported.materials.push(exported);
+                materials.insert(instance.material(), imported.materials.len() - 1);
+                imported.materials.len() - 1
+            }
+        };
+
+        imported.instances.push(ImportedInstance {
+            mesh,
+            material: Some(material),
+            transform: *instance.transform(),
+        });
+    }
+
+    Ok(imported)
+}
+
+// Picks the exporter from the file extension
+pub fn save(ctx: &Ctx, scene: &Scene, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf" | "glb") => gltf::save(&collect(ctx, scene)?, path),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("No exporter for {}", path.display()),
+        )),
+    }
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,147 @@
+use std::f32::consts::PI;
+
+// Entries of the inverse CDF the ray generation shader interpolates
+pub const FILTER_TABLE_SIZE: usize = 64;
+// Integration steps used to build the table
+const FILTER_INTEGRATION_STEPS: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum FilterKind {
+    // Uniform jitter inside the pixel, the filter of earlier versions
+    #[default]
+    Box,
+    Tent,
+    // Truncated at the radius, three standard deviations
+    Gaussian,
+    // B = C = 1/3, has negative lobes that sharpen
+    Mitchell,
+    BlackmanHarris,
+}
+
+impl FilterKind {
+    pub fn default_radius(&self) -> f32 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::BlackmanHarris => 1.5,
+        }
+    }
+}
+
+// Separable reconstruction filter. Sample positions are drawn in proportion to the
+// filter, negative lobes flip the sign of the sample weight, so accumulating the
+// weighted samples and dividing by the count stays correct.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelFilter {
+    pub kind: FilterKind,
+    // In pixels, measured from the pixel center
+    pub radius: f32,
+}
+
+impl Default for PixelFilter {
+    fn default() -> Self {
+        Self::from(FilterKind::default())
+    }
+}
+
+impl From<FilterKind> for PixelFilter {
+    fn from(kind: FilterKind) -> Self {
+        Self::new(kind, kind.default_radius())
+    }
+}
+
+// One table entry, the offset from the pixel center and the weight of samples there
+pub type GpuFilterEntry = [f32; 4];
+
+impl PixelFilter {
+    pub fn new(kind: FilterKind, radius: f32) -> Self {
+        assert!(radius > 0.0, "Filter radius must be positive");
+        Self { kind, radius }
+    }
+
+    // One dimensional filter value, zero outside of the radius
+    pub fn evaluate(&self, x: f32) -> f32 {
+        if x.abs() > self.radius {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - x.abs() / self.radius,
+            FilterKind::Gaussian => {
+                let sigma = self.radius / 3.0;
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(self.radius)).max(0.0)
+            }
+            FilterKind::Mitchell => {
+                let x = (2.0 * x / self.radius).abs();
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+            FilterKind::BlackmanHarris => {
+                let t = 2.0 * PI * (x + self.radius) / (2.0 * self.radius);
+                0.35875 - 0.48829 * t.cos() + 0.14128 * (2.0 * t).cos() - 0.01168 * (3.0 * t).cos()
+            }
+        }
+    }
+
+    // Inverse CDF of the absolute filter value at evenly spaced u in [0, 1], with the
+    // sample weight in the second component
+    pub fn sample_table(&self) -> [GpuFilterEntry; FILTER_TABLE_SIZE] {
+        let step = 2.0 * self.radius / FILTER_INTEGRATION_STEPS as f32;
+        let position = |i: usize| -self.radius + i as f32 * step;
+
+        let mut cdf = Vec::with_capacity(FILTER_INTEGRATION_STEPS + 1);
+        cdf.push(0.0f64);
+        let mut signed = 0.0f64;
+        for i in 0..FILTER_INTEGRATION_STEPS {
+            let value = self.evaluate(position(i) + 0.5 * step) as f64;
+            signed += value;
+            cdf.push(cdf[i] + value.abs());
+        }
+        let total = cdf[FILTER_INTEGRATION_STEPS];
+        // Makes the expected weight one
+        let scale = (total / signed) as f32;
+
+        let mut table = [[0.0; 4]; FILTER_TABLE_SIZE];
+        let mut segment = 0;
+        for (i, entry) in table.iter_mut().enumerate() {
+            let target = total * i as f64 / (FILTER_TABLE_SIZE - 1) as f64;
+            while segment + 1 < FILTER_INTEGRATION_STEPS && cdf[segment + 1] < target {
+                segment += 1;
+            }
+            let width = cdf[segment + 1] - cdf[segment];
+            let t = if width > 0.0 {
+                ((target - cdf[segment]) / width).clamp(0.0, 1.0) as f32
+            } else {
+                0.0
+            };
+            let x = position(segment) + t * step;
+            let value = self.evaluate(position(segment) + 0.5 * step);
+            *entry = [x, scale * value.signum(), 0.0, 0.0];
+        }
+        table
+    }
+
+    // Offset from the pixel center and sample weight for u in [0, 1], interpolated from
+    // the table like ray_gen.rgen does
+    pub fn sample(table: &[GpuFilterEntry; FILTER_TABLE_SIZE], u: f32) -> (f32, f32) {
+        let position = u.clamp(0.0, 1.0) * (FILTER_TABLE_SIZE - 1) as f32;
+        let index = (position as usize).min(FILTER_TABLE_SIZE - 2);
+        let t = position - index as f32;
+        let (a, b) = (table[index], table[index + 1]);
+        (a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t)
+    }
+}
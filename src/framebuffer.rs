@@ -1,4 +1,5 @@
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use ash::vk::{
     BufferUsageFlags, Format, ImageAspectFlags, ImageLayout, ImageSubresourceRange,
@@ -10,17 +11,107 @@ use vk_utils::{
     image2d_resource::Image2DResource, image_resource::ImageResource, queue::CommandQueue,
 };
 
-use crate::math::Real;
+use crate::adaptive::{mean_relative_error, AdaptiveSampling};
+use crate::aov::{AovData, AovKind, AOV_COUNT};
+use crate::denoise::{ATrousDenoiser, DenoiseInput, Denoiser};
+use crate::filter::PixelFilter;
+use crate::hdr_image::HdrImage;
+use crate::math::{Real, Vec3};
+use crate::region::Region;
+use crate::tonemap::ToneMapping;
+
+pub struct StorageImage {
+    pub image: Image2DResource,
+    pub image_view: ImageView,
+}
+
+impl StorageImage {
+    fn new(device: Rc<DeviceContext>, width: u32, height: u32, format: Format) -> Self {
+        let image = Image2DResource::new(
+            device.clone(),
+            width,
+            height,
+            format,
+            ImageUsageFlags::STORAGE | ImageUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let view_info = ImageViewCreateInfo::builder()
+            .format(format)
+            .subresource_range(
+                ImageSubresourceRange::builder()
+                    .aspect_mask(ImageAspectFlags::COLOR)
+                    .layer_count(1)
+                    .level_count(1)
+                    .build(),
+            )
+            .view_type(ImageViewType::TYPE_2D)
+            .image(image.handle());
+        let image_view = unsafe {
+            device
+                .handle()
+                .create_image_view(&view_info, None)
+                .expect("Image View creation failed")
+        };
+        Self { image, image_view }
+    }
+}
+
+// Copies the whole image into a host visible buffer, waiting for all work on the device
+fn download_image(
+    device: &Rc<DeviceContext>,
+    queue: &Rc<CommandQueue>,
+    image: &mut Image2DResource,
+    size: u64,
+) -> BufferResource {
+    let buffer = BufferResource::new(
+        device.clone(),
+        size,
+        MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
+        BufferUsageFlags::TRANSFER_DST,
+    );
+    device.wait();
+    let mut command_buffer = CommandBuffer::new(device.clone(), queue.clone());
+    command_buffer.begin();
+    command_buffer.image_resource_transition(image, ImageLayout::TRANSFER_SRC_OPTIMAL);
+    command_buffer.copy_image_to_buffer(image, &buffer);
+    command_buffer.submit();
+    device.wait();
+    buffer
+}
+
+static NEXT_FRAMEBUFFER_ID: AtomicU64 = AtomicU64::new(0);
 
 pub struct FrameBuffer {
     pub width: u32,
     pub height: u32,
+    // Identify the images frame resources were bound to, see Ctx::bind_framebuffer
+    id: u64,
+    generation: u64,
+    // Size of the full image and the part of it rendered into the top left corner of
+    // this framebuffer, see set_region
+    image_width: u32,
+    image_height: u32,
+    region: Region,
     device: Rc<DeviceContext>,
     queue: Rc<CommandQueue>,
     pub accumulation_image: Image2DResource,
     pub accumulation_image_view: ImageView,
     pub final_image: Image2DResource,
     pub final_image_view: ImageView,
+    // Sum of the squared sample luminances, the variance estimate of adaptive sampling
+    pub moment_image: StorageImage,
+    // One per AovKind, in AovKind::ALL order. Kinds that weren't requested get a 1x1 placeholder.
+    pub aov_images: Vec<StorageImage>,
+    aov_mask: u32,
+    denoiser: Option<ATrousDenoiser>,
+    // Ping pong targets of the denoiser, allocated when it is first enabled
+    pub denoise_images: Vec<StorageImage>,
+    // Applied when resolving the accumulation image into the final image
+    pub tone_mapping: ToneMapping,
+    // Disabled by default, every pixel then takes the same number of samples
+    pub adaptive_sampling: AdaptiveSampling,
+    // Part of the camera, takes effect when frame resources are built or rebound
+    pub pixel_filter: PixelFilter,
 }
 
 impl FrameBuffer {
@@ -29,6 +120,16 @@ impl FrameBuffer {
         queue: Rc<CommandQueue>,
         width: u32,
         height: u32,
+    ) -> Self {
+        Self::with_aovs(device, queue, width, height, &[])
+    }
+
+    pub fn with_aovs(
+        device: Rc<DeviceContext>,
+        queue: Rc<CommandQueue>,
+        width: u32,
+        height: u32,
+        aovs: &[AovKind],
     ) -> Self {
         let final_image = Image2DResource::new(
             device.clone(),
@@ -44,7 +145,7 @@ impl FrameBuffer {
             width as _,
             height as _,
             Format::R32G32B32A32_SFLOAT,
-            ImageUsageFlags::STORAGE,
+            ImageUsageFlags::STORAGE | ImageUsageFlags::TRANSFER_SRC,
             MemoryPropertyFlags::DEVICE_LOCAL,
         );
 
@@ -86,38 +187,273 @@ impl FrameBuffer {
                 .expect("Image View creation failed")
         };
 
+        let moment_image = StorageImage::new(device.clone(), width, height, Format::R32_SFLOAT);
+
+        let aov_mask = aovs.iter().fold(0, |mask, kind| mask | kind.mask());
+        let aov_images = AovKind::ALL
+            .iter()
+            .map(|kind| {
+                let (aov_width, aov_height) = if aov_mask & kind.mask() != 0 {
+                    (width, height)
+                } else {
+                    (1, 1)
+                };
+                StorageImage::new(device.clone(), aov_width, aov_height, kind.format())
+            })
+            .collect();
+
         Self {
             width,
             height,
+            id: NEXT_FRAMEBUFFER_ID.fetch_add(1, Ordering::Relaxed),
+            generation: 0,
+            image_width: width,
+            image_height: height,
+            region: Region::full(width, height),
             device,
             queue,
             accumulation_image,
             accumulation_image_view,
             final_image,
             final_image_view,
+            moment_image,
+            aov_images,
+            aov_mask,
+            denoiser: None,
+            denoise_images: Vec::new(),
+            tone_mapping: ToneMapping::default(),
+            adaptive_sampling: AdaptiveSampling::disabled(),
+            pixel_filter: PixelFilter::default(),
+        }
+    }
+
+    // Reallocates every image at the new size, which restarts the accumulation. Frame
+    // resources built before have to be rebound with Ctx::bind_framebuffer.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        let aovs: Vec<AovKind> = AovKind::ALL
+            .iter()
+            .copied()
+            .filter(|kind| self.has_aov(*kind))
+            .collect();
+        let mut resized = Self::with_aovs(
+            self.device.clone(),
+            self.queue.clone(),
+            width,
+            height,
+            &aovs,
+        );
+        resized.id = self.id;
+        resized.generation = self.generation + 1;
+        resized.tone_mapping = self.tone_mapping;
+        resized.adaptive_sampling = self.adaptive_sampling;
+        resized.pixel_filter = self.pixel_filter;
+        resized.set_denoiser(self.denoiser);
+
+        // The old images may still be in use
+        self.device.wait();
+        *self = resized;
+    }
+
+    // Changes with every resize, frame resources are bound to one generation
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(crate) fn binding(&self) -> (u64, u64) {
+        (self.id, self.generation)
+    }
+
+    // Filters the image on the GPU before tone mapping. Uses the albedo, normal and depth
+    // AOVs as guides when the framebuffer was created with them.
+    pub fn set_denoiser(&mut self, denoiser: Option<ATrousDenoiser>) {
+        if denoiser.is_some() && self.denoise_images.is_empty() {
+            self.denoise_images = (0..2)
+                .map(|_| {
+                    StorageImage::new(
+                        self.device.clone(),
+                        self.width,
+                        self.height,
+                        Format::R32G32B32A32_SFLOAT,
+                    )
+                })
+                .collect();
         }
+        self.denoiser = denoiser;
+    }
+
+    pub fn denoiser(&self) -> Option<&ATrousDenoiser> {
+        self.denoiser.as_ref()
+    }
+
+    pub fn aov_mask(&self) -> u32 {
+        self.aov_mask
+    }
+
+    pub fn has_aov(&self, kind: AovKind) -> bool {
+        self.aov_mask & kind.mask() != 0
+    }
+
+    pub fn aov_image_views(&self) -> [ImageView; AOV_COUNT] {
+        let mut views = [ImageView::null(); AOV_COUNT];
+        for (view, aov) in views.iter_mut().zip(&self.aov_images) {
+            *view = aov.image_view;
+        }
+        views
+    }
+
+    // Renders only the region of a larger image, for crop windows and tiles. The region
+    // can be smaller than the framebuffer, the pixels outside of it are left undefined.
+    // Build the frame resources after this, the camera uses the aspect ratio of the image.
+    pub fn set_region(&mut self, image_width: u32, image_height: u32, region: Region) {
+        assert!(
+            region.fits_in(image_width, image_height),
+            "Region outside of the image"
+        );
+        assert!(
+            region.width <= self.width && region.height <= self.height,
+            "Region larger than the framebuffer"
+        );
+        self.image_width = image_width;
+        self.image_height = image_height;
+        self.region = region;
+    }
+
+    // Renders the framebuffer as a whole image again
+    pub fn reset_region(&mut self) {
+        self.set_region(
+            self.width,
+            self.height,
+            Region::full(self.width, self.height),
+        );
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn image_size(&self) -> (u32, u32) {
+        (self.image_width, self.image_height)
     }
 
     pub fn aspect_ratio(&self) -> Real {
-        self.width as Real / self.height as Real
+        self.image_width as Real / self.image_height as Real
+    }
+
+    fn pixel_count(&self) -> u64 {
+        self.width as u64 * self.height as u64
     }
 
     pub fn download_output(&mut self) -> Vec<u8> {
-        let size = self.width as u64 * self.height as u64 * 4;
-        let buffer = BufferResource::new(
-            self.device.clone(),
+        let size = self.pixel_count() * 4;
+        download_image(&self.device, &self.queue, &mut self.final_image, size).copy_data::<u8>()
+    }
+
+    // Sum of the samples in rgb and the sample count in alpha
+    fn download_raw_accumulation(&mut self) -> Vec<f32> {
+        let size = self.pixel_count() * 16;
+        download_image(
+            &self.device,
+            &self.queue,
+            &mut self.accumulation_image,
             size,
-            MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE,
-            BufferUsageFlags::TRANSFER_DST,
-        );
-        self.device.wait();
-        let mut command_buffer = CommandBuffer::new(self.device.clone(), self.queue.clone());
-        command_buffer.begin();
-        command_buffer
-            .image_resource_transition(&mut self.final_image, ImageLayout::TRANSFER_SRC_OPTIMAL);
-        command_buffer.copy_image_to_buffer(&self.final_image, &buffer);
-        command_buffer.submit();
-        self.device.wait();
-        buffer.copy_data::<u8>()
+        )
+        .copy_data::<f32>()
+    }
+
+    // Linear radiance averaged over the samples taken so far, before tone mapping
+    pub fn download_accumulation(&mut self) -> HdrImage {
+        let pixels = self
+            .download_raw_accumulation()
+            .chunks_exact(4)
+            .map(|p| {
+                if p[3] > 0.0 {
+                    Vec3::new(p[0], p[1], p[2]) / p[3]
+                } else {
+                    Vec3::new(0.0, 0.0, 0.0)
+                }
+            })
+            .collect();
+        HdrImage::new(self.width, self.height, pixels)
+    }
+
+    // Samples taken per pixel, converged pixels stop early with adaptive sampling
+    pub fn download_sample_counts(&mut self) -> Vec<u32> {
+        self.download_raw_accumulation()
+            .chunks_exact(4)
+            .map(|p| p[3] as u32)
+            .collect()
+    }
+
+    // Mean relative error of the pixels, see adaptive::mean_relative_error. Reads back the
+    // accumulation and moment images, so it is too slow to call after every pass.
+    pub fn estimate_noise(&mut self) -> Option<f32> {
+        let accumulation = self.download_raw_accumulation();
+        let size = self.pixel_count() * 4;
+        let moments = download_image(
+            &self.device,
+            &self.queue,
+            &mut self.moment_image.image,
+            size,
+        )
+        .copy_data::<f32>();
+        mean_relative_error(&accumulation, &moments)
+    }
+
+    // Runs a CPU denoiser on the accumulated radiance, guided by whichever of the albedo,
+    // normal and depth AOVs the framebuffer has
+    pub fn download_denoised(&mut self, denoiser: &dyn Denoiser) -> HdrImage {
+        let color = self.download_accumulation();
+        let albedo = match self.download_aov(AovKind::Albedo) {
+            Some(AovData::Vector(albedo)) => Some(albedo),
+            _ => None,
+        };
+        let normal = match self.download_aov(AovKind::Normal) {
+            Some(AovData::Vector(normal)) => Some(normal),
+            _ => None,
+        };
+        let depth = match self.download_aov(AovKind::Depth) {
+            Some(AovData::Scalar(depth)) => Some(depth),
+            _ => None,
+        };
+
+        let mut input = DenoiseInput::new(&color);
+        input.albedo = albedo.as_deref();
+        input.normal = normal.as_deref();
+        input.depth = depth.as_deref();
+        denoiser.denoise(&input)
+    }
+
+    // None if the framebuffer wasn't created with this AOV
+    pub fn download_aov(&mut self, kind: AovKind) -> Option<AovData> {
+        if !self.has_aov(kind) {
+            return None;
+        }
+
+        let size = self.pixel_count() * kind.texel_size() as u64;
+        let image = &mut self.aov_images[kind as usize].image;
+        let buffer = download_image(&self.device, &self.queue, image, size);
+        Some(AovData::from_bytes(kind, &buffer.copy_data::<u8>()))
+    }
+}
+
+impl Drop for FrameBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            let device = self.device.handle();
+            device.destroy_image_view(self.final_image_view, None);
+            device.destroy_image_view(self.accumulation_image_view, None);
+            for image in self
+                .aov_images
+                .iter()
+                .chain(&self.denoise_images)
+                .chain(std::iter::once(&self.moment_image))
+            {
+                device.destroy_image_view(image.image_view, None);
+            }
+        }
     }
 }
@@ -1,6 +1,6 @@
 use std::rc::Rc;
 
-use crate::math::{Mat4, Vec2, Vec3};
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
 use crate::rtx_extensions::RtxExtensions;
 use ash::vk::{
     AccelerationStructureBuildGeometryInfoKHR, AccelerationStructureBuildRangeInfoKHR,
@@ -23,6 +23,7 @@ pub type Position = Vec3;
 pub type Normal = Vec3;
 pub type Tangent = Vec3;
 pub type Texcoord = Vec2;
+pub type Color = Vec4;
 
 fn transform_to_array(t: &Mat4) -> [f32; 12] {
     let t = t.transpose();
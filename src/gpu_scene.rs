@@ -1,6 +1,9 @@
 use crate::geometry::TopLevelAccelerationStructure;
 
+use std::rc::Rc;
+
 use vk_utils::buffer_resource::BufferResource;
+use vk_utils::device_context::DeviceContext;
 use vk_utils::image2d_resource::Image2DResource;
 
 use ash::vk::DescriptorSet;
@@ -10,6 +13,32 @@ pub struct GpuTexture {
     pub image_view: ash::vk::ImageView,
 }
 
+pub struct GpuEnvironment {
+    pub buffer: BufferResource,
+    pub width: u32,
+    pub height: u32,
+    pub gamma_encoded: bool,
+}
+
+pub struct GpuCubeTexture {
+    pub device: Rc<DeviceContext>,
+    pub image: ash::vk::Image,
+    pub memory: ash::vk::DeviceMemory,
+    pub image_view: ash::vk::ImageView,
+    pub environment: GpuEnvironment,
+}
+
+impl Drop for GpuCubeTexture {
+    fn drop(&mut self) {
+        unsafe {
+            let device = self.device.handle();
+            device.destroy_image_view(self.image_view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
 pub struct Frame {
     pub material_buffer: BufferResource,
     pub material_address_buffer: BufferResource,
@@ -0,0 +1,16 @@
+// Hashes shared by the samplers and the blue noise mask, which build.rs compiles too
+
+// Integer hash by Chris Wellons, lowbias32
+pub fn hash(x: u32) -> u32 {
+    let mut x = x;
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    x
+}
+
+pub fn hash_combine(seed: u32, value: u32) -> u32 {
+    seed ^ (value.wrapping_add(seed << 6).wrapping_add(seed >> 2))
+}
@@ -0,0 +1,255 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec,
+    WritableImage,
+};
+
+use crate::aov::{AovData, AovKind};
+use crate::math::Vec3;
+use crate::region::Region;
+use crate::tonemap::ToneMapping;
+
+// Linear radiance in row major order, top row first
+#[derive(Clone)]
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Vec3>,
+}
+
+impl HdrImage {
+    pub fn new(width: u32, height: u32, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn black(width: u32, height: u32) -> Self {
+        Self::new(
+            width,
+            height,
+            vec![Vec3::new(0.0, 0.0, 0.0); width as usize * height as usize],
+        )
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Vec3 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // Copies the top left region.width x region.height pixels of the source to region.x,
+    // region.y. Stitches tiles rendered into a larger framebuffer.
+    pub fn blit(&mut self, source: &HdrImage, region: Region) {
+        assert!(region.fits_in(self.width, self.height));
+        assert!(region.width <= source.width && region.height <= source.height);
+        for row in 0..region.height {
+            let src = (row * source.width) as usize;
+            let dst = ((region.y + row) * self.width + region.x) as usize;
+            self.pixels[dst..dst + region.width as usize]
+                .copy_from_slice(&source.pixels[src..src + region.width as usize]);
+        }
+    }
+
+    // Tone mapped RGBA8, the layout of FrameBuffer::download_output
+    pub fn to_rgba8(&self, tone_mapping: &ToneMapping) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|p| {
+                let c = tone_mapping.apply(*p);
+                [
+                    (c.x * 255.0).round() as u8,
+                    (c.y * 255.0).round() as u8,
+                    (c.z * 255.0).round() as u8,
+                    255,
+                ]
+            })
+            .collect()
+    }
+
+    // Single part scanline EXR with the beauty as R, G, B and every AOV as extra
+    // channels, named like "albedo.R" so compositors group them into layers
+    pub fn write_exr(
+        &self,
+        path: impl AsRef<Path>,
+        aovs: &[(AovKind, &AovData)],
+    ) -> std::io::Result<()> {
+        let pixel_count = self.pixels.len();
+        let mut channels = Vec::new();
+        for (name, component) in [("R", 0), ("G", 1), ("B", 2)] {
+            channels.push(AnyChannel::new(
+                name,
+                FlatSamples::F32(self.pixels.iter().map(|p| p[component]).collect()),
+            ));
+        }
+
+        for (kind, data) in aovs {
+            let names = kind.exr_channels();
+            match data {
+                AovData::Scalar(values) => {
+                    check_aov_size(*kind, values.len(), pixel_count)?;
+                    channels.push(AnyChannel::new(names[0], FlatSamples::F32(values.clone())));
+                }
+                AovData::Vector(values) => {
+                    check_aov_size(*kind, values.len(), pixel_count)?;
+                    for (component, name) in names.iter().enumerate() {
+                        channels.push(AnyChannel::new(
+                            *name,
+                            FlatSamples::F32(values.iter().map(|v| v[component]).collect()),
+                        ));
+                    }
+                }
+                AovData::Id(values) => {
+                    check_aov_size(*kind, values.len(), pixel_count)?;
+                    channels.push(AnyChannel::new(names[0], FlatSamples::U32(values.clone())));
+                }
+            }
+        }
+
+        let layer = Layer::new(
+            (self.width as usize, self.height as usize),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+        Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .map_err(|e| std::io::Error::other(e.to_string()))
+    }
+
+    pub fn write_pfm(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_pfm_to(&mut writer)?;
+        writer.flush()
+    }
+
+    // Little endian color PFM, rows are stored bottom to top
+    pub fn write_pfm_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        write!(writer, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.pixels.chunks(self.width as usize).rev() {
+            for p in row {
+                for c in [p.x, p.y, p.z] {
+                    writer.write_all(&c.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_hdr(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_hdr_to(&mut writer)?;
+        writer.flush()
+    }
+
+    // Radiance RGBE with run length encoded scanlines where the format allows it
+    pub fn write_hdr_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        write!(
+            writer,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width
+        )?;
+
+        let rle = (8..0x8000).contains(&self.width);
+        let mut components: [Vec<u8>; 4] = Default::default();
+        for row in self.pixels.chunks(self.width as usize) {
+            if !rle {
+                for p in row {
+                    writer.write_all(&rgbe(*p))?;
+                }
+                continue;
+            }
+
+            for component in &mut components {
+                component.clear();
+            }
+            for p in row {
+                for (component, byte) in components.iter_mut().zip(rgbe(*p)) {
+                    component.push(byte);
+                }
+            }
+            writer.write_all(&[2, 2, (self.width >> 8) as u8, self.width as u8])?;
+            for component in &components {
+                write_rle(writer, component)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_aov_size(kind: AovKind, len: usize, pixel_count: usize) -> std::io::Result<()> {
+    if len == pixel_count {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{:?} has {} pixels, expected {}", kind, len, pixel_count),
+        ))
+    }
+}
+
+pub fn rgbe(color: Vec3) -> [u8; 4] {
+    let v = color.x.max(color.y).max(color.z);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2.0f32.powi(e) >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / 2.0f32.powi(e);
+    [
+        (color.x.max(0.0) * scale) as u8,
+        (color.y.max(0.0) * scale) as u8,
+        (color.z.max(0.0) * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+// Runs of at least four equal bytes become (128 + count, byte), the rest is copied
+// in literal chunks of at most 128 bytes, as in Greg Ward's reference encoder
+fn write_rle(writer: &mut impl Write, data: &[u8]) -> std::io::Result<()> {
+    let mut current = 0;
+    while current < data.len() {
+        let mut run_start = current;
+        let mut run_count = 0;
+        let mut previous_run_count = 0;
+        while run_count < 4 && run_start < data.len() {
+            run_start += run_count;
+            previous_run_count = run_count;
+            run_count = 1;
+            while run_start + run_count < data.len()
+                && run_count < 127
+                && data[run_start] == data[run_start + run_count]
+            {
+                run_count += 1;
+            }
+        }
+
+        // A short run right before the long one is cheaper as a run too
+        if previous_run_count > 1 && previous_run_count == run_start - current {
+            writer.write_all(&[128 + previous_run_count as u8, data[current]])?;
+            current = run_start;
+        }
+
+        while current < run_start {
+            let count = (run_start - current).min(128);
+            writer.write_all(&[count as u8])?;
+            writer.write_all(&data[current..current + count])?;
+            current += count;
+        }
+
+        if run_count >= 4 {
+            writer.write_all(&[128 + run_count as u8, data[run_start]])?;
+            current += run_count;
+        }
+    }
+    Ok(())
+}
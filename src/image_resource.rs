@@ -1,3 +1,10 @@
+use std::path::Path;
+
+use image::{ColorType, DynamicImage, EncodableLayout, Rgba32FImage, RgbaImage};
+
+use crate::math::Vec4;
+
+#[derive(Clone)]
 pub struct TextureImageData {
     pub format: ash::vk::Format,
     pub width: u32,
@@ -31,3 +38,155 @@ impl TextureImageData {
         }
     }
 }
+
+impl TextureImageData {
+    // 8 bit RGBA for regular images, 32 bit float RGBA for HDR formats like EXR
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let image = image::open(path).map_err(|e| std::io::Error::other(e.to_string()))?;
+        Ok(Self::from_image(image))
+    }
+
+    // Same as load, for encoded images that are already in memory
+    pub fn from_memory(bytes: &[u8]) -> std::io::Result<Self> {
+        let image =
+            image::load_from_memory(bytes).map_err(|e| std::io::Error::other(e.to_string()))?;
+        Ok(Self::from_image(image))
+    }
+
+    // The encoder is picked from the extension. 8 bit textures are written as 8 bit RGBA,
+    // all others as float RGBA, which needs a float format like EXR.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let texels = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.texel(x, y))
+            .collect::<Option<Vec<Vec4>>>()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Can't read {:?} textures on the CPU", self.format),
+                )
+            })?;
+        let channels = texels.iter().flat_map(|t| [t.x, t.y, t.z, t.w]);
+        let image = if self.is_gamma_encoded() {
+            let pixels = channels.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+            RgbaImage::from_raw(self.width, self.height, pixels.collect()).map(DynamicImage::from)
+        } else {
+            Rgba32FImage::from_raw(self.width, self.height, channels.collect())
+                .map(DynamicImage::from)
+        };
+        image
+            .expect("The texels match the image size")
+            .save(path)
+            .map_err(|e| std::io::Error::other(e.to_string()))
+    }
+
+    fn from_image(image: DynamicImage) -> Self {
+        match image.color() {
+            ColorType::Rgb32F | ColorType::Rgba32F => {
+                let pixels = image.to_rgba32f();
+                Self::new(
+                    ash::vk::Format::R32G32B32A32_SFLOAT,
+                    pixels.width(),
+                    pixels.height(),
+                    pixels.as_bytes(),
+                )
+            }
+            _ => {
+                let pixels = image.to_rgba8();
+                Self::new(
+                    ash::vk::Format::R8G8B8A8_UNORM,
+                    pixels.width(),
+                    pixels.height(),
+                    pixels.as_bytes(),
+                )
+            }
+        }
+    }
+}
+
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => sign * f32::INFINITY,
+        31 => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+impl TextureImageData {
+    // 8 bit images are stored with a 2.2 gamma, float images are linear
+    pub fn is_gamma_encoded(&self) -> bool {
+        use ash::vk::Format;
+        matches!(
+            self.format,
+            Format::R8_UINT
+                | Format::R8_UNORM
+                | Format::R8G8_UINT
+                | Format::R8G8_UNORM
+                | Format::R8G8B8_UINT
+                | Format::R8G8B8_UNORM
+                | Format::R8G8B8A8_UINT
+                | Format::R8G8B8A8_UNORM
+                | Format::B8G8R8A8_UINT
+                | Format::B8G8R8A8_UNORM
+        )
+    }
+
+    // Normalized texel value, missing channels read as 0 and missing alpha as 1.
+    // Returns None for formats that can't be read back on the CPU.
+    pub fn texel(&self, x: u32, y: u32) -> Option<Vec4> {
+        use ash::vk::Format;
+        let index = (y * self.width + x) as usize;
+        let bytes = |channels: usize| {
+            let start = index * channels;
+            self.pixels.get(start..start + channels)
+        };
+        let halfs = |channels: usize| {
+            let start = index * channels * 2;
+            self.pixels.get(start..start + channels * 2).map(|p| {
+                p.chunks_exact(2)
+                    .map(|c| half_to_f32(u16::from_le_bytes([c[0], c[1]])))
+                    .collect::<Vec<f32>>()
+            })
+        };
+        let floats = |channels: usize| {
+            let start = index * channels * 4;
+            self.pixels.get(start..start + channels * 4).map(|p| {
+                p.chunks_exact(4)
+                    .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect::<Vec<f32>>()
+            })
+        };
+        let unorm = |v: u8| v as f32 / 255.0;
+
+        match self.format {
+            Format::R8_UINT | Format::R8_UNORM => {
+                bytes(1).map(|p| Vec4::new(unorm(p[0]), 0.0, 0.0, 1.0))
+            }
+            Format::R8G8_UINT | Format::R8G8_UNORM => {
+                bytes(2).map(|p| Vec4::new(unorm(p[0]), unorm(p[1]), 0.0, 1.0))
+            }
+            Format::R8G8B8_UINT | Format::R8G8B8_UNORM => {
+                bytes(3).map(|p| Vec4::new(unorm(p[0]), unorm(p[1]), unorm(p[2]), 1.0))
+            }
+            Format::R8G8B8A8_UINT | Format::R8G8B8A8_UNORM => {
+                bytes(4).map(|p| Vec4::new(unorm(p[0]), unorm(p[1]), unorm(p[2]), unorm(p[3])))
+            }
+            Format::B8G8R8A8_UINT | Format::B8G8R8A8_UNORM => {
+                bytes(4).map(|p| Vec4::new(unorm(p[2]), unorm(p[1]), unorm(p[0]), unorm(p[3])))
+            }
+            Format::R16_SFLOAT => halfs(1).map(|p| Vec4::new(p[0], 0.0, 0.0, 1.0)),
+            Format::R16G16_SFLOAT => halfs(2).map(|p| Vec4::new(p[0], p[1], 0.0, 1.0)),
+            Format::R16G16B16_SFLOAT => halfs(3).map(|p| Vec4::new(p[0], p[1], p[2], 1.0)),
+            Format::R16G16B16A16_SFLOAT => halfs(4).map(|p| Vec4::new(p[0], p[1], p[2], p[3])),
+            Format::R32_SFLOAT => floats(1).map(|p| Vec4::new(p[0], 0.0, 0.0, 1.0)),
+            Format::R32G32_SFLOAT => floats(2).map(|p| Vec4::new(p[0], p[1], 0.0, 1.0)),
+            Format::R32G32B32_SFLOAT => floats(3).map(|p| Vec4::new(p[0], p[1], p[2], 1.0)),
+            Format::R32G32B32A32_SFLOAT => floats(4).map(|p| Vec4::new(p[0], p[1], p[2], p[3])),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,2228 @@
+// Decoder for KHR_draco_mesh_compression. Supports Draco 2.2 triangle meshes with edgebreaker
+// connectivity, either the standard or the valence traversal, and the sequential attribute
+// decoders with the prediction schemes the encoder picks for positions, normals and UVs.
+use std::collections::HashMap;
+use std::io;
+
+use super::invalid_data;
+
+const INVALID: u32 = u32::MAX;
+
+// Edgebreaker symbols as written in the bitstream
+const TOPOLOGY_C: u32 = 0;
+const TOPOLOGY_S: u32 = 1;
+const TOPOLOGY_L: u32 = 3;
+const TOPOLOGY_R: u32 = 5;
+const TOPOLOGY_E: u32 = 7;
+
+const ATTRIBUTE_POSITION: u8 = 0;
+
+const CODING_GENERIC: u8 = 0;
+const CODING_INTEGER: u8 = 1;
+const CODING_QUANTIZATION: u8 = 2;
+const CODING_NORMALS: u8 = 3;
+
+const DATA_TYPE_FLOAT32: u8 = 9;
+
+pub struct DracoMesh {
+    pub indices: Vec<u32>,
+    pub attributes: Vec<DracoAttribute>,
+}
+
+// Values are stored per point, the points are what the indices refer to. Integer attributes
+// keep their integer values, normalizing them is up to the caller.
+pub struct DracoAttribute {
+    pub unique_id: u32,
+    pub components: usize,
+    pub values: Vec<f32>,
+}
+
+impl DracoMesh {
+    pub fn attribute(&self, unique_id: u32) -> Option<&DracoAttribute> {
+        self.attributes.iter().find(|a| a.unique_id == unique_id)
+    }
+
+    pub fn num_points(&self) -> usize {
+        self.attributes
+            .first()
+            .map(|a| a.values.len() / a.components)
+            .unwrap_or_default()
+    }
+}
+
+pub fn decode(data: &[u8]) -> io::Result<DracoMesh> {
+    let mut buffer = Buffer::new(data);
+    if buffer.bytes(5)? != b"DRACO" {
+        return Err(invalid_data("Not a Draco bitstream"));
+    }
+    let version = (buffer.u8()?, buffer.u8()?);
+    if version != (2, 2) {
+        return Err(unsupported(format!(
+            "Draco version {}.{}, only 2.2 is supported",
+            version.0, version.1
+        )));
+    }
+    if buffer.u8()? != 1 {
+        return Err(unsupported("Draco point clouds".to_string()));
+    }
+    if buffer.u8()? != 1 {
+        return Err(unsupported("Draco sequential connectivity".to_string()));
+    }
+    if buffer.u16()? & 0x8000 != 0 {
+        return Err(unsupported("Draco metadata".to_string()));
+    }
+
+    let connectivity = decode_connectivity(&mut buffer)?;
+    let attributes = decode_attributes(&mut buffer, &connectivity)?;
+
+    let attributes = attributes
+        .into_iter()
+        .map(|attribute| {
+            let components = attribute.components;
+            let mut values = Vec::with_capacity(connectivity.num_points * components);
+            for &value in &attribute.point_to_value {
+                let start = value as usize * components;
+                values.extend_from_slice(&attribute.values[start..start + components]);
+            }
+            DracoAttribute {
+                unique_id: attribute.unique_id,
+                components,
+                values,
+            }
+        })
+        .collect();
+
+    Ok(DracoMesh {
+        indices: connectivity.corner_to_point,
+        attributes,
+    })
+}
+
+fn unsupported(what: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} are not supported", what),
+    )
+}
+
+fn truncated() -> io::Error {
+    invalid_data("Unexpected end of Draco data")
+}
+
+#[derive(Clone)]
+struct Buffer<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Buffer<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn bytes(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        if count > self.remaining() {
+            return Err(truncated());
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i8(&mut self) -> io::Result<i8> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_bits(self.i32()? as u32))
+    }
+
+    // Seven bits per byte, least significant group first
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data("Invalid Draco varint"))
+    }
+
+    fn varint_u32(&mut self) -> io::Result<u32> {
+        u32::try_from(self.varint()?).map_err(|_| invalid_data("Invalid Draco varint"))
+    }
+
+    fn bits(&self) -> BitReader<'a> {
+        BitReader {
+            data: &self.data[self.position..],
+            position: 0,
+        }
+    }
+
+    // Continues at the first whole byte after the bits that were read. Bits read past the
+    // end of the data are an error.
+    fn skip_bits(&mut self, bits: &BitReader) -> io::Result<()> {
+        let count = bits.position.div_ceil(8);
+        if count > self.remaining() {
+            return Err(truncated());
+        }
+        self.position += count;
+        Ok(())
+    }
+}
+
+// Bits are read least significant first, reading past the end gives zeros
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for bit in 0..count {
+            if let Some(byte) = self.data.get(self.position / 8) {
+                value |= ((*byte as u32 >> (self.position % 8)) & 1) << bit;
+            }
+            self.position += 1;
+        }
+        value
+    }
+}
+
+// rANS streams keep their initial state in the last bytes, the top two bits of the very last
+// byte tell how many bytes it takes. Returns the remaining length and the state.
+fn ans_start(data: &[u8], base: u32) -> io::Result<(usize, u32)> {
+    let last = *data.last().ok_or_else(truncated)?;
+    let (size, mask) = match last >> 6 {
+        0 => (1, 0x3f),
+        1 => (2, 0x3fff),
+        2 => (3, 0x3f_ffff),
+        _ => (4, 0x3fff_ffff),
+    };
+    if data.len() < size {
+        return Err(truncated());
+    }
+    let offset = data.len() - size;
+    let state = data[offset..]
+        .iter()
+        .rev()
+        .fold(0u32, |state, &byte| state << 8 | byte as u32);
+    let state = (state & mask) + base;
+    if state >= base * 256 {
+        return Err(invalid_data("Invalid Draco rANS state"));
+    }
+    Ok((offset, state))
+}
+
+// Binary rANS with a fixed probability for zero
+struct BitDecoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+    state: u32,
+    probability_one: u32,
+}
+
+impl<'a> BitDecoder<'a> {
+    const BASE: u32 = 4096;
+
+    fn start(buffer: &mut Buffer<'a>) -> io::Result<Self> {
+        let probability_zero = buffer.u8()? as u32;
+        let size = buffer.varint()? as usize;
+        let data = buffer.bytes(size)?;
+        let (offset, state) = ans_start(data, Self::BASE)?;
+        Ok(Self {
+            data,
+            offset,
+            state,
+            probability_one: 256 - probability_zero,
+        })
+    }
+
+    fn read(&mut self) -> bool {
+        if self.state < Self::BASE && self.offset > 0 {
+            self.offset -= 1;
+            self.state = self.state * 256 + self.data[self.offset] as u32;
+        }
+        let quotient = self.state / 256;
+        let remainder = self.state % 256;
+        let scaled = quotient * self.probability_one;
+        let bit = remainder < self.probability_one;
+        self.state = if bit {
+            scaled + remainder
+        } else {
+            self.state - scaled - self.probability_one
+        };
+        bit
+    }
+}
+
+// rANS over an alphabet with a probability table sent up front
+struct SymbolDecoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+    state: u32,
+    precision_bits: u32,
+    probabilities: Vec<u32>,
+    cumulative: Vec<u32>,
+    lookup: Vec<u32>,
+}
+
+impl<'a> SymbolDecoder<'a> {
+    fn start(buffer: &mut Buffer<'a>, symbol_bits: u32) -> io::Result<Self> {
+        let precision_bits = (3 * symbol_bits / 2).clamp(12, 20);
+        let precision = 1 << precision_bits;
+        let num_symbols = buffer.varint_u32()? as usize;
+        // Runs of unused symbols take a byte per 64 entries
+        if num_symbols == 0 || num_symbols / 64 > buffer.remaining() {
+            return Err(invalid_data("Invalid Draco symbol table"));
+        }
+
+        let mut probabilities = vec![0; num_symbols];
+        let mut symbol = 0;
+        while symbol < num_symbols {
+            let first = buffer.u8()?;
+            // The low two bits count the extra bytes, 3 marks a run of zero probabilities
+            match first & 3 {
+                3 => symbol += (first >> 2) as usize + 1,
+                extra_bytes => {
+                    let mut probability = (first >> 2) as u32;
+                    for byte in 0..extra_bytes as u32 {
+                        probability |= (buffer.u8()? as u32) << (8 * (byte + 1) - 2);
+                    }
+                    probabilities[symbol] = probability;
+                    symbol += 1;
+                }
+            }
+        }
+        if symbol > num_symbols {
+            return Err(invalid_data("Invalid Draco symbol table"));
+        }
+
+        let mut cumulative = Vec::with_capacity(num_symbols);
+        let mut lookup = Vec::with_capacity(precision as usize);
+        let mut total = 0;
+        for (symbol, probability) in probabilities.iter().enumerate() {
+            cumulative.push(total);
+            total += probability;
+            if total > precision {
+                return Err(invalid_data("Invalid Draco symbol table"));
+            }
+            lookup.resize(total as usize, symbol as u32);
+        }
+        if total != precision {
+            return Err(invalid_data("Invalid Draco symbol table"));
+        }
+
+        let size = buffer.varint()? as usize;
+        let data = buffer.bytes(size)?;
+        let (offset, state) = ans_start(data, precision * 4)?;
+        Ok(Self {
+            data,
+            offset,
+            state,
+            precision_bits,
+            probabilities,
+            cumulative,
+            lookup,
+        })
+    }
+
+    fn read(&mut self) -> u32 {
+        let base = 4 << self.precision_bits;
+        while self.state < base && self.offset > 0 {
+            self.offset -= 1;
+            self.state = self.state * 256 + self.data[self.offset] as u32;
+        }
+        let quotient = self.state >> self.precision_bits;
+        let remainder = self.state & ((1 << self.precision_bits) - 1);
+        let symbol = self.lookup[remainder as usize];
+        self.state = quotient * self.probabilities[symbol as usize] + remainder
+            - self.cumulative[symbol as usize];
+        symbol
+    }
+}
+
+fn decode_symbols(
+    buffer: &mut Buffer,
+    num_values: usize,
+    num_components: usize,
+) -> io::Result<Vec<u32>> {
+    if num_values == 0 {
+        return Ok(Vec::new());
+    }
+    let mut values = Vec::with_capacity(num_values);
+    match buffer.u8()? {
+        // Tagged: an entropy coded bit length per entry followed by the raw bits
+        0 => {
+            let mut tags = SymbolDecoder::start(buffer, 5)?;
+            let mut bits = buffer.bits();
+            while values.len() < num_values {
+                let length = tags.read();
+                if length > 32 {
+                    return Err(invalid_data("Invalid Draco symbol length"));
+                }
+                for _ in 0..num_components {
+                    values.push(bits.read(length));
+                }
+            }
+            buffer.skip_bits(&bits)?;
+            values.truncate(num_values);
+        }
+        // Raw: every value is a symbol
+        1 => {
+            let max_bit_length = buffer.u8()? as u32;
+            if !(1..=18).contains(&max_bit_length) {
+                return Err(invalid_data("Invalid Draco symbol length"));
+            }
+            let mut symbols = SymbolDecoder::start(buffer, max_bit_length)?;
+            for _ in 0..num_values {
+                values.push(symbols.read());
+            }
+        }
+        scheme => {
+            return Err(invalid_data(format!(
+                "Unknown Draco symbol coding {}",
+                scheme
+            )))
+        }
+    }
+    Ok(values)
+}
+
+fn next(corner: u32) -> u32 {
+    match corner % 3 {
+        _ if corner == INVALID => INVALID,
+        2 => corner - 2,
+        _ => corner + 1,
+    }
+}
+
+fn previous(corner: u32) -> u32 {
+    match corner % 3 {
+        _ if corner == INVALID => INVALID,
+        0 => corner + 2,
+        _ => corner - 1,
+    }
+}
+
+// The connectivity and the per attribute tables, which cut the mesh open along attribute seams
+trait Corners {
+    fn vertex(&self, corner: u32) -> u32;
+    fn opposite(&self, corner: u32) -> u32;
+    fn left_most_corner(&self, vertex: u32) -> u32;
+    fn num_vertices(&self) -> usize;
+    fn num_corners(&self) -> usize;
+
+    fn swing_left(&self, corner: u32) -> u32 {
+        next(self.opposite(next(corner)))
+    }
+
+    fn swing_right(&self, corner: u32) -> u32 {
+        previous(self.opposite(previous(corner)))
+    }
+
+    fn left_corner(&self, corner: u32) -> u32 {
+        self.opposite(previous(corner))
+    }
+
+    fn right_corner(&self, corner: u32) -> u32 {
+        self.opposite(next(corner))
+    }
+
+    fn is_on_boundary(&self, vertex: u32) -> bool {
+        let corner = self.left_most_corner(vertex);
+        corner == INVALID || self.swing_left(corner) == INVALID
+    }
+
+    // Corners around the vertex at `start`, swinging left first and right from `start` once
+    // a boundary is hit
+    fn corners_around(&self, start: u32) -> Vec<u32> {
+        let mut corners = vec![start];
+        let mut corner = self.swing_left(start);
+        while corner != INVALID && corner != start && corners.len() <= self.num_corners() {
+            corners.push(corner);
+            corner = self.swing_left(corner);
+        }
+        if corner == INVALID {
+            corner = self.swing_right(start);
+            while corner != INVALID && corners.len() <= self.num_corners() {
+                corners.push(corner);
+                corner = self.swing_right(corner);
+            }
+        }
+        corners
+    }
+}
+
+struct CornerTable {
+    vertices: Vec<u32>,
+    opposites: Vec<u32>,
+    left_most: Vec<u32>,
+}
+
+impl CornerTable {
+    fn new(num_faces: usize) -> Self {
+        Self {
+            vertices: vec![INVALID; num_faces * 3],
+            opposites: vec![INVALID; num_faces * 3],
+            left_most: Vec::new(),
+        }
+    }
+
+    fn add_vertex(&mut self) -> u32 {
+        self.left_most.push(INVALID);
+        self.left_most.len() as u32 - 1
+    }
+
+    fn set_opposites(&mut self, a: u32, b: u32) {
+        self.opposites[a as usize] = b;
+        self.opposites[b as usize] = a;
+    }
+}
+
+impl Corners for CornerTable {
+    fn vertex(&self, corner: u32) -> u32 {
+        self.vertices
+            .get(corner as usize)
+            .copied()
+            .unwrap_or(INVALID)
+    }
+
+    fn opposite(&self, corner: u32) -> u32 {
+        self.opposites
+            .get(corner as usize)
+            .copied()
+            .unwrap_or(INVALID)
+    }
+
+    fn left_most_corner(&self, vertex: u32) -> u32 {
+        self.left_most
+            .get(vertex as usize)
+            .copied()
+            .unwrap_or(INVALID)
+    }
+
+    fn num_vertices(&self) -> usize {
+        self.left_most.len()
+    }
+
+    fn num_corners(&self) -> usize {
+        self.vertices.len()
+    }
+}
+
+// Connectivity of one attribute: edges on a seam have no opposite, so the vertices on a seam
+// split into one attribute vertex per side
+struct SeamCorners {
+    seams: Vec<bool>,
+    vertex_on_seam: Vec<bool>,
+    opposites: Vec<u32>,
+    vertices: Vec<u32>,
+    left_most: Vec<u32>,
+}
+
+impl SeamCorners {
+    fn new(table: &CornerTable, seam_corners: &[u32]) -> io::Result<Self> {
+        let mut corners = Self {
+            seams: vec![false; table.num_corners()],
+            vertex_on_seam: vec![false; table.num_vertices()],
+            opposites: table.opposites.clone(),
+            vertices: vec![INVALID; table.num_corners()],
+            left_most: Vec::new(),
+        };
+        for &corner in seam_corners {
+            for corner in [corner, table.opposite(corner)] {
+                if corner != INVALID {
+                    corners.seams[corner as usize] = true;
+                    for vertex in [table.vertex(next(corner)), table.vertex(previous(corner))] {
+                        corners.vertex_on_seam[vertex as usize] = true;
+                    }
+                }
+            }
+        }
+
+        for vertex in 0..table.num_vertices() {
+            let corner = table.left_most_corner(vertex as u32);
+            if corner == INVALID {
+                continue;
+            }
+            // Start from the seam or boundary reached by swinging left, then walk right
+            // and start a new attribute vertex at every seam crossed
+            let mut first = corner;
+            if corners.vertex_on_seam[vertex] {
+                let mut swung = corners.swing_left(first);
+                while swung != INVALID {
+                    first = swung;
+                    swung = corners.swing_left(swung);
+                    if swung == corner {
+                        return Err(invalid_data("Invalid Draco attribute seams"));
+                    }
+                }
+            }
+            let mut attribute_vertex = corners.left_most.len() as u32;
+            corners.vertices[first as usize] = attribute_vertex;
+            corners.left_most.push(first);
+            let mut swung = table.swing_right(first);
+            while swung != INVALID && swung != first {
+                if corners.seams[next(swung) as usize] {
+                    attribute_vertex = corners.left_most.len() as u32;
+                    corners.left_most.push(swung);
+                }
+                corners.vertices[swung as usize] = attribute_vertex;
+                swung = table.swing_right(swung);
+            }
+        }
+        Ok(corners)
+    }
+}
+
+impl Corners for SeamCorners {
+    fn vertex(&self, corner: u32) -> u32 {
+        self.vertices
+            .get(corner as usize)
+            .copied()
+            .unwrap_or(INVALID)
+    }
+
+    fn opposite(&self, corner: u32) -> u32 {
+        match self.seams.get(corner as usize) {
+            Some(false) => self.opposites[corner as usize],
+            _ => INVALID,
+        }
+    }
+
+    fn left_most_corner(&self, vertex: u32) -> u32 {
+        self.left_most
+            .get(vertex as usize)
+            .copied()
+            .unwrap_or(INVALID)
+    }
+
+    fn num_vertices(&self) -> usize {
+        self.left_most.len()
+    }
+
+    fn num_corners(&self) -> usize {
+        self.vertices.len()
+    }
+}
+
+struct TopologySplit {
+    source_symbol: u32,
+    split_symbol: u32,
+    right_edge: bool,
+}
+
+// Where the traversal symbols come from
+enum Symbols<'a> {
+    Standard(BitReader<'a>),
+    // Symbols are entropy coded per context, the context being the valence of the vertex
+    // the next face attaches to
+    Valence {
+        valences: Vec<i32>,
+        contexts: Vec<Vec<u32>>,
+        active: Option<usize>,
+        last: u32,
+    },
+}
+
+struct Traversal<'a> {
+    symbols: Symbols<'a>,
+    start_faces: BitDecoder<'a>,
+    seams: Vec<BitDecoder<'a>>,
+}
+
+impl<'a> Traversal<'a> {
+    const STANDARD: u8 = 0;
+    const VALENCE: u8 = 2;
+    const MIN_VALENCE: i32 = 2;
+    const MAX_VALENCE: i32 = 7;
+
+    fn start(
+        buffer: &mut Buffer<'a>,
+        kind: u8,
+        num_vertices: usize,
+        num_faces: usize,
+        num_attribute_data: usize,
+    ) -> io::Result<Self> {
+        let standard_symbols = match kind {
+            Self::STANDARD => {
+                let size = buffer.varint()? as usize;
+                Some(BitReader {
+                    data: buffer.bytes(size)?,
+                    position: 0,
+                })
+            }
+            Self::VALENCE => None,
+            _ => return Err(unsupported(format!("Edgebreaker traversal {}", kind))),
+        };
+        let start_faces = BitDecoder::start(buffer)?;
+        let seams = (0..num_attribute_data)
+            .map(|_| BitDecoder::start(buffer))
+            .collect::<io::Result<_>>()?;
+
+        let symbols = match standard_symbols {
+            Some(symbols) => Symbols::Standard(symbols),
+            None => {
+                let mut contexts = Vec::new();
+                for _ in Self::MIN_VALENCE..=Self::MAX_VALENCE {
+                    let count = buffer.varint_u32()? as usize;
+                    if count > num_faces {
+                        return Err(invalid_data("Invalid Draco valence context"));
+                    }
+                    contexts.push(decode_symbols(buffer, count, 1)?);
+                }
+                Symbols::Valence {
+                    valences: vec![0; num_vertices],
+                    contexts,
+                    active: None,
+                    last: TOPOLOGY_E,
+                }
+            }
+        };
+
+        Ok(Self {
+            symbols,
+            start_faces,
+            seams,
+        })
+    }
+
+    fn symbol(&mut self) -> io::Result<u32> {
+        match &mut self.symbols {
+            Symbols::Standard(bits) => {
+                let symbol = bits.read(1);
+                if symbol == TOPOLOGY_C {
+                    Ok(symbol)
+                } else {
+                    Ok(symbol | bits.read(2) << 1)
+                }
+            }
+            Symbols::Valence {
+                contexts,
+                active,
+                last,
+                ..
+            } => {
+                // The first face is always an E, the contexts are read back to front
+                *last = match active {
+                    Some(context) => {
+                        let symbol = contexts[*context]
+                            .pop()
+                            .ok_or_else(|| invalid_data("Draco valence context exhausted"))?;
+                        *[TOPOLOGY_C, TOPOLOGY_S, TOPOLOGY_L, TOPOLOGY_R, TOPOLOGY_E]
+                            .get(symbol as usize)
+                            .ok_or_else(|| invalid_data("Invalid Draco symbol"))?
+                    }
+                    None => TOPOLOGY_E,
+                };
+                Ok(*last)
+            }
+        }
+    }
+
+    fn new_active_corner(&mut self, corner: u32, table: &CornerTable) {
+        if let Symbols::Valence {
+            valences,
+            active,
+            last,
+            ..
+        } = &mut self.symbols
+        {
+            let vertex = |corner| table.vertex(corner) as usize;
+            let (tip, next, previous) = (
+                vertex(corner),
+                vertex(next(corner)),
+                vertex(previous(corner)),
+            );
+            let increments = match *last {
+                TOPOLOGY_C | TOPOLOGY_S => [0, 1, 1],
+                TOPOLOGY_R => [1, 1, 2],
+                TOPOLOGY_L => [1, 2, 1],
+                TOPOLOGY_E => [2, 2, 2],
+                _ => [0, 0, 0],
+            };
+            for (vertex, increment) in [tip, next, previous].into_iter().zip(increments) {
+                valences[vertex] += increment;
+            }
+            let valence = valences[next].clamp(Self::MIN_VALENCE, Self::MAX_VALENCE);
+            *active = Some((valence - Self::MIN_VALENCE) as usize);
+        }
+    }
+
+    fn merge_vertices(&mut self, destination: u32, source: u32) {
+        if let Symbols::Valence { valences, .. } = &mut self.symbols {
+            valences[destination as usize] += valences[source as usize];
+        }
+    }
+}
+
+struct Connectivity {
+    table: CornerTable,
+    attribute_tables: Vec<SeamCorners>,
+    corner_to_point: Vec<u32>,
+    num_points: usize,
+}
+
+// Edgebreaker decodes faces in the reverse order the encoder visited them, growing the mesh
+// from active boundary edges kept on a stack
+fn decode_connectivity(buffer: &mut Buffer) -> io::Result<Connectivity> {
+    let kind = buffer.u8()?;
+    let num_encoded_vertices = buffer.varint_u32()? as usize;
+    let num_faces = buffer.varint_u32()? as usize;
+    let num_attribute_data = buffer.u8()? as usize;
+    let num_symbols = buffer.varint_u32()? as usize;
+    let num_split_symbols = buffer.varint_u32()? as usize;
+    if num_faces > (INVALID / 3) as usize || num_symbols > num_faces {
+        return Err(invalid_data("Invalid Draco face count"));
+    }
+
+    let num_splits = buffer.varint_u32()?;
+    if num_splits as usize > num_faces {
+        return Err(invalid_data("Invalid Draco topology split count"));
+    }
+    let mut splits = Vec::new();
+    let mut last_source_symbol = 0u32;
+    for _ in 0..num_splits {
+        let source_symbol = last_source_symbol
+            .checked_add(buffer.varint_u32()?)
+            .ok_or_else(|| invalid_data("Invalid Draco topology split"))?;
+        let split_symbol = source_symbol
+            .checked_sub(buffer.varint_u32()?)
+            .ok_or_else(|| invalid_data("Invalid Draco topology split"))?;
+        splits.push(TopologySplit {
+            source_symbol,
+            split_symbol,
+            right_edge: false,
+        });
+        last_source_symbol = source_symbol;
+    }
+    let mut bits = buffer.bits();
+    for split in &mut splits {
+        split.right_edge = bits.read(1) == 1;
+    }
+    buffer.skip_bits(&bits)?;
+
+    let max_vertices = num_encoded_vertices + num_split_symbols;
+    let mut traversal =
+        Traversal::start(buffer, kind, max_vertices, num_faces, num_attribute_data)?;
+
+    let invalid = || invalid_data("Invalid Draco connectivity");
+    let mut table = CornerTable::new(num_faces);
+    let mut is_vertex_hole = vec![true; max_vertices];
+    let mut active_corners: Vec<u32> = Vec::new();
+    // Edges that split off, keyed by the decoder symbol of the S face that merges them back
+    let mut split_corners = HashMap::new();
+    let mut isolated_vertices = Vec::new();
+    let mut num_decoded_faces = 0;
+
+    for symbol_id in 0..num_symbols {
+        let corner = 3 * num_decoded_faces as u32;
+        num_decoded_faces += 1;
+        let mut check_split = false;
+        match traversal.symbol()? {
+            TOPOLOGY_C => {
+                // A new face between the active edge and the next edge around its vertex
+                let corner_a = *active_corners.last().ok_or_else(invalid)?;
+                let vertex_x = table.vertex(next(corner_a));
+                let corner_b = next(table.left_most_corner(vertex_x));
+                if corner_a == corner_b
+                    || table.opposite(corner_a) != INVALID
+                    || table.opposite(corner_b) != INVALID
+                {
+                    return Err(invalid());
+                }
+                table.set_opposites(corner_a, corner + 1);
+                table.set_opposites(corner_b, corner + 2);
+                let vertex_a_previous = table.vertex(previous(corner_a));
+                let vertex_b_next = table.vertex(next(corner_b));
+                if vertex_x == vertex_a_previous || vertex_x == vertex_b_next {
+                    return Err(invalid());
+                }
+                table.vertices[corner as usize] = vertex_x;
+                table.vertices[corner as usize + 1] = vertex_b_next;
+                table.vertices[corner as usize + 2] = vertex_a_previous;
+                table.left_most[vertex_a_previous as usize] = corner + 2;
+                is_vertex_hole[vertex_x as usize] = false;
+                *active_corners.last_mut().unwrap() = corner;
+            }
+            symbol @ (TOPOLOGY_R | TOPOLOGY_L) => {
+                // A new face with a new vertex on the active edge
+                let corner_a = *active_corners.last().ok_or_else(invalid)?;
+                if table.opposite(corner_a) != INVALID {
+                    return Err(invalid());
+                }
+                let (opposite_corner, corner_l, corner_r) = if symbol == TOPOLOGY_R {
+                    (corner + 2, corner + 1, corner)
+                } else {
+                    (corner + 1, corner, corner + 2)
+                };
+                table.set_opposites(opposite_corner, corner_a);
+                let new_vertex = table.add_vertex();
+                if table.num_vertices() > max_vertices {
+                    return Err(invalid());
+                }
+                table.vertices[opposite_corner as usize] = new_vertex;
+                table.left_most[new_vertex as usize] = opposite_corner;
+                let vertex_r = table.vertex(previous(corner_a));
+                table.vertices[corner_r as usize] = vertex_r;
+                table.left_most[vertex_r as usize] = corner_r;
+                table.vertices[corner_l as usize] = table.vertex(next(corner_a));
+                *active_corners.last_mut().unwrap() = corner;
+                check_split = true;
+            }
+            TOPOLOGY_S => {
+                // A new face that merges the two topmost active edges
+                let corner_b = active_corners.pop().ok_or_else(invalid)?;
+                if let Some(&split_corner) = split_corners.get(&symbol_id) {
+                    active_corners.push(split_corner);
+                }
+                let corner_a = *active_corners.last().ok_or_else(invalid)?;
+                if corner_a == corner_b
+                    || table.opposite(corner_a) != INVALID
+                    || table.opposite(corner_b) != INVALID
+                {
+                    return Err(invalid());
+                }
+                table.set_opposites(corner_a, corner + 2);
+                table.set_opposites(corner_b, corner + 1);
+                let vertex_p = table.vertex(previous(corner_a));
+                table.vertices[corner as usize] = vertex_p;
+                table.vertices[corner as usize + 1] = table.vertex(next(corner_a));
+                let vertex_b_previous = table.vertex(previous(corner_b));
+                table.vertices[corner as usize + 2] = vertex_b_previous;
+                table.left_most[vertex_b_previous as usize] = corner + 2;
+                let mut corner_n = next(corner_b);
+                let vertex_n = table.vertex(corner_n);
+                traversal.merge_vertices(vertex_p, vertex_n);
+                table.left_most[vertex_p as usize] = table.left_most_corner(vertex_n);
+                // Every corner of vertex n now belongs to p
+                let first_corner = corner_n;
+                while corner_n != INVALID {
+                    table.vertices[corner_n as usize] = vertex_p;
+                    corner_n = table.swing_left(corner_n);
+                    if corner_n == first_corner {
+                        return Err(invalid());
+                    }
+                }
+                table.left_most[vertex_n as usize] = INVALID;
+                if num_attribute_data == 0 {
+                    isolated_vertices.push(vertex_n);
+                }
+                *active_corners.last_mut().unwrap() = corner;
+            }
+            TOPOLOGY_E => {
+                // A new face with three new vertices, starting a new active edge
+                for offset in 0..3 {
+                    let vertex = table.add_vertex();
+                    table.vertices[(corner + offset) as usize] = vertex;
+                    table.left_most[vertex as usize] = corner + offset;
+                }
+                if table.num_vertices() > max_vertices {
+                    return Err(invalid());
+                }
+                active_corners.push(corner);
+                check_split = true;
+            }
+            _ => return Err(invalid()),
+        }
+        let active_corner = *active_corners.last().ok_or_else(invalid)?;
+        traversal.new_active_corner(active_corner, &table);
+
+        if check_split {
+            let encoder_symbol = (num_symbols - symbol_id - 1) as u32;
+            while let Some(split) = splits.last() {
+                if split.source_symbol > encoder_symbol {
+                    return Err(invalid());
+                }
+                if split.source_symbol != encoder_symbol {
+                    break;
+                }
+                let top = *active_corners.last().ok_or_else(invalid)?;
+                let new_active_corner = if split.right_edge {
+                    next(top)
+                } else {
+                    previous(top)
+                };
+                let decoder_split_symbol = num_symbols
+                    .checked_sub(split.split_symbol as usize + 1)
+                    .ok_or_else(invalid)?;
+                split_corners.insert(decoder_split_symbol, new_active_corner);
+                splits.pop();
+            }
+        }
+    }
+
+    // Close the remaining active edges with their start faces
+    while let Some(corner) = active_corners.pop() {
+        if !traversal.start_faces.read() {
+            // The traversal started on an open boundary, there is no face to add
+            continue;
+        }
+        if num_decoded_faces >= num_faces {
+            return Err(invalid());
+        }
+        let vertex_n = table.vertex(next(corner));
+        let corner_b = next(table.left_most_corner(vertex_n));
+        let vertex_x = table.vertex(next(corner_b));
+        let corner_c = next(table.left_most_corner(vertex_x));
+        if corner == corner_b
+            || corner == corner_c
+            || corner_b == corner_c
+            || table.opposite(corner) != INVALID
+            || table.opposite(corner_b) != INVALID
+            || table.opposite(corner_c) != INVALID
+        {
+            return Err(invalid());
+        }
+        let vertex_p = table.vertex(next(corner_c));
+        let new_corner = 3 * num_decoded_faces as u32;
+        num_decoded_faces += 1;
+        table.set_opposites(new_corner, corner);
+        table.set_opposites(new_corner + 1, corner_b);
+        table.set_opposites(new_corner + 2, corner_c);
+        table.vertices[new_corner as usize] = vertex_x;
+        table.vertices[new_corner as usize + 1] = vertex_p;
+        table.vertices[new_corner as usize + 2] = vertex_n;
+        for vertex in [vertex_x, vertex_p, vertex_n] {
+            is_vertex_hole[vertex as usize] = false;
+        }
+    }
+    if num_decoded_faces != num_faces {
+        return Err(invalid());
+    }
+
+    // Move the last vertices into the slots of the ones merged away by S faces
+    let mut num_vertices = table.num_vertices();
+    for isolated in isolated_vertices {
+        let mut last = num_vertices - 1;
+        while table.left_most_corner(last as u32) == INVALID {
+            num_vertices -= 1;
+            last = num_vertices - 1;
+        }
+        if (last as u32) < isolated {
+            continue;
+        }
+        for corner in table.corners_around(table.left_most_corner(last as u32)) {
+            if table.vertex(corner) != last as u32 {
+                return Err(invalid());
+            }
+            table.vertices[corner as usize] = isolated;
+        }
+        table.left_most[isolated as usize] = table.left_most[last];
+        table.left_most[last] = INVALID;
+        is_vertex_hole[isolated as usize] = is_vertex_hole[last];
+        is_vertex_hole[last] = false;
+        num_vertices -= 1;
+    }
+    table.left_most.truncate(num_vertices);
+
+    // One seam flag per attribute for every interior edge, boundaries are always seams
+    let mut seam_corners = vec![Vec::new(); num_attribute_data];
+    if num_attribute_data > 0 {
+        for face in 0..num_faces as u32 {
+            let first = 3 * face;
+            for corner in [first, next(first), previous(first)] {
+                let opposite = table.opposite(corner);
+                if opposite == INVALID {
+                    seam_corners.iter_mut().for_each(|seams| seams.push(corner));
+                } else if opposite / 3 >= face {
+                    for (seams, decoder) in seam_corners.iter_mut().zip(&mut traversal.seams) {
+                        if decoder.read() {
+                            seams.push(corner);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let attribute_tables = seam_corners
+        .iter()
+        .map(|seams| SeamCorners::new(&table, seams))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let (corner_to_point, num_points) = assign_points(&table, &attribute_tables, &is_vertex_hole)?;
+    Ok(Connectivity {
+        table,
+        attribute_tables,
+        corner_to_point,
+        num_points,
+    })
+}
+
+// Splits every vertex into one point per distinct combination of attribute vertices
+fn assign_points(
+    table: &CornerTable,
+    attribute_tables: &[SeamCorners],
+    is_vertex_hole: &[bool],
+) -> io::Result<(Vec<u32>, usize)> {
+    if attribute_tables.is_empty() {
+        return Ok((table.vertices.clone(), table.num_vertices()));
+    }
+
+    let mut corner_to_point = vec![0; table.num_corners()];
+    let mut num_points = 0;
+    let vertices = is_vertex_hole.iter().enumerate().take(table.num_vertices());
+    for (vertex, is_hole) in vertices {
+        let corner = table.left_most_corner(vertex as u32);
+        if corner == INVALID {
+            continue;
+        }
+        // Interior vertices start at the first seam found when swinging right
+        let mut first = corner;
+        if !is_hole {
+            for attribute in attribute_tables {
+                if !attribute.vertex_on_seam[vertex] {
+                    continue;
+                }
+                let attribute_vertex = attribute.vertex(corner);
+                let mut swung = table.swing_right(corner);
+                let mut seam_found = false;
+                while swung != corner {
+                    if swung == INVALID {
+                        return Err(invalid_data("Invalid Draco attribute seams"));
+                    }
+                    if attribute.vertex(swung) != attribute_vertex {
+                        first = swung;
+                        seam_found = true;
+                        break;
+                    }
+                    swung = table.swing_right(swung);
+                }
+                if seam_found {
+                    break;
+                }
+            }
+        }
+
+        corner_to_point[first as usize] = num_points;
+        num_points += 1;
+        let mut previous_corner = first;
+        let mut swung = table.swing_right(first);
+        while swung != INVALID && swung != first {
+            let seam = attribute_tables
+                .iter()
+                .any(|a| a.vertex(swung) != a.vertex(previous_corner));
+            corner_to_point[swung as usize] = if seam {
+                num_points += 1;
+                num_points - 1
+            } else {
+                corner_to_point[previous_corner as usize]
+            };
+            previous_corner = swung;
+            swung = table.swing_right(swung);
+        }
+    }
+    Ok((corner_to_point, num_points as usize))
+}
+
+// The order attribute values are encoded in, found by traversing the mesh
+struct Sequence {
+    point_ids: Vec<u32>,
+    data_to_corner: Vec<u32>,
+    vertex_to_data: Vec<u32>,
+}
+
+struct Traverser<'t> {
+    table: &'t dyn Corners,
+    corner_to_point: &'t [u32],
+    visited_faces: Vec<bool>,
+    visited_vertices: Vec<bool>,
+    sequence: Sequence,
+}
+
+impl<'t> Traverser<'t> {
+    const DEPTH_FIRST: u8 = 0;
+    const PREDICTION_DEGREE: u8 = 1;
+
+    fn run(table: &'t dyn Corners, method: u8, corner_to_point: &'t [u32]) -> io::Result<Sequence> {
+        let mut traverser = Self {
+            table,
+            corner_to_point,
+            visited_faces: vec![false; table.num_corners() / 3],
+            visited_vertices: vec![false; table.num_vertices()],
+            sequence: Sequence {
+                point_ids: Vec::new(),
+                data_to_corner: Vec::new(),
+                vertex_to_data: vec![0; table.num_vertices()],
+            },
+        };
+        let mut prediction_degrees = vec![0; table.num_vertices()];
+        for corner in (0..table.num_corners() as u32).step_by(3) {
+            match method {
+                Self::DEPTH_FIRST => traverser.depth_first(corner)?,
+                Self::PREDICTION_DEGREE => {
+                    traverser.prediction_degree(corner, &mut prediction_degrees)?
+                }
+                _ => return Err(unsupported(format!("Draco traversal method {}", method))),
+            }
+        }
+        Ok(traverser.sequence)
+    }
+
+    fn face_visited(&self, corner: u32) -> bool {
+        corner == INVALID || self.visited_faces[corner as usize / 3]
+    }
+
+    fn visit_vertex(&mut self, corner: u32) -> io::Result<()> {
+        let vertex = self.table.vertex(corner);
+        if vertex == INVALID {
+            return Err(invalid_data("Invalid Draco connectivity"));
+        }
+        if !self.visited_vertices[vertex as usize] {
+            self.visited_vertices[vertex as usize] = true;
+            let sequence = &mut self.sequence;
+            sequence.vertex_to_data[vertex as usize] = sequence.point_ids.len() as u32;
+            sequence
+                .point_ids
+                .push(self.corner_to_point[corner as usize]);
+            sequence.data_to_corner.push(corner);
+        }
+        Ok(())
+    }
+
+    fn depth_first(&mut self, start: u32) -> io::Result<()> {
+        if self.face_visited(start) {
+            return Ok(());
+        }
+        self.visit_vertex(next(start))?;
+        self.visit_vertex(previous(start))?;
+
+        let mut stack = vec![start];
+        while let Some(&top) = stack.last() {
+            if self.face_visited(top) {
+                stack.pop();
+                continue;
+            }
+            let mut corner = top;
+            loop {
+                self.visited_faces[corner as usize / 3] = true;
+                let vertex = self.table.vertex(corner);
+                if vertex == INVALID {
+                    return Err(invalid_data("Invalid Draco connectivity"));
+                }
+                if !self.visited_vertices[vertex as usize] {
+                    let on_boundary = self.table.is_on_boundary(vertex);
+                    self.visit_vertex(corner)?;
+                    if !on_boundary {
+                        corner = self.table.right_corner(corner);
+                        if corner == INVALID {
+                            return Err(invalid_data("Invalid Draco connectivity"));
+                        }
+                        continue;
+                    }
+                }
+                let right = self.table.right_corner(corner);
+                let left = self.table.left_corner(corner);
+                match (self.face_visited(right), self.face_visited(left)) {
+                    (true, true) => {
+                        stack.pop();
+                        break;
+                    }
+                    (true, false) => corner = left,
+                    (false, true) => corner = right,
+                    (false, false) => {
+                        // Left is handled after everything reachable on the right
+                        *stack.last_mut().unwrap() = left;
+                        stack.push(right);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Prefers faces whose tip vertex can be predicted from the most already decoded neighbors
+    fn prediction_degree(&mut self, start: u32, degrees: &mut [u32]) -> io::Result<()> {
+        const PRIORITIES: usize = 3;
+        let mut stacks: [Vec<u32>; PRIORITIES] = Default::default();
+        let mut best_priority = 0;
+        stacks[0].push(start);
+        self.visit_vertex(next(start))?;
+        self.visit_vertex(previous(start))?;
+        self.visit_vertex(start)?;
+
+        loop {
+            let popped = (best_priority..PRIORITIES)
+                .find_map(|priority| stacks[priority].pop().map(|corner| (priority, corner)));
+            let mut corner = match popped {
+                Some((priority, corner)) => {
+                    best_priority = priority;
+                    corner
+                }
+                None => break,
+            };
+            if self.face_visited(corner) {
+                continue;
+            }
+            loop {
+                self.visited_faces[corner as usize / 3] = true;
+                self.visit_vertex(corner)?;
+
+                let right = self.table.right_corner(corner);
+                let left = self.table.left_corner(corner);
+                let right_visited = self.face_visited(right);
+                let left_visited = self.face_visited(left);
+                let mut priority_of = |corner: u32, visited: &[bool]| {
+                    let tip = self.table.vertex(corner) as usize;
+                    if visited[tip] {
+                        0
+                    } else {
+                        degrees[tip] += 1;
+                        if degrees[tip] > 1 {
+                            1
+                        } else {
+                            2
+                        }
+                    }
+                };
+                if !left_visited {
+                    let priority = priority_of(left, &self.visited_vertices);
+                    if right_visited && priority <= best_priority {
+                        corner = left;
+                        continue;
+                    }
+                    stacks[priority].push(left);
+                    best_priority = best_priority.min(priority);
+                }
+                if !right_visited {
+                    let priority = priority_of(right, &self.visited_vertices);
+                    if priority <= best_priority {
+                        corner = right;
+                        continue;
+                    }
+                    stacks[priority].push(right);
+                    best_priority = best_priority.min(priority);
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct AttributesDecoder {
+    data_id: Option<usize>,
+    per_corner: bool,
+    traversal: u8,
+    attributes: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Attribute {
+    kind: u8,
+    data_type: u8,
+    components: usize,
+    unique_id: u32,
+    coding: u8,
+    // Integer values before the inverse transform, quantized positions feed the predictions
+    // of later attributes
+    portable: Vec<i32>,
+    values: Vec<f32>,
+    point_to_value: Vec<u32>,
+}
+
+// The connectivity seen by one attributes decoder
+struct MeshData<'t> {
+    table: &'t dyn Corners,
+    sequence: &'t Sequence,
+}
+
+impl MeshData<'_> {
+    fn data_of_corner(&self, corner: u32) -> usize {
+        self.sequence.vertex_to_data[self.table.vertex(corner) as usize] as usize
+    }
+}
+
+// Quantized positions, looked up through the points of the attribute being predicted
+struct Positions<'t> {
+    values: &'t [i32],
+    point_to_value: &'t [u32],
+    point_ids: &'t [u32],
+}
+
+impl Positions<'_> {
+    fn at_data(&self, data: usize) -> [i64; 3] {
+        let value = self.point_to_value[self.point_ids[data] as usize] as usize * 3;
+        [0, 1, 2].map(|c| self.values[value + c] as i64)
+    }
+}
+
+fn decode_attributes(
+    buffer: &mut Buffer,
+    connectivity: &Connectivity,
+) -> io::Result<Vec<Attribute>> {
+    let num_attribute_data = connectivity.attribute_tables.len();
+    let mut decoders = Vec::new();
+    for _ in 0..buffer.u8()? {
+        let data_id = buffer.i8()?;
+        let per_corner = buffer.u8()? == 1;
+        let traversal = buffer.u8()?;
+        let data_id = match usize::try_from(data_id) {
+            Ok(id) if id >= num_attribute_data => {
+                return Err(invalid_data("Invalid Draco attribute data"))
+            }
+            Ok(id) => Some(id),
+            Err(_) if per_corner => return Err(invalid_data("Invalid Draco attribute data")),
+            Err(_) => None,
+        };
+        if decoders
+            .iter()
+            .any(|d: &AttributesDecoder| d.data_id == data_id)
+        {
+            return Err(invalid_data("Invalid Draco attribute data"));
+        }
+        decoders.push(AttributesDecoder {
+            data_id,
+            per_corner,
+            traversal,
+            attributes: Vec::new(),
+        });
+    }
+
+    let mut attributes = Vec::new();
+    for decoder in &mut decoders {
+        let count = buffer.varint_u32()?;
+        if count == 0 || count as usize > buffer.data.len() {
+            return Err(invalid_data("Invalid Draco attribute count"));
+        }
+        for _ in 0..count {
+            let attribute = Attribute {
+                kind: buffer.u8()?,
+                data_type: buffer.u8()?,
+                components: buffer.u8()? as usize,
+                ..Attribute::default()
+            };
+            // The normalized flag, normalization follows the glTF accessor instead
+            buffer.u8()?;
+            let attribute = Attribute {
+                unique_id: buffer.varint_u32()?,
+                ..attribute
+            };
+            if attribute.kind > 4
+                || !(1..=11).contains(&attribute.data_type)
+                || attribute.components == 0
+            {
+                return Err(invalid_data("Invalid Draco attribute"));
+            }
+            decoder.attributes.push(attributes.len());
+            attributes.push(attribute);
+        }
+        for &attribute in &decoder.attributes {
+            attributes[attribute].coding = buffer.u8()?;
+        }
+    }
+
+    for decoder in &decoders {
+        let table: &dyn Corners = match decoder.data_id {
+            Some(id) if decoder.per_corner => &connectivity.attribute_tables[id],
+            _ => &connectivity.table,
+        };
+        let sequence = Traverser::run(table, decoder.traversal, &connectivity.corner_to_point)?;
+        let mut point_to_value = vec![0; connectivity.num_points];
+        for corner in 0..table.num_corners() as u32 {
+            let vertex = table.vertex(corner);
+            if vertex == INVALID {
+                return Err(invalid_data("Invalid Draco connectivity"));
+            }
+            let point = connectivity.corner_to_point[corner as usize] as usize;
+            point_to_value[point] = sequence.vertex_to_data[vertex as usize];
+        }
+        let mesh = MeshData {
+            table,
+            sequence: &sequence,
+        };
+
+        for &index in &decoder.attributes {
+            let mut attribute = std::mem::take(&mut attributes[index]);
+            attribute.point_to_value = point_to_value.clone();
+            let positions = attributes
+                .iter()
+                .find(|a| a.kind == ATTRIBUTE_POSITION && !a.portable.is_empty())
+                .map(|a| Positions {
+                    values: &a.portable,
+                    point_to_value: &a.point_to_value,
+                    point_ids: &sequence.point_ids,
+                });
+            decode_portable(buffer, &mut attribute, &mesh, positions)?;
+            attributes[index] = attribute;
+        }
+        for &index in &decoder.attributes {
+            decode_values(buffer, &mut attributes[index])?;
+        }
+    }
+    Ok(attributes)
+}
+
+fn read_value(buffer: &mut Buffer, data_type: u8) -> io::Result<f32> {
+    let bytes = buffer.bytes(match data_type {
+        1 | 2 | 11 => 1,
+        3 | 4 => 2,
+        5 | 6 | 9 => 4,
+        _ => 8,
+    })?;
+    let array = |n| {
+        let mut array = [0; 8];
+        array[..n].copy_from_slice(&bytes[..n]);
+        array
+    };
+    Ok(match data_type {
+        1 => bytes[0] as i8 as f32,
+        3 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+        4 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+        5 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+        6 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+        7 => i64::from_le_bytes(array(8)) as f32,
+        8 => u64::from_le_bytes(array(8)) as f32,
+        9 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        10 => f64::from_le_bytes(array(8)) as f32,
+        _ => bytes[0] as f32,
+    })
+}
+
+fn decode_portable(
+    buffer: &mut Buffer,
+    attribute: &mut Attribute,
+    mesh: &MeshData,
+    positions: Option<Positions>,
+) -> io::Result<()> {
+    let num_entries = mesh.sequence.point_ids.len();
+    let components = match attribute.coding {
+        CODING_GENERIC => {
+            for _ in 0..num_entries * attribute.components {
+                attribute
+                    .values
+                    .push(read_value(buffer, attribute.data_type)?);
+            }
+            return Ok(());
+        }
+        CODING_INTEGER => attribute.components,
+        CODING_QUANTIZATION | CODING_NORMALS if attribute.data_type != DATA_TYPE_FLOAT32 => {
+            return Err(invalid_data("Draco quantization of a non float attribute"))
+        }
+        CODING_QUANTIZATION => attribute.components,
+        // Octahedral coordinates
+        CODING_NORMALS if attribute.components == 3 => 2,
+        CODING_NORMALS => return Err(invalid_data("Draco normals need 3 components")),
+        coding => return Err(unsupported(format!("Draco attribute coding {}", coding))),
+    };
+
+    let method = buffer.i8()?;
+    let mut scheme = match method {
+        -2 => None,
+        _ => Some(Scheme::new(method, buffer.i8()?, attribute.coding)?),
+    };
+
+    let num_values = num_entries * components;
+    let mut values: Vec<i32> = if buffer.u8()? > 0 {
+        decode_symbols(buffer, num_values, components)?
+            .into_iter()
+            .map(|v| v as i32)
+            .collect()
+    } else {
+        let size = buffer.u8()? as usize;
+        if !(1..=4).contains(&size) {
+            return Err(invalid_data("Invalid Draco value size"));
+        }
+        let bytes = buffer.bytes(size * num_values)?;
+        bytes
+            .chunks_exact(size)
+            .map(|value| {
+                let mut array = [0; 4];
+                array[..size].copy_from_slice(value);
+                i32::from_le_bytes(array)
+            })
+            .collect()
+    };
+
+    let corrections_positive = scheme
+        .as_ref()
+        .map(|s| s.transform.corrections_positive())
+        .unwrap_or(false);
+    if !corrections_positive {
+        // Zigzag coded, the lowest bit is the sign
+        for value in &mut values {
+            let symbol = *value as u32;
+            *value = (symbol >> 1) as i32 ^ -((symbol & 1) as i32);
+        }
+    }
+
+    if let Some(scheme) = &mut scheme {
+        scheme.decode_data(buffer, mesh.table.num_corners())?;
+        scheme.restore(&mut values, components, mesh, positions)?;
+    }
+    attribute.portable = values;
+    Ok(())
+}
+
+// Data for the inverse transforms follows the values of every attribute in the decoder
+fn decode_values(buffer: &mut Buffer, attribute: &mut Attribute) -> io::Result<()> {
+    match attribute.coding {
+        CODING_INTEGER => {
+            attribute.values = attribute.portable.iter().map(|&v| v as f32).collect();
+        }
+        CODING_QUANTIZATION => {
+            let minimum = (0..attribute.components)
+                .map(|_| buffer.f32())
+                .collect::<io::Result<Vec<_>>>()?;
+            let range = buffer.f32()?;
+            let bits = buffer.u8()?;
+            if !(1..=30).contains(&bits) {
+                return Err(invalid_data("Invalid Draco quantization"));
+            }
+            let delta = range / ((1u32 << bits) - 1) as f32;
+            attribute.values = attribute
+                .portable
+                .chunks_exact(attribute.components)
+                .flat_map(|value| {
+                    value
+                        .iter()
+                        .zip(&minimum)
+                        .map(|(&q, min)| q as f32 * delta + min)
+                })
+                .collect();
+        }
+        CODING_NORMALS => {
+            let octahedron = Octahedron::new(buffer.u8()? as u32)?;
+            attribute.values = attribute
+                .portable
+                .chunks_exact(2)
+                .flat_map(|st| octahedron.unit_vector(st[0], st[1]))
+                .collect();
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Octahedral normal coordinates at a given quantization
+#[derive(Clone, Copy)]
+struct Octahedron {
+    max_quantized: i32,
+    max: i32,
+    center: i32,
+}
+
+impl Octahedron {
+    fn new(bits: u32) -> io::Result<Self> {
+        if !(2..=30).contains(&bits) {
+            return Err(invalid_data("Invalid Draco normal quantization"));
+        }
+        let max_quantized = (1 << bits) - 1;
+        Ok(Self {
+            max_quantized,
+            max: max_quantized - 1,
+            center: (max_quantized - 1) / 2,
+        })
+    }
+
+    fn unit_vector(&self, s: i32, t: i32) -> [f32; 3] {
+        let scale = 2.0 / self.max as f32;
+        let mut y = s as f32 * scale - 1.0;
+        let mut z = t as f32 * scale - 1.0;
+        let x = 1.0 - y.abs() - z.abs();
+        // Points outside the central diamond wrap over to the far side of the octahedron
+        let offset = (-x).max(0.0);
+        y += if y < 0.0 { offset } else { -offset };
+        z += if z < 0.0 { offset } else { -offset };
+        let length2 = x * x + y * y + z * z;
+        if length2 < 1e-6 {
+            [0.0; 3]
+        } else {
+            let inverse = 1.0 / length2.sqrt();
+            [x * inverse, y * inverse, z * inverse]
+        }
+    }
+
+    // Scales a vector so its absolute components add up to the center value
+    fn canonicalize(&self, vector: [i64; 3]) -> [i32; 3] {
+        let center = self.center as i64;
+        let sum = vector.iter().map(|v| v.abs()).sum::<i64>();
+        if sum == 0 {
+            return [self.center, 0, 0];
+        }
+        let x = vector[0] * center / sum;
+        let y = vector[1] * center / sum;
+        let z = center - x.abs() - y.abs();
+        [
+            x as i32,
+            y as i32,
+            if vector[2] >= 0 { z } else { -z } as i32,
+        ]
+    }
+
+    fn coordinates(&self, vector: [i32; 3]) -> [i32; 2] {
+        let (s, t) = if vector[0] >= 0 {
+            (vector[1] + self.center, vector[2] + self.center)
+        } else {
+            (
+                if vector[1] < 0 {
+                    vector[2].abs()
+                } else {
+                    self.max - vector[2].abs()
+                },
+                if vector[2] < 0 {
+                    vector[1].abs()
+                } else {
+                    self.max - vector[1].abs()
+                },
+            )
+        };
+        // Points on the outer edge have two encodings, keep the one the encoder uses
+        let (max, center) = (self.max, self.center);
+        match (s, t) {
+            (0, 0) | (0, _) if t == 0 || t == max => [max, max],
+            (_, 0) if s == max => [max, max],
+            (0, _) if t > center => [0, center - (t - center)],
+            _ if s == max && t < center => [s, center + (center - t)],
+            _ if t == max && s < center => [center - (s - center), t],
+            _ if t == 0 && s > center => [center + (center - s), t],
+            _ => [s, t],
+        }
+    }
+}
+
+enum Transform {
+    // Values wrap around inside [min, max], which keeps the corrections small
+    Wrap { min: i32, max: i32 },
+    Octahedron(Octahedron),
+    // Also rotates the prediction into the bottom left quadrant
+    OctahedronCanonicalized(Octahedron),
+}
+
+impl Transform {
+    fn corrections_positive(&self) -> bool {
+        !matches!(self, Transform::Wrap { .. })
+    }
+
+    fn decode_data(&mut self, buffer: &mut Buffer) -> io::Result<()> {
+        let canonicalized = matches!(self, Transform::OctahedronCanonicalized(_));
+        match self {
+            Transform::Wrap { min, max } => {
+                *min = buffer.i32()?;
+                *max = buffer.i32()?;
+                if *min > *max || *max as i64 - *min as i64 >= i32::MAX as i64 {
+                    return Err(invalid_data("Invalid Draco wrap transform"));
+                }
+            }
+            Transform::Octahedron(octahedron) | Transform::OctahedronCanonicalized(octahedron) => {
+                let max_quantized = buffer.i32()?;
+                if canonicalized {
+                    // The center value follows, it is implied by the maximum
+                    buffer.i32()?;
+                }
+                if max_quantized <= 0 || max_quantized % 2 == 0 {
+                    return Err(invalid_data("Invalid Draco octahedron transform"));
+                }
+                *octahedron = Octahedron::new(32 - max_quantized.leading_zeros())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn octahedron(&self) -> Option<Octahedron> {
+        match self {
+            Transform::Octahedron(o) | Transform::OctahedronCanonicalized(o) => Some(*o),
+            Transform::Wrap { .. } => None,
+        }
+    }
+
+    fn original(&self, prediction: &[i32], corrections: &[i32], out: &mut [i32]) {
+        match self {
+            Transform::Wrap { min, max } => {
+                let range = max - min + 1;
+                for ((out, &prediction), &correction) in
+                    out.iter_mut().zip(prediction).zip(corrections)
+                {
+                    let value = prediction.clamp(*min, *max).wrapping_add(correction);
+                    *out = if value > *max {
+                        value - range
+                    } else if value < *min {
+                        value + range
+                    } else {
+                        value
+                    };
+                }
+            }
+            Transform::Octahedron(o) => {
+                let center = o.center;
+                let mut prediction = [prediction[0] - center, prediction[1] - center];
+                let in_diamond = prediction[0].abs() + prediction[1].abs() <= center;
+                if !in_diamond {
+                    prediction = invert_diamond(prediction, center);
+                }
+                let mut original =
+                    [0, 1].map(|i| mod_max(prediction[i].wrapping_add(corrections[i]), o));
+                if !in_diamond {
+                    original = invert_diamond(original, center);
+                }
+                out[0] = original[0] + center;
+                out[1] = original[1] + center;
+            }
+            Transform::OctahedronCanonicalized(o) => {
+                let center = o.center;
+                let mut prediction = [prediction[0] - center, prediction[1] - center];
+                let in_diamond = prediction[0].abs() + prediction[1].abs() <= center;
+                if !in_diamond {
+                    prediction = invert_diamond(prediction, center);
+                }
+                let in_bottom_left = (prediction[0] == 0 && prediction[1] == 0)
+                    || (prediction[0] < 0 && prediction[1] <= 0);
+                let rotation = rotation_count(prediction);
+                if !in_bottom_left {
+                    prediction = rotate(prediction, rotation);
+                }
+                let mut original =
+                    [0, 1].map(|i| mod_max(prediction[i].wrapping_add(corrections[i]), o));
+                if !in_bottom_left {
+                    original = rotate(original, (4 - rotation) % 4);
+                }
+                if !in_diamond {
+                    original = invert_diamond(original, center);
+                }
+                out[0] = original[0] + center;
+                out[1] = original[1] + center;
+            }
+        }
+    }
+}
+
+fn mod_max(value: i32, octahedron: &Octahedron) -> i32 {
+    if value > octahedron.center {
+        value - octahedron.max_quantized
+    } else if value < -octahedron.center {
+        value + octahedron.max_quantized
+    } else {
+        value
+    }
+}
+
+// Mirrors a point outside the diamond |s| + |t| <= center to the inside and back
+fn invert_diamond([s, t]: [i32; 2], center: i32) -> [i32; 2] {
+    let (sign_s, sign_t) = if s >= 0 && t >= 0 {
+        (1, 1)
+    } else if s <= 0 && t <= 0 {
+        (-1, -1)
+    } else {
+        (if s > 0 { 1 } else { -1 }, if t > 0 { 1 } else { -1 })
+    };
+    let corner_s = sign_s * center;
+    let corner_t = sign_t * center;
+    let us = 2 * s - corner_s;
+    let ut = 2 * t - corner_t;
+    let (us, ut) = if sign_s * sign_t >= 0 {
+        (-ut, -us)
+    } else {
+        (ut, us)
+    };
+    [(us + corner_s) / 2, (ut + corner_t) / 2]
+}
+
+fn rotation_count([s, t]: [i32; 2]) -> i32 {
+    match (s.signum(), t.signum()) {
+        (0, 0) => 0,
+        (0, 1) => 3,
+        (0, _) => 1,
+        (1, -1) => 1,
+        (1, _) => 2,
+        (_, 1) => 3,
+        _ => 0,
+    }
+}
+
+fn rotate([s, t]: [i32; 2], count: i32) -> [i32; 2] {
+    match count {
+        1 => [t, -s],
+        2 => [-s, -t],
+        3 => [-t, s],
+        _ => [s, t],
+    }
+}
+
+enum Prediction<'a> {
+    Difference,
+    Parallelogram,
+    // Averages up to four parallelograms, skipping the ones flagged as crossing a crease
+    ConstrainedMultiParallelogram { creases: [Vec<bool>; 4] },
+    // Projects the triangle's positions into UV space
+    TexCoords { orientations: Vec<bool> },
+    // Predicts from the area weighted normal of the positions around the vertex
+    GeometricNormal { flips: Option<BitDecoder<'a>> },
+}
+
+struct Scheme<'a> {
+    prediction: Prediction<'a>,
+    transform: Transform,
+}
+
+impl<'a> Scheme<'a> {
+    fn new(method: i8, transform: i8, coding: u8) -> io::Result<Self> {
+        let transform = match (transform, coding) {
+            (1, CODING_INTEGER | CODING_QUANTIZATION) => Transform::Wrap { min: 0, max: 0 },
+            (2, CODING_NORMALS) => Transform::Octahedron(Octahedron::new(2)?),
+            (3, CODING_NORMALS) => Transform::OctahedronCanonicalized(Octahedron::new(2)?),
+            _ => {
+                return Err(unsupported(format!(
+                    "Draco prediction transform {}",
+                    transform
+                )))
+            }
+        };
+        let prediction = match method {
+            0 => Prediction::Difference,
+            1 => Prediction::Parallelogram,
+            4 => Prediction::ConstrainedMultiParallelogram {
+                creases: Default::default(),
+            },
+            5 => Prediction::TexCoords {
+                orientations: Vec::new(),
+            },
+            6 => Prediction::GeometricNormal { flips: None },
+            _ => return Err(unsupported(format!("Draco prediction method {}", method))),
+        };
+        if matches!(transform, Transform::Wrap { .. })
+            == matches!(prediction, Prediction::GeometricNormal { .. })
+            && !matches!(prediction, Prediction::Difference)
+        {
+            return Err(invalid_data("Invalid Draco prediction scheme"));
+        }
+        Ok(Self {
+            prediction,
+            transform,
+        })
+    }
+
+    fn decode_data(&mut self, buffer: &mut Buffer<'a>, num_corners: usize) -> io::Result<()> {
+        match &mut self.prediction {
+            Prediction::ConstrainedMultiParallelogram { creases } => {
+                for flags in creases.iter_mut() {
+                    let count = buffer.varint_u32()? as usize;
+                    if count > num_corners {
+                        return Err(invalid_data("Invalid Draco crease flags"));
+                    }
+                    if count > 0 {
+                        let mut decoder = BitDecoder::start(buffer)?;
+                        *flags = (0..count).map(|_| decoder.read()).collect();
+                    }
+                }
+            }
+            Prediction::TexCoords { orientations } => {
+                let count = buffer.i32()?;
+                if count < 0 || count as usize > num_corners {
+                    return Err(invalid_data("Invalid Draco UV orientations"));
+                }
+                // Delta coded, a zero bit flips the orientation
+                let mut decoder = BitDecoder::start(buffer)?;
+                let mut orientation = true;
+                for _ in 0..count {
+                    if !decoder.read() {
+                        orientation = !orientation;
+                    }
+                    orientations.push(orientation);
+                }
+            }
+            _ => {}
+        }
+        self.transform.decode_data(buffer)?;
+        if let Prediction::GeometricNormal { flips } = &mut self.prediction {
+            *flips = Some(BitDecoder::start(buffer)?);
+        }
+        Ok(())
+    }
+
+    fn restore(
+        &mut self,
+        data: &mut [i32],
+        n: usize,
+        mesh: &MeshData,
+        positions: Option<Positions>,
+    ) -> io::Result<()> {
+        let transform = &self.transform;
+        let count = mesh.sequence.data_to_corner.len();
+        if data.len() != count * n {
+            return Err(invalid_data("Invalid Draco attribute size"));
+        }
+        let restore = |data: &mut [i32], entry: usize, prediction: &[i32]| {
+            let corrections = data[entry * n..(entry + 1) * n].to_vec();
+            transform.original(
+                prediction,
+                &corrections,
+                &mut data[entry * n..(entry + 1) * n],
+            );
+        };
+        let previous_value = |data: &[i32], entry: usize| -> Vec<i32> {
+            match entry {
+                0 => vec![0; n],
+                _ => data[(entry - 1) * n..entry * n].to_vec(),
+            }
+        };
+        let need_positions = || {
+            positions
+                .as_ref()
+                .ok_or_else(|| invalid_data("Draco prediction without positions"))
+        };
+
+        match &mut self.prediction {
+            Prediction::Difference => {
+                for entry in 0..count {
+                    let prediction = previous_value(data, entry);
+                    restore(data, entry, &prediction);
+                }
+            }
+            Prediction::Parallelogram => {
+                for entry in 0..count {
+                    let corner = mesh.sequence.data_to_corner[entry];
+                    let prediction = match parallelogram(mesh, data, entry, corner, n) {
+                        Some(prediction) if entry > 0 => prediction,
+                        _ => previous_value(data, entry),
+                    };
+                    restore(data, entry, &prediction);
+                }
+            }
+            Prediction::ConstrainedMultiParallelogram { creases } => {
+                let mut crease_positions = [0; 4];
+                restore(data, 0, &vec![0; n]);
+                for entry in 1..count {
+                    let start = mesh.sequence.data_to_corner[entry];
+                    let mut predictions = Vec::new();
+                    let mut corner = start;
+                    let mut first_pass = true;
+                    while corner != INVALID {
+                        if let Some(prediction) = parallelogram(mesh, data, entry, corner, n) {
+                            predictions.push(prediction);
+                            if predictions.len() == 4 {
+                                break;
+                            }
+                        }
+                        corner = if first_pass {
+                            mesh.table.swing_left(corner)
+                        } else {
+                            mesh.table.swing_right(corner)
+                        };
+                        if corner == start {
+                            break;
+                        }
+                        if corner == INVALID && first_pass {
+                            first_pass = false;
+                            corner = mesh.table.swing_right(start);
+                        }
+                    }
+
+                    let mut sum = vec![0i32; n];
+                    let mut used = 0;
+                    if !predictions.is_empty() {
+                        let context = predictions.len() - 1;
+                        for prediction in &predictions {
+                            let position = crease_positions[context];
+                            crease_positions[context] += 1;
+                            let is_crease = *creases[context]
+                                .get(position)
+                                .ok_or_else(|| invalid_data("Draco crease flags exhausted"))?;
+                            if !is_crease {
+                                used += 1;
+                                for (sum, value) in sum.iter_mut().zip(prediction) {
+                                    *sum = sum.wrapping_add(*value);
+                                }
+                            }
+                        }
+                    }
+                    let prediction = match used {
+                        0 => previous_value(data, entry),
+                        _ => sum.iter().map(|v| v / used).collect(),
+                    };
+                    restore(data, entry, &prediction);
+                }
+            }
+            Prediction::TexCoords { orientations } => {
+                if n != 2 {
+                    return Err(invalid_data("Draco UV prediction needs 2 components"));
+                }
+                let positions = need_positions()?;
+                for entry in 0..count {
+                    let corner = mesh.sequence.data_to_corner[entry];
+                    let prediction =
+                        predict_tex_coord(mesh, positions, data, entry, corner, orientations)?;
+                    restore(data, entry, &prediction);
+                }
+            }
+            Prediction::GeometricNormal { flips } => {
+                let positions = need_positions()?;
+                let flips = flips
+                    .as_mut()
+                    .ok_or_else(|| invalid_data("Missing Draco normal flips"))?;
+                let octahedron = transform.octahedron().unwrap();
+                for entry in 0..count {
+                    let corner = mesh.sequence.data_to_corner[entry];
+                    let mut normal =
+                        octahedron.canonicalize(predict_normal(mesh, positions, corner));
+                    if flips.read() {
+                        normal = normal.map(|v| -v);
+                    }
+                    let prediction = octahedron.coordinates(normal);
+                    restore(data, entry, &prediction);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Completes the parallelogram across the edge opposite to `corner`, if all three of its
+// values come before `entry`
+fn parallelogram(
+    mesh: &MeshData,
+    data: &[i32],
+    entry: usize,
+    corner: u32,
+    n: usize,
+) -> Option<Vec<i32>> {
+    let opposite = mesh.table.opposite(corner);
+    if opposite == INVALID {
+        return None;
+    }
+    let opposite_data = mesh.data_of_corner(opposite);
+    let next_data = mesh.data_of_corner(next(opposite));
+    let previous_data = mesh.data_of_corner(previous(opposite));
+    if opposite_data >= entry || next_data >= entry || previous_data >= entry {
+        return None;
+    }
+    Some(
+        (0..n)
+            .map(|c| {
+                (data[next_data * n + c] as i64 + data[previous_data * n + c] as i64
+                    - data[opposite_data * n + c] as i64) as i32
+            })
+            .collect(),
+    )
+}
+
+fn predict_tex_coord(
+    mesh: &MeshData,
+    positions: &Positions,
+    data: &[i32],
+    entry: usize,
+    corner: u32,
+    orientations: &mut Vec<bool>,
+) -> io::Result<Vec<i32>> {
+    let next_data = mesh.data_of_corner(next(corner));
+    let previous_data = mesh.data_of_corner(previous(corner));
+    let uv = |data_id: usize| [data[data_id * 2] as i64, data[data_id * 2 + 1] as i64];
+
+    if previous_data < entry && next_data < entry {
+        let n_uv = uv(next_data);
+        let p_uv = uv(previous_data);
+        if p_uv == n_uv {
+            return Ok(vec![p_uv[0] as i32, p_uv[1] as i32]);
+        }
+        let tip = positions.at_data(entry);
+        let next_position = positions.at_data(next_data);
+        let previous_position = positions.at_data(previous_data);
+        let pn = [0, 1, 2].map(|i| previous_position[i] - next_position[i]);
+        let pn_norm2 = pn.iter().map(|v| v * v).sum::<i64>();
+        if pn_norm2 != 0 {
+            // Project the tip onto the opposite edge, then step away from it in UV space
+            // by the distance to the edge, on the side given by the orientation bit
+            let cn = [0, 1, 2].map(|i| tip[i] - next_position[i]);
+            let cn_dot_pn = (0..3).map(|i| pn[i] * cn[i]).sum::<i64>();
+            let pn_uv = [p_uv[0] - n_uv[0], p_uv[1] - n_uv[1]];
+            let x_uv = [0, 1].map(|i| n_uv[i] * pn_norm2 + cn_dot_pn * pn_uv[i]);
+            let x_position = [0, 1, 2].map(|i| next_position[i] + cn_dot_pn * pn[i] / pn_norm2);
+            let cx_norm2 = (0..3).map(|i| (tip[i] - x_position[i]).pow(2)).sum::<i64>() as u64;
+            let norm = integer_sqrt(cx_norm2.wrapping_mul(pn_norm2 as u64)) as i64;
+            let cx_uv = [pn_uv[1] * norm, -pn_uv[0] * norm];
+            let orientation = orientations
+                .pop()
+                .ok_or_else(|| invalid_data("Draco UV orientations exhausted"))?;
+            let predicted = if orientation {
+                [0, 1].map(|i| (x_uv[i] + cx_uv[i]) / pn_norm2)
+            } else {
+                [0, 1].map(|i| (x_uv[i] - cx_uv[i]) / pn_norm2)
+            };
+            return Ok(vec![predicted[0] as i32, predicted[1] as i32]);
+        }
+    }
+
+    // Without both neighbors fall back to a neighbor or the previous value
+    let source = if next_data < entry {
+        next_data
+    } else if entry > 0 {
+        if previous_data < entry {
+            previous_data
+        } else {
+            entry - 1
+        }
+    } else {
+        return Ok(vec![0, 0]);
+    };
+    Ok(vec![data[source * 2], data[source * 2 + 1]])
+}
+
+fn integer_sqrt(number: u64) -> u64 {
+    if number == 0 {
+        return 0;
+    }
+    let mut root = 1u64;
+    let mut rest = number;
+    while rest >= 2 {
+        root *= 2;
+        rest /= 4;
+    }
+    loop {
+        root = (root + number / root) / 2;
+        if root
+            .checked_mul(root)
+            .is_some_and(|square| square <= number)
+        {
+            return root;
+        }
+    }
+}
+
+fn predict_normal(mesh: &MeshData, positions: &Positions, corner: u32) -> [i64; 3] {
+    let position = |corner| positions.at_data(mesh.data_of_corner(corner));
+    let center = position(corner);
+    let mut normal = [0i64; 3];
+    for around in mesh.table.corners_around(corner) {
+        let next_position = position(next(around));
+        let previous_position = position(previous(around));
+        let a = [0, 1, 2].map(|i| next_position[i] - center[i]);
+        let b = [0, 1, 2].map(|i| previous_position[i] - center[i]);
+        let cross = [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ];
+        for (normal, cross) in normal.iter_mut().zip(cross) {
+            *normal = normal.wrapping_add(cross);
+        }
+    }
+    const UPPER_BOUND: i64 = 1 << 29;
+    let sum = normal.iter().map(|v| v.abs()).sum::<i64>();
+    if sum > UPPER_BOUND {
+        let quotient = sum / UPPER_BOUND;
+        normal = normal.map(|v| v / quotient);
+    }
+    normal
+}
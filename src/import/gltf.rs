@@ -0,0 +1,717 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use cgmath::{ElementWise, SquareMatrix};
+use gltf::json::validation::Validate;
+use serde::Deserialize;
+
+use super::{draco, invalid_data, ImportedInstance, ImportedMaterial, ImportedMesh, ImportedScene};
+use crate::camera::Camera;
+use crate::image_resource::TextureImageData;
+use crate::material::{Material, NormalMapConvention, TextureTransform};
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::mesh::MAX_TEX_COORD_SETS;
+use crate::mesh_resource::MeshResource;
+
+// Extensions a file may require, others are refused instead of being imported incorrectly
+const SUPPORTED_EXTENSIONS: [&str; 4] = [
+    "KHR_draco_mesh_compression",
+    "KHR_materials_emissive_strength",
+    "KHR_materials_pbrSpecularGlossiness",
+    "KHR_texture_transform",
+];
+
+// Used for cameras with an infinite projection
+const DEFAULT_Z_FAR: f32 = 1000.0;
+
+// Reflectance of dielectrics in the metallic-roughness model
+const DIELECTRIC_SPECULAR: f32 = 0.04;
+
+#[derive(Deserialize)]
+struct DracoExtension {
+    #[serde(rename = "bufferView")]
+    buffer_view: usize,
+    attributes: HashMap<String, u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpecularGlossiness {
+    #[serde(default = "SpecularGlossiness::white")]
+    diffuse_factor: [f32; 4],
+    diffuse_texture: Option<TextureReference>,
+    #[serde(default = "SpecularGlossiness::white")]
+    specular_factor: [f32; 3],
+    #[serde(default = "SpecularGlossiness::one")]
+    glossiness_factor: f32,
+    specular_glossiness_texture: Option<TextureReference>,
+}
+
+impl SpecularGlossiness {
+    fn white<const N: usize>() -> [f32; N] {
+        [1.0; N]
+    }
+
+    fn one() -> f32 {
+        1.0
+    }
+}
+
+// A textureInfo the gltf crate doesn't parse for us
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextureReference {
+    index: usize,
+    #[serde(default)]
+    tex_coord: u32,
+    #[serde(default)]
+    extensions: HashMap<String, serde_json::Value>,
+}
+
+impl TextureReference {
+    fn transform(&self) -> TextureTransform {
+        let mut transform = TextureTransform {
+            tex_coord: self.tex_coord,
+            ..TextureTransform::default()
+        };
+        if let Some(khr_transform) = self.extensions.get("KHR_texture_transform") {
+            let vector = |name: &str| {
+                let value = &khr_transform[name];
+                value[0]
+                    .as_f64()
+                    .zip(value[1].as_f64())
+                    .map(|(x, y)| Vec2::new(x as f32, y as f32))
+            };
+            if let Some(offset) = vector("offset") {
+                transform.offset = offset;
+            }
+            if let Some(scale) = vector("scale") {
+                transform.scale = scale;
+            }
+            if let Some(rotation) = khr_transform["rotation"].as_f64() {
+                transform.rotation = rotation as f32;
+            }
+            if let Some(tex_coord) = khr_transform["texCoord"].as_u64() {
+                transform.tex_coord = tex_coord as u32;
+            }
+        }
+        transform
+    }
+}
+
+// Loads .gltf and .glb files, with buffers and images stored in the binary chunk, in data URIs
+// or in files next to it
+pub fn load(path: impl AsRef<Path>) -> std::io::Result<ImportedScene> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    let gltf = gltf::Gltf::from_slice_without_validation(&bytes)
+        .map_err(|e| invalid_data(e.to_string()))?;
+    let document = &gltf.document;
+
+    // The gltf crate drops the Draco extension of primitives, so it is read from the JSON
+    let json: serde_json::Value = if bytes.starts_with(b"glTF") {
+        let glb = gltf::binary::Glb::from_slice(&bytes).map_err(|e| invalid_data(e.to_string()))?;
+        serde_json::from_slice(&glb.json)?
+    } else {
+        serde_json::from_slice(&bytes)?
+    };
+    validate(&document.clone().into_json())?;
+    if let Some(required) = json["extensionsRequired"].as_array() {
+        for extension in required.iter().filter_map(|e| e.as_str()) {
+            if !SUPPORTED_EXTENSIONS.contains(&extension) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    format!("Required glTF extension {} is not supported", extension),
+                ));
+            }
+        }
+    }
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => gltf
+                .blob
+                .clone()
+                .ok_or_else(|| invalid_data("Missing glb binary chunk".to_string()))?,
+            gltf::buffer::Source::Uri(uri) => read_uri(uri, directory)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(invalid_data(format!(
+                "Buffer {} is shorter than its length",
+                buffer.index()
+            )));
+        }
+        buffers.push(data);
+    }
+    let view_data = |view: &gltf::buffer::View| {
+        buffers[view.buffer().index()]
+            .get(view.offset()..view.offset() + view.length())
+            .ok_or_else(|| invalid_data(format!("Buffer view {} is out of range", view.index())))
+    };
+
+    // Image files that are missing are left out, the materials using them lose the texture
+    let mut imported = ImportedScene::new();
+    let mut images = Vec::new();
+    for image in document.images() {
+        let bytes = match image.source() {
+            gltf::image::Source::View { view, .. } => view_data(&view)?.to_vec(),
+            gltf::image::Source::Uri { uri, .. } => match read_uri(uri, directory) {
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    images.push(None);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        imported
+            .textures
+            .push(TextureImageData::from_memory(&bytes)?);
+        images.push(Some(imported.textures.len() - 1));
+    }
+
+    for material in document.materials() {
+        let pointer = format!("/materials/{}", material.index().unwrap_or_default());
+        let mut imported_material = import_material(&material, json.pointer(&pointer), &images)?;
+        let extensions = format!("{}/extensions", pointer);
+        let pointer = format!(
+            "{}/KHR_materials_emissive_strength/emissiveStrength",
+            extensions
+        );
+        if let Some(strength) = json.pointer(&pointer).and_then(serde_json::Value::as_f64) {
+            let emission = &mut imported_material.material.emission;
+            *emission = (emission.truncate() * strength as f32).extend(emission.w);
+        }
+        let pointer = format!("{}/KHR_materials_pbrSpecularGlossiness", extensions);
+        if let Some(extension) = json.pointer(&pointer) {
+            let extension = SpecularGlossiness::deserialize(extension)?;
+            let image = |reference: &Option<TextureReference>| {
+                let reference = reference.as_ref()?;
+                let texture = document.textures().nth(reference.index)?;
+                images[texture.source().index()]
+            };
+            let diffuse = image(&extension.diffuse_texture);
+            let specular_glossiness = image(&extension.specular_glossiness_texture);
+            convert_specular_glossiness(
+                &extension,
+                diffuse,
+                specular_glossiness,
+                &mut imported_material,
+                &mut imported.textures,
+            );
+        }
+        imported.materials.push(imported_material);
+    }
+
+    // Every primitive becomes its own mesh, remember where each glTF mesh starts
+    let mut mesh_offsets = Vec::new();
+    for mesh in document.meshes() {
+        mesh_offsets.push(imported.meshes.len());
+        for primitive in mesh.primitives() {
+            let name = match mesh.name() {
+                Some(name) => format!("{}/{}", name, primitive.index()),
+                None => format!("mesh {}/{}", mesh.index(), primitive.index()),
+            };
+            let pointer = format!(
+                "/meshes/{}/primitives/{}/extensions/KHR_draco_mesh_compression",
+                mesh.index(),
+                primitive.index()
+            );
+            let resource = match json.pointer(&pointer) {
+                Some(extension) => {
+                    let extension = DracoExtension::deserialize(extension)?;
+                    let view = document
+                        .views()
+                        .nth(extension.buffer_view)
+                        .ok_or_else(|| invalid_data(format!("Missing buffer view in {}", name)))?;
+                    import_draco_primitive(&primitive, &extension, view_data(&view)?)
+                        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", name, e)))?
+                }
+                None => import_primitive(&primitive, &buffers),
+            };
+            imported.meshes.push(ImportedMesh { name, resource });
+        }
+    }
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next());
+    if let Some(scene) = scene {
+        for node in scene.nodes() {
+            add_node(&node, Mat4::identity(), &mesh_offsets, &mut imported);
+        }
+    }
+
+    Ok(imported)
+}
+
+fn validate(root: &gltf::json::Root) -> std::io::Result<()> {
+    let mut errors = Vec::new();
+    root.validate(root, gltf::json::Path::new, &mut |path, error| {
+        errors.push((path(), error))
+    });
+    for (path, error) in errors {
+        // Draco compressed accessors have no buffer view of their own, and required
+        // extensions are checked against the ones this importer supports
+        let path = path.as_str();
+        let is_compressed_accessor = path.starts_with("accessors[")
+            && path.ends_with("].bufferView")
+            && matches!(error, gltf::json::validation::Error::Missing);
+        if !is_compressed_accessor && !path.starts_with("extensionsRequired") {
+            return Err(invalid_data(format!(
+                "Invalid glTF at {}: {:?}",
+                path, error
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn read_uri(uri: &str, directory: &Path) -> std::io::Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data
+            .split_once(";base64,")
+            .ok_or_else(|| invalid_data("Only base64 data URIs are supported".to_string()))?;
+        return decode_base64(encoded);
+    }
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    std::fs::read(directory.join(percent_decode(path)))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decode_base64(text: &str) -> std::io::Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for byte in text.bytes().take_while(|b| *b != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(invalid_data("Invalid base64 in data URI".to_string())),
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+        }
+    }
+    Ok(decoded)
+}
+
+fn add_node(
+    node: &gltf::Node,
+    parent_transform: Mat4,
+    mesh_offsets: &[usize],
+    imported: &mut ImportedScene,
+) {
+    let transform = parent_transform * Mat4::from(node.transform().matrix());
+    // The first perspective camera in the scene, orthographic ones aren't supported
+    if let (Some(camera), None) = (node.camera(), &imported.camera) {
+        if let gltf::camera::Projection::Perspective(perspective) = camera.projection() {
+            let z_far = perspective.zfar().unwrap_or(DEFAULT_Z_FAR);
+            let mut camera =
+                Camera::new(perspective.yfov().to_degrees(), perspective.znear(), z_far);
+            // The renderer places a camera at its view_matrix, the inverse of its transform
+            camera.transform(transform.invert().unwrap_or_else(Mat4::identity));
+            imported.camera = Some(camera);
+        }
+    }
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            imported.instances.push(ImportedInstance {
+                mesh: mesh_offsets[mesh.index()] + primitive.index(),
+                material: primitive.material().index(),
+                transform,
+            });
+        }
+    }
+    for child in node.children() {
+        add_node(&child, transform, mesh_offsets, imported);
+    }
+}
+
+fn texture_transform(info: &gltf::texture::Info) -> TextureTransform {
+    let mut transform = TextureTransform {
+        tex_coord: info.tex_coord(),
+        ..TextureTransform::default()
+    };
+
+    if let Some(khr_transform) = info.texture_transform() {
+        transform.offset = Vec2::from(khr_transform.offset());
+        transform.scale = Vec2::from(khr_transform.scale());
+        transform.rotation = khr_transform.rotation();
+        if let Some(tex_coord) = khr_transform.tex_coord() {
+            transform.tex_coord = tex_coord;
+        }
+    }
+
+    transform
+}
+
+// The raw JSON of the material provides what the gltf crate doesn't parse
+fn import_material(
+    material: &gltf::Material,
+    json: Option<&serde_json::Value>,
+    images: &[Option<usize>],
+) -> std::io::Result<ImportedMaterial> {
+    let raw_transform = |slot: &str| -> std::io::Result<Option<TextureTransform>> {
+        match json.and_then(|json| json.get(slot)) {
+            Some(reference) => Ok(Some(TextureReference::deserialize(reference)?.transform())),
+            None => Ok(None),
+        }
+    };
+    let image = |texture: gltf::Texture| images[texture.source().index()];
+    let name = match material.name() {
+        Some(name) => name.to_string(),
+        None => format!("material {}", material.index().unwrap_or_default()),
+    };
+    let mut imported = ImportedMaterial::new(&name, Material::new());
+    let mat = &mut imported.material;
+    let pbr = material.pbr_metallic_roughness();
+    mat.base_color = Vec4::from(pbr.base_color_factor());
+    if let Some(texture) = pbr.base_color_texture() {
+        imported.base_color_texture = image(texture.texture());
+        mat.base_color_texture_transform = texture_transform(&texture);
+    }
+
+    if let Some(texture) = material.normal_texture() {
+        imported.normal_texture = image(texture.texture());
+        mat.normal_scale = texture.scale();
+        // The gltf crate doesn't expose KHR_texture_transform on normal
+        // and occlusion textures
+        mat.normal_texture_transform = raw_transform("normalTexture")?.unwrap_or_default();
+    }
+
+    // glTF normal maps are always authored with the OpenGL convention
+    mat.normal_map_convention = NormalMapConvention::OpenGl;
+
+    if let Some(texture) = material.occlusion_texture() {
+        imported.occlusion_texture = image(texture.texture());
+        mat.occlusion_strength = texture.strength();
+        mat.occlusion_texture_transform = raw_transform("occlusionTexture")?.unwrap_or_default();
+    }
+
+    mat.roughness = pbr.roughness_factor();
+    mat.metallic = pbr.metallic_factor();
+    if let Some(texture) = pbr.metallic_roughness_texture() {
+        imported.metallic_roughness_texture = image(texture.texture());
+        mat.metallic_roughness_texture_transform = texture_transform(&texture);
+    }
+
+    let emissive_factor = material.emissive_factor();
+    mat.emission = Vec4::new(
+        emissive_factor[0],
+        emissive_factor[1],
+        emissive_factor[2],
+        1.0,
+    );
+    if let Some(texture) = material.emissive_texture() {
+        imported.emission_texture = image(texture.texture());
+        mat.emission_texture_transform = texture_transform(&texture);
+    }
+
+    Ok(imported)
+}
+
+fn perceived_brightness(color: Vec3) -> f32 {
+    (0.299 * color.x * color.x + 0.587 * color.y * color.y + 0.114 * color.z * color.z).sqrt()
+}
+
+// The metallic value for which the metallic-roughness model reflects the given specular
+// brightness, solved from the quadratic both models give for the diffuse brightness
+fn solve_metallic(diffuse: f32, specular: f32, one_minus_specular_strength: f32) -> f32 {
+    if specular < DIELECTRIC_SPECULAR {
+        return 0.0;
+    }
+    let a = DIELECTRIC_SPECULAR;
+    let b = diffuse * one_minus_specular_strength / (1.0 - DIELECTRIC_SPECULAR) + specular
+        - 2.0 * DIELECTRIC_SPECULAR;
+    let c = DIELECTRIC_SPECULAR - specular;
+    let discriminant = (b * b - 4.0 * a * c).max(0.0);
+    ((-b + discriminant.sqrt()) / (2.0 * a)).clamp(0.0, 1.0)
+}
+
+// Linear diffuse, specular and glossiness to linear base color, metallic and roughness
+fn metallic_roughness(diffuse: Vec4, specular: Vec3, glossiness: f32) -> (Vec4, f32, f32) {
+    let one_minus_specular_strength = 1.0 - specular.x.max(specular.y).max(specular.z);
+    let metallic = solve_metallic(
+        perceived_brightness(diffuse.truncate()),
+        perceived_brightness(specular),
+        one_minus_specular_strength,
+    );
+    let from_diffuse = diffuse.truncate() * one_minus_specular_strength
+        / (1.0 - DIELECTRIC_SPECULAR)
+        / (1.0 - metallic).max(f32::EPSILON);
+    let from_specular = (specular
+        - Vec3::new(1.0, 1.0, 1.0) * DIELECTRIC_SPECULAR * (1.0 - metallic))
+        / metallic.max(f32::EPSILON);
+    let base_color = from_diffuse + (from_specular - from_diffuse) * metallic * metallic;
+    let base_color = base_color.map(|c| c.clamp(0.0, 1.0));
+    (base_color.extend(diffuse.w), metallic, 1.0 - glossiness)
+}
+
+// Replaces the metallic-roughness parameters of the material. Textures are converted per
+// texel into a base color and a metallic-roughness texture, as metallic depends on both.
+fn convert_specular_glossiness(
+    extension: &SpecularGlossiness,
+    diffuse_texture: Option<usize>,
+    specular_glossiness_texture: Option<usize>,
+    imported: &mut ImportedMaterial,
+    textures: &mut Vec<TextureImageData>,
+) {
+    let diffuse_texture = diffuse_texture.map(|index| &textures[index]);
+    let specular_glossiness_texture = specular_glossiness_texture.map(|index| &textures[index]);
+    let diffuse_factor = Vec4::from(extension.diffuse_factor);
+    let specular_factor = Vec3::from(extension.specular_factor);
+    let material = &mut imported.material;
+    imported.base_color_texture = None;
+    imported.metallic_roughness_texture = None;
+
+    if diffuse_texture.is_none() && specular_glossiness_texture.is_none() {
+        let (base_color, metallic, roughness) =
+            metallic_roughness(diffuse_factor, specular_factor, extension.glossiness_factor);
+        material.base_color = base_color;
+        material.metallic = metallic;
+        material.roughness = roughness;
+        return;
+    }
+
+    let maps = [diffuse_texture, specular_glossiness_texture];
+    let width = maps
+        .iter()
+        .flatten()
+        .map(|map| map.width)
+        .max()
+        .unwrap_or(1);
+    let height = maps
+        .iter()
+        .flatten()
+        .map(|map| map.height)
+        .max()
+        .unwrap_or(1);
+    // Linear texel values, missing textures read as white
+    let sample = |map: Option<&TextureImageData>, x: u32, y: u32| {
+        let map = match map {
+            Some(map) => map,
+            None => return Vec4::new(1.0, 1.0, 1.0, 1.0),
+        };
+        let texel = map
+            .texel(x * map.width / width, y * map.height / height)
+            .unwrap_or(Vec4::new(1.0, 1.0, 1.0, 1.0));
+        let gamma = if map.is_gamma_encoded() { 2.2 } else { 1.0 };
+        texel
+            .truncate()
+            .map(|c| c.max(0.0).powf(gamma))
+            .extend(texel.w)
+    };
+    let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut base_color_pixels = Vec::with_capacity((width * height * 4) as usize);
+    let mut metallic_roughness_pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let diffuse = sample(diffuse_texture, x, y);
+            let specular_glossiness = sample(specular_glossiness_texture, x, y);
+            let (base_color, metallic, roughness) = metallic_roughness(
+                diffuse_factor.mul_element_wise(diffuse),
+                specular_factor.mul_element_wise(specular_glossiness.truncate()),
+                extension.glossiness_factor * specular_glossiness.w,
+            );
+            // 8 bit base colors are stored with a 2.2 gamma
+            base_color_pixels.extend([
+                to_byte(base_color.x.powf(1.0 / 2.2)),
+                to_byte(base_color.y.powf(1.0 / 2.2)),
+                to_byte(base_color.z.powf(1.0 / 2.2)),
+                to_byte(base_color.w),
+            ]);
+            metallic_roughness_pixels.extend([255, to_byte(roughness), to_byte(metallic), 255]);
+        }
+    }
+    let format = ash::vk::Format::R8G8B8A8_UNORM;
+    let diffuse_loaded = diffuse_texture.is_some();
+    textures.push(TextureImageData::new(
+        format,
+        width,
+        height,
+        &base_color_pixels,
+    ));
+    imported.base_color_texture = Some(textures.len() - 1);
+    textures.push(TextureImageData::new(
+        format,
+        width,
+        height,
+        &metallic_roughness_pixels,
+    ));
+    imported.metallic_roughness_texture = Some(textures.len() - 1);
+
+    // Both textures share the UVs of the combined texture
+    let reference = match &extension.diffuse_texture {
+        Some(reference) if diffuse_loaded => reference,
+        _ => extension.specular_glossiness_texture.as_ref().unwrap(),
+    };
+    material.base_color_texture_transform = reference.transform();
+    material.metallic_roughness_texture_transform = reference.transform();
+    // The textures hold the converted values, including the diffuse factor's alpha
+    material.base_color = Vec4::new(1.0, 1.0, 1.0, 1.0);
+    material.metallic = 1.0;
+    material.roughness = 1.0;
+}
+
+fn import_primitive(primitive: &gltf::Primitive, buffers: &[Vec<u8>]) -> MeshResource {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+    let vertices: Vec<Vec3> = reader
+        .read_positions()
+        .map(|positions| positions.map(Vec3::from).collect())
+        .unwrap_or_default();
+    let normals: Option<Vec<Vec3>> = reader
+        .read_normals()
+        .map(|normals| normals.map(Vec3::from).collect());
+    let tangents: Option<Vec<Vec3>> = reader
+        .read_tangents()
+        .map(|tangents| tangents.map(|t| Vec3::new(t[0], t[1], t[2])).collect());
+    let mut tex_coords = Vec::new();
+    for set in 0..MAX_TEX_COORD_SETS as u32 {
+        match reader.read_tex_coords(set) {
+            Some(uvs) => tex_coords.push(uvs.into_f32().map(Vec2::from).collect()),
+            None => break,
+        }
+    }
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    let colors: Option<Vec<Vec4>> = reader
+        .read_colors(0)
+        .map(|colors| colors.into_rgba_f32().map(Vec4::from).collect());
+
+    build_resource(indices, vertices, normals, tangents, tex_coords, colors)
+}
+
+fn import_draco_primitive(
+    primitive: &gltf::Primitive,
+    extension: &DracoExtension,
+    data: &[u8],
+) -> std::io::Result<MeshResource> {
+    let mesh = draco::decode(data)?;
+    if mesh.indices.is_empty() {
+        return Err(invalid_data(
+            "Draco point clouds are not supported".to_string(),
+        ));
+    }
+    // Values of an attribute as floats, integer attributes are normalized when the glTF
+    // accessor says so
+    let attribute = |semantic: gltf::Semantic| {
+        let accessor = primitive.get(&semantic)?;
+        let unique_id = extension.attributes.get(&semantic.to_string())?;
+        let attribute = mesh.attribute(*unique_id)?;
+        let scale = match accessor.data_type() {
+            _ if !accessor.normalized() => None,
+            gltf::accessor::DataType::I8 => Some(127.0),
+            gltf::accessor::DataType::U8 => Some(255.0),
+            gltf::accessor::DataType::I16 => Some(32767.0),
+            gltf::accessor::DataType::U16 => Some(65535.0),
+            _ => None,
+        };
+        let values = attribute
+            .values
+            .chunks_exact(attribute.components)
+            .map(|value| {
+                let mut padded = [0.0, 0.0, 0.0, 1.0];
+                for (padded, value) in padded.iter_mut().zip(value) {
+                    *padded = match scale {
+                        Some(scale) => (value / scale).max(-1.0),
+                        None => *value,
+                    };
+                }
+                padded
+            });
+        Some(values.collect::<Vec<[f32; 4]>>())
+    };
+
+    let vertices: Vec<Vec3> = attribute(gltf::Semantic::Positions)
+        .ok_or_else(|| invalid_data("Draco primitive without positions".to_string()))?
+        .iter()
+        .map(|p| Vec3::new(p[0], p[1], p[2]))
+        .collect();
+    let normals = attribute(gltf::Semantic::Normals).map(|normals| {
+        normals
+            .iter()
+            .map(|n| Vec3::new(n[0], n[1], n[2]))
+            .collect()
+    });
+    let tangents = attribute(gltf::Semantic::Tangents).map(|tangents| {
+        tangents
+            .iter()
+            .map(|t| Vec3::new(t[0], t[1], t[2]))
+            .collect()
+    });
+    let mut tex_coords = Vec::new();
+    for set in 0..MAX_TEX_COORD_SETS as u32 {
+        match attribute(gltf::Semantic::TexCoords(set)) {
+            Some(uvs) => tex_coords.push(uvs.iter().map(|uv| Vec2::new(uv[0], uv[1])).collect()),
+            None => break,
+        }
+    }
+    let colors = attribute(gltf::Semantic::Colors(0))
+        .map(|colors| colors.into_iter().map(Vec4::from).collect());
+
+    Ok(build_resource(
+        mesh.indices,
+        vertices,
+        normals,
+        tangents,
+        tex_coords,
+        colors,
+    ))
+}
+
+fn build_resource(
+    indices: Vec<u32>,
+    vertices: Vec<Vec3>,
+    normals: Option<Vec<Vec3>>,
+    tangents: Option<Vec<Vec3>>,
+    tex_coords: Vec<Vec<Vec2>>,
+    colors: Option<Vec<Vec4>>,
+) -> MeshResource {
+    let mut resource = MeshResource::new(indices, vertices, Vec::new(), Vec::new(), tex_coords);
+    match normals {
+        Some(normals) => resource.normals = normals,
+        None => resource.generate_normals(),
+    }
+    match tangents {
+        Some(tangents) => resource.tangents = tangents,
+        None => resource.generate_tangents(),
+    }
+    if let Some(colors) = colors {
+        resource = resource.with_colors(colors);
+    }
+    resource
+}
@@ -0,0 +1,234 @@
+// Importers for interchange formats. They read everything into an ImportedScene on the
+// CPU, which uploads it into a Ctx.
+use std::path::Path;
+
+use cgmath::{InnerSpace, SquareMatrix};
+
+use crate::camera::Camera;
+use crate::ctx::{Ctx, Handle};
+use crate::image_resource::TextureImageData;
+use crate::material::Material;
+use crate::math::{Mat4, Vec2, Vec3};
+use crate::mesh_resource::MeshResource;
+use crate::scene::Scene;
+
+pub mod draco;
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+pub struct ImportedMesh {
+    pub name: String,
+    pub resource: MeshResource,
+}
+
+pub struct ImportedMaterial {
+    pub name: String,
+    // The texture handles of the material are ignored, these index the imported textures
+    pub material: Material,
+    pub base_color_texture: Option<usize>,
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub emission_texture: Option<usize>,
+    pub occlusion_texture: Option<usize>,
+}
+
+impl ImportedMaterial {
+    pub fn new(name: &str, material: Material) -> Self {
+        Self {
+            name: name.to_string(),
+            material,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            emission_texture: None,
+            occlusion_texture: None,
+        }
+    }
+}
+
+pub struct ImportedInstance {
+    pub mesh: usize,
+    // None uses the default material of the Ctx
+    pub material: Option<usize>,
+    pub transform: Mat4,
+}
+
+#[derive(Default)]
+pub struct ImportedScene {
+    pub meshes: Vec<ImportedMesh>,
+    pub textures: Vec<TextureImageData>,
+    pub materials: Vec<ImportedMaterial>,
+    pub instances: Vec<ImportedInstance>,
+    pub camera: Option<Camera>,
+}
+
+impl ImportedScene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A single untextured mesh placed at the origin with the default material
+    pub fn from_mesh(name: &str, resource: MeshResource) -> Self {
+        Self {
+            meshes: vec![ImportedMesh {
+                name: name.to_string(),
+                resource,
+            }],
+            instances: vec![ImportedInstance {
+                mesh: 0,
+                material: None,
+                transform: Mat4::identity(),
+            }],
+            ..Self::default()
+        }
+    }
+
+    // Creates the meshes, textures, materials and instances in the Ctx and adds the
+    // instances to the scene, which also gets the camera if there is one. Returns the
+    // instance handles in import order.
+    pub fn upload(&self, ctx: &mut Ctx, scene: &mut Scene) -> Vec<Handle> {
+        if let Some(camera) = self.camera {
+            scene.set_camera(camera);
+        }
+        let textures: Vec<Handle> = self
+            .textures
+            .iter()
+            .map(|texture| ctx.create_texture(texture))
+            .collect();
+        let meshes: Vec<Handle> = self
+            .meshes
+            .iter()
+            .map(|mesh| ctx.create_mesh(&mesh.resource))
+            .collect();
+        let texture = |index: Option<usize>| index.map(|index| textures[index]);
+        let materials: Vec<Handle> = self
+            .materials
+            .iter()
+            .map(|imported| {
+                let handle = ctx.create_material();
+                let material = ctx.material_mut(handle).unwrap();
+                *material = imported.material.clone();
+                material.base_color_texture = texture(imported.base_color_texture);
+                material.metallic_roughness_texture = texture(imported.metallic_roughness_texture);
+                material.normal_texture = texture(imported.normal_texture);
+                material.emission_texture = texture(imported.emission_texture);
+                material.occlusion_texture = texture(imported.occlusion_texture);
+                handle
+            })
+            .collect();
+
+        self.instances
+            .iter()
+            .map(|imported| {
+                let handle = ctx.create_instance(meshes[imported.mesh]);
+                let instance = ctx.instance_mut(handle).unwrap();
+                instance.set_transform(imported.transform);
+                if let Some(material) = imported.material {
+                    instance.set_material(materials[material]);
+                }
+                scene.add_instance(handle);
+                handle
+            })
+            .collect()
+    }
+}
+
+// The error of every importer and file reader for malformed files
+pub(crate) fn invalid_data(message: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
+
+// Picks the importer from the file extension
+pub fn load(path: impl AsRef<Path>) -> std::io::Result<ImportedScene> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf" | "glb") => gltf::load(path),
+        Some("obj") => obj::load(path),
+        Some("ply") => ply::load(path),
+        Some("stl") => stl::load(path),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("No importer for {}", path.display()),
+        )),
+    }
+}
+
+// Ear clipping in the plane of the polygon, so concave polygons triangulate correctly.
+// Returns corners of the polygon with its winding kept.
+pub fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let count = points.len();
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = Vec::with_capacity(count.saturating_sub(2));
+
+    // Newell's method, robust for non planar polygons
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+    for i in 0..count {
+        let (a, b) = (points[i], points[(i + 1) % count]);
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
+    }
+
+    if count > 3 && normal.magnitude2() > 0.0 {
+        // A basis in which the polygon winds counter clockwise
+        let normal = normal.normalize();
+        let axis = if normal.x.abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let u = axis.cross(normal).normalize();
+        let v = normal.cross(u);
+        let projected: Vec<Vec2> = points
+            .iter()
+            .map(|p| Vec2::new(p.dot(u), p.dot(v)))
+            .collect();
+        let cross =
+            |a: Vec2, b: Vec2, c: Vec2| (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+
+        while remaining.len() > 3 {
+            let n = remaining.len();
+            let ear = (0..n).find(|i| {
+                let (a, b, c) = (
+                    remaining[(i + n - 1) % n],
+                    remaining[*i],
+                    remaining[(i + 1) % n],
+                );
+                let (pa, pb, pc) = (projected[a], projected[b], projected[c]);
+                cross(pa, pb, pc) > 0.0
+                    && !remaining.iter().any(|j| {
+                        let p = projected[*j];
+                        *j != a
+                            && *j != b
+                            && *j != c
+                            && cross(pa, pb, p) >= 0.0
+                            && cross(pb, pc, p) >= 0.0
+                            && cross(pc, pa, p) >= 0.0
+                    })
+            });
+            match ear {
+                Some(i) => {
+                    triangles.push([
+                        remaining[(i + n - 1) % n],
+                        remaining[i],
+                        remaining[(i + 1) % n],
+                    ]);
+                    remaining.remove(i);
+                }
+                None => break,
+            }
+        }
+    }
+
+    // Triangles and whatever degenerate rest ear clipping gave up on are fanned
+    for i in 1..remaining.len().saturating_sub(1) {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
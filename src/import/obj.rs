@@ -0,0 +1,656 @@
+// Wavefront OBJ with MTL material libraries. Every group or object is split into one mesh
+// per material it uses. Polygons are triangulated by ear clipping and faces without
+// normals get them generated, smooth within their smoothing group and flat otherwise.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use cgmath::{InnerSpace, SquareMatrix};
+
+use super::{
+    invalid_data, triangulate, ImportedInstance, ImportedMaterial, ImportedMesh, ImportedScene,
+};
+use crate::image_resource::TextureImageData;
+use crate::material::{Material, TextureTransform};
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::mesh_resource::MeshResource;
+
+pub fn load(path: impl AsRef<Path>) -> std::io::Result<ImportedScene> {
+    let path = path.as_ref();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut parser = ObjParser::new(directory);
+    let reader = BufReader::new(File::open(path)?);
+    for (number, line) in reader.lines().enumerate() {
+        parser
+            .parse_line(&line?)
+            .map_err(|e| annotate(e, path, number))?;
+    }
+    Ok(parser.finish())
+}
+
+fn annotate(error: std::io::Error, path: &Path, line: usize) -> std::io::Error {
+    std::io::Error::new(
+        error.kind(),
+        format!("{}:{}: {}", path.display(), line + 1, error),
+    )
+}
+
+fn parse_floats<const N: usize>(args: &[&str]) -> std::io::Result<[f32; N]> {
+    let mut values = [0.0; N];
+    if args.len() < N {
+        return Err(invalid_data(format!(
+            "Expected {} numbers, found {}",
+            N,
+            args.len()
+        )));
+    }
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| invalid_data(format!("Invalid number {}", arg)))?;
+    }
+    Ok(values)
+}
+
+// OBJ indices are 1 based, negative ones count back from the last element
+fn resolve_index(index: &str, count: usize) -> std::io::Result<usize> {
+    let value: i64 = index
+        .parse()
+        .map_err(|_| invalid_data(format!("Invalid index {}", index)))?;
+    let resolved = if value < 0 {
+        count as i64 + value
+    } else {
+        value - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(invalid_data(format!("Index {} out of range", index)));
+    }
+    Ok(resolved as usize)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Shading {
+    // The face supplies its own normals
+    Explicit,
+    // Shares generated normals with the faces of the same smoothing group
+    Smooth(u32),
+    // Smoothing off, normals of this face only
+    Flat(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct VertexKey {
+    position: usize,
+    tex_coord: Option<usize>,
+    normal: Option<usize>,
+    shading: Shading,
+}
+
+struct MeshBuilder {
+    name: String,
+    material: Option<usize>,
+    vertices: HashMap<VertexKey, u32>,
+    keys: Vec<VertexKey>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, key: VertexKey) -> u32 {
+        let keys = &mut self.keys;
+        *self.vertices.entry(key).or_insert_with(|| {
+            keys.push(key);
+            keys.len() as u32 - 1
+        })
+    }
+}
+
+struct ObjParser<'a> {
+    directory: &'a Path,
+    positions: Vec<Vec3>,
+    // One per position, white for vertices without a color
+    colors: Vec<Vec4>,
+    has_colors: bool,
+    tex_coords: Vec<Vec2>,
+    normals: Vec<Vec3>,
+    group: String,
+    smoothing_group: u32,
+    face_count: usize,
+    material: Option<usize>,
+    material_names: HashMap<String, usize>,
+    texture_files: HashMap<PathBuf, usize>,
+    builders: Vec<MeshBuilder>,
+    builder_lookup: HashMap<(String, Option<usize>), usize>,
+    imported: ImportedScene,
+}
+
+impl<'a> ObjParser<'a> {
+    fn new(directory: &'a Path) -> Self {
+        Self {
+            directory,
+            positions: Vec::new(),
+            colors: Vec::new(),
+            has_colors: false,
+            tex_coords: Vec::new(),
+            normals: Vec::new(),
+            group: "default".to_string(),
+            smoothing_group: 0,
+            face_count: 0,
+            material: None,
+            material_names: HashMap::new(),
+            texture_files: HashMap::new(),
+            builders: Vec::new(),
+            builder_lookup: HashMap::new(),
+            imported: ImportedScene::new(),
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> std::io::Result<()> {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => return Ok(()),
+        };
+        let args: Vec<&str> = tokens.collect();
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(&args)?;
+                self.positions.push(Vec3::new(x, y, z));
+                // Some exporters append an RGB color to the position
+                if args.len() >= 6 {
+                    let [r, g, b] = parse_floats(&args[3..])?;
+                    self.colors.push(Vec4::new(r, g, b, 1.0));
+                    self.has_colors = true;
+                } else {
+                    self.colors.push(Vec4::new(1.0, 1.0, 1.0, 1.0));
+                }
+            }
+            "vt" => {
+                let [u] = parse_floats(&args)?;
+                let v = if args.len() > 1 {
+                    parse_floats::<1>(&args[1..])?[0]
+                } else {
+                    0.0
+                };
+                // OBJ puts the origin of the texture at the bottom left
+                self.tex_coords.push(Vec2::new(u, 1.0 - v));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(&args)?;
+                self.normals.push(Vec3::new(x, y, z));
+            }
+            "f" => self.parse_face(&args)?,
+            "g" | "o" => {
+                self.group = if args.is_empty() {
+                    "default".to_string()
+                } else {
+                    args.join(" ")
+                };
+            }
+            "s" => {
+                self.smoothing_group = match args.first() {
+                    None | Some(&"off") => 0,
+                    Some(group) => group
+                        .parse()
+                        .map_err(|_| invalid_data(format!("Invalid smoothing group {}", group)))?,
+                };
+            }
+            // Materials no library defines fall back to the default material
+            "usemtl" => self.material = self.material_names.get(&args.join(" ")).copied(),
+            "mtllib" => {
+                for library in &args {
+                    self.load_library(library)?;
+                }
+            }
+            // Lines, points, free form geometry and display attributes aren't supported
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse_face(&mut self, args: &[&str]) -> std::io::Result<()> {
+        if args.len() < 3 {
+            return Err(invalid_data("Faces need at least 3 vertices".to_string()));
+        }
+        let shading_for_missing_normals = if self.smoothing_group == 0 {
+            Shading::Flat(self.face_count)
+        } else {
+            Shading::Smooth(self.smoothing_group)
+        };
+        self.face_count += 1;
+
+        let mut keys = Vec::with_capacity(args.len());
+        for arg in args {
+            let mut parts = arg.split('/');
+            let position = resolve_index(parts.next().unwrap_or_default(), self.positions.len())?;
+            let tex_coord = match parts.next() {
+                Some(index) if !index.is_empty() => {
+                    Some(resolve_index(index, self.tex_coords.len())?)
+                }
+                _ => None,
+            };
+            let normal = match parts.next() {
+                Some(index) if !index.is_empty() => Some(resolve_index(index, self.normals.len())?),
+                _ => None,
+            };
+            keys.push(VertexKey {
+                position,
+                tex_coord,
+                normal,
+                shading: if normal.is_some() {
+                    Shading::Explicit
+                } else {
+                    shading_for_missing_normals
+                },
+            });
+        }
+
+        let points: Vec<Vec3> = keys
+            .iter()
+            .map(|key| self.positions[key.position])
+            .collect();
+        let builder = self.builder();
+        let indices: Vec<u32> = keys.into_iter().map(|key| builder.vertex(key)).collect();
+        for triangle in triangulate(&points) {
+            builder
+                .indices
+                .extend(triangle.iter().map(|corner| indices[*corner]));
+        }
+        Ok(())
+    }
+
+    fn builder(&mut self) -> &mut MeshBuilder {
+        let key = (self.group.clone(), self.material);
+        let index = match self.builder_lookup.get(&key) {
+            Some(index) => *index,
+            None => {
+                self.builders.push(MeshBuilder {
+                    name: self.group.clone(),
+                    material: self.material,
+                    vertices: HashMap::new(),
+                    keys: Vec::new(),
+                    indices: Vec::new(),
+                });
+                self.builder_lookup.insert(key, self.builders.len() - 1);
+                self.builders.len() - 1
+            }
+        };
+        &mut self.builders[index]
+    }
+
+    fn load_library(&mut self, file: &str) -> std::io::Result<()> {
+        let path = self.directory.join(file.replace('\\', "/"));
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            // Like an unknown usemtl, its materials fall back to the default material
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let reader = BufReader::new(file);
+        let mut materials: Vec<MtlMaterial> = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            parse_mtl_line(&line?, &mut materials).map_err(|e| annotate(e, &path, number))?;
+        }
+        for mtl in materials {
+            let material = self.convert_material(&mtl)?;
+            self.material_names
+                .insert(mtl.name, self.imported.materials.len());
+            self.imported.materials.push(material);
+        }
+        Ok(())
+    }
+
+    fn texture(&mut self, map: &TextureMap) -> std::io::Result<usize> {
+        let path = self.directory.join(&map.file);
+        if let Some(index) = self.texture_files.get(&path) {
+            return Ok(*index);
+        }
+        self.imported.textures.push(TextureImageData::load(&path)?);
+        let index = self.imported.textures.len() - 1;
+        self.texture_files.insert(path, index);
+        Ok(index)
+    }
+
+    // Phong style parameters map to the metallic roughness model: the specular exponent
+    // becomes a roughness, a bright specular color over a darker diffuse one a metal and
+    // the dissolve a transmission. The PBR extension (Pr, Pm, Ps, Pc) takes precedence.
+    fn convert_material(&mut self, mtl: &MtlMaterial) -> std::io::Result<ImportedMaterial> {
+        let mut material = Material::new();
+        let diffuse = mtl.diffuse;
+        let specular = mtl.specular.x.max(mtl.specular.y).max(mtl.specular.z);
+        let looks_metallic = specular > 0.25 && specular > diffuse.x.max(diffuse.y).max(diffuse.z);
+        material.metallic = mtl
+            .metallic
+            .unwrap_or(if looks_metallic { 1.0 } else { 0.0 });
+        let base_color = if mtl.metallic.is_none() && looks_metallic {
+            mtl.specular
+        } else {
+            diffuse
+        };
+        material.base_color = base_color.extend(1.0);
+        material.roughness = mtl
+            .roughness
+            .unwrap_or_else(|| (2.0 / (mtl.shininess.max(0.0) + 2.0)).sqrt())
+            .clamp(0.0, 1.0);
+        material.emission = mtl.emission.extend(1.0);
+        if let Some(ior) = mtl.ior {
+            material.ior = ior;
+        }
+        material.transmission = (1.0 - mtl.dissolve).clamp(0.0, 1.0);
+        material.sheen = mtl.sheen.unwrap_or(0.0);
+        material.clear_coat = mtl.clear_coat.unwrap_or(0.0);
+
+        let mut imported = ImportedMaterial::new(&mtl.name, material);
+        if let Some(map) = &mtl.diffuse_map {
+            imported.base_color_texture = Some(self.texture(map)?);
+            imported.material.base_color_texture_transform = map.transform();
+            if material_is_black(&imported.material.base_color) {
+                imported.material.base_color = Vec4::new(1.0, 1.0, 1.0, 1.0);
+            }
+        }
+        if let Some(map) = &mtl.emission_map {
+            imported.emission_texture = Some(self.texture(map)?);
+            imported.material.emission_texture_transform = map.transform();
+            // The shader scales the texture by Ke, which exporters often leave out
+            if material_is_black(&imported.material.emission) {
+                imported.material.emission = Vec4::new(1.0, 1.0, 1.0, 1.0);
+            }
+        }
+        if let Some(map) = &mtl.normal_map {
+            imported.normal_texture = Some(self.texture(map)?);
+            imported.material.normal_texture_transform = map.transform();
+            imported.material.normal_scale = map.bump_multiplier;
+        }
+        if mtl.roughness_map.is_some() || mtl.metallic_map.is_some() {
+            let load = |map: &Option<TextureMap>| {
+                map.as_ref()
+                    .map(|map| TextureImageData::load(self.directory.join(&map.file)))
+                    .transpose()
+            };
+            let (roughness, metallic) = (load(&mtl.roughness_map)?, load(&mtl.metallic_map)?);
+            self.imported.textures.push(metallic_roughness_texture(
+                roughness.as_ref(),
+                metallic.as_ref(),
+            ));
+            imported.metallic_roughness_texture = Some(self.imported.textures.len() - 1);
+            // Both maps share the UVs of the combined texture
+            let map = mtl.roughness_map.as_ref().or(mtl.metallic_map.as_ref());
+            imported.material.metallic_roughness_texture_transform = map.unwrap().transform();
+            // The maps hold the values, the factors only scale them
+            if mtl.roughness_map.is_some() {
+                imported.material.roughness = 1.0;
+            }
+            if mtl.metallic_map.is_some() {
+                imported.material.metallic = 1.0;
+            }
+        }
+        Ok(imported)
+    }
+
+    fn finish(mut self) -> ImportedScene {
+        for builder in std::mem::take(&mut self.builders) {
+            if builder.indices.is_empty() {
+                continue;
+            }
+            let vertices = builder
+                .keys
+                .iter()
+                .map(|key| self.positions[key.position])
+                .collect();
+            let has_tex_coords = builder.keys.iter().any(|key| key.tex_coord.is_some());
+            let tex_coords = if has_tex_coords {
+                let uvs = builder
+                    .keys
+                    .iter()
+                    .map(|key| match key.tex_coord {
+                        Some(index) => self.tex_coords[index],
+                        None => Vec2::new(0.0, 0.0),
+                    })
+                    .collect();
+                vec![uvs]
+            } else {
+                Vec::new()
+            };
+
+            let mut resource = MeshResource::new(
+                builder.indices,
+                vertices,
+                Vec::new(),
+                Vec::new(),
+                tex_coords,
+            );
+            resource.generate_normals();
+            for (normal, key) in resource.normals.iter_mut().zip(&builder.keys) {
+                if let Some(index) = key.normal {
+                    *normal = self.normals[index].normalize();
+                }
+            }
+            resource.generate_tangents();
+            if self.has_colors {
+                let colors = builder
+                    .keys
+                    .iter()
+                    .map(|key| self.colors[key.position])
+                    .collect();
+                resource = resource.with_colors(colors);
+            }
+
+            self.imported.instances.push(ImportedInstance {
+                mesh: self.imported.meshes.len(),
+                material: builder.material,
+                transform: Mat4::identity(),
+            });
+            self.imported.meshes.push(ImportedMesh {
+                name: builder.name,
+                resource,
+            });
+        }
+        self.imported
+    }
+}
+
+fn material_is_black(color: &Vec4) -> bool {
+    color.x == 0.0 && color.y == 0.0 && color.z == 0.0
+}
+
+struct TextureMap {
+    file: PathBuf,
+    offset: Vec2,
+    scale: Vec2,
+    bump_multiplier: f32,
+}
+
+impl TextureMap {
+    // -o and -s apply with the origin at the bottom left, flip them like the UVs
+    fn transform(&self) -> TextureTransform {
+        TextureTransform {
+            offset: Vec2::new(self.offset.x, 1.0 - self.scale.y - self.offset.y),
+            scale: self.scale,
+            ..TextureTransform::default()
+        }
+    }
+}
+
+fn parse_texture_map(args: &[&str]) -> std::io::Result<TextureMap> {
+    let mut map = TextureMap {
+        file: PathBuf::new(),
+        offset: Vec2::new(0.0, 0.0),
+        scale: Vec2::new(1.0, 1.0),
+        bump_multiplier: 1.0,
+    };
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with('-') {
+        let option = args[i];
+        i += 1;
+        // Up to three numbers for the vector options, exactly the listed count otherwise
+        let values: Vec<f32> = match option {
+            "-o" | "-s" | "-t" => {
+                let values: Vec<f32> = args[i..]
+                    .iter()
+                    .take(3)
+                    .map_while(|arg| arg.parse().ok())
+                    .collect();
+                if values.is_empty() {
+                    return Err(invalid_data(format!("{} needs a value", option)));
+                }
+                values
+            }
+            "-mm" => parse_floats::<2>(&args[i..])?.to_vec(),
+            "-bm" | "-boost" | "-texres" => parse_floats::<1>(&args[i..])?.to_vec(),
+            "-blendu" | "-blendv" | "-clamp" | "-cc" | "-imfchan" | "-type" => {
+                i += 1;
+                Vec::new()
+            }
+            _ => return Err(invalid_data(format!("Unknown texture option {}", option))),
+        };
+        i += values.len();
+        match option {
+            "-o" => map.offset = Vec2::new(values[0], values.get(1).copied().unwrap_or(0.0)),
+            "-s" => map.scale = Vec2::new(values[0], values.get(1).copied().unwrap_or(1.0)),
+            "-bm" => map.bump_multiplier = values[0],
+            _ => {}
+        }
+    }
+    if i >= args.len() {
+        return Err(invalid_data("Texture map without a file".to_string()));
+    }
+    map.file = PathBuf::from(args[i..].join(" ").replace('\\', "/"));
+    Ok(map)
+}
+
+struct MtlMaterial {
+    name: String,
+    diffuse: Vec3,
+    specular: Vec3,
+    shininess: f32,
+    emission: Vec3,
+    ior: Option<f32>,
+    dissolve: f32,
+    roughness: Option<f32>,
+    metallic: Option<f32>,
+    sheen: Option<f32>,
+    clear_coat: Option<f32>,
+    diffuse_map: Option<TextureMap>,
+    emission_map: Option<TextureMap>,
+    normal_map: Option<TextureMap>,
+    roughness_map: Option<TextureMap>,
+    metallic_map: Option<TextureMap>,
+}
+
+impl MtlMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            diffuse: Vec3::new(0.8, 0.8, 0.8),
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            emission: Vec3::new(0.0, 0.0, 0.0),
+            ior: None,
+            dissolve: 1.0,
+            roughness: None,
+            metallic: None,
+            sheen: None,
+            clear_coat: None,
+            diffuse_map: None,
+            emission_map: None,
+            normal_map: None,
+            roughness_map: None,
+            metallic_map: None,
+        }
+    }
+}
+
+fn parse_mtl_line(line: &str, materials: &mut Vec<MtlMaterial>) -> std::io::Result<()> {
+    let line = line.split('#').next().unwrap_or_default();
+    let mut tokens = line.split_whitespace();
+    let keyword = match tokens.next() {
+        Some(keyword) => keyword,
+        None => return Ok(()),
+    };
+    let args: Vec<&str> = tokens.collect();
+    if keyword == "newmtl" {
+        materials.push(MtlMaterial::new(&args.join(" ")));
+        return Ok(());
+    }
+    let material = match materials.last_mut() {
+        Some(material) => material,
+        None => return Err(invalid_data(format!("{} before newmtl", keyword))),
+    };
+    let color = |args: &[&str]| -> std::io::Result<Vec3> {
+        // A single value is a gray, spectral and XYZ colors aren't supported
+        match args.first() {
+            Some(&"spectral") | Some(&"xyz") => {
+                Err(invalid_data("Only RGB colors are supported".to_string()))
+            }
+            _ if args.len() == 1 => {
+                let [gray] = parse_floats(args)?;
+                Ok(Vec3::new(gray, gray, gray))
+            }
+            _ => Ok(Vec3::from(parse_floats::<3>(args)?)),
+        }
+    };
+    // The last value, which skips the -halo option of d
+    let scalar = |args: &[&str]| parse_floats::<1>(&args[args.len().saturating_sub(1)..]);
+    match keyword {
+        "Kd" => material.diffuse = color(&args)?,
+        "Ks" => material.specular = color(&args)?,
+        "Ke" => material.emission = color(&args)?,
+        "Ns" => material.shininess = scalar(&args)?[0],
+        "Ni" => material.ior = Some(scalar(&args)?[0]),
+        "d" => material.dissolve = scalar(&args)?[0],
+        "Tr" => material.dissolve = 1.0 - scalar(&args)?[0],
+        "Pr" => material.roughness = Some(scalar(&args)?[0]),
+        "Pm" => material.metallic = Some(scalar(&args)?[0]),
+        "Ps" => material.sheen = Some(scalar(&args)?[0]),
+        "Pc" => material.clear_coat = Some(scalar(&args)?[0]),
+        "map_Kd" => material.diffuse_map = Some(parse_texture_map(&args)?),
+        "map_Ke" => material.emission_map = Some(parse_texture_map(&args)?),
+        "norm" => material.normal_map = Some(parse_texture_map(&args)?),
+        "map_Pr" => material.roughness_map = Some(parse_texture_map(&args)?),
+        "map_Pm" => material.metallic_map = Some(parse_texture_map(&args)?),
+        // Ambient, specular, alpha and reflection maps have no equivalent, neither have the
+        // height maps of bump and map_Bump
+        _ => {}
+    }
+    Ok(())
+}
+
+// Roughness in green and metallic in blue like glTF, at the size of the larger map.
+// A missing map reads as 1 so its factor applies unchanged.
+fn metallic_roughness_texture(
+    roughness: Option<&TextureImageData>,
+    metallic: Option<&TextureImageData>,
+) -> TextureImageData {
+    let maps = [roughness, metallic];
+    let width = maps
+        .iter()
+        .flatten()
+        .map(|map| map.width)
+        .max()
+        .unwrap_or(1);
+    let height = maps
+        .iter()
+        .flatten()
+        .map(|map| map.height)
+        .max()
+        .unwrap_or(1);
+    let sample = |map: Option<&TextureImageData>, x: u32, y: u32| {
+        map.and_then(|map| map.texel(x * map.width / width, y * map.height / height))
+            .map(|texel| texel.x)
+            .unwrap_or(1.0)
+    };
+    let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            pixels.extend([
+                255,
+                to_byte(sample(roughness, x, y)),
+                to_byte(sample(metallic, x, y)),
+                255,
+            ]);
+        }
+    }
+    TextureImageData::new(ash::vk::Format::R8G8B8A8_UNORM, width, height, &pixels)
+}
@@ -0,0 +1,388 @@
+// Stanford PLY in ASCII and binary little or big endian. Vertices keep their normals, UVs
+// and colors, faces of any size are triangulated and elements other than vertices and
+// faces are skipped.
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+
+use super::{invalid_data, triangulate, ImportedScene};
+use crate::math::{Vec2, Vec3, Vec4};
+use crate::mesh_resource::MeshResource;
+use crate::tonemap::srgb_eotf;
+
+pub fn load(path: impl AsRef<Path>) -> std::io::Result<ImportedScene> {
+    let path = path.as_ref();
+    let mesh = read(&mut BufReader::new(File::open(path)?))?;
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(ImportedScene::from_mesh(&name, mesh))
+}
+
+pub fn read(reader: &mut impl BufRead) -> std::io::Result<MeshResource> {
+    let header = read_header(reader)?;
+    match header.format {
+        Format::Ascii => read_body(&mut AsciiValues::new(reader), &header.elements),
+        Format::BinaryLittleEndian => read_body(
+            &mut BinaryValues::<_, LittleEndian>::new(reader),
+            &header.elements,
+        ),
+        Format::BinaryBigEndian => read_body(
+            &mut BinaryValues::<_, BigEndian>::new(reader),
+            &header.elements,
+        ),
+    }
+}
+
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> std::io::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Self::Char,
+            "uchar" | "uint8" => Self::UChar,
+            "short" | "int16" => Self::Short,
+            "ushort" | "uint16" => Self::UShort,
+            "int" | "int32" => Self::Int,
+            "uint" | "uint32" => Self::UInt,
+            "float" | "float32" => Self::Float,
+            "double" | "float64" => Self::Double,
+            _ => return Err(invalid_data(format!("Unknown PLY type {}", name))),
+        })
+    }
+
+    // Integer colors use the full range of their type
+    fn normalize(self, value: f64) -> f32 {
+        match self {
+            Self::UChar => (value / 255.0) as f32,
+            Self::UShort => (value / 65535.0) as f32,
+            _ => value as f32,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+fn read_header(reader: &mut impl BufRead) -> std::io::Result<Header> {
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| -> std::io::Result<()> {
+        line.clear();
+        if reader.read_line(line)? == 0 {
+            return Err(invalid_data("PLY header without end_header".to_string()));
+        }
+        Ok(())
+    };
+
+    next_line(&mut line)?;
+    if line.trim_end() != "ply" {
+        return Err(invalid_data("Not a PLY file".to_string()));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        next_line(&mut line)?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["end_header"] => break,
+            ["format", name, _] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid_data(format!("Unknown PLY format {}", name))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid_data(format!("Invalid element count {}", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid_data("Property before any element".to_string()))?
+                .properties
+                .push(Property::List(
+                    name.to_string(),
+                    Scalar::parse(count)?,
+                    Scalar::parse(item)?,
+                )),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid_data("Property before any element".to_string()))?
+                .properties
+                .push(Property::Scalar(name.to_string(), Scalar::parse(scalar)?)),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => {
+                return Err(invalid_data(format!(
+                    "Invalid PLY header line {}",
+                    line.trim()
+                )))
+            }
+        }
+    }
+
+    match format {
+        Some(format) => Ok(Header { format, elements }),
+        None => Err(invalid_data("PLY header without a format".to_string())),
+    }
+}
+
+trait Values {
+    fn value(&mut self, scalar: Scalar) -> std::io::Result<f64>;
+}
+
+struct AsciiValues<'a, R> {
+    reader: &'a mut R,
+    line: String,
+    // Offset of the next unread token in the line
+    position: usize,
+}
+
+impl<'a, R: BufRead> AsciiValues<'a, R> {
+    fn new(reader: &'a mut R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Values for AsciiValues<'_, R> {
+    fn value(&mut self, _: Scalar) -> std::io::Result<f64> {
+        loop {
+            let rest = &self.line[self.position..];
+            let start = rest.len() - rest.trim_start().len();
+            let rest = &rest[start..];
+            if !rest.is_empty() {
+                let length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                let token = &rest[..length];
+                self.position += start + length;
+                return token
+                    .parse()
+                    .map_err(|_| invalid_data(format!("Invalid PLY value {}", token)));
+            }
+            self.line.clear();
+            self.position = 0;
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+}
+
+struct BinaryValues<'a, R, E> {
+    reader: &'a mut R,
+    _byte_order: std::marker::PhantomData<E>,
+}
+
+impl<'a, R: BufRead, E: ByteOrder> BinaryValues<'a, R, E> {
+    fn new(reader: &'a mut R) -> Self {
+        Self {
+            reader,
+            _byte_order: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<R: BufRead, E: ByteOrder> Values for BinaryValues<'_, R, E> {
+    fn value(&mut self, scalar: Scalar) -> std::io::Result<f64> {
+        let reader = &mut self.reader;
+        Ok(match scalar {
+            Scalar::Char => reader.read_i8()? as f64,
+            Scalar::UChar => reader.read_u8()? as f64,
+            Scalar::Short => reader.read_i16::<E>()? as f64,
+            Scalar::UShort => reader.read_u16::<E>()? as f64,
+            Scalar::Int => reader.read_i32::<E>()? as f64,
+            Scalar::UInt => reader.read_u32::<E>()? as f64,
+            Scalar::Float => reader.read_f32::<E>()? as f64,
+            Scalar::Double => reader.read_f64::<E>()?,
+        })
+    }
+}
+
+// Vertex property a value lands in
+#[derive(Clone, Copy)]
+enum Slot {
+    Position(usize),
+    Normal(usize),
+    TexCoord(usize),
+    Color(usize),
+    Ignored,
+}
+
+fn vertex_slot(name: &str) -> Slot {
+    match name {
+        "x" => Slot::Position(0),
+        "y" => Slot::Position(1),
+        "z" => Slot::Position(2),
+        "nx" => Slot::Normal(0),
+        "ny" => Slot::Normal(1),
+        "nz" => Slot::Normal(2),
+        "u" | "s" | "texture_u" | "texture_s" => Slot::TexCoord(0),
+        "v" | "t" | "texture_v" | "texture_t" => Slot::TexCoord(1),
+        "red" | "diffuse_red" => Slot::Color(0),
+        "green" | "diffuse_green" => Slot::Color(1),
+        "blue" | "diffuse_blue" => Slot::Color(2),
+        "alpha" => Slot::Color(3),
+        _ => Slot::Ignored,
+    }
+}
+
+fn read_body(values: &mut impl Values, elements: &[Element]) -> std::io::Result<MeshResource> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut colors = Vec::new();
+    let mut has_normals = false;
+    let mut has_tex_coords = false;
+    let mut has_colors = false;
+    // Polygon corners back to back, with the size of every polygon
+    let mut corners = Vec::new();
+    let mut polygon_sizes = Vec::new();
+
+    for element in elements {
+        let is_vertex = element.name == "vertex";
+        let is_face = element.name == "face";
+        let slots: Vec<Slot> = element
+            .properties
+            .iter()
+            .map(|property| match property {
+                Property::Scalar(name, _) if is_vertex => vertex_slot(name),
+                _ => Slot::Ignored,
+            })
+            .collect();
+        if is_vertex {
+            has_normals = slots.iter().any(|slot| matches!(slot, Slot::Normal(_)));
+            has_tex_coords = slots.iter().any(|slot| matches!(slot, Slot::TexCoord(_)));
+            has_colors = slots.iter().any(|slot| matches!(slot, Slot::Color(_)));
+            // The count comes from the header, a bogus one mustn't allocate up front
+            positions.reserve(element.count.min(1 << 20));
+        }
+
+        for _ in 0..element.count {
+            let mut position = [0.0; 3];
+            let mut normal = [0.0; 3];
+            let mut tex_coord = [0.0; 2];
+            let mut color = [1.0; 4];
+            for (property, slot) in element.properties.iter().zip(&slots) {
+                match property {
+                    Property::Scalar(_, scalar) => {
+                        let value = values.value(*scalar)?;
+                        match *slot {
+                            Slot::Position(i) => position[i] = value as f32,
+                            Slot::Normal(i) => normal[i] = value as f32,
+                            Slot::TexCoord(i) => tex_coord[i] = value as f32,
+                            Slot::Color(i) => color[i] = scalar.normalize(value),
+                            Slot::Ignored => {}
+                        }
+                    }
+                    Property::List(name, count, item) => {
+                        let count = values.value(*count)? as usize;
+                        let is_indices = name == "vertex_indices" || name == "vertex_index";
+                        for _ in 0..count {
+                            let value = values.value(*item)?;
+                            if is_face && is_indices {
+                                corners.push(value as u32);
+                            }
+                        }
+                        if is_face && is_indices {
+                            polygon_sizes.push(count);
+                        }
+                    }
+                }
+            }
+            if is_vertex {
+                positions.push(Vec3::from(position));
+                normals.push(Vec3::from(normal));
+                // PLY puts the origin of the texture at the bottom left
+                tex_coords.push(Vec2::new(tex_coord[0], 1.0 - tex_coord[1]));
+                colors.push(Vec4::from(color));
+            }
+        }
+    }
+
+    let mut indices = Vec::with_capacity(corners.len());
+    let mut start = 0;
+    for size in polygon_sizes {
+        let polygon = &corners[start..start + size];
+        start += size;
+        if let Some(corner) = polygon.iter().find(|c| **c as usize >= positions.len()) {
+            return Err(invalid_data(format!(
+                "Vertex index {} out of range",
+                corner
+            )));
+        }
+        if size == 3 {
+            indices.extend_from_slice(polygon);
+        } else {
+            let points: Vec<Vec3> = polygon.iter().map(|c| positions[*c as usize]).collect();
+            for triangle in triangulate(&points) {
+                indices.extend(triangle.iter().map(|corner| polygon[*corner]));
+            }
+        }
+    }
+
+    let tex_coords = if has_tex_coords {
+        vec![tex_coords]
+    } else {
+        Vec::new()
+    };
+    let mut mesh = MeshResource::new(indices, positions, normals, Vec::new(), tex_coords);
+    if !has_normals {
+        mesh.generate_normals();
+    }
+    mesh.generate_tangents();
+    if has_colors {
+        // 8 and 16 bit colors are stored sRGB encoded like images
+        let integer_colors = elements
+            .iter()
+            .filter(|element| element.name == "vertex")
+            .flat_map(|element| &element.properties)
+            .any(|property| {
+                matches!(property, Property::Scalar(name, Scalar::UChar | Scalar::UShort)
+                    if matches!(vertex_slot(name), Slot::Color(0..=2)))
+            });
+        if integer_colors {
+            for color in &mut colors {
+                color.x = srgb_eotf(color.x);
+                color.y = srgb_eotf(color.y);
+                color.z = srgb_eotf(color.z);
+            }
+        }
+        mesh = mesh.with_colors(colors);
+    }
+    Ok(mesh)
+}
@@ -0,0 +1,181 @@
+// Binary and ASCII STL. The facets are welded into an indexed mesh: corners at the same
+// position share a vertex when their facet normals lie within the crease angle, so curved
+// surfaces shade smooth and sharp edges stay sharp.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use cgmath::InnerSpace;
+
+use super::{invalid_data, ImportedScene};
+use crate::math::Vec3;
+use crate::mesh_resource::MeshResource;
+
+// Largest angle in degrees between facet normals that still shade smooth across an edge
+pub const WELD_CREASE_ANGLE: f32 = 30.0;
+
+pub fn load(path: impl AsRef<Path>) -> std::io::Result<ImportedScene> {
+    let path = path.as_ref();
+    let mesh = read(&mut BufReader::new(File::open(path)?))?;
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(ImportedScene::from_mesh(&name, mesh))
+}
+
+pub fn read(reader: &mut impl BufRead) -> std::io::Result<MeshResource> {
+    let mut welder = Welder::new();
+    if is_ascii(reader)? {
+        read_ascii(reader, &mut welder)?;
+    } else {
+        read_binary(reader, &mut welder)?;
+    }
+    Ok(welder.finish())
+}
+
+// Binary files may start with "solid" as well, but only ASCII ones follow it with text
+fn is_ascii(reader: &mut impl BufRead) -> std::io::Result<bool> {
+    let buffer = reader.fill_buf()?;
+    if !buffer.starts_with(b"solid") {
+        return Ok(false);
+    }
+    let start = &buffer[..buffer.len().min(1024)];
+    Ok(start.windows(5).any(|word| word == b"facet") || start.windows(8).any(|w| w == b"endsolid"))
+}
+
+fn read_binary(reader: &mut impl BufRead, welder: &mut Welder) -> std::io::Result<()> {
+    let mut header = [0; 80];
+    reader.read_exact(&mut header)?;
+    let count = reader.read_u32::<LittleEndian>()?;
+    let read_vector = |reader: &mut dyn Read| -> std::io::Result<Vec3> {
+        Ok(Vec3::new(
+            reader.read_f32::<LittleEndian>()?,
+            reader.read_f32::<LittleEndian>()?,
+            reader.read_f32::<LittleEndian>()?,
+        ))
+    };
+    for _ in 0..count {
+        let normal = read_vector(reader)?;
+        let corners = [
+            read_vector(reader)?,
+            read_vector(reader)?,
+            read_vector(reader)?,
+        ];
+        // Attribute byte count, used for colors by some tools without an agreed layout
+        reader.read_u16::<LittleEndian>()?;
+        welder.add(normal, corners);
+    }
+    Ok(())
+}
+
+fn read_ascii(reader: &mut impl BufRead, welder: &mut Welder) -> std::io::Result<()> {
+    let parse_vector = |args: &[&str]| -> std::io::Result<Vec3> {
+        let mut values = [0.0; 3];
+        if args.len() != 3 {
+            return Err(invalid_data(format!(
+                "Expected 3 numbers, found {}",
+                args.len()
+            )));
+        }
+        for (value, arg) in values.iter_mut().zip(args) {
+            *value = arg
+                .parse()
+                .map_err(|_| invalid_data(format!("Invalid number {}", arg)))?;
+        }
+        Ok(Vec3::from(values))
+    };
+
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+    let mut corners = Vec::with_capacity(3);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? != 0 {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["facet", "normal", args @ ..] => {
+                normal = parse_vector(args)?;
+                corners.clear();
+            }
+            ["vertex", args @ ..] => corners.push(parse_vector(args)?),
+            ["endfacet"] => {
+                if corners.len() != 3 {
+                    return Err(invalid_data(format!(
+                        "Facet with {} vertices",
+                        corners.len()
+                    )));
+                }
+                welder.add(normal, [corners[0], corners[1], corners[2]]);
+            }
+            // solid, outer loop, endloop and endsolid carry nothing
+            _ => {}
+        }
+        line.clear();
+    }
+    Ok(())
+}
+
+struct Welder {
+    cos_crease: f32,
+    // Welded vertices at every position, with the facet normal each one started from
+    vertices: HashMap<[u32; 3], Vec<(u32, Vec3)>>,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    indices: Vec<u32>,
+}
+
+impl Welder {
+    fn new() -> Self {
+        Self {
+            cos_crease: WELD_CREASE_ANGLE.to_radians().cos(),
+            vertices: HashMap::new(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, normal: Vec3, corners: [Vec3; 3]) {
+        let cross = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+        let area = cross.magnitude();
+        if area == 0.0 {
+            return;
+        }
+        // Lots of exporters write zero normals, the winding gives them as well
+        let normal = if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            cross / area
+        };
+        for corner in corners {
+            // Adding zero turns -0.0 into 0.0 so both weld
+            let key = [corner.x, corner.y, corner.z].map(|v| (v + 0.0).to_bits());
+            let candidates = self.vertices.entry(key).or_default();
+            let cos_crease = self.cos_crease;
+            let index = match candidates.iter().find(|(_, n)| n.dot(normal) >= cos_crease) {
+                Some((index, _)) => *index,
+                None => {
+                    let index = self.positions.len() as u32;
+                    self.positions.push(corner);
+                    self.normals.push(Vec3::new(0.0, 0.0, 0.0));
+                    candidates.push((index, normal));
+                    index
+                }
+            };
+            // Area weighted like MeshResource::generate_normals
+            self.normals[index as usize] += normal * area;
+            self.indices.push(index);
+        }
+    }
+
+    fn finish(self) -> MeshResource {
+        let normals = self.normals.into_iter().map(|n| n.normalize()).collect();
+        let mut mesh = MeshResource::new(
+            self.indices,
+            self.positions,
+            normals,
+            Vec::new(),
+            Vec::new(),
+        );
+        mesh.generate_tangents();
+        mesh
+    }
+}
@@ -1,17 +1,40 @@
 pub mod geometry;
 pub mod rtx_extensions;
 pub use ash::vk;
+pub mod adaptive;
+pub mod aov;
+pub mod blue_noise;
 pub mod camera;
 pub mod ctx;
+pub mod cubemap;
+pub mod denoise;
+pub mod denoise_pipeline;
 pub mod descriptor_sets;
+pub mod emissive;
+pub mod environment;
+pub mod export;
+pub mod filter;
 pub mod framebuffer;
 pub mod gpu_scene;
+pub mod hash;
+pub mod hdr_image;
 pub mod image_resource;
+pub mod import;
+pub mod light;
 pub mod material;
 pub mod math;
 pub mod mesh;
 pub mod mesh_instance;
 pub mod mesh_resource;
+pub mod progressive;
+pub mod region;
+pub mod render_settings;
 pub mod rtx_pipeline;
+pub mod sampler;
+pub mod sampling;
 pub mod scene;
+pub mod scene_file;
+pub mod sky;
 pub mod skybox;
+pub mod tonemap;
+pub mod tonemap_pipeline;
@@ -0,0 +1,180 @@
+use cgmath::{InnerSpace, SquareMatrix, Transform};
+use serde::{Deserialize, Serialize};
+
+use crate::math::{Mat4, Point, Real, Vec3};
+
+/// Angles are in degrees, matching `MeshInstance::rotate` and `Camera`. Cone angles are
+/// half angles, measured from the axis to the edge of the cone.
+/// Lights emit along their local -Z axis.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum LightType {
+    /// Radiant intensity in W/sr
+    Point,
+    /// Radiant intensity in W/sr on the axis, falling off smoothly from the inner to the
+    /// outer cone
+    Spot {
+        inner_angle: Real,
+        outer_angle: Real,
+    },
+    /// Irradiance in W/m² on a surface facing the light
+    Directional { angular_radius: Real },
+    /// Radiant power in W, emitted from one side of a width x height quad
+    Area { width: Real, height: Real },
+}
+
+#[derive(Clone, Copy)]
+pub struct Light {
+    light_type: LightType,
+    pub color: Vec3,
+    pub intensity: Real,
+    transform: Mat4,
+}
+
+impl Light {
+    pub fn new(light_type: LightType, color: Vec3, intensity: Real) -> Self {
+        Self {
+            light_type,
+            color,
+            intensity,
+            transform: Mat4::identity(),
+        }
+    }
+
+    pub fn point(color: Vec3, intensity: Real) -> Self {
+        Self::new(LightType::Point, color, intensity)
+    }
+
+    pub fn spot(color: Vec3, intensity: Real, inner_angle: Real, outer_angle: Real) -> Self {
+        Self::new(
+            LightType::Spot {
+                inner_angle,
+                outer_angle,
+            },
+            color,
+            intensity,
+        )
+    }
+
+    pub fn directional(color: Vec3, intensity: Real, angular_radius: Real) -> Self {
+        Self::new(LightType::Directional { angular_radius }, color, intensity)
+    }
+
+    pub fn area(color: Vec3, intensity: Real, width: Real, height: Real) -> Self {
+        Self::new(LightType::Area { width, height }, color, intensity)
+    }
+
+    pub fn light_type(&self) -> LightType {
+        self.light_type
+    }
+
+    pub fn set_light_type(&mut self, light_type: LightType) {
+        self.light_type = light_type
+    }
+
+    pub fn transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, t: Mat4) {
+        self.transform = t
+    }
+
+    pub fn translate(&mut self, translation: &Vec3) -> &mut Self {
+        self.transform = self.transform * Mat4::from_translation(*translation);
+        self
+    }
+
+    pub fn rotate(&mut self, rotation: &Vec3) -> &mut Self {
+        self.transform = self.transform * Mat4::from_angle_x(cgmath::Deg(rotation.x));
+        self.transform = self.transform * Mat4::from_angle_y(cgmath::Deg(rotation.y));
+        self.transform = self.transform * Mat4::from_angle_z(cgmath::Deg(rotation.z));
+        self
+    }
+
+    pub fn to_gpu(&self) -> GpuLight {
+        let position = self.transform.transform_point(Point::new(0.0, 0.0, 0.0));
+        let direction = self
+            .transform
+            .transform_vector(Vec3::new(0.0, 0.0, -1.0))
+            .normalize();
+        let radiance = self.color * self.intensity;
+        let mut light = GpuLight {
+            _position: Vec3::new(position.x, position.y, position.z),
+            _light_type: 0,
+            _direction: direction,
+            _cos_inner: 1.0,
+            _radiance: radiance,
+            _cos_outer: 1.0,
+            _edge_u: Vec3::new(0.0, 0.0, 0.0),
+            _area: 0.0,
+            _edge_v: Vec3::new(0.0, 0.0, 0.0),
+            _padding: 0.0,
+        };
+
+        match self.light_type {
+            LightType::Point => {
+                light._light_type = 0;
+            }
+            LightType::Spot {
+                inner_angle,
+                outer_angle,
+            } => {
+                light._light_type = 1;
+                light._cos_outer = outer_angle.to_radians().cos();
+                // smoothstep needs distinct edges, equal angles give a hard edge
+                light._cos_inner = inner_angle
+                    .min(outer_angle)
+                    .to_radians()
+                    .cos()
+                    .max(light._cos_outer + 1e-6);
+            }
+            LightType::Directional { angular_radius } => {
+                light._light_type = 2;
+                light._cos_inner = angular_radius.to_radians().cos();
+            }
+            LightType::Area { width, height } => {
+                let edge_u = self.transform.transform_vector(Vec3::new(width, 0.0, 0.0));
+                let edge_v = self.transform.transform_vector(Vec3::new(0.0, height, 0.0));
+                let area = edge_u.cross(edge_v).magnitude();
+                light._light_type = 3;
+                light._position = light._position - edge_u * 0.5 - edge_v * 0.5;
+                light._edge_u = edge_u;
+                light._edge_v = edge_v;
+                light._area = area;
+                // Power to radiance of a one sided Lambertian emitter
+                light._radiance = radiance / (std::f32::consts::PI * area.max(1e-8));
+            }
+        }
+
+        light
+    }
+
+    // Fraction of the intensity emitted towards a world space direction, the falloff of
+    // sample_light in light.glsl. One for lights that aren't spots.
+    pub fn falloff(&self, direction: Vec3) -> Real {
+        let light = self.to_gpu();
+        match self.light_type {
+            LightType::Spot { .. } => {
+                let cos_theta = direction.normalize().dot(light._direction);
+                let t = ((cos_theta - light._cos_outer) / (light._cos_inner - light._cos_outer))
+                    .clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct GpuLight {
+    pub _position: Vec3,
+    pub _light_type: u32,
+    pub _direction: Vec3,
+    pub _cos_inner: f32,
+    pub _radiance: Vec3,
+    pub _cos_outer: f32,
+    pub _edge_u: Vec3,
+    pub _area: f32,
+    pub _edge_v: Vec3,
+    pub _padding: f32,
+}
@@ -1,7 +1,59 @@
-use crate::{ctx::Handle, math::Vec4};
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    ctx::Handle,
+    math::{Vec2, Vec4},
+};
+
+/// Orientation of the green channel in tangent space normal maps.
+/// glTF uses the OpenGL (+Y) convention, DirectX tools export -Y.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum NormalMapConvention {
+    OpenGl,
+    DirectX,
+}
+
+/// Selects the UV set a texture slot reads from and transforms it
+/// the way KHR_texture_transform does: scale, then rotate, then offset.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureTransform {
+    pub tex_coord: u32,
+    pub offset: Vec2,
+    pub scale: Vec2,
+    pub rotation: f32,
+}
+
+impl TextureTransform {
+    pub fn new() -> Self {
+        Self {
+            tex_coord: 0,
+            offset: Vec2::new(0.0, 0.0),
+            scale: Vec2::new(1.0, 1.0),
+            rotation: 0.0,
+        }
+    }
+
+    fn to_gpu(self) -> GpuTextureTransform {
+        GpuTextureTransform {
+            _offset: self.offset,
+            _scale: self.scale,
+            _rotation: self.rotation,
+            _tex_coord: self.tex_coord,
+        }
+    }
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone)]
 pub struct Material {
     pub base_color: Vec4,
+    // Radiance in rgb, scaled by w. Emission textures are multiplied with it like glTF's
+    // emissiveFactor, set it to (1, 1, 1, 1) to use a texture as it is.
     pub emission: Vec4,
     pub roughness: f32,
     pub metallic: f32,
@@ -9,10 +61,21 @@ pub struct Material {
     pub clear_coat: f32,
     pub ior: f32,
     pub transmission: f32,
+    pub normal_scale: f32,
+    pub normal_map_convention: NormalMapConvention,
+    pub occlusion_strength: f32,
     pub base_color_texture: Option<Handle>,
     pub metallic_roughness_texture: Option<Handle>,
     pub normal_texture: Option<Handle>,
     pub emission_texture: Option<Handle>,
+    // Occlusion is read from the red channel, so a packed ORM texture can be
+    // assigned to both this slot and `metallic_roughness_texture`.
+    pub occlusion_texture: Option<Handle>,
+    pub base_color_texture_transform: TextureTransform,
+    pub metallic_roughness_texture_transform: TextureTransform,
+    pub normal_texture_transform: TextureTransform,
+    pub emission_texture_transform: TextureTransform,
+    pub occlusion_texture_transform: TextureTransform,
 }
 
 impl Material {
@@ -26,12 +89,31 @@ impl Material {
             clear_coat: 0.0,
             ior: 1.0,
             transmission: 0.0,
+            normal_scale: 1.0,
+            normal_map_convention: NormalMapConvention::OpenGl,
+            occlusion_strength: 1.0,
             base_color_texture: None,
             metallic_roughness_texture: None,
             normal_texture: None,
             emission_texture: None,
+            occlusion_texture: None,
+            base_color_texture_transform: TextureTransform::new(),
+            metallic_roughness_texture_transform: TextureTransform::new(),
+            normal_texture_transform: TextureTransform::new(),
+            emission_texture_transform: TextureTransform::new(),
+            occlusion_texture_transform: TextureTransform::new(),
         }
     }
+
+    pub fn gpu_texture_transforms(&self) -> [GpuTextureTransform; 5] {
+        [
+            self.base_color_texture_transform.to_gpu(),
+            self.metallic_roughness_texture_transform.to_gpu(),
+            self.normal_texture_transform.to_gpu(),
+            self.emission_texture_transform.to_gpu(),
+            self.occlusion_texture_transform.to_gpu(),
+        ]
+    }
 }
 
 impl Default for Material {
@@ -40,6 +122,15 @@ impl Default for Material {
     }
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GpuTextureTransform {
+    pub _offset: Vec2,
+    pub _scale: Vec2,
+    pub _rotation: f32,
+    pub _tex_coord: u32,
+}
+
 #[repr(C)]
 pub struct GpuMaterial {
     pub _base_color: Vec4,
@@ -54,4 +145,10 @@ pub struct GpuMaterial {
     pub _metallic_roughness_texture: i32,
     pub _normal_texture: i32,
     pub _emission_texture: i32,
+    pub _occlusion_texture: i32,
+    pub _normal_scale: f32,
+    pub _occlusion_strength: f32,
+    pub _normal_map_convention: u32,
+    // Ordered base color, metallic roughness, normal, emission, occlusion
+    pub _texture_transforms: [GpuTextureTransform; 5],
 }
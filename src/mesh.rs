@@ -6,16 +6,19 @@ use vk_utils::{
 };
 
 use crate::{
-    geometry::{BottomLevelAccelerationStructure, Normal, Position, Tangent, Texcoord},
+    geometry::BottomLevelAccelerationStructure, mesh_resource::MeshResource,
     rtx_extensions::RtxExtensions,
 };
 
+pub const MAX_TEX_COORD_SETS: usize = 2;
+
 pub struct Mesh {
     pub index_buffer: BufferResource,
     pub vertex_buffer: BufferResource,
     pub normal_buffer: BufferResource,
     pub tangent_buffer: BufferResource,
-    pub tex_coord_buffer: BufferResource,
+    pub tex_coord_buffers: Vec<BufferResource>,
+    pub color_buffer: Option<BufferResource>,
     pub blas: BottomLevelAccelerationStructure,
 }
 
@@ -24,15 +27,12 @@ impl Mesh {
         device: Rc<DeviceContext>,
         rtx: &RtxExtensions,
         queue: Rc<CommandQueue>,
-        indices: &[u32],
-        positions: &[Position],
-        normals: &[Normal],
-        tangents: &[Tangent],
-        tex_coords: &[Texcoord],
+        mesh: &MeshResource,
     ) -> Self {
+        let (indices, positions) = (mesh.indices.as_slice(), mesh.vertices.as_slice());
         let mut index_buffer = BufferResource::new(
             device.clone(),
-            (indices.len() * std::mem::size_of::<u32>()) as u64,
+            std::mem::size_of_val(indices) as u64,
             MemoryPropertyFlags::HOST_VISIBLE,
             BufferUsageFlags::SHADER_DEVICE_ADDRESS
                 | BufferUsageFlags::STORAGE_BUFFER
@@ -43,7 +43,7 @@ impl Mesh {
 
         let mut vertex_buffer = BufferResource::new(
             device.clone(),
-            (positions.len() * std::mem::size_of::<Position>()) as u64,
+            std::mem::size_of_val(positions) as u64,
             MemoryPropertyFlags::HOST_VISIBLE,
             BufferUsageFlags::SHADER_DEVICE_ADDRESS
                 | BufferUsageFlags::STORAGE_BUFFER
@@ -54,36 +54,56 @@ impl Mesh {
 
         let mut normal_buffer = BufferResource::new(
             device.clone(),
-            (normals.len() * std::mem::size_of::<Normal>()) as u64,
+            std::mem::size_of_val(mesh.normals.as_slice()) as u64,
             MemoryPropertyFlags::HOST_VISIBLE,
             BufferUsageFlags::SHADER_DEVICE_ADDRESS
                 | BufferUsageFlags::STORAGE_BUFFER
                 | BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
         );
 
-        normal_buffer.upload(normals);
+        normal_buffer.upload(&mesh.normals);
 
         let mut tangent_buffer = BufferResource::new(
             device.clone(),
-            (tangents.len() * std::mem::size_of::<Tangent>()) as u64,
+            std::mem::size_of_val(mesh.tangents.as_slice()) as u64,
             MemoryPropertyFlags::HOST_VISIBLE,
             BufferUsageFlags::SHADER_DEVICE_ADDRESS
                 | BufferUsageFlags::STORAGE_BUFFER
                 | BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
         );
 
-        tangent_buffer.upload(tangents);
-
-        let mut tex_coord_buffer = BufferResource::new(
-            device.clone(),
-            (tex_coords.len() * std::mem::size_of::<Texcoord>()) as u64,
-            MemoryPropertyFlags::HOST_VISIBLE,
-            BufferUsageFlags::SHADER_DEVICE_ADDRESS
-                | BufferUsageFlags::STORAGE_BUFFER
-                | BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
-        );
-
-        tex_coord_buffer.upload(tex_coords);
+        tangent_buffer.upload(&mesh.tangents);
+
+        let tex_coord_buffers = mesh
+            .gpu_tex_coords()
+            .iter()
+            .take(MAX_TEX_COORD_SETS)
+            .map(|set| {
+                let mut tex_coord_buffer = BufferResource::new(
+                    device.clone(),
+                    std::mem::size_of_val(set.as_slice()) as u64,
+                    MemoryPropertyFlags::HOST_VISIBLE,
+                    BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                        | BufferUsageFlags::STORAGE_BUFFER
+                        | BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
+                );
+
+                tex_coord_buffer.upload(set);
+                tex_coord_buffer
+            })
+            .collect();
+
+        let color_buffer = mesh.colors.as_deref().map(|colors| {
+            let mut color_buffer = BufferResource::new(
+                device.clone(),
+                std::mem::size_of_val(colors) as u64,
+                MemoryPropertyFlags::HOST_VISIBLE,
+                BufferUsageFlags::SHADER_DEVICE_ADDRESS | BufferUsageFlags::STORAGE_BUFFER,
+            );
+
+            color_buffer.upload(colors);
+            color_buffer
+        });
 
         let blas = BottomLevelAccelerationStructure::new(
             device,
@@ -102,29 +122,49 @@ impl Mesh {
             vertex_buffer,
             normal_buffer,
             tangent_buffer,
-            tex_coord_buffer,
+            tex_coord_buffers,
+            color_buffer,
             blas,
         }
     }
 }
 
+#[repr(C)]
 #[derive(Clone)]
 pub struct MeshAddress {
     _index_address: ash::vk::DeviceAddress,
     _vertex_address: ash::vk::DeviceAddress,
     _normal_address: ash::vk::DeviceAddress,
     _tangent_address: ash::vk::DeviceAddress,
-    _tex_coord_address: ash::vk::DeviceAddress,
+    _tex_coord_addresses: [ash::vk::DeviceAddress; MAX_TEX_COORD_SETS],
+    // Zero when the mesh has no vertex colors
+    _color_address: ash::vk::DeviceAddress,
 }
 
 impl MeshAddress {
     pub fn new(gpu_mesh: &Mesh) -> Self {
+        // Missing UV sets alias the first one so every slot stays readable
+        let mut tex_coord_addresses = [0; MAX_TEX_COORD_SETS];
+        for (set, address) in tex_coord_addresses.iter_mut().enumerate() {
+            if let Some(buffer) = gpu_mesh
+                .tex_coord_buffers
+                .get(set)
+                .or_else(|| gpu_mesh.tex_coord_buffers.first())
+            {
+                *address = buffer.device_address();
+            }
+        }
+
         Self {
             _index_address: gpu_mesh.index_buffer.device_address(),
             _vertex_address: gpu_mesh.vertex_buffer.device_address(),
             _normal_address: gpu_mesh.normal_buffer.device_address(),
             _tangent_address: gpu_mesh.tangent_buffer.device_address(),
-            _tex_coord_address: gpu_mesh.tex_coord_buffer.device_address(),
+            _tex_coord_addresses: tex_coord_addresses,
+            _color_address: gpu_mesh
+                .color_buffer
+                .as_ref()
+                .map_or(0, |buffer| buffer.device_address()),
         }
     }
 }
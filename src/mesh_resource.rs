@@ -1,11 +1,24 @@
-use crate::geometry::{Normal, Position, Tangent, Texcoord};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
 
+use cgmath::InnerSpace;
+
+use crate::geometry::{Color, Normal, Position, Tangent, Texcoord};
+use crate::import::invalid_data;
+use crate::math::{Vec2, Vec3, Vec4};
+
+#[derive(Clone)]
 pub struct MeshResource {
     pub indices: Vec<u32>,
     pub vertices: Vec<Position>,
     pub normals: Vec<Normal>,
     pub tangents: Vec<Tangent>,
-    pub tex_coords: Vec<Texcoord>,
+    // One stream per UV set, TEXCOORD_0 first
+    pub tex_coords: Vec<Vec<Texcoord>>,
+    // Linear RGBA, multiplied into the material base color
+    pub colors: Option<Vec<Color>>,
 }
 
 impl MeshResource {
@@ -14,7 +27,7 @@ impl MeshResource {
         vertices: Vec<Position>,
         normals: Vec<Normal>,
         tangents: Vec<Tangent>,
-        tex_coords: Vec<Texcoord>,
+        tex_coords: Vec<Vec<Texcoord>>,
     ) -> Self {
         Self {
             indices,
@@ -22,6 +35,186 @@ impl MeshResource {
             normals,
             tangents,
             tex_coords,
+            colors: None,
+        }
+    }
+
+    pub fn with_colors(mut self, colors: Vec<Color>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    // UV sets as they're uploaded. The shaders interpolate the first set of every mesh, so
+    // meshes without UVs, like scans, get one filled with zeros.
+    pub fn gpu_tex_coords(&self) -> Cow<'_, [Vec<Texcoord>]> {
+        match self.tex_coords.first() {
+            Some(uvs) if !uvs.is_empty() => Cow::Borrowed(&self.tex_coords),
+            _ => Cow::Owned(vec![vec![Vec2::new(0.0, 0.0); self.vertices.len()]]),
+        }
+    }
+
+    // Area weighted average of the faces sharing a vertex. Faces only smooth across
+    // vertices they share by index.
+    pub fn generate_normals(&mut self) {
+        let mut normals = vec![Vec3::new(0.0, 0.0, 0.0); self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+            let face =
+                (self.vertices[b] - self.vertices[a]).cross(self.vertices[c] - self.vertices[a]);
+            for vertex in [a, b, c] {
+                normals[vertex] += face;
+            }
+        }
+        self.normals = normals
+            .into_iter()
+            .map(|n| {
+                if n.magnitude2() > 0.0 {
+                    n.normalize()
+                } else {
+                    Vec3::new(0.0, 0.0, 1.0)
+                }
+            })
+            .collect();
+    }
+
+    // Tangents along the U direction of the first UV set, orthogonalized against the
+    // normals. Without UVs they stay zero and the shader picks a frame around the normal.
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![Vec3::new(0.0, 0.0, 0.0); self.vertices.len()];
+        if let Some(uvs) = self.tex_coords.first().filter(|uvs| !uvs.is_empty()) {
+            for triangle in self.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+                let (e1, e2) = (
+                    self.vertices[b] - self.vertices[a],
+                    self.vertices[c] - self.vertices[a],
+                );
+                let (d1, d2) = (uvs[b] - uvs[a], uvs[c] - uvs[a]);
+                let determinant = d1.x * d2.y - d2.x * d1.y;
+                if determinant.abs() < 1e-12 {
+                    continue;
+                }
+                let tangent = (e1 * d2.y - e2 * d1.y) / determinant;
+                for vertex in [a, b, c] {
+                    tangents[vertex] += tangent;
+                }
+            }
+            for (tangent, normal) in tangents.iter_mut().zip(&self.normals) {
+                let t = *tangent - normal * normal.dot(*tangent);
+                *tangent = if t.magnitude2() > 1e-12 {
+                    t.normalize()
+                } else {
+                    Vec3::new(0.0, 0.0, 0.0)
+                };
+            }
+        }
+        self.tangents = tangents;
+    }
+}
+
+// Native mesh files, little endian: the magic, the version, then every stream as a u32
+// count followed by its floats or indices
+const MESH_FILE_MAGIC: &[u8; 4] = b"TMSH";
+const MESH_FILE_VERSION: u32 = 1;
+
+impl MeshResource {
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> std::io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MESH_FILE_MAGIC {
+            return Err(invalid_data("Not a mesh file".to_string()));
+        }
+        let version = read_u32(reader)?;
+        if version > MESH_FILE_VERSION {
+            return Err(invalid_data(format!(
+                "Mesh file version {} is newer than the supported {}",
+                version, MESH_FILE_VERSION
+            )));
+        }
+
+        let index_count = read_u32(reader)?;
+        let indices = (0..index_count)
+            .map(|_| read_u32(reader))
+            .collect::<std::io::Result<_>>()?;
+        let vertices = read_stream(reader, |v: [f32; 3]| Vec3::new(v[0], v[1], v[2]))?;
+        let normals = read_stream(reader, |v: [f32; 3]| Vec3::new(v[0], v[1], v[2]))?;
+        let tangents = read_stream(reader, |v: [f32; 3]| Vec3::new(v[0], v[1], v[2]))?;
+        let set_count = read_u32(reader)?;
+        let tex_coords = (0..set_count)
+            .map(|_| read_stream(reader, |v: [f32; 2]| Vec2::new(v[0], v[1])))
+            .collect::<std::io::Result<_>>()?;
+        let colors = read_stream(reader, |v: [f32; 4]| Vec4::new(v[0], v[1], v[2], v[3]))?;
+
+        let mesh = Self::new(indices, vertices, normals, tangents, tex_coords);
+        Ok(if colors.is_empty() {
+            mesh
+        } else {
+            mesh.with_colors(colors)
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(MESH_FILE_MAGIC)?;
+        writer.write_all(&MESH_FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.indices.len() as u32).to_le_bytes())?;
+        for index in &self.indices {
+            writer.write_all(&index.to_le_bytes())?;
+        }
+        write_stream(writer, &self.vertices, |v| [v.x, v.y, v.z])?;
+        write_stream(writer, &self.normals, |v| [v.x, v.y, v.z])?;
+        write_stream(writer, &self.tangents, |v| [v.x, v.y, v.z])?;
+        writer.write_all(&(self.tex_coords.len() as u32).to_le_bytes())?;
+        for set in &self.tex_coords {
+            write_stream(writer, set, |v| [v.x, v.y])?;
+        }
+        // An empty color stream means the mesh has none
+        write_stream(writer, self.colors.as_deref().unwrap_or(&[]), |v| {
+            [v.x, v.y, v.z, v.w]
+        })
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_stream<T, const N: usize>(
+    reader: &mut impl Read,
+    convert: impl Fn([f32; N]) -> T,
+) -> std::io::Result<Vec<T>> {
+    let count = read_u32(reader)?;
+    (0..count)
+        .map(|_| {
+            let mut values = [0.0; N];
+            for value in &mut values {
+                *value = f32::from_bits(read_u32(reader)?);
+            }
+            Ok(convert(values))
+        })
+        .collect()
+}
+
+fn write_stream<T, const N: usize>(
+    writer: &mut impl Write,
+    stream: &[T],
+    convert: impl Fn(&T) -> [f32; N],
+) -> std::io::Result<()> {
+    writer.write_all(&(stream.len() as u32).to_le_bytes())?;
+    for item in stream {
+        for value in convert(item) {
+            writer.write_all(&value.to_le_bytes())?;
         }
     }
+    Ok(())
 }
@@ -0,0 +1,337 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::ctx::{Ctx, FrameResources};
+use crate::framebuffer::FrameBuffer;
+use crate::render_settings::RenderSettings;
+
+// Rendering stops at whichever limit is reached first. Without any limit it runs until
+// stopped or cancelled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Termination {
+    pub max_passes: Option<u32>,
+    // Stops before the next pass would exceed the budget, judged by the average pass time
+    pub time_budget: Option<Duration>,
+    // Mean relative error as FrameBuffer::estimate_noise reports it
+    pub target_noise: Option<f32>,
+}
+
+impl Termination {
+    pub fn passes(max_passes: u32) -> Self {
+        Self::default().with_max_passes(max_passes)
+    }
+
+    pub fn time(budget: Duration) -> Self {
+        Self::default().with_time_budget(budget)
+    }
+
+    pub fn noise(target: f32) -> Self {
+        Self::default().with_target_noise(target)
+    }
+
+    pub fn with_max_passes(mut self, max_passes: u32) -> Self {
+        self.max_passes = Some(max_passes);
+        self
+    }
+
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    pub fn with_target_noise(mut self, target: f32) -> Self {
+        self.target_noise = Some(target);
+        self
+    }
+
+    pub fn is_reached(&self, passes: u32, elapsed: Duration, noise: Option<f32>) -> bool {
+        if self.max_passes.is_some_and(|max| passes >= max) {
+            return true;
+        }
+        if let Some(budget) = self.time_budget {
+            let next = if passes > 0 {
+                elapsed / passes
+            } else {
+                Duration::ZERO
+            };
+            if elapsed + next > budget {
+                return true;
+            }
+        }
+        matches!((self.target_noise, noise), (Some(target), Some(noise)) if noise <= target)
+    }
+
+    // Shortest estimate over the limits that are set. Noise falls with the square root of
+    // the sample count, so halving it takes four times the passes done so far.
+    pub fn estimate_remaining(
+        &self,
+        passes: u32,
+        elapsed: Duration,
+        noise: Option<f32>,
+    ) -> Option<Duration> {
+        if passes == 0 {
+            return None;
+        }
+
+        let per_pass = elapsed.as_secs_f64() / passes as f64;
+        let by_passes = self
+            .max_passes
+            .map(|max| per_pass * max.saturating_sub(passes) as f64);
+        let by_time = self
+            .time_budget
+            .map(|budget| budget.saturating_sub(elapsed).as_secs_f64());
+        let by_noise = match (self.target_noise, noise) {
+            (Some(target), Some(noise)) if target > 0.0 => {
+                let needed = passes as f64 * (noise as f64 / target as f64).powi(2);
+                Some(per_pass * (needed - passes as f64).max(0.0))
+            }
+            _ => None,
+        };
+
+        [by_passes, by_time, by_noise]
+            .into_iter()
+            .flatten()
+            .min_by(f64::total_cmp)
+            .map(Duration::from_secs_f64)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ProgressiveSettings {
+    pub samples_per_pass: u32,
+    pub render_settings: RenderSettings,
+    pub termination: Termination,
+    // Passes between noise estimates, each one reads back two full size images
+    pub noise_interval: u32,
+    // Passes between updates of the final image, 0 only resolves it when rendering ends
+    pub display_interval: u32,
+}
+
+impl Default for ProgressiveSettings {
+    fn default() -> Self {
+        Self {
+            samples_per_pass: 4,
+            render_settings: RenderSettings::default(),
+            termination: Termination::passes(128),
+            noise_interval: 8,
+            display_interval: 1,
+        }
+    }
+}
+
+impl ProgressiveSettings {
+    pub fn new(samples_per_pass: u32, termination: Termination) -> Self {
+        Self {
+            samples_per_pass,
+            termination,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderState {
+    Idle,
+    Running,
+    Finished,
+    Cancelled,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    pub state: RenderState,
+    pub passes: u32,
+    // Taken by every pixel so far, converged pixels stop early with adaptive sampling
+    pub samples_per_pixel: u32,
+    pub elapsed: Duration,
+    // Last estimate, only measured when the termination has a target noise
+    pub noise: Option<f32>,
+    pub estimated_remaining: Option<Duration>,
+}
+
+// Cancels a ProgressiveRenderer from anywhere, including other threads. Takes effect at
+// the start of the next step.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+type ProgressCallback = Box<dyn FnMut(&Progress)>;
+
+// Renders a frame one pass per step, so the caller stays in control between passes.
+// Either drive it with start and step from a UI loop or call run to block until done.
+pub struct ProgressiveRenderer {
+    settings: ProgressiveSettings,
+    state: RenderState,
+    passes: u32,
+    started: Option<Instant>,
+    elapsed: Duration,
+    noise: Option<f32>,
+    cancel: CancelToken,
+    callback: Option<ProgressCallback>,
+}
+
+impl ProgressiveRenderer {
+    pub fn new(settings: ProgressiveSettings) -> Self {
+        Self {
+            settings,
+            state: RenderState::Idle,
+            passes: 0,
+            started: None,
+            elapsed: Duration::ZERO,
+            noise: None,
+            cancel: CancelToken::default(),
+            callback: None,
+        }
+    }
+
+    // Called after every step and when rendering stops
+    pub fn with_progress_callback(mut self, callback: impl FnMut(&Progress) + 'static) -> Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    pub fn settings(&self) -> &ProgressiveSettings {
+        &self.settings
+    }
+
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    pub fn state(&self) -> RenderState {
+        self.state
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == RenderState::Running
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            state: self.state,
+            passes: self.passes,
+            samples_per_pixel: self.passes * self.settings.samples_per_pass,
+            elapsed: self.elapsed,
+            noise: self.noise,
+            estimated_remaining: match self.state {
+                RenderState::Running => self.settings.termination.estimate_remaining(
+                    self.passes,
+                    self.elapsed,
+                    self.noise,
+                ),
+                _ => None,
+            },
+        }
+    }
+
+    // Restarts the accumulation on the next step, also after a previous render ended
+    pub fn start(&mut self) {
+        self.state = RenderState::Running;
+        self.passes = 0;
+        self.started = Some(Instant::now());
+        self.elapsed = Duration::ZERO;
+        self.noise = None;
+        self.cancel.reset();
+    }
+
+    // Renders one pass, or nothing when the renderer isn't running
+    pub fn step(
+        &mut self,
+        ctx: &Ctx,
+        framebuffer: &mut FrameBuffer,
+        frame: &FrameResources,
+    ) -> Progress {
+        if !self.is_running() {
+            return self.progress();
+        }
+        if self.cancel.is_cancelled() {
+            self.finish(ctx, framebuffer, RenderState::Cancelled);
+            return self.progress();
+        }
+
+        ctx.render_pass(
+            framebuffer,
+            frame,
+            self.passes,
+            self.settings.samples_per_pass,
+            &self.settings.render_settings,
+        );
+        ctx.wait();
+        self.passes += 1;
+        if let Some(started) = self.started {
+            self.elapsed = started.elapsed();
+        }
+
+        let termination = &self.settings.termination;
+        if termination.target_noise.is_some()
+            && self
+                .passes
+                .is_multiple_of(self.settings.noise_interval.max(1))
+        {
+            self.noise = framebuffer.estimate_noise();
+        }
+
+        if termination.is_reached(self.passes, self.elapsed, self.noise) {
+            self.finish(ctx, framebuffer, RenderState::Finished);
+        } else {
+            if self.settings.display_interval > 0
+                && self.passes.is_multiple_of(self.settings.display_interval)
+            {
+                ctx.tone_map(framebuffer);
+            }
+            self.notify();
+        }
+        self.progress()
+    }
+
+    // Ends rendering and resolves the samples taken so far into the final image
+    pub fn stop(&mut self, ctx: &Ctx, framebuffer: &mut FrameBuffer) -> Progress {
+        if self.is_running() {
+            self.finish(ctx, framebuffer, RenderState::Cancelled);
+        }
+        self.progress()
+    }
+
+    // Starts and steps until a limit is reached or the render is cancelled
+    pub fn run(
+        &mut self,
+        ctx: &Ctx,
+        framebuffer: &mut FrameBuffer,
+        frame: &FrameResources,
+    ) -> Progress {
+        self.start();
+        while self.is_running() {
+            self.step(ctx, framebuffer, frame);
+        }
+        self.progress()
+    }
+
+    fn finish(&mut self, ctx: &Ctx, framebuffer: &mut FrameBuffer, state: RenderState) {
+        self.state = state;
+        if self.passes > 0 {
+            ctx.tone_map(framebuffer);
+        }
+        self.notify();
+    }
+
+    fn notify(&mut self) {
+        let progress = self.progress();
+        if let Some(callback) = &mut self.callback {
+            callback(&progress);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+// Rectangle of pixels in an image, y pointing down from the top left corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn full(width: u32, height: u32) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    pub fn pixel_count(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    pub fn fits_in(&self, width: u32, height: u32) -> bool {
+        self.x as u64 + self.width as u64 <= width as u64
+            && self.y as u64 + self.height as u64 <= height as u64
+    }
+
+    // Covers the image row by row, tiles on the right and bottom edge are cut off
+    pub fn tiles(width: u32, height: u32, tile_width: u32, tile_height: u32) -> Vec<Region> {
+        assert!(tile_width > 0 && tile_height > 0, "Empty tiles");
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(tile_height as usize) {
+            for x in (0..width).step_by(tile_width as usize) {
+                tiles.push(Self::new(
+                    x,
+                    y,
+                    tile_width.min(width - x),
+                    tile_height.min(height - y),
+                ));
+            }
+        }
+        tiles
+    }
+}
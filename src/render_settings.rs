@@ -0,0 +1,80 @@
+use crate::sampler::SamplerKind;
+
+// Integrator parameters of one render, pushed with every pass. The defaults match the
+// constants the shaders used before they were configurable.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    // Bounces after the camera ray in total and per lobe a path scatters into, 0 leaves
+    // direct light only
+    pub max_depth: u32,
+    pub max_diffuse_depth: u32,
+    pub max_specular_depth: u32,
+    pub max_transmission_depth: u32,
+    // First bounce that Russian roulette can terminate
+    pub russian_roulette_depth: u32,
+    // Caps the luminance of a single sample, biased but removes fireflies
+    pub firefly_clamp: Option<f32>,
+    // Shortest ray distance, new rays start a tenth of it away from the surface they leave
+    pub ray_epsilon: f32,
+    // Longest ray distance, anything further away counts as the sky
+    pub far_distance: f32,
+    // Renders with the same seed are identical, different seeds give independent noise
+    pub seed: u32,
+    pub sampler: SamplerKind,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            // 16 rays, the camera ray and 15 bounces
+            max_depth: 15,
+            max_diffuse_depth: 16,
+            max_specular_depth: 16,
+            max_transmission_depth: 16,
+            russian_roulette_depth: 4,
+            firefly_clamp: None,
+            ray_epsilon: 0.001,
+            far_distance: 10000.0,
+            seed: 0,
+            sampler: SamplerKind::Independent,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_firefly_clamp(mut self, firefly_clamp: f32) -> Self {
+        self.firefly_clamp = Some(firefly_clamp);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    // Second half of the push constants in render_settings.glsl, floats as their bits
+    pub fn push_constants(&self) -> [u32; 10] {
+        [
+            self.max_depth,
+            self.max_diffuse_depth,
+            self.max_specular_depth,
+            self.max_transmission_depth,
+            self.russian_roulette_depth,
+            self.firefly_clamp.unwrap_or(0.0).max(0.0).to_bits(),
+            self.ray_epsilon.to_bits(),
+            self.far_distance.to_bits(),
+            self.seed,
+            self.sampler as u32,
+        ]
+    }
+}
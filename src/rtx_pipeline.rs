@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{path::PathBuf, rc::Rc};
 
 use ash::vk::{
     BufferUsageFlags, DeferredOperationKHR, MemoryPropertyFlags, Pipeline, PipelineCache,
@@ -12,6 +12,22 @@ use vk_utils::{
 
 use crate::{descriptor_sets::RTXDescriptorSets, rtx_extensions::RtxExtensions};
 
+// The precompiled shaders next to the crate root, found relative to target/<profile>/examples
+pub(crate) fn shader_directory() -> PathBuf {
+    std::env::current_exe()
+        .expect("current dir check failed")
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("shaders")
+        .join("simple_pipeline")
+}
+
 pub struct RtxPipeline {
     pub descriptor_sets: RTXDescriptorSets,
     pub pipeline: Pipeline,
@@ -23,18 +39,7 @@ impl RtxPipeline {
     pub fn new(device: Rc<DeviceContext>, rtx: Rc<RtxExtensions>, max_sets: u32) -> Self {
         let descriptor_sets = RTXDescriptorSets::new(device.clone(), max_sets);
 
-        let dir = std::env::current_exe()
-            .expect("current dir check failed")
-            .parent()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .join("shaders")
-            .join("simple_pipeline");
+        let dir = shader_directory();
         unsafe {
             let code = load_spirv(dir.join("ray_gen.rgen.spv").to_str().unwrap());
             let shader_module_info = ShaderModuleCreateInfo::builder().code(&code);
@@ -54,6 +59,12 @@ impl RtxPipeline {
                 .handle()
                 .create_shader_module(&shader_module_info, None)
                 .expect("Ray miss shader compilation failed");
+            let code = load_spirv(dir.join("ray_shadow.rmiss.spv").to_str().unwrap());
+            let shader_module_info = ShaderModuleCreateInfo::builder().code(&code);
+            let shadow_miss = device
+                .handle()
+                .create_shader_module(&shader_module_info, None)
+                .expect("Shadow miss shader compilation failed");
 
             let shader_groups = vec![
                 // group0 = [ raygen ]
@@ -77,21 +88,32 @@ impl RtxPipeline {
                     .closest_hit_shader(SHADER_UNUSED_KHR)
                     .any_hit_shader(SHADER_UNUSED_KHR)
                     .intersection_shader(SHADER_UNUSED_KHR),
+                // group3 = [ shadow miss ]
+                *RayTracingShaderGroupCreateInfoKHR::builder()
+                    .ty(RayTracingShaderGroupTypeKHR::GENERAL)
+                    .general_shader(3)
+                    .closest_hit_shader(SHADER_UNUSED_KHR)
+                    .any_hit_shader(SHADER_UNUSED_KHR)
+                    .intersection_shader(SHADER_UNUSED_KHR),
             ];
 
             let shader_stages = vec![
                 *PipelineShaderStageCreateInfo::builder()
                     .stage(ShaderStageFlags::RAYGEN_KHR)
                     .module(gen)
-                    .name(std::ffi::CStr::from_bytes_with_nul(b"main\0").unwrap()),
+                    .name(c"main"),
                 *PipelineShaderStageCreateInfo::builder()
                     .stage(ShaderStageFlags::CLOSEST_HIT_KHR)
                     .module(chit)
-                    .name(std::ffi::CStr::from_bytes_with_nul(b"main\0").unwrap()),
+                    .name(c"main"),
                 *PipelineShaderStageCreateInfo::builder()
                     .stage(ShaderStageFlags::MISS_KHR)
                     .module(miss)
-                    .name(std::ffi::CStr::from_bytes_with_nul(b"main\0").unwrap()),
+                    .name(c"main"),
+                *PipelineShaderStageCreateInfo::builder()
+                    .stage(ShaderStageFlags::MISS_KHR)
+                    .module(shadow_miss)
+                    .name(c"main"),
             ];
 
             let infos = [*RayTracingPipelineCreateInfoKHR::builder()
@@ -110,7 +132,7 @@ impl RtxPipeline {
                 )
                 .expect("Raytracing pipeline creation failed")[0];
 
-            let group_count = 3;
+            let group_count = 4;
             let properties = rtx.pipeline_properties();
             let aligned_group_size = properties.shader_group_handle_size
                 + (properties.shader_group_base_alignment - properties.shader_group_handle_size);
@@ -145,8 +167,9 @@ impl RtxPipeline {
                 .stride(aligned_group_size.into())
                 .device_address(shader_binding_table.device_address() + aligned_group_size as u64);
 
+            // The regular and the shadow miss shader are adjacent in the table
             let miss_address = *StridedDeviceAddressRegionKHR::builder()
-                .size(aligned_group_size.into())
+                .size(aligned_group_size as u64 * 2)
                 .stride(aligned_group_size.into())
                 .device_address(
                     shader_binding_table.device_address() + aligned_group_size as u64 * 2,
@@ -0,0 +1,168 @@
+// Sample generators of the path tracer, mirrored by sampler.glsl. Tables are built here
+// and uploaded once by the Ctx, the blue noise mask is built by build.rs.
+
+pub use crate::blue_noise::{blue_noise_mask, BLUE_NOISE_SIZE};
+pub use crate::hash::{hash, hash_combine};
+
+// Dimensions with their own Sobol matrix, higher dimensions reuse them with a different
+// scramble
+pub const SOBOL_DIMENSIONS: usize = 16;
+pub const SOBOL_BITS: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum SamplerKind {
+    // Sobol (0, 2) pixel offsets and hashed random numbers for everything else
+    #[default]
+    Independent,
+    // Owen scrambled Sobol, scrambled differently in every pixel
+    Sobol,
+    // Owen scrambled Sobol shared by all pixels and shifted per pixel and dimension by a
+    // blue noise mask, which moves the remaining error to high frequencies
+    BlueNoiseSobol,
+}
+
+// Degree, polynomial coefficients and initial direction numbers of dimensions 1 and up,
+// from new-joe-kuo-6.21201 by Joe and Kuo. Dimension 0 is the van der Corput sequence.
+const SOBOL_POLYNOMIALS: [(u32, u32, &[u32]); SOBOL_DIMENSIONS - 1] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+];
+
+// Generator matrices, one column per index bit with the most significant bit first
+pub fn sobol_matrices() -> Vec<[u32; SOBOL_BITS]> {
+    let mut matrices = Vec::with_capacity(SOBOL_DIMENSIONS);
+    let mut van_der_corput = [0; SOBOL_BITS];
+    for (bit, column) in van_der_corput.iter_mut().enumerate() {
+        *column = 1 << (SOBOL_BITS - 1 - bit);
+    }
+    matrices.push(van_der_corput);
+
+    for (degree, a, m) in SOBOL_POLYNOMIALS {
+        let degree = degree as usize;
+        let mut v = [0u32; SOBOL_BITS];
+        for (bit, m) in m.iter().enumerate() {
+            v[bit] = m << (SOBOL_BITS - 1 - bit);
+        }
+        for bit in degree..SOBOL_BITS {
+            let mut column = v[bit - degree] ^ (v[bit - degree] >> degree);
+            for k in 1..degree {
+                if (a >> (degree - 1 - k)) & 1 == 1 {
+                    column ^= v[bit - k];
+                }
+            }
+            v[bit] = column;
+        }
+        matrices.push(v);
+    }
+    matrices
+}
+
+pub fn sobol(matrix: &[u32; SOBOL_BITS], index: u32) -> u32 {
+    let mut result = 0;
+    let mut index = index;
+    let mut bit = 0;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= matrix[bit];
+        }
+        index >>= 1;
+        bit += 1;
+    }
+    result
+}
+
+// "Practical Hash-based Owen Scrambling", Burley 2020. Flips every bit depending on the
+// bits above it, like a full Owen scramble.
+pub fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x.reverse_bits()
+}
+
+pub fn to_unit_float(x: u32) -> f32 {
+    (x >> 8) as f32 / (1 << 24) as f32
+}
+
+// Ranks of blue_noise_mask(BLUE_NOISE_SIZE, 0) as little endian u16, written by build.rs
+const BLUE_NOISE_RANKS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/blue_noise.bin"));
+
+pub struct SamplerTables {
+    pub sobol_matrices: Vec<[u32; SOBOL_BITS]>,
+    // BLUE_NOISE_SIZE squared values in row major order
+    pub blue_noise: Vec<f32>,
+}
+
+impl Default for SamplerTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SamplerTables {
+    pub fn new() -> Self {
+        Self {
+            sobol_matrices: sobol_matrices(),
+            blue_noise: BLUE_NOISE_RANKS
+                .chunks(2)
+                .map(|rank| {
+                    let rank = u16::from_le_bytes([rank[0], rank[1]]);
+                    (rank as f32 + 0.5) / (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as f32
+                })
+                .collect(),
+        }
+    }
+
+    fn blue_noise_shift(&self, pixel: (u32, u32), dimension: u32) -> f32 {
+        // Every dimension reads the mask at its own toroidal offset
+        let offset = hash(dimension);
+        let x = (pixel.0 + (offset & 0xff)) % BLUE_NOISE_SIZE;
+        let y = (pixel.1 + ((offset >> 8) & 0xff)) % BLUE_NOISE_SIZE;
+        self.blue_noise[(y * BLUE_NOISE_SIZE + x) as usize]
+    }
+
+    // Dimension of a sample as sample_dimension in sampler.glsl computes it. None for the
+    // independent sampler, which hashes a running seed.
+    pub fn sample(
+        &self,
+        kind: SamplerKind,
+        seed: u32,
+        index: u32,
+        pixel: (u32, u32),
+        dimension: u32,
+    ) -> Option<f32> {
+        let scramble = match kind {
+            SamplerKind::Independent => return None,
+            SamplerKind::Sobol => hash(hash_combine(hash_combine(hash(seed), pixel.0), pixel.1)),
+            SamplerKind::BlueNoiseSobol => hash(seed),
+        };
+        let group = dimension / SOBOL_DIMENSIONS as u32;
+        let matrix = &self.sobol_matrices[dimension as usize % SOBOL_DIMENSIONS];
+        let shuffled = owen_scramble(index, hash_combine(scramble, group));
+        let value = owen_scramble(
+            sobol(matrix, shuffled),
+            hash_combine(scramble, dimension + 1),
+        );
+        let u = to_unit_float(value);
+        Some(match kind {
+            SamplerKind::BlueNoiseSobol => (u + self.blue_noise_shift(pixel, dimension)).fract(),
+            _ => u,
+        })
+    }
+}
@@ -0,0 +1,88 @@
+// Walker's alias method, O(1) sampling of a discrete distribution on the GPU
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AliasEntry {
+    // Probability of keeping this entry instead of jumping to the alias
+    pub threshold: f32,
+    pub alias: u32,
+    // Normalized probability of picking this entry
+    pub pdf: f32,
+}
+
+pub struct AliasTable {
+    entries: Vec<AliasEntry>,
+    total: f32,
+}
+
+impl AliasTable {
+    pub fn new(weights: &[f32]) -> Self {
+        let total: f32 = weights.iter().map(|w| w.max(0.0)).sum();
+        let count = weights.len();
+        let mut entries: Vec<AliasEntry> = (0..count)
+            .map(|i| AliasEntry {
+                threshold: 1.0,
+                alias: i as u32,
+                pdf: if total > 0.0 {
+                    weights[i].max(0.0) / total
+                } else {
+                    1.0 / count as f32
+                },
+            })
+            .collect();
+
+        let mut scaled: Vec<f32> = entries.iter().map(|e| e.pdf * count as f32).collect();
+        let mut small = Vec::new();
+        let mut large = Vec::new();
+        for (i, s) in scaled.iter().enumerate() {
+            if *s < 1.0 {
+                small.push(i)
+            } else {
+                large.push(i)
+            }
+        }
+
+        while let (Some(s), Some(l)) = (small.pop(), large.last().copied()) {
+            entries[s].threshold = scaled[s];
+            entries[s].alias = l as u32;
+            scaled[l] -= 1.0 - scaled[s];
+            if scaled[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+
+        // Whatever is left over is 1 up to rounding errors
+        for i in small.into_iter().chain(large) {
+            entries[i].threshold = 1.0;
+            entries[i].alias = i as u32;
+        }
+
+        Self { entries, total }
+    }
+
+    pub fn entries(&self) -> &[AliasEntry] {
+        &self.entries
+    }
+
+    pub fn total_weight(&self) -> f32 {
+        self.total
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // u in [0, 1), same lookup as the shaders
+    pub fn sample(&self, u: f32) -> usize {
+        let scaled = u * self.entries.len() as f32;
+        let index = (scaled as usize).min(self.entries.len() - 1);
+        if scaled - (index as f32) < self.entries[index].threshold {
+            index
+        } else {
+            self.entries[index].alias as usize
+        }
+    }
+}
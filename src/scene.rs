@@ -2,6 +2,7 @@ use crate::{camera::Camera, ctx::Handle, skybox::SkyBox};
 
 pub struct Scene {
     instances: Vec<Handle>,
+    lights: Vec<Handle>,
     camera: Camera,
     skybox: Option<SkyBox>,
 }
@@ -10,6 +11,7 @@ impl Scene {
     pub fn new() -> Self {
         Self {
             instances: Vec::new(),
+            lights: Vec::new(),
             camera: Camera::new(1.13, 0.01, 1000.0),
             skybox: None,
         }
@@ -23,6 +25,14 @@ impl Scene {
         self.instances.push(instance)
     }
 
+    pub fn lights(&self) -> &[Handle] {
+        &self.lights
+    }
+
+    pub fn add_light(&mut self, light: Handle) {
+        self.lights.push(light)
+    }
+
     pub fn set_camera(&mut self, camera: Camera) {
         self.camera = camera
     }
@@ -35,6 +45,10 @@ impl Scene {
         self.skybox
     }
 
+    pub fn skybox_mut(&mut self) -> Option<&mut SkyBox> {
+        self.skybox.as_mut()
+    }
+
     pub fn camera(&self) -> &Camera {
         &self.camera
     }
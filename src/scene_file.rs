@@ -0,0 +1,577 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use cgmath::SquareMatrix;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::ctx::{Ctx, Handle};
+use crate::cubemap::CubeMap;
+use crate::image_resource::TextureImageData;
+use crate::import::invalid_data;
+use crate::light::{Light, LightType};
+use crate::material::{Material, NormalMapConvention, TextureTransform};
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::scene::Scene;
+
+// Bumped whenever a change would make older readers misread a file. Fields added later
+// get defaults, so older files keep loading.
+pub const SCENE_FORMAT_VERSION: u32 = 2;
+
+// Column major, like Mat4
+pub type MatrixDescription = [[f32; 4]; 4];
+
+// A scene as it is stored on disk. Meshes and textures are files, everything else
+// refers to them by index. Relative paths are relative to the scene file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SceneDescription {
+    pub version: u32,
+    pub camera: CameraDescription,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skybox: Option<SkyBoxDescription>,
+    // Native mesh files, see MeshResource::save
+    #[serde(default)]
+    pub meshes: Vec<PathBuf>,
+    #[serde(default)]
+    pub textures: Vec<PathBuf>,
+    #[serde(default)]
+    pub materials: Vec<MaterialDescription>,
+    #[serde(default)]
+    pub instances: Vec<InstanceDescription>,
+    #[serde(default)]
+    pub lights: Vec<LightDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraDescription {
+    pub fov: f32,
+    pub z_near: f32,
+    pub z_far: f32,
+    // Camera to world, like a glTF camera node
+    pub transform: MatrixDescription,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkyBoxLayout {
+    #[default]
+    Equirectangular,
+    // Horizontal or vertical cube map cross, see CubeMap::from_cross
+    Cross,
+}
+
+impl SkyBoxLayout {
+    fn is_equirectangular(&self) -> bool {
+        *self == SkyBoxLayout::Equirectangular
+    }
+}
+
+// Image file, equirectangular unless the layout says otherwise. Version 1 files have no
+// layout.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SkyBoxDescription {
+    pub file: PathBuf,
+    #[serde(default, skip_serializing_if = "SkyBoxLayout::is_equirectangular")]
+    pub layout: SkyBoxLayout,
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+    #[serde(default = "default_background_visible")]
+    pub background_visible: bool,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+fn default_background_visible() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureTransformDescription {
+    pub tex_coord: u32,
+    pub offset: [f32; 2],
+    pub scale: [f32; 2],
+    pub rotation: f32,
+}
+
+impl Default for TextureTransformDescription {
+    fn default() -> Self {
+        Self::from(&TextureTransform::new())
+    }
+}
+
+impl From<&TextureTransform> for TextureTransformDescription {
+    fn from(transform: &TextureTransform) -> Self {
+        Self {
+            tex_coord: transform.tex_coord,
+            offset: transform.offset.into(),
+            scale: transform.scale.into(),
+            rotation: transform.rotation,
+        }
+    }
+}
+
+impl From<&TextureTransformDescription> for TextureTransform {
+    fn from(description: &TextureTransformDescription) -> Self {
+        Self {
+            tex_coord: description.tex_coord,
+            offset: Vec2::from(description.offset),
+            scale: Vec2::from(description.scale),
+            rotation: description.rotation,
+        }
+    }
+}
+
+// Index into SceneDescription::textures and how the slot reads it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TextureSlotDescription {
+    pub texture: usize,
+    #[serde(default)]
+    pub transform: TextureTransformDescription,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialDescription {
+    pub base_color: [f32; 4],
+    pub emission: [f32; 4],
+    pub roughness: f32,
+    pub metallic: f32,
+    pub sheen: f32,
+    pub clear_coat: f32,
+    pub ior: f32,
+    pub transmission: f32,
+    pub normal_scale: f32,
+    pub normal_map_convention: NormalMapConvention,
+    pub occlusion_strength: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_color_texture: Option<TextureSlotDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metallic_roughness_texture: Option<TextureSlotDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal_texture: Option<TextureSlotDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emission_texture: Option<TextureSlotDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occlusion_texture: Option<TextureSlotDescription>,
+}
+
+impl Default for MaterialDescription {
+    fn default() -> Self {
+        Self::new(&Material::new(), |_| {
+            unreachable!("A new material has no textures")
+        })
+    }
+}
+
+impl MaterialDescription {
+    // texture_index maps the texture handles of the material to SceneDescription::textures
+    pub fn new(material: &Material, mut texture_index: impl FnMut(Handle) -> usize) -> Self {
+        let mut slot = |texture: Option<Handle>, transform: &TextureTransform| {
+            texture.map(|texture| TextureSlotDescription {
+                texture: texture_index(texture),
+                transform: transform.into(),
+            })
+        };
+        Self {
+            base_color: material.base_color.into(),
+            emission: material.emission.into(),
+            roughness: material.roughness,
+            metallic: material.metallic,
+            sheen: material.sheen,
+            clear_coat: material.clear_coat,
+            ior: material.ior,
+            transmission: material.transmission,
+            normal_scale: material.normal_scale,
+            normal_map_convention: material.normal_map_convention,
+            occlusion_strength: material.occlusion_strength,
+            base_color_texture: slot(
+                material.base_color_texture,
+                &material.base_color_texture_transform,
+            ),
+            metallic_roughness_texture: slot(
+                material.metallic_roughness_texture,
+                &material.metallic_roughness_texture_transform,
+            ),
+            normal_texture: slot(material.normal_texture, &material.normal_texture_transform),
+            emission_texture: slot(
+                material.emission_texture,
+                &material.emission_texture_transform,
+            ),
+            occlusion_texture: slot(
+                material.occlusion_texture,
+                &material.occlusion_texture_transform,
+            ),
+        }
+    }
+
+    // Textures are the handles of SceneDescription::textures in order
+    pub fn to_material(&self, textures: &[Handle]) -> Material {
+        let texture = |slot: &Option<TextureSlotDescription>| {
+            slot.as_ref().map(|slot| textures[slot.texture])
+        };
+        let transform = |slot: &Option<TextureSlotDescription>| {
+            slot.as_ref()
+                .map(|slot| TextureTransform::from(&slot.transform))
+                .unwrap_or_default()
+        };
+        Material {
+            base_color: Vec4::from(self.base_color),
+            emission: Vec4::from(self.emission),
+            roughness: self.roughness,
+            metallic: self.metallic,
+            sheen: self.sheen,
+            clear_coat: self.clear_coat,
+            ior: self.ior,
+            transmission: self.transmission,
+            normal_scale: self.normal_scale,
+            normal_map_convention: self.normal_map_convention,
+            occlusion_strength: self.occlusion_strength,
+            base_color_texture: texture(&self.base_color_texture),
+            metallic_roughness_texture: texture(&self.metallic_roughness_texture),
+            normal_texture: texture(&self.normal_texture),
+            emission_texture: texture(&self.emission_texture),
+            occlusion_texture: texture(&self.occlusion_texture),
+            base_color_texture_transform: transform(&self.base_color_texture),
+            metallic_roughness_texture_transform: transform(&self.metallic_roughness_texture),
+            normal_texture_transform: transform(&self.normal_texture),
+            emission_texture_transform: transform(&self.emission_texture),
+            occlusion_texture_transform: transform(&self.occlusion_texture),
+        }
+    }
+
+    fn texture_slots(&self) -> impl Iterator<Item = &TextureSlotDescription> {
+        [
+            &self.base_color_texture,
+            &self.metallic_roughness_texture,
+            &self.normal_texture,
+            &self.emission_texture,
+            &self.occlusion_texture,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InstanceDescription {
+    pub mesh: usize,
+    pub material: usize,
+    pub transform: MatrixDescription,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LightDescription {
+    pub light_type: LightType,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub transform: MatrixDescription,
+}
+
+impl SceneDescription {
+    pub fn new(camera: &Camera) -> Self {
+        Self {
+            version: SCENE_FORMAT_VERSION,
+            camera: CameraDescription {
+                fov: camera.fov(),
+                z_near: camera.z_near(),
+                z_far: camera.z_far(),
+                transform: camera.view_matrix().into(),
+            },
+            skybox: None,
+            meshes: Vec::new(),
+            textures: Vec::new(),
+            materials: Vec::new(),
+            instances: Vec::new(),
+            lights: Vec::new(),
+        }
+    }
+
+    // Checks the version and every index, so a loaded description can be used as is
+    pub fn from_json(json: &str) -> std::io::Result<Self> {
+        let description: Self = serde_json::from_str(json)?;
+        description.validate()?;
+        Ok(description)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Scene descriptions always serialize")
+    }
+
+    pub fn validate(&self) -> std::io::Result<()> {
+        if self.version > SCENE_FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "Scene format version {} is newer than the supported {}",
+                self.version, SCENE_FORMAT_VERSION
+            )));
+        }
+        let check = |what: &str, index: usize, count: usize, kind: &str| {
+            if index < count {
+                Ok(())
+            } else {
+                Err(invalid_data(format!(
+                    "{} refers to {} {} but there are only {}",
+                    what, kind, index, count
+                )))
+            }
+        };
+        for (i, material) in self.materials.iter().enumerate() {
+            for slot in material.texture_slots() {
+                check(
+                    &format!("Material {}", i),
+                    slot.texture,
+                    self.textures.len(),
+                    "texture",
+                )?;
+            }
+        }
+        for (i, instance) in self.instances.iter().enumerate() {
+            let what = format!("Instance {}", i);
+            check(&what, instance.mesh, self.meshes.len(), "mesh")?;
+            check(&what, instance.material, self.materials.len(), "material")?;
+        }
+        Ok(())
+    }
+
+    pub fn camera(&self) -> Camera {
+        let mut camera = Camera::new(self.camera.fov, self.camera.z_near, self.camera.z_far);
+        let transform = Mat4::from(self.camera.transform);
+        camera.transform(transform.invert().unwrap_or_else(Mat4::identity));
+        camera
+    }
+}
+
+// Writes the scene and the meshes and textures it uses. Meshes, textures and skyboxes
+// without a source file are written next to the scene, meshes as native mesh files, 8 bit
+// images as PNG, float images as EXR and cube maps as an EXR cross.
+pub fn save_scene(ctx: &Ctx, scene: &Scene, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "scene".to_string());
+
+    let mut description = SceneDescription::new(scene.camera());
+    let mut meshes = HashMap::new();
+    let mut textures = HashMap::new();
+    let mut materials = HashMap::new();
+
+    for handle in scene.instances() {
+        let instance = ctx
+            .instance(*handle)
+            .ok_or_else(|| invalid_input("The scene refers to a removed instance".to_string()))?;
+
+        let mesh = match meshes.get(&instance.mesh()) {
+            Some(index) => *index,
+            None => {
+                let file = match ctx.mesh_source(instance.mesh()) {
+                    Some(source) => relative_to(directory, source),
+                    None => {
+                        let file = PathBuf::from(format!(
+                            "{}_mesh_{}.mesh",
+                            stem,
+                            description.meshes.len()
+                        ));
+                        ctx.mesh_resource(instance.mesh())
+                            .ok_or_else(|| {
+                                invalid_input("An instance refers to a removed mesh".to_string())
+                            })?
+                            .save(directory.join(&file))?;
+                        file
+                    }
+                };
+                description.meshes.push(file);
+                meshes.insert(instance.mesh(), description.meshes.len() - 1);
+                description.meshes.len() - 1
+            }
+        };
+
+        let material = match materials.get(&instance.material()) {
+            Some(index) => *index,
+            None => {
+                let material = ctx.material(instance.material()).ok_or_else(|| {
+                    invalid_input("An instance refers to a removed material".to_string())
+                })?;
+                for texture in [
+                    material.base_color_texture,
+                    material.metallic_roughness_texture,
+                    material.normal_texture,
+                    material.emission_texture,
+                    material.occlusion_texture,
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    if textures.contains_key(&texture) {
+                        continue;
+                    }
+                    let name = format!("{}_texture_{}", stem, description.textures.len());
+                    let file = texture_file(ctx, texture, directory, &name)?;
+                    description.textures.push(file);
+                    textures.insert(texture, description.textures.len() - 1);
+                }
+                let material = MaterialDescription::new(material, |texture| textures[&texture]);
+                description.materials.push(material);
+                materials.insert(instance.material(), description.materials.len() - 1);
+                description.materials.len() - 1
+            }
+        };
+
+        description.instances.push(InstanceDescription {
+            mesh,
+            material,
+            transform: (*instance.transform()).into(),
+        });
+    }
+
+    for handle in scene.lights() {
+        let light = ctx
+            .light(*handle)
+            .ok_or_else(|| invalid_input("The scene refers to a removed light".to_string()))?;
+        description.lights.push(LightDescription {
+            light_type: light.light_type(),
+            color: light.color.into(),
+            intensity: light.intensity,
+            transform: (*light.transform()).into(),
+        });
+    }
+
+    if let Some(skybox) = scene.skybox() {
+        let name = format!("{}_skybox", stem);
+        let (file, layout) = if skybox.is_cube_map() {
+            let cube_map = ctx.cube_map(skybox.gpu_texture_handle).ok_or_else(|| {
+                invalid_input("The skybox refers to a removed cube map".to_string())
+            })?;
+            let file = PathBuf::from(format!("{}.exr", name));
+            cube_map.to_cross().save(directory.join(&file))?;
+            (file, SkyBoxLayout::Cross)
+        } else {
+            let file = texture_file(ctx, skybox.gpu_texture_handle, directory, &name)?;
+            (file, SkyBoxLayout::Equirectangular)
+        };
+        description.skybox = Some(SkyBoxDescription {
+            file,
+            layout,
+            rotation: skybox.rotation.into(),
+            intensity: skybox.intensity,
+            background_visible: skybox.background_visible,
+        });
+    }
+
+    std::fs::write(path, description.to_json())
+}
+
+// Loads every mesh and texture the scene refers to into the Ctx and creates its
+// materials, instances and lights
+pub fn load_scene(ctx: &mut Ctx, path: impl AsRef<Path>) -> std::io::Result<Scene> {
+    let path = path.as_ref();
+    let directory = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    let description = SceneDescription::from_json(&std::fs::read_to_string(path)?)?;
+
+    let meshes = description
+        .meshes
+        .iter()
+        .map(|file| ctx.load_mesh(directory.join(file)))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let textures = description
+        .textures
+        .iter()
+        .map(|file| ctx.load_texture(directory.join(file)))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let materials: Vec<Handle> = description
+        .materials
+        .iter()
+        .map(|material| {
+            let handle = ctx.create_material();
+            if let Some(target) = ctx.material_mut(handle) {
+                *target = material.to_material(&textures);
+            }
+            handle
+        })
+        .collect();
+
+    let mut scene = Scene::new();
+    scene.set_camera(description.camera());
+    for instance in &description.instances {
+        let handle = ctx.create_instance(meshes[instance.mesh]);
+        if let Some(target) = ctx.instance_mut(handle) {
+            target.set_material(materials[instance.material]);
+            target.set_transform(Mat4::from(instance.transform));
+        }
+        scene.add_instance(handle);
+    }
+    for light in &description.lights {
+        let mut target = Light::new(light.light_type, Vec3::from(light.color), light.intensity);
+        target.set_transform(Mat4::from(light.transform));
+        scene.add_light(ctx.create_light(target));
+    }
+    if let Some(skybox) = &description.skybox {
+        let file = directory.join(&skybox.file);
+        let loaded = match skybox.layout {
+            SkyBoxLayout::Equirectangular => ctx.load_skybox(file)?,
+            SkyBoxLayout::Cross => {
+                let cube_map =
+                    CubeMap::from_cross(&TextureImageData::load(&file)?).ok_or_else(|| {
+                        invalid_data(format!(
+                            "{} isn't a 4x3 or 3x4 cube map cross",
+                            file.display()
+                        ))
+                    })?;
+                ctx.create_cube_map_skybox(&cube_map)
+            }
+        }
+        .with_rotation(Vec3::from(skybox.rotation))
+        .with_intensity(skybox.intensity)
+        .with_background_visible(skybox.background_visible);
+        scene.set_skybox(loaded);
+    }
+    Ok(scene)
+}
+
+// The source file of a texture, or a new file next to the scene holding its pixels
+fn texture_file(
+    ctx: &Ctx,
+    texture: Handle,
+    directory: &Path,
+    name: &str,
+) -> std::io::Result<PathBuf> {
+    if let Some(source) = ctx.texture_source(texture) {
+        return Ok(relative_to(directory, source));
+    }
+    let data = ctx
+        .texture_data(texture)
+        .ok_or_else(|| invalid_input("The scene refers to a removed texture".to_string()))?;
+    let extension = if data.is_gamma_encoded() {
+        "png"
+    } else {
+        "exr"
+    };
+    let file = PathBuf::from(format!("{}.{}", name, extension));
+    data.save(directory.join(&file))?;
+    Ok(file)
+}
+
+// Relative to the scene directory when the file is inside it, absolute otherwise
+fn relative_to(directory: &Path, file: &Path) -> PathBuf {
+    let canonical =
+        |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let file = canonical(file);
+    match file.strip_prefix(canonical(directory)) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => file,
+    }
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,148 @@
+use std::f32::consts::PI;
+
+use ash::vk::Format;
+use cgmath::InnerSpace;
+
+use crate::environment::{direction_to_spherical, spherical_to_direction};
+use crate::image_resource::TextureImageData;
+use crate::math::{Vec2, Vec3};
+
+// Analytic daylight model from "A Practical Analytic Model for Daylight",
+// Preetham, Shirley and Smits 1999. The sun disk itself isn't part of the model,
+// pair the sky with a directional light for sharp shadows.
+#[derive(Clone, Copy, Debug)]
+pub struct PreethamSky {
+    // Degrees above the horizon
+    pub sun_elevation: f32,
+    // Degrees, 0 is the center of the image
+    pub sun_azimuth: f32,
+    // 2 is a very clear sky, 10 is hazy
+    pub turbidity: f32,
+    // Fraction of the horizon radiance reflected by the ground below the horizon
+    pub ground_albedo: f32,
+}
+
+impl PreethamSky {
+    pub fn new(sun_elevation: f32, sun_azimuth: f32, turbidity: f32) -> Self {
+        Self {
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+            ground_albedo: 0.3,
+        }
+    }
+
+    // The sun direction in skybox space, using the same mapping as the skybox lookup
+    pub fn from_sun_direction(sun_direction: Vec3, turbidity: f32) -> Self {
+        let st = direction_to_spherical(sun_direction.normalize());
+        Self::new(90.0 - st.y * 180.0, st.x * 360.0 - 180.0, turbidity)
+    }
+
+    // Linear radiance in W/(m²·sr), the same units as the lights. Daylight is bright, a
+    // clear sky zenith is a few W/(m²·sr), so expect to lower the exposure.
+    pub fn radiance(&self, dir: Vec3) -> Vec3 {
+        let st = direction_to_spherical(dir.normalize());
+        let theta = st.y * PI;
+        let phi = st.x * 2.0 * PI;
+        let ground = theta > 0.5 * PI;
+        let theta = theta.min(0.5 * PI - 1e-3);
+
+        let theta_s = (90.0 - self.sun_elevation.clamp(-90.0, 90.0)).to_radians();
+        let theta_s = theta_s.min(0.5 * PI);
+        let phi_s = (self.sun_azimuth + 180.0).to_radians();
+
+        let cos_gamma =
+            theta.cos() * theta_s.cos() + theta.sin() * theta_s.sin() * (phi - phi_s).cos();
+        let gamma = cos_gamma.clamp(-1.0, 1.0).acos();
+
+        let t = self.turbidity.max(1.0);
+        let perez = |[a, b, c, d, e]: [f32; 5], theta: f32, gamma: f32| {
+            (1.0 + a * (b / theta.cos().max(1e-3)).exp())
+                * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+        };
+
+        let coefficients_y = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let coefficients_x = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let coefficients_yy = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        let ts = theta_s;
+        let ts2 = ts * ts;
+        let ts3 = ts2 * ts;
+        let zenith_x = t * t * (0.00166 * ts3 - 0.00375 * ts2 + 0.00209 * ts)
+            + t * (-0.02903 * ts3 + 0.06377 * ts2 - 0.03202 * ts + 0.00394)
+            + (0.11693 * ts3 - 0.21196 * ts2 + 0.06052 * ts + 0.25886);
+        let zenith_y = t * t * (0.00275 * ts3 - 0.00610 * ts2 + 0.00317 * ts)
+            + t * (-0.04214 * ts3 + 0.08970 * ts2 - 0.04153 * ts + 0.00516)
+            + (0.15346 * ts3 - 0.26756 * ts2 + 0.06670 * ts + 0.26688);
+
+        // Zenith luminance in kcd/m², turned into radiance with the 683 lm/W luminous efficacy
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * ts);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_luminance = zenith_luminance.max(0.0) * 1000.0 / 683.0;
+
+        let ratio = |coefficients| perez(coefficients, theta, gamma) / perez(coefficients, 0.0, ts);
+        let luminance = zenith_luminance * ratio(coefficients_y);
+        let x = zenith_x * ratio(coefficients_x);
+        let y = zenith_y * ratio(coefficients_yy);
+
+        // xyY to linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        let rgb = Vec3::new(
+            3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+        );
+        let rgb = Vec3::new(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0));
+
+        // The model is only defined down to the sun reaching the horizon
+        let night = if self.sun_elevation < 0.0 {
+            (1.0 + self.sun_elevation / 6.0).max(0.0)
+        } else {
+            1.0
+        };
+
+        if ground {
+            rgb * self.ground_albedo * night
+        } else {
+            rgb * night
+        }
+    }
+
+    // Equirectangular image in the layout expected by Ctx::create_skybox
+    pub fn to_image(&self, width: u32, height: u32) -> TextureImageData {
+        let mut pixels = Vec::with_capacity((width * height * 16) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let st = Vec2::new(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                );
+                let c = self.radiance(spherical_to_direction(st));
+                for v in [c.x, c.y, c.z, 1.0] {
+                    pixels.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+
+        TextureImageData::new(Format::R32G32B32A32_SFLOAT, width, height, &pixels)
+    }
+}
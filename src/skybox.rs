@@ -1,5 +1,85 @@
 use crate::ctx::Handle;
+use crate::cubemap::FACE_COUNT;
+use crate::image_resource::TextureImageData;
+use crate::math::{Mat4, Vec3};
+
+pub enum SkyBoxImage<'a> {
+    Equirectangular(&'a TextureImageData),
+    // Ordered +X, -X, +Y, -Y, +Z, -Z
+    CubeFaces([&'a TextureImageData; FACE_COUNT]),
+    // Horizontal or vertical cross, see CubeMap::from_cross
+    Cross(&'a TextureImageData),
+}
+
+impl<'a> From<&'a TextureImageData> for SkyBoxImage<'a> {
+    fn from(image: &'a TextureImageData) -> Self {
+        SkyBoxImage::Equirectangular(image)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct SkyBox {
     pub gpu_texture_handle: Handle,
+    // Euler angles in degrees, applied like MeshInstance::rotate
+    pub rotation: Vec3,
+    // Multiplies the radiance seen by both the camera and the lighting
+    pub intensity: f32,
+    // When false camera rays show black, the skybox still lights the scene
+    pub background_visible: bool,
+    // The handle refers to a cube texture instead of an equirectangular one
+    cube_map: bool,
+}
+
+impl SkyBox {
+    pub fn new(gpu_texture_handle: Handle) -> Self {
+        Self {
+            gpu_texture_handle,
+            rotation: Vec3::new(0.0, 0.0, 0.0),
+            intensity: 1.0,
+            background_visible: true,
+            cube_map: false,
+        }
+    }
+
+    pub(crate) fn new_cube_map(gpu_texture_handle: Handle) -> Self {
+        Self {
+            cube_map: true,
+            ..Self::new(gpu_texture_handle)
+        }
+    }
+
+    pub fn is_cube_map(&self) -> bool {
+        self.cube_map
+    }
+
+    pub fn with_rotation(mut self, rotation: Vec3) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_background_visible(mut self, visible: bool) -> Self {
+        self.background_visible = visible;
+        self
+    }
+
+    // Skybox to world space
+    pub fn rotation_matrix(&self) -> Mat4 {
+        Mat4::from_angle_x(cgmath::Deg(self.rotation.x))
+            * Mat4::from_angle_y(cgmath::Deg(self.rotation.y))
+            * Mat4::from_angle_z(cgmath::Deg(self.rotation.z))
+    }
+}
+
+#[repr(C)]
+pub struct GpuSkyBox {
+    pub _rotation: Mat4,
+    pub _intensity: f32,
+    pub _background_visible: u32,
+    pub _gamma_encoded: u32,
+    pub _cube_map: u32,
 }
@@ -0,0 +1,153 @@
+use cgmath::Matrix3;
+
+use crate::math::Vec3;
+
+// Curves applied by the tone mapping pass, mirrored in tonemap.comp. All of them map
+// scene linear radiance to display linear values in [0, 1] before the sRGB encoding.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum ToneMapOperator {
+    // Clamps to [0, 1]
+    Linear,
+    // x / (1 + x) per channel
+    #[default]
+    Reinhard,
+    // Narkowicz's fit of the ACES reference rendering transform
+    AcesFilmic,
+    // Sobotka's AgX with the default look
+    AgX,
+    // Hable's filmic curve from Uncharted 2
+    Hable,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    // In stops, the radiance is scaled by 2^exposure before the curve
+    pub exposure: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GpuToneMapping {
+    pub _operator: u32,
+    pub _exposure: f32,
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator) -> Self {
+        Self {
+            operator,
+            exposure: 0.0,
+        }
+    }
+
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    // Scene linear radiance to display linear values
+    pub fn tone_map(&self, radiance: Vec3) -> Vec3 {
+        let x = radiance * 2.0f32.powf(self.exposure);
+        let x = Vec3::new(x.x.max(0.0), x.y.max(0.0), x.z.max(0.0));
+        let mapped = match self.operator {
+            ToneMapOperator::Linear => x,
+            ToneMapOperator::Reinhard => x.map(reinhard),
+            ToneMapOperator::AcesFilmic => x.map(aces_filmic),
+            ToneMapOperator::AgX => agx(x),
+            ToneMapOperator::Hable => x.map(hable),
+        };
+        mapped.map(|c| c.clamp(0.0, 1.0))
+    }
+
+    // Scene linear radiance to sRGB encoded values, what ends up in the output image
+    pub fn apply(&self, radiance: Vec3) -> Vec3 {
+        self.tone_map(radiance).map(srgb_oetf)
+    }
+
+    pub fn to_gpu(&self) -> GpuToneMapping {
+        GpuToneMapping {
+            _operator: self.operator as u32,
+            _exposure: self.exposure,
+        }
+    }
+}
+
+pub fn srgb_oetf(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_eotf(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn reinhard(x: f32) -> f32 {
+    x / (1.0 + x)
+}
+
+fn aces_filmic(x: f32) -> f32 {
+    // The fit expects the exposure of the ACES reference, which is 0.6 times brighter
+    let x = x * 0.6;
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+fn hable_partial(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn hable(x: f32) -> f32 {
+    let exposure_bias = 2.0;
+    let white = 11.2;
+    hable_partial(x * exposure_bias) / hable_partial(white)
+}
+
+fn agx_contrast(x: f32) -> f32 {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232
+}
+
+fn agx(x: Vec3) -> Vec3 {
+    let min_ev = -12.47393f32;
+    let max_ev = 4.026069f32;
+    let inset = Matrix3::new(
+        0.84247905,
+        0.042328242,
+        0.042375654,
+        0.0784336,
+        0.87846863,
+        0.0784336,
+        0.079223745,
+        0.07916613,
+        0.879143,
+    );
+    let outset = Matrix3::new(
+        1.196879,
+        -0.052896854,
+        -0.052971635,
+        -0.09802088,
+        1.1519032,
+        -0.09804345,
+        -0.09902974,
+        -0.098961174,
+        1.1510737,
+    );
+
+    let encoded = (inset * x).map(|c| {
+        let ev = c.max(f32::MIN_POSITIVE).log2().clamp(min_ev, max_ev);
+        agx_contrast((ev - min_ev) / (max_ev - min_ev))
+    });
+    // The curve produces display encoded values, decode them so every operator ends
+    // with the same sRGB encoding
+    (outset * encoded).map(|c| c.max(0.0).powf(2.2))
+}
@@ -0,0 +1,230 @@
+use std::rc::Rc;
+
+use ash::vk::{
+    AccessFlags, ComputePipelineCreateInfo, DependencyFlags, DescriptorImageInfo, DescriptorPool,
+    DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo,
+    DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType,
+    ImageLayout, ImageView, MemoryBarrier, Pipeline, PipelineBindPoint, PipelineCache,
+    PipelineLayout, PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags,
+    PushConstantRange, ShaderModuleCreateInfo, ShaderStageFlags, WriteDescriptorSet,
+};
+use vk_utils::{
+    command_buffer::CommandBuffer, device_context::DeviceContext, shader_library::load_spirv,
+};
+
+use crate::{
+    framebuffer::FrameBuffer,
+    rtx_pipeline::shader_directory,
+    tonemap::{GpuToneMapping, ToneMapping},
+};
+
+pub const TONE_MAP_ACCUMULATION_IMAGE_LOCATION: (u32, u32) = (0, 0);
+pub const TONE_MAP_OUTPUT_IMAGE_LOCATION: (u32, u32) = (0, 1);
+
+const WORKGROUP_SIZE: u32 = 16;
+
+// Compute pass that resolves the accumulation image into the RGBA8 output image
+pub struct ToneMapPipeline {
+    device: Rc<DeviceContext>,
+    descriptor_set_layout: DescriptorSetLayout,
+    descriptor_pool: DescriptorPool,
+    // Shared by every dispatch, only update it when the previous one has finished
+    descriptor_set: DescriptorSet,
+    pipeline_layout: PipelineLayout,
+    pipeline: Pipeline,
+}
+
+impl ToneMapPipeline {
+    pub fn new(device: Rc<DeviceContext>) -> Self {
+        unsafe {
+            let bindings = [
+                *DescriptorSetLayoutBinding::builder()
+                    .descriptor_count(1)
+                    .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                    .stage_flags(ShaderStageFlags::COMPUTE)
+                    .binding(TONE_MAP_ACCUMULATION_IMAGE_LOCATION.1),
+                *DescriptorSetLayoutBinding::builder()
+                    .descriptor_count(1)
+                    .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                    .stage_flags(ShaderStageFlags::COMPUTE)
+                    .binding(TONE_MAP_OUTPUT_IMAGE_LOCATION.1),
+            ];
+            let descriptor_set_layout = device
+                .handle()
+                .create_descriptor_set_layout(
+                    &DescriptorSetLayoutCreateInfo::builder().bindings(&bindings),
+                    None,
+                )
+                .expect("Descriptor set layout creation failed");
+
+            let constant_ranges = [*PushConstantRange::builder()
+                .stage_flags(ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(std::mem::size_of::<GpuToneMapping>() as u32)];
+            let set_layouts = [descriptor_set_layout];
+            let pipeline_layout = device
+                .handle()
+                .create_pipeline_layout(
+                    &PipelineLayoutCreateInfo::builder()
+                        .set_layouts(&set_layouts)
+                        .push_constant_ranges(&constant_ranges),
+                    None,
+                )
+                .expect("Pipeline layout creation failed");
+
+            let sizes = [DescriptorPoolSize {
+                ty: DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 2,
+            }];
+            let descriptor_pool = device
+                .handle()
+                .create_descriptor_pool(
+                    &DescriptorPoolCreateInfo::builder()
+                        .pool_sizes(&sizes)
+                        .max_sets(1),
+                    None,
+                )
+                .expect("Descriptor pool creation failed");
+            let descriptor_set = device
+                .handle()
+                .allocate_descriptor_sets(
+                    &DescriptorSetAllocateInfo::builder()
+                        .descriptor_pool(descriptor_pool)
+                        .set_layouts(&set_layouts),
+                )
+                .expect("Descriptor set allocation failed")[0];
+
+            let code = load_spirv(
+                shader_directory()
+                    .join("tonemap.comp.spv")
+                    .to_str()
+                    .unwrap(),
+            );
+            let shader_module_info = ShaderModuleCreateInfo::builder().code(&code);
+            let module = device
+                .handle()
+                .create_shader_module(&shader_module_info, None)
+                .expect("Tone map shader compilation failed");
+            let stage = *PipelineShaderStageCreateInfo::builder()
+                .stage(ShaderStageFlags::COMPUTE)
+                .module(module)
+                .name(c"main");
+            let infos = [*ComputePipelineCreateInfo::builder()
+                .stage(stage)
+                .layout(pipeline_layout)];
+            let pipeline = device
+                .handle()
+                .create_compute_pipelines(PipelineCache::null(), &infos, None)
+                .expect("Tone map pipeline creation failed")[0];
+            device.handle().destroy_shader_module(module, None);
+
+            Self {
+                device,
+                descriptor_set_layout,
+                descriptor_pool,
+                descriptor_set,
+                pipeline_layout,
+                pipeline,
+            }
+        }
+    }
+
+    // Source holds summed radiance and the sample count, like the accumulation image.
+    // Source and the final image have to be in the GENERAL layout.
+    pub fn record(
+        &self,
+        command_buffer: &mut CommandBuffer,
+        source: ImageView,
+        framebuffer: &FrameBuffer,
+        tone_mapping: &ToneMapping,
+    ) {
+        let accumulation_image_info = [*DescriptorImageInfo::builder()
+            .image_view(source)
+            .image_layout(ImageLayout::GENERAL)];
+        let output_image_info = [*DescriptorImageInfo::builder()
+            .image_view(framebuffer.final_image_view)
+            .image_layout(ImageLayout::GENERAL)];
+        let writes = [
+            *WriteDescriptorSet::builder()
+                .image_info(&accumulation_image_info)
+                .dst_set(self.descriptor_set)
+                .dst_binding(TONE_MAP_ACCUMULATION_IMAGE_LOCATION.1)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE),
+            *WriteDescriptorSet::builder()
+                .image_info(&output_image_info)
+                .dst_set(self.descriptor_set)
+                .dst_binding(TONE_MAP_OUTPUT_IMAGE_LOCATION.1)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE),
+        ];
+
+        let gpu = tone_mapping.to_gpu();
+        let constants: Vec<u8> = [gpu._operator.to_le_bytes(), gpu._exposure.to_le_bytes()]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+
+        unsafe {
+            self.device.handle().update_descriptor_sets(&writes, &[]);
+            command_buffer.record_handle(|handle| {
+                // Wait for the ray tracing or denoise passes that wrote the source
+                let barriers = [*MemoryBarrier::builder()
+                    .src_access_mask(AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(AccessFlags::SHADER_READ)];
+                self.device.handle().cmd_pipeline_barrier(
+                    handle,
+                    PipelineStageFlags::RAY_TRACING_SHADER_KHR | PipelineStageFlags::COMPUTE_SHADER,
+                    PipelineStageFlags::COMPUTE_SHADER,
+                    DependencyFlags::empty(),
+                    &barriers,
+                    &[],
+                    &[],
+                );
+                self.device.handle().cmd_bind_pipeline(
+                    handle,
+                    PipelineBindPoint::COMPUTE,
+                    self.pipeline,
+                );
+                self.device.handle().cmd_bind_descriptor_sets(
+                    handle,
+                    PipelineBindPoint::COMPUTE,
+                    self.pipeline_layout,
+                    0,
+                    &[self.descriptor_set],
+                    &[],
+                );
+                self.device.handle().cmd_push_constants(
+                    handle,
+                    self.pipeline_layout,
+                    ShaderStageFlags::COMPUTE,
+                    0,
+                    &constants,
+                );
+                self.device.handle().cmd_dispatch(
+                    handle,
+                    framebuffer.width.div_ceil(WORKGROUP_SIZE),
+                    framebuffer.height.div_ceil(WORKGROUP_SIZE),
+                    1,
+                );
+                handle
+            });
+        }
+    }
+}
+
+impl Drop for ToneMapPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle().destroy_pipeline(self.pipeline, None);
+            self.device
+                .handle()
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .handle()
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .handle()
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use renderer::adaptive::{standard_error, AdaptiveSampling, SampleCountStats};
+use renderer::math::Vec3;
+
+// Running sums as the ray generation shader keeps them for a grey pixel
+fn accumulate(samples: &[f32]) -> (Vec3, f32, u32) {
+    let sum = samples.iter().sum::<f32>();
+    let sum_squares = samples.iter().map(|s| s * s).sum::<f32>();
+    (Vec3::new(sum, sum, sum), sum_squares, samples.len() as u32)
+}
+
+#[test]
+fn constant_pixels_converge_after_the_minimum() {
+    let adaptive = AdaptiveSampling::new(0.01).with_min_samples(8);
+    let (sum, squares, count) = accumulate(&[0.5; 7]);
+    assert!(!adaptive.is_converged(sum, squares, count));
+    let (sum, squares, count) = accumulate(&[0.5; 8]);
+    assert!(adaptive.is_converged(sum, squares, count));
+}
+
+#[test]
+fn noisy_pixels_keep_sampling() {
+    let adaptive = AdaptiveSampling::new(0.05).with_min_samples(2);
+    // Alternating 0 and 1, a relative error of 1 / sqrt(n)
+    let samples: Vec<f32> = (0..64).map(|i| (i % 2) as f32).collect();
+    let (sum, squares, count) = accumulate(&samples);
+    assert!(!adaptive.is_converged(sum, squares, count));
+
+    let samples: Vec<f32> = (0..1024).map(|i| (i % 2) as f32).collect();
+    let (sum, squares, count) = accumulate(&samples);
+    assert!(adaptive.is_converged(sum, squares, count));
+}
+
+#[test]
+fn standard_error_matches_the_sample_variance() {
+    let samples = [1.0f32, 2.0, 3.0, 4.0];
+    let (sum, squares, count) = accumulate(&samples);
+    let mean = sum.x / count as f32;
+    // Unbiased variance of 1..4 is 5 / 3
+    let expected = (5.0f32 / 3.0 / 4.0).sqrt();
+    assert!((standard_error(mean, squares, count) - expected).abs() < 1e-5);
+}
+
+#[test]
+fn disabled_never_converges() {
+    let (sum, squares, count) = accumulate(&[0.5; 1000]);
+    assert!(!AdaptiveSampling::disabled().is_converged(sum, squares, count));
+}
+
+#[test]
+fn sample_count_stats() {
+    let stats = SampleCountStats::new(&[16, 64, 16, 32]);
+    assert_eq!(stats.min, 16);
+    assert_eq!(stats.max, 64);
+    assert_eq!(stats.total, 128);
+    assert_eq!(stats.average, 32.0);
+    assert_eq!(SampleCountStats::new(&[]).total, 0);
+}
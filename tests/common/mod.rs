@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+// A fresh directory per test, the tests run in parallel. Named after the test binary so
+// the binaries can't clash either.
+pub fn scratch_directory(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("renderer-{}-{}", env!("CARGO_CRATE_NAME"), name));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
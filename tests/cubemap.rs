@@ -0,0 +1,146 @@
+mod common;
+
+use common::scratch_directory;
+use renderer::cubemap::{CubeFace, CubeMap};
+use renderer::image_resource::TextureImageData;
+use renderer::math::{Vec3, Vec4};
+use renderer::vk::Format;
+
+fn float_image(width: u32, height: u32, color: impl Fn(u32, u32) -> Vec4) -> TextureImageData {
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let c = color(x, y);
+            for v in [c.x, c.y, c.z, c.w] {
+                pixels.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+    TextureImageData::new(Format::R32G32B32A32_SFLOAT, width, height, &pixels)
+}
+
+#[test]
+fn face_directions_round_trip() {
+    for face in CubeFace::ALL {
+        for (s, t) in [(0.5, 0.5), (0.1, 0.2), (0.9, 0.7), (0.3, 0.95)] {
+            let (found, st) = CubeFace::from_direction(face.direction(s, t));
+            assert_eq!(found, face);
+            assert!((st.x - s).abs() < 1e-5 && (st.y - t).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn face_centers_point_along_their_axis() {
+    let axes = [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(-1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+    ];
+    for (face, axis) in CubeFace::ALL.iter().zip(axes) {
+        let d = face.direction(0.5, 0.5);
+        assert!(
+            (d - axis).x.abs() < 1e-6 && (d - axis).y.abs() < 1e-6 && (d - axis).z.abs() < 1e-6
+        );
+    }
+}
+
+#[test]
+fn crosses_are_split_into_faces() {
+    // Every face is filled with its own index
+    let size = 4;
+    let horizontal_layout = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+    let horizontal = float_image(size * 4, size * 3, |x, y| {
+        let cell = (x / size, y / size);
+        let face = horizontal_layout.iter().position(|c| *c == cell);
+        let v = face.map_or(-1.0, |f| f as f32);
+        Vec4::new(v, v, v, 1.0)
+    });
+    let vertical_layout = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)];
+    let vertical = float_image(size * 3, size * 4, |x, y| {
+        let cell = (x / size, y / size);
+        let face = vertical_layout.iter().position(|c| *c == cell);
+        let v = face.map_or(-1.0, |f| f as f32);
+        // -Z is stored upside down in the vertical cross, mark its last row
+        let marker = if cell == (1, 3) && y % size == size - 1 {
+            1.0
+        } else {
+            0.0
+        };
+        Vec4::new(v, marker, v, 1.0)
+    });
+
+    let horizontal = CubeMap::from_cross(&horizontal).unwrap();
+    let vertical = CubeMap::from_cross(&vertical).unwrap();
+    assert_eq!(horizontal.size(), size);
+    assert_eq!(vertical.size(), size);
+    for (index, face) in CubeFace::ALL.iter().enumerate() {
+        assert!(horizontal.face(*face).iter().all(|c| c.x == index as f32));
+        assert!(vertical.face(*face).iter().all(|c| c.x == index as f32));
+    }
+
+    // The last row of the -Z cell ends up as the first row of the face
+    let negative_z = vertical.face(CubeFace::NegativeZ);
+    assert!(negative_z[..size as usize].iter().all(|c| c.y == 1.0));
+    assert!(negative_z[size as usize..].iter().all(|c| c.y == 0.0));
+}
+
+#[test]
+fn crosses_round_trip_through_exr() {
+    let faces: Vec<TextureImageData> = (0..6)
+        .map(|f| {
+            float_image(4, 4, |x, y| {
+                Vec4::new(f as f32, x as f32 * 0.25, y as f32 * 2.5, 1.0)
+            })
+        })
+        .collect();
+    let cube = CubeMap::from_faces(std::array::from_fn(|f| &faces[f])).unwrap();
+    let cross = cube.to_cross();
+    assert_eq!((cross.width, cross.height), (16, 12));
+
+    let file = scratch_directory("cross").join("cross.exr");
+    cross.save(&file).unwrap();
+    let loaded = CubeMap::from_cross(&TextureImageData::load(&file).unwrap()).unwrap();
+    for face in CubeFace::ALL {
+        assert_eq!(loaded.face(face), cube.face(face));
+    }
+}
+
+#[test]
+fn crosses_with_other_aspect_ratios_are_rejected() {
+    let image = float_image(8, 8, |_, _| Vec4::new(1.0, 1.0, 1.0, 1.0));
+    assert!(CubeMap::from_cross(&image).is_none());
+}
+
+#[test]
+fn equirect_round_trip_preserves_smooth_content() {
+    let (width, height) = (64, 32);
+    // A gradient from top to bottom, smooth everywhere on the sphere
+    let equirect = float_image(width, height, |_, y| {
+        let t = (y as f32 + 0.5) / height as f32;
+        Vec4::new(t, 1.0 - t, 0.5, 1.0)
+    });
+
+    let cube = CubeMap::from_equirect(&equirect, 32).unwrap();
+    let back = cube.to_equirect(width, height);
+    for y in 2..height - 2 {
+        for x in 0..width {
+            let a = equirect.texel(x, y).unwrap();
+            let b = back.texel(x, y).unwrap();
+            assert!((a.x - b.x).abs() < 0.05, "{} {} {:?} {:?}", x, y, a, b);
+            assert!((a.y - b.y).abs() < 0.05, "{} {} {:?} {:?}", x, y, a, b);
+        }
+    }
+}
+
+#[test]
+fn gamma_encoded_faces_are_linearized() {
+    let face = TextureImageData::new(Format::R8G8B8A8_UNORM, 2, 2, &[128; 16]);
+    let cube = CubeMap::from_faces([&face, &face, &face, &face, &face, &face]).unwrap();
+    let expected = (128.0f32 / 255.0).powf(2.2);
+    let c = cube.sample(Vec3::new(0.3, -1.0, 0.2));
+    assert!((c.x - expected).abs() < 1e-4);
+}
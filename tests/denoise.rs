@@ -0,0 +1,148 @@
+use renderer::denoise::{ATrousDenoiser, DenoiseInput, Denoiser};
+use renderer::hdr_image::HdrImage;
+use renderer::math::Vec3;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 32;
+
+// Deterministic noise in [-1, 1]
+fn noise(x: u32, y: u32) -> f32 {
+    let mut h = x.wrapping_mul(374761393) ^ y.wrapping_mul(668265263);
+    h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+    (h ^ (h >> 16)) as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+fn image(color: impl Fn(u32, u32) -> Vec3) -> HdrImage {
+    let mut pixels = Vec::new();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            pixels.push(color(x, y));
+        }
+    }
+    HdrImage::new(WIDTH, HEIGHT, pixels)
+}
+
+fn grey(v: f32) -> Vec3 {
+    Vec3::new(v, v, v)
+}
+
+fn variance(values: impl Iterator<Item = f32> + Clone) -> f32 {
+    let count = values.clone().count() as f32;
+    let mean = values.clone().sum::<f32>() / count;
+    values.map(|v| (v - mean) * (v - mean)).sum::<f32>() / count
+}
+
+#[test]
+fn constant_images_are_unchanged() {
+    let color = image(|_, _| Vec3::new(0.2, 0.5, 3.0));
+    let result = ATrousDenoiser::default().denoise(&DenoiseInput::new(&color));
+    for p in &result.pixels {
+        assert!((p.x - 0.2).abs() < 1e-5 && (p.y - 0.5).abs() < 1e-5 && (p.z - 3.0).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn noise_is_reduced() {
+    let color = image(|x, y| grey(0.5 + 0.2 * noise(x, y)));
+    let result = ATrousDenoiser::default().denoise(&DenoiseInput::new(&color));
+
+    let before = variance(color.pixels.iter().map(|p| p.x));
+    let after = variance(result.pixels.iter().map(|p| p.x));
+    assert!(after < before / 10.0, "{} {}", before, after);
+    let mean = result.pixels.iter().map(|p| p.x).sum::<f32>() / result.pixels.len() as f32;
+    assert!((mean - 0.5).abs() < 0.02, "{}", mean);
+}
+
+#[test]
+fn normal_edges_are_preserved() {
+    // A dark and a bright wall meeting at a right angle, both noisy
+    let color = image(|x, y| {
+        let base = if x < WIDTH / 2 { 0.1 } else { 0.4 };
+        grey(base + 0.05 * noise(x, y))
+    });
+    let normals: Vec<Vec3> = (0..HEIGHT)
+        .flat_map(|_| {
+            (0..WIDTH).map(|x| {
+                if x < WIDTH / 2 {
+                    Vec3::new(1.0, 0.0, 0.0)
+                } else {
+                    Vec3::new(0.0, 1.0, 0.0)
+                }
+            })
+        })
+        .collect();
+
+    let denoiser = ATrousDenoiser {
+        color_sigma: 10.0,
+        ..Default::default()
+    };
+    let guided = denoiser.denoise(&DenoiseInput::new(&color).with_normal(&normals));
+    let unguided = denoiser.denoise(&DenoiseInput::new(&color));
+
+    let y = HEIGHT / 2;
+    let dark = guided.pixel(WIDTH / 2 - 1, y).x;
+    let bright = guided.pixel(WIDTH / 2, y).x;
+    assert!((dark - 0.1).abs() < 0.03, "{}", dark);
+    assert!((bright - 0.4).abs() < 0.03, "{}", bright);
+    // Without the guide the wide color sigma blurs across the edge
+    assert!(unguided.pixel(WIDTH / 2 - 1, y).x > 0.15);
+}
+
+#[test]
+fn albedo_detail_survives() {
+    // Constant lighting on a checkerboard texture, with noisy lighting
+    let albedo: Vec<Vec3> = (0..HEIGHT)
+        .flat_map(|y| {
+            (0..WIDTH).map(move |x| {
+                if (x + y) % 2 == 0 {
+                    grey(0.8)
+                } else {
+                    grey(0.2)
+                }
+            })
+        })
+        .collect();
+    let color = image(|x, y| albedo[(y * WIDTH + x) as usize] * (1.0 + 0.3 * noise(x, y)));
+    let result = ATrousDenoiser::default().denoise(&DenoiseInput::new(&color).with_albedo(&albedo));
+
+    // Both checker colors keep their level, only the lighting noise goes away
+    for level in [0.8, 0.2] {
+        let cells = || {
+            color
+                .pixels
+                .iter()
+                .zip(&result.pixels)
+                .zip(&albedo)
+                .filter(move |(_, a)| a.x == level)
+        };
+        let mean = cells().map(|((_, r), _)| r.x).sum::<f32>() / cells().count() as f32;
+        assert!((mean - level).abs() < 0.02 * level, "{} {}", level, mean);
+        let before = variance(cells().map(|((c, _), _)| c.x));
+        let after = variance(cells().map(|((_, r), _)| r.x));
+        assert!(after < before / 10.0, "{} {} {}", level, before, after);
+    }
+}
+
+#[test]
+fn external_denoisers_plug_in_through_the_trait() {
+    struct Clamp;
+    impl Denoiser for Clamp {
+        fn denoise(&self, input: &DenoiseInput) -> HdrImage {
+            let pixels = input
+                .color
+                .pixels
+                .iter()
+                .map(|p| grey(p.x.min(1.0)))
+                .collect();
+            HdrImage::new(input.color.width, input.color.height, pixels)
+        }
+    }
+
+    let color = image(|x, _| grey(x as f32));
+    let denoisers: [&dyn Denoiser; 2] = [&Clamp, &ATrousDenoiser::default().with_iterations(0)];
+    let clamped = denoisers[0].denoise(&DenoiseInput::new(&color));
+    assert!(clamped.pixels.iter().all(|p| p.x <= 1.0));
+    // No iterations leaves the image untouched
+    let untouched = denoisers[1].denoise(&DenoiseInput::new(&color));
+    assert_eq!(untouched.pixels, color.pixels);
+}
@@ -0,0 +1,92 @@
+use cgmath::{vec3, vec4, SquareMatrix};
+use renderer::ctx::Handle;
+use renderer::emissive::{emissive_weight, luminance, EmissiveTriangles};
+use renderer::material::Material;
+use renderer::math::Mat4;
+use renderer::mesh_resource::MeshResource;
+
+// A unit square in the XY plane as two triangles
+fn square() -> MeshResource {
+    MeshResource::new(
+        vec![0, 1, 2, 0, 2, 3],
+        vec![
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ],
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+    )
+}
+
+fn emitter(r: f32, g: f32, b: f32) -> Material {
+    let mut material = Material::new();
+    material.emission = vec4(r, g, b, 1.0);
+    material
+}
+
+#[test]
+fn only_emissive_materials_add_triangles() {
+    let mut triangles = EmissiveTriangles::new();
+    let offset = triangles.add_instance(0, &Mat4::identity(), &square(), &Material::new());
+    assert_eq!(offset, None);
+    assert!(triangles.is_empty());
+    assert!(triangles.build().is_empty());
+
+    assert_eq!(emissive_weight(&Material::new()), 0.0);
+    assert_eq!(
+        emissive_weight(&emitter(1.0, 2.0, 3.0)),
+        luminance(vec3(1.0, 2.0, 3.0))
+    );
+
+    // Emission textures scale the factor, without one they stay dark
+    let mut textured = emitter(1.0, 2.0, 3.0);
+    textured.emission_texture = Some(Handle::default());
+    assert_eq!(emissive_weight(&textured), luminance(vec3(1.0, 2.0, 3.0)));
+    textured.emission = Material::new().emission;
+    assert_eq!(emissive_weight(&textured), 0.0);
+    assert_eq!(
+        EmissiveTriangles::new().add_instance(0, &Mat4::identity(), &square(), &textured),
+        None
+    );
+}
+
+#[test]
+fn triangles_are_picked_by_power() {
+    let mut triangles = EmissiveTriangles::new();
+    let white = emitter(1.0, 1.0, 1.0);
+    let scaled = Mat4::from_translation(vec3(0.0, 0.0, 5.0)) * Mat4::from_scale(2.0);
+    // Not emissive, doesn't take an offset
+    assert_eq!(
+        triangles.add_instance(0, &Mat4::identity(), &square(), &Material::new()),
+        None
+    );
+    assert_eq!(
+        triangles.add_instance(1, &Mat4::identity(), &square(), &white),
+        Some(0)
+    );
+    // Four times the area and twice the radiance
+    assert_eq!(
+        triangles.add_instance(2, &scaled, &square(), &emitter(2.0, 2.0, 2.0)),
+        Some(2)
+    );
+    assert_eq!(triangles.len(), 4);
+
+    let built = triangles.build();
+    let instances: Vec<(u32, u32)> = built.iter().map(|t| (t._instance, t._primitive)).collect();
+    assert_eq!(instances, vec![(1, 0), (1, 1), (2, 0), (2, 1)]);
+    // Vertices are in world space
+    assert_eq!(built[2]._v1, vec3(2.0, 0.0, 5.0));
+
+    let pdfs: Vec<f32> = built.iter().map(|t| t._pdf).collect();
+    let expected = [1.0 / 18.0, 1.0 / 18.0, 8.0 / 18.0, 8.0 / 18.0];
+    for (pdf, expected) in pdfs.iter().zip(expected) {
+        assert!((pdf - expected).abs() < 1e-6, "{:?}", pdfs);
+    }
+    for triangle in &built {
+        assert!((0.0..=1.0).contains(&triangle._threshold));
+        assert!((triangle._alias as usize) < built.len());
+    }
+}
@@ -0,0 +1,137 @@
+use renderer::environment::{
+    direction_to_spherical, spherical_to_direction, EnvironmentDistribution,
+};
+use renderer::image_resource::TextureImageData;
+use renderer::math::Vec2;
+use renderer::vk::Format;
+
+fn image(width: u32, height: u32, luminance: impl Fn(u32, u32) -> f32) -> TextureImageData {
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let l = luminance(x, y);
+            for c in [l, l, l, 1.0] {
+                pixels.extend_from_slice(&f32::to_le_bytes(c));
+            }
+        }
+    }
+    TextureImageData::new(Format::R32G32B32A32_SFLOAT, width, height, &pixels)
+}
+
+fn assert_valid_cdf(cdf: &[f32]) {
+    assert_eq!(cdf[0], 0.0);
+    assert_eq!(*cdf.last().unwrap(), 1.0);
+    assert!(cdf.windows(2).all(|w| w[0] <= w[1]), "{:?}", cdf);
+}
+
+#[test]
+fn cdfs_are_monotonic_and_normalized() {
+    let distribution = EnvironmentDistribution::new(&image(16, 8, |x, y| (x * y) as f32));
+    assert_eq!(distribution.marginal_cdf().len(), 9);
+    assert_valid_cdf(distribution.marginal_cdf());
+    for row in 0..8 {
+        assert_eq!(distribution.conditional_cdf(row).len(), 17);
+        assert_valid_cdf(distribution.conditional_cdf(row));
+    }
+}
+
+#[test]
+fn pdf_integrates_to_one() {
+    let (width, height) = (32, 16);
+    let distribution = EnvironmentDistribution::new(&image(width, height, |x, y| {
+        1.0 + ((x * 7 + y * 3) % 5) as f32
+    }));
+
+    // Over the image uv
+    let mut uv_integral = 0.0;
+    for y in 0..height {
+        for x in 0..width {
+            let uv = Vec2::new(
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            );
+            uv_integral += distribution.pdf_uv(uv) / (width * height) as f32;
+        }
+    }
+    assert!((uv_integral - 1.0).abs() < 1e-4, "{}", uv_integral);
+
+    // Over the sphere, with a midpoint rule in theta and phi
+    let steps = 256;
+    let mut solid_angle_integral = 0.0;
+    for j in 0..steps {
+        for i in 0..steps {
+            let uv = Vec2::new(
+                (i as f32 + 0.5) / steps as f32,
+                (j as f32 + 0.5) / steps as f32,
+            );
+            let sin_theta = (uv.y * std::f32::consts::PI).sin();
+            let d_omega = 2.0 * std::f32::consts::PI * std::f32::consts::PI * sin_theta
+                / (steps * steps) as f32;
+            solid_angle_integral += distribution.pdf(spherical_to_direction(uv)) * d_omega;
+        }
+    }
+    assert!(
+        (solid_angle_integral - 1.0).abs() < 1e-2,
+        "{}",
+        solid_angle_integral
+    );
+}
+
+#[test]
+fn samples_land_on_the_only_bright_pixel() {
+    let distribution =
+        EnvironmentDistribution::new(&image(
+            8,
+            4,
+            |x, y| if x == 5 && y == 2 { 10.0 } else { 0.0 },
+        ));
+    for i in 0..64 {
+        let u = Vec2::new((i % 8) as f32 / 8.0 + 0.01, (i / 8) as f32 / 8.0 + 0.01);
+        let (uv, pdf) = distribution.sample_uv(u);
+        assert_eq!((uv.x * 8.0) as u32, 5);
+        assert_eq!((uv.y * 4.0) as u32, 2);
+        // All density is in one of 32 pixels
+        assert!((pdf - 32.0).abs() < 1e-3, "{}", pdf);
+        assert_eq!(pdf, distribution.pdf_uv(uv));
+    }
+}
+
+#[test]
+fn sampled_pdf_matches_lookup() {
+    let distribution = EnvironmentDistribution::new(&image(16, 8, |x, y| (x + 2 * y) as f32));
+    for i in 0..100 {
+        let u = Vec2::new((i as f32 * 0.618_034).fract(), (i as f32 + 0.5) / 100.0);
+        let (uv, pdf) = distribution.sample_uv(u);
+        assert!((0.0..1.0).contains(&uv.x) && (0.0..=1.0).contains(&uv.y));
+        assert!(pdf > 0.0);
+        assert_eq!(pdf, distribution.pdf_uv(uv));
+    }
+}
+
+#[test]
+fn black_environment_falls_back_to_uniform_directions() {
+    let (width, height) = (8, 4);
+    let distribution = EnvironmentDistribution::new(&image(width, height, |_, _| 0.0));
+    assert_valid_cdf(distribution.marginal_cdf());
+    // Uniform over the sphere is 1 / 4pi per steradian
+    let uv = Vec2::new(0.3, 0.5);
+    let expected = 1.0 / (4.0 * std::f32::consts::PI);
+    let pdf = distribution.pdf(spherical_to_direction(uv));
+    assert!(
+        (pdf - expected).abs() / expected < 0.1,
+        "{} {}",
+        pdf,
+        expected
+    );
+}
+
+#[test]
+fn spherical_mapping_round_trips() {
+    for i in 1..20 {
+        for j in 1..20 {
+            let uv = Vec2::new(i as f32 / 20.0, j as f32 / 20.0);
+            let back = direction_to_spherical(spherical_to_direction(uv));
+            assert!((back.x - uv.x).abs() < 1e-4 && (back.y - uv.y).abs() < 1e-4);
+        }
+    }
+}
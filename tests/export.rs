@@ -0,0 +1,248 @@
+mod common;
+
+use cgmath::{vec2, vec3, vec4, InnerSpace, Matrix4, SquareMatrix};
+use common::scratch_directory;
+use renderer::camera::Camera;
+use renderer::export;
+use renderer::image_resource::TextureImageData;
+use renderer::import::{self, ImportedInstance, ImportedMaterial, ImportedMesh, ImportedScene};
+use renderer::material::{Material, NormalMapConvention, TextureTransform};
+use renderer::math::Mat4;
+use renderer::mesh_resource::MeshResource;
+use renderer::vk::Format;
+
+// Relative to the size of the elements, transforms are inverted on the way
+fn close(a: Mat4, b: Mat4) -> bool {
+    let (a, b): ([[f32; 4]; 4], [[f32; 4]; 4]) = (a.into(), b.into());
+    a.iter()
+        .flatten()
+        .zip(b.iter().flatten())
+        .all(|(a, b)| (a - b).abs() <= 1e-5 * a.abs().max(1.0))
+}
+
+// A quad with two UV sets and vertex colors, used by two instances with different
+// materials, and a camera
+fn procedural_scene() -> ImportedScene {
+    let mut quad = MeshResource::new(
+        vec![0, 1, 2, 0, 2, 3],
+        vec![
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ],
+        Vec::new(),
+        Vec::new(),
+        vec![
+            vec![
+                vec2(0.0, 1.0),
+                vec2(1.0, 1.0),
+                vec2(1.0, 0.0),
+                vec2(0.0, 0.0),
+            ],
+            vec![vec2(0.5, 0.5); 4],
+        ],
+    )
+    .with_colors(vec![
+        vec4(1.0, 0.0, 0.0, 1.0),
+        vec4(0.0, 1.0, 0.0, 0.5),
+        vec4(0.0, 0.0, 1.0, 1.0),
+        vec4(1.0, 1.0, 1.0, 1.0),
+    ]);
+    quad.generate_normals();
+    quad.generate_tangents();
+
+    let pixels = [
+        255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 10, 20, 30, 40,
+    ];
+    let texture = TextureImageData::new(Format::R8G8B8A8_UNORM, 2, 2, &pixels);
+
+    let mut textured = Material::new();
+    textured.base_color = vec4(0.8, 0.6, 0.4, 1.0);
+    textured.roughness = 0.3;
+    textured.metallic = 0.7;
+    textured.base_color_texture_transform = TextureTransform {
+        tex_coord: 1,
+        offset: vec2(0.5, 0.25),
+        scale: vec2(2.0, 3.0),
+        rotation: 0.5,
+    };
+    textured.normal_map_convention = NormalMapConvention::DirectX;
+    textured.normal_scale = 0.5;
+    textured.normal_texture_transform = TextureTransform {
+        tex_coord: 0,
+        offset: vec2(0.1, 0.2),
+        scale: vec2(4.0, 4.0),
+        rotation: 0.0,
+    };
+    textured.occlusion_strength = 0.6;
+    textured.occlusion_texture_transform = TextureTransform {
+        tex_coord: 1,
+        offset: vec2(0.0, 0.0),
+        scale: vec2(1.0, 1.0),
+        rotation: 1.5,
+    };
+    let mut textured = ImportedMaterial::new("textured", textured);
+    textured.base_color_texture = Some(0);
+    textured.normal_texture = Some(0);
+    textured.occlusion_texture = Some(0);
+
+    let mut emissive = Material::new();
+    emissive.emission = vec4(2.0, 1.0, 0.5, 2.0);
+    let emissive = ImportedMaterial::new("emissive", emissive);
+
+    let mut camera = Camera::new(40.0, 0.1, 500.0);
+    camera.translate(vec3(1.0, 2.0, -10.0));
+
+    ImportedScene {
+        meshes: vec![ImportedMesh {
+            name: "quad".to_string(),
+            resource: quad,
+        }],
+        textures: vec![texture],
+        materials: vec![textured, emissive],
+        instances: vec![
+            ImportedInstance {
+                mesh: 0,
+                material: Some(0),
+                transform: Mat4::identity(),
+            },
+            ImportedInstance {
+                mesh: 0,
+                material: Some(1),
+                transform: Matrix4::from_translation(vec3(2.0, 0.0, 0.0))
+                    * Matrix4::from_angle_y(cgmath::Deg(30.0))
+                    * Matrix4::from_scale(2.0),
+            },
+        ],
+        camera: Some(camera),
+    }
+}
+
+#[test]
+fn scenes_round_trip_through_gltf_and_glb() {
+    let directory = scratch_directory("round-trip");
+    let scene = procedural_scene();
+    // The space checks that the buffer uri is encoded
+    for file in ["exported scene.gltf", "exported.glb"] {
+        let path = directory.join(file);
+        export::gltf::save(&scene, &path).unwrap();
+        let imported = import::load(&path).unwrap();
+
+        assert_eq!(imported.instances.len(), 2);
+        for (instance, original) in imported.instances.iter().zip(&scene.instances) {
+            assert!(close(instance.transform, original.transform));
+            assert_eq!(instance.material, original.material);
+            let mesh = &imported.meshes[instance.mesh].resource;
+            let original = &scene.meshes[original.mesh].resource;
+            assert_eq!(mesh.indices, original.indices);
+            assert_eq!(mesh.vertices, original.vertices);
+            assert_eq!(mesh.normals, original.normals);
+            assert_eq!(mesh.tangents, original.tangents);
+            assert_eq!(mesh.tex_coords, original.tex_coords);
+            assert_eq!(mesh.colors, original.colors);
+        }
+
+        let textured = &imported.materials[0];
+        let original = &scene.materials[0].material;
+        assert_eq!(textured.name, "textured");
+        assert_eq!(textured.material.base_color, original.base_color);
+        assert_eq!(textured.material.roughness, original.roughness);
+        assert_eq!(textured.material.metallic, original.metallic);
+        assert_eq!(textured.material.normal_scale, original.normal_scale);
+        assert_eq!(
+            textured.material.occlusion_strength,
+            original.occlusion_strength
+        );
+        assert_eq!(
+            textured.material.base_color_texture_transform,
+            original.base_color_texture_transform
+        );
+        assert_eq!(
+            textured.material.normal_texture_transform,
+            original.normal_texture_transform
+        );
+        assert_eq!(
+            textured.material.occlusion_texture_transform,
+            original.occlusion_texture_transform
+        );
+        assert_eq!(textured.occlusion_texture, textured.base_color_texture);
+        // The normal map is converted to the OpenGL convention glTF uses
+        let base_color = &imported.textures[textured.base_color_texture.unwrap()];
+        let normal = &imported.textures[textured.normal_texture.unwrap()];
+        assert_eq!(
+            textured.material.normal_map_convention,
+            NormalMapConvention::OpenGl
+        );
+        assert_eq!(base_color.pixels, scene.textures[0].pixels);
+        for (flipped, pixel) in normal.pixels.chunks(4).zip(base_color.pixels.chunks(4)) {
+            assert_eq!(
+                [flipped[0], 255 - flipped[1], flipped[2], flipped[3]],
+                [pixel[0], pixel[1], pixel[2], pixel[3]]
+            );
+        }
+
+        let emissive = &imported.materials[1];
+        assert_eq!(emissive.base_color_texture, None);
+        // w is folded into the strength, the radiance stays the same
+        let emission = emissive.material.emission - vec4(4.0, 2.0, 1.0, 1.0);
+        assert!(emission.magnitude() < 1e-5);
+
+        let camera = imported.camera.unwrap();
+        let original = scene.camera.unwrap();
+        assert!((camera.fov() - original.fov()).abs() < 1e-4);
+        assert_eq!(
+            (camera.z_near(), camera.z_far()),
+            (original.z_near(), original.z_far())
+        );
+        assert!(close(
+            *camera.world_transform(),
+            *original.world_transform()
+        ));
+    }
+}
+
+#[test]
+fn imported_assets_survive_an_export() {
+    let directory = scratch_directory("assets");
+    let scene = import::load("assets/Duck/glTF-Binary/Duck.glb").unwrap();
+    let path = directory.join("Duck.gltf");
+    export::gltf::save(&scene, &path).unwrap();
+    let imported = import::load(&path).unwrap();
+
+    assert_eq!(imported.meshes.len(), scene.meshes.len());
+    assert_eq!(imported.textures.len(), scene.textures.len());
+    for (mesh, original) in imported.meshes.iter().zip(&scene.meshes) {
+        assert_eq!(mesh.resource.indices, original.resource.indices);
+        assert_eq!(mesh.resource.vertices, original.resource.vertices);
+        assert_eq!(mesh.resource.tex_coords, original.resource.tex_coords);
+    }
+    for (texture, original) in imported.textures.iter().zip(&scene.textures) {
+        assert_eq!(
+            (texture.width, texture.height),
+            (original.width, original.height)
+        );
+        assert_eq!(texture.pixels, original.pixels);
+    }
+    for (instance, original) in imported.instances.iter().zip(&scene.instances) {
+        assert!(close(instance.transform, original.transform));
+    }
+    // The Duck has a camera of its own, placed under a node scaled down a hundred times
+    let (camera, original) = (imported.camera.unwrap(), scene.camera.unwrap());
+    assert!((camera.fov() - original.fov()).abs() < 1e-4);
+    assert!(close(camera.view_matrix(), original.view_matrix()));
+}
+
+#[test]
+fn float_textures_are_rejected() {
+    let directory = scratch_directory("float");
+    let mut scene = procedural_scene();
+    // An HDR value that an 8 bit PNG would clamp
+    let pixels: Vec<u8> = [4.0f32, 2.0, 1.0, 1.0]
+        .iter()
+        .flat_map(|c| c.to_le_bytes())
+        .collect();
+    scene.textures[0] = TextureImageData::new(Format::R32G32B32A32_SFLOAT, 1, 1, &pixels);
+    let error = export::gltf::save(&scene, directory.join("float.glb")).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
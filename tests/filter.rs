@@ -0,0 +1,83 @@
+use renderer::filter::{FilterKind, PixelFilter, FILTER_TABLE_SIZE};
+
+const KINDS: [FilterKind; 5] = [
+    FilterKind::Box,
+    FilterKind::Tent,
+    FilterKind::Gaussian,
+    FilterKind::Mitchell,
+    FilterKind::BlackmanHarris,
+];
+
+fn samples(filter: &PixelFilter, count: usize) -> Vec<(f32, f32)> {
+    let table = filter.sample_table();
+    (0..count)
+        .map(|i| PixelFilter::sample(&table, (i as f32 + 0.5) / count as f32))
+        .collect()
+}
+
+#[test]
+fn box_filter_is_uniform_jitter() {
+    let table = PixelFilter::default().sample_table();
+    for i in 0..=10 {
+        let u = i as f32 / 10.0;
+        let (offset, weight) = PixelFilter::sample(&table, u);
+        assert!((offset - (u - 0.5)).abs() < 1e-3, "{} {}", u, offset);
+        assert!((weight - 1.0).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn samples_stay_inside_the_radius() {
+    for kind in KINDS {
+        let filter = PixelFilter::from(kind);
+        let table = filter.sample_table();
+        assert_eq!(table.len(), FILTER_TABLE_SIZE);
+        assert!((table[0][0] + filter.radius).abs() < 1e-3, "{:?}", kind);
+        assert!((table[FILTER_TABLE_SIZE - 1][0] - filter.radius).abs() < 1e-3);
+        for (offset, _) in samples(&filter, 256) {
+            assert!(offset.abs() <= filter.radius + 1e-4);
+        }
+    }
+}
+
+#[test]
+fn filters_are_symmetric_and_peak_in_the_center() {
+    for kind in KINDS {
+        let filter = PixelFilter::new(kind, 2.0);
+        for i in 0..20 {
+            let x = i as f32 / 10.0;
+            assert!((filter.evaluate(x) - filter.evaluate(-x)).abs() < 1e-5);
+            assert!(filter.evaluate(x) <= filter.evaluate(0.0) + 1e-6);
+        }
+        assert_eq!(filter.evaluate(2.5), 0.0);
+    }
+}
+
+#[test]
+fn expected_weight_is_one() {
+    for kind in KINDS {
+        let weights = samples(&PixelFilter::from(kind), 4096);
+        let mean = weights.iter().map(|(_, w)| w).sum::<f32>() / weights.len() as f32;
+        assert!((mean - 1.0).abs() < 0.02, "{:?} {}", kind, mean);
+        let mean_offset = weights.iter().map(|(x, _)| x).sum::<f32>() / weights.len() as f32;
+        assert!(mean_offset.abs() < 1e-3);
+    }
+}
+
+#[test]
+fn mitchell_has_negative_lobes() {
+    let filter = PixelFilter::from(FilterKind::Mitchell);
+    assert!(filter.evaluate(1.5) < 0.0);
+    let weights = samples(&filter, 1024);
+    assert!(weights.iter().any(|(x, w)| x.abs() > 1.2 && *w < 0.0));
+    assert!(weights.iter().all(|(x, w)| x.abs() > 0.9 || *w > 0.0));
+}
+
+#[test]
+fn samples_follow_the_filter() {
+    // Tent: the fraction of samples within half the radius is 3/4
+    let filter = PixelFilter::new(FilterKind::Tent, 1.0);
+    let offsets = samples(&filter, 4096);
+    let inner = offsets.iter().filter(|(x, _)| x.abs() < 0.5).count() as f32;
+    assert!((inner / offsets.len() as f32 - 0.75).abs() < 0.02);
+}
@@ -0,0 +1,193 @@
+use exr::prelude::{read, FlatSamples, ReadChannels, ReadLayers};
+use renderer::aov::{AovData, AovKind};
+use renderer::hdr_image::{rgbe, HdrImage};
+use renderer::math::Vec3;
+
+fn gradient(width: u32, height: u32) -> HdrImage {
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            // Long runs of equal values in the first rows, noise further down
+            let v = if y < 2 {
+                1.0
+            } else {
+                (x * 31 + y * 17) as f32 * 0.37
+            };
+            pixels.push(Vec3::new(v, v * 0.5, y as f32));
+        }
+    }
+    HdrImage::new(width, height, pixels)
+}
+
+fn decode_rgbe(rgbe: &[u8]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2.0f32.powi(rgbe[3] as i32 - 136);
+    Vec3::new(rgbe[0] as f32, rgbe[1] as f32, rgbe[2] as f32) * scale
+}
+
+// Reads back the scanlines written by write_hdr_to, run length encoded or flat
+fn decode_hdr(bytes: &[u8], width: usize, height: usize) -> Vec<[u8; 4]> {
+    let header_end = bytes.windows(2).position(|w| w == b"\n\n").unwrap() + 2;
+    let resolution_end = header_end
+        + bytes[header_end..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&bytes[header_end..resolution_end]).unwrap(),
+        format!("-Y {} +X {}", height, width)
+    );
+
+    let mut data = &bytes[resolution_end + 1..];
+    let mut pixels = Vec::new();
+    for _ in 0..height {
+        if data[0] != 2 || data[1] != 2 {
+            for p in data[..width * 4].chunks(4) {
+                pixels.push([p[0], p[1], p[2], p[3]]);
+            }
+            data = &data[width * 4..];
+            continue;
+        }
+
+        assert_eq!(((data[2] as usize) << 8) | data[3] as usize, width);
+        data = &data[4..];
+        let mut row = vec![[0u8; 4]; width];
+        for component in 0..4 {
+            let mut x = 0;
+            while x < width {
+                if data[0] > 128 {
+                    let count = (data[0] - 128) as usize;
+                    for p in &mut row[x..x + count] {
+                        p[component] = data[1];
+                    }
+                    x += count;
+                    data = &data[2..];
+                } else {
+                    let count = data[0] as usize;
+                    for (p, b) in row[x..x + count].iter_mut().zip(&data[1..=count]) {
+                        p[component] = *b;
+                    }
+                    x += count;
+                    data = &data[1 + count..];
+                }
+            }
+        }
+        pixels.extend(row);
+    }
+    assert!(data.is_empty());
+    pixels
+}
+
+#[test]
+fn rgbe_encodes_powers_of_two_exactly() {
+    assert_eq!(rgbe(Vec3::new(1.0, 1.0, 1.0)), [128, 128, 128, 129]);
+    assert_eq!(rgbe(Vec3::new(0.5, 0.25, 0.0)), [128, 64, 0, 128]);
+    assert_eq!(rgbe(Vec3::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    let c = Vec3::new(123.4, 5.6, 0.01);
+    let back = decode_rgbe(&rgbe(c));
+    assert!((back.x - c.x).abs() / c.x < 1.0 / 128.0);
+}
+
+#[test]
+fn hdr_scanlines_round_trip() {
+    for (width, height) in [(300, 5), (5, 3)] {
+        let image = gradient(width, height);
+        let mut bytes = Vec::new();
+        image.write_hdr_to(&mut bytes).unwrap();
+        assert!(bytes.starts_with(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n"));
+
+        let pixels = decode_hdr(&bytes, width as usize, height as usize);
+        for (decoded, p) in pixels.iter().zip(&image.pixels) {
+            assert_eq!(*decoded, rgbe(*p));
+        }
+    }
+}
+
+#[test]
+fn pfm_stores_rows_bottom_up() {
+    let image = gradient(3, 2);
+    let mut bytes = Vec::new();
+    image.write_pfm_to(&mut bytes).unwrap();
+
+    let header = b"PF\n3 2\n-1.0\n";
+    assert!(bytes.starts_with(header));
+    let floats: Vec<f32> = bytes[header.len()..]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    assert_eq!(floats.len(), 3 * 2 * 3);
+    // First stored pixel is the bottom left one
+    let bottom_left = image.pixel(0, 1);
+    assert_eq!(floats[..3], [bottom_left.x, bottom_left.y, bottom_left.z]);
+}
+
+#[test]
+fn exr_contains_beauty_and_aov_channels() {
+    let (width, height) = (4, 3);
+    let image = gradient(width, height);
+    let pixel_count = (width * height) as usize;
+    let depth = AovData::Scalar((0..pixel_count).map(|i| i as f32).collect());
+    let normal = AovData::Vector(vec![Vec3::new(0.0, 1.0, 0.0); pixel_count]);
+    let ids = AovData::Id((0..pixel_count as u32).collect());
+
+    let path = std::env::temp_dir().join("renderer_hdr_image_test.exr");
+    image
+        .write_exr(
+            &path,
+            &[
+                (AovKind::Depth, &depth),
+                (AovKind::Normal, &normal),
+                (AovKind::InstanceId, &ids),
+            ],
+        )
+        .unwrap();
+
+    let read_back = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .all_channels()
+        .first_valid_layer()
+        .all_attributes()
+        .from_file(&path)
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let channels = &read_back.layer_data.channel_data.list;
+    let channel = |name: &str| {
+        &channels
+            .iter()
+            .find(|c| c.name.to_string() == name)
+            .unwrap_or_else(|| panic!("missing channel {}", name))
+            .sample_data
+    };
+
+    match channel("R") {
+        FlatSamples::F32(values) => {
+            assert_eq!(values.len(), pixel_count);
+            assert_eq!(values[7], image.pixels[7].x);
+        }
+        _ => panic!("beauty should be stored as f32"),
+    }
+    match channel("Z") {
+        FlatSamples::F32(values) => assert_eq!(values[5], 5.0),
+        _ => panic!("depth should be stored as f32"),
+    }
+    match channel("normal.Y") {
+        FlatSamples::F32(values) => assert!(values.iter().all(|v| *v == 1.0)),
+        _ => panic!("normals should be stored as f32"),
+    }
+    match channel("instance_id") {
+        FlatSamples::U32(values) => assert_eq!(values[11], 11),
+        _ => panic!("ids should be stored as u32"),
+    }
+}
+
+#[test]
+fn exr_rejects_aovs_of_the_wrong_size() {
+    let image = gradient(4, 3);
+    let depth = AovData::Scalar(vec![0.0; 5]);
+    let path = std::env::temp_dir().join("renderer_hdr_image_wrong_size.exr");
+    assert!(image.write_exr(&path, &[(AovKind::Depth, &depth)]).is_err());
+}
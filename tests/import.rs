@@ -0,0 +1,767 @@
+mod common;
+
+use cgmath::{vec2, vec3, vec4, InnerSpace};
+use common::scratch_directory;
+use renderer::import::{self, draco, ply, stl, triangulate};
+use renderer::math::Vec3;
+use renderer::mesh_resource::MeshResource;
+
+fn triangle_area(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    (b - a).cross(c - a) * 0.5
+}
+
+#[test]
+fn concave_polygons_triangulate_inside_the_outline() {
+    // An L shape, fanning from the first corner would cover the notch
+    let points = [
+        vec3(0.0, 0.0, 0.0),
+        vec3(2.0, 0.0, 0.0),
+        vec3(2.0, 1.0, 0.0),
+        vec3(1.0, 1.0, 0.0),
+        vec3(1.0, 2.0, 0.0),
+        vec3(0.0, 2.0, 0.0),
+    ];
+    let triangles = triangulate(&points);
+    assert_eq!(triangles.len(), 4);
+    let mut area = 0.0;
+    for [a, b, c] in &triangles {
+        let normal = triangle_area(points[*a], points[*b], points[*c]);
+        // Same winding as the polygon
+        assert!(normal.z > 0.0);
+        area += normal.magnitude();
+    }
+    assert!((area - 3.0).abs() < 1e-5);
+}
+
+#[test]
+fn obj_groups_and_materials_split_meshes() {
+    let directory = scratch_directory("groups");
+    std::fs::write(
+        directory.join("scene.mtl"),
+        "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n",
+    )
+    .unwrap();
+    std::fs::write(
+        directory.join("scene.obj"),
+        "mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+g first
+usemtl red
+f 1 2 3 4
+usemtl blue
+f -4 -2 -1
+g second
+usemtl red
+f 1 2 3
+",
+    )
+    .unwrap();
+
+    let imported = import::load(directory.join("scene.obj")).unwrap();
+    let names: Vec<&str> = imported.meshes.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["first", "first", "second"]);
+    assert_eq!(imported.meshes[0].resource.indices.len(), 6);
+    assert_eq!(imported.meshes[1].resource.indices.len(), 3);
+    assert_eq!(imported.instances.len(), 3);
+    let material = |instance: usize| {
+        let index = imported.instances[instance].material.unwrap();
+        imported.materials[index].name.as_str()
+    };
+    assert_eq!(
+        [material(0), material(1), material(2)],
+        ["red", "blue", "red"]
+    );
+    // A quad with missing normals gets them generated facing +Z
+    for normal in &imported.meshes[0].resource.normals {
+        assert!((normal - vec3(0.0, 0.0, 1.0)).magnitude() < 1e-5);
+    }
+}
+
+#[test]
+fn smoothing_groups_share_generated_normals() {
+    // Two faces folded along the Y axis
+    let geometry = "v 0 0 0\nv 0 1 0\nv 1 0 0\nv 1 1 0\nv 0 0 1\nv 0 1 1\n";
+    let directory = scratch_directory("smoothing");
+    let load = |smoothing: &str| {
+        let path = directory.join(format!("fold_{}.obj", smoothing));
+        let faces = format!("s {}\nf 1 3 4 2\nf 1 2 6 5\n", smoothing);
+        std::fs::write(&path, format!("{}{}", geometry, faces)).unwrap();
+        import::load(&path).unwrap().meshes.remove(0).resource
+    };
+
+    let smooth = load("1");
+    assert_eq!(smooth.vertices.len(), 6);
+    let shared = smooth
+        .vertices
+        .iter()
+        .position(|v| *v == vec3(0.0, 0.0, 0.0))
+        .unwrap();
+    // Between the +Z and +X facing faces
+    let normal = smooth.normals[shared];
+    assert!(normal.x > 0.3 && normal.z > 0.3 && normal.y.abs() < 1e-5);
+
+    let flat = load("off");
+    assert_eq!(flat.vertices.len(), 8);
+    for normal in &flat.normals {
+        assert!(normal.x.abs() > 0.99 || normal.z.abs() > 0.99);
+    }
+}
+
+#[test]
+fn obj_attributes_are_kept() {
+    let directory = scratch_directory("attributes");
+    std::fs::write(
+        directory.join("triangle.obj"),
+        "v 0 0 0 1 0 0
+v 1 0 0 0 1 0
+v 0 1 0 0 0 1
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 2
+f 1/1/1 2/2/1 3/3/1
+",
+    )
+    .unwrap();
+    let imported = import::load(directory.join("triangle.obj")).unwrap();
+    let mesh = &imported.meshes[0].resource;
+    // V is flipped to put the origin at the top left
+    assert_eq!(mesh.tex_coords[0][0], cgmath::vec2(0.0, 1.0));
+    assert_eq!(mesh.tex_coords[0][2], cgmath::vec2(0.0, 0.0));
+    assert_eq!(mesh.normals[1], vec3(0.0, 0.0, 1.0));
+    assert_eq!(
+        mesh.colors.as_ref().unwrap()[1],
+        cgmath::vec4(0.0, 1.0, 0.0, 1.0)
+    );
+    assert!((mesh.tangents[0] - vec3(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+    // No usemtl, the default material of the Ctx applies
+    assert_eq!(imported.instances[0].material, None);
+}
+
+#[test]
+fn mtl_parameters_map_to_materials() {
+    let directory = scratch_directory("materials");
+    image::RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255]))
+        .save(directory.join("albedo.png"))
+        .unwrap();
+    image::GrayImage::from_pixel(2, 2, image::Luma([128]))
+        .save(directory.join("rough.png"))
+        .unwrap();
+    std::fs::write(
+        directory.join("materials.mtl"),
+        "newmtl glass
+Kd 0.2 0.4 0.6
+Ks 0.04 0.04 0.04
+Ns 98
+Ni 1.5
+d 0.25
+Ke 1 2 3
+map_Kd -s 2 2 1 -o 0.5 0 albedo.png
+map_Bump -bm 2 rough.png
+newmtl gold
+Kd 0.1 0.1 0.1
+Ks 1.0 0.8 0.3
+Ns 2000
+map_Pr rough.png
+map_Ke albedo.png
+norm -bm 0.5 albedo.png
+",
+    )
+    .unwrap();
+    std::fs::write(
+        directory.join("materials.obj"),
+        "mtllib materials.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl glass\nf 1 2 3\n",
+    )
+    .unwrap();
+
+    let imported = import::load(directory.join("materials.obj")).unwrap();
+    assert_eq!(imported.materials.len(), 2);
+    let glass = &imported.materials[0];
+    assert_eq!(glass.name, "glass");
+    assert_eq!(glass.material.base_color, cgmath::vec4(0.2, 0.4, 0.6, 1.0));
+    assert_eq!(glass.material.metallic, 0.0);
+    assert!((glass.material.roughness - 0.1414).abs() < 1e-3);
+    assert_eq!(glass.material.ior, 1.5);
+    assert_eq!(glass.material.transmission, 0.75);
+    assert_eq!(glass.material.emission, cgmath::vec4(1.0, 2.0, 3.0, 1.0));
+    let texture = &imported.textures[glass.base_color_texture.unwrap()];
+    assert_eq!((texture.width, texture.height), (4, 2));
+    let transform = glass.material.base_color_texture_transform;
+    assert_eq!(transform.scale, cgmath::vec2(2.0, 2.0));
+    assert_eq!(transform.offset, cgmath::vec2(0.5, -1.0));
+    // map_Bump is a height map, not a normal map
+    assert_eq!(glass.normal_texture, None);
+
+    let gold = &imported.materials[1];
+    assert_eq!(gold.material.metallic, 1.0);
+    assert_eq!(gold.material.base_color, cgmath::vec4(1.0, 0.8, 0.3, 1.0));
+    // The roughness map lands in the green channel of a glTF style texture
+    let combined = &imported.textures[gold.metallic_roughness_texture.unwrap()];
+    let texel = combined.texel(1, 1).unwrap();
+    assert!((texel.y - 128.0 / 255.0).abs() < 1e-3);
+    assert_eq!(texel.z, 1.0);
+    assert_eq!(gold.material.roughness, 1.0);
+    // Without a Ke the emission map would be scaled to black
+    assert!(gold.emission_texture.is_some());
+    assert_eq!(gold.material.emission, cgmath::vec4(1.0, 1.0, 1.0, 1.0));
+    assert!(gold.normal_texture.is_some());
+    assert_eq!(gold.material.normal_scale, 0.5);
+}
+
+#[test]
+fn unknown_materials_fall_back_to_the_default() {
+    let directory = scratch_directory("unknown-materials");
+    std::fs::write(directory.join("scene.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+    std::fs::write(
+        directory.join("scene.obj"),
+        "mtllib scene.mtl missing.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl red
+f 1 2 3
+usemtl missing
+f 1 3 2
+",
+    )
+    .unwrap();
+
+    let imported = import::load(directory.join("scene.obj")).unwrap();
+    assert_eq!(imported.materials.len(), 1);
+    let materials: Vec<_> = imported.instances.iter().map(|i| i.material).collect();
+    assert_eq!(materials, [Some(0), None]);
+}
+
+#[test]
+fn missing_files_and_bad_indices_are_errors() {
+    let directory = scratch_directory("errors");
+    assert!(import::load(directory.join("missing.obj")).is_err());
+    std::fs::write(directory.join("bad.obj"), "v 0 0 0\nf 1 2 3\n").unwrap();
+    assert!(import::load(directory.join("bad.obj")).is_err());
+    assert!(import::load(directory.join("scene.unknown")).is_err());
+}
+
+#[test]
+fn gltf_files_import_through_the_library() {
+    let imported = import::load("assets/Cube/glTF/Cube.gltf").unwrap();
+    assert_eq!(imported.meshes.len(), 1);
+    assert_eq!(imported.textures.len(), 2);
+    assert_eq!(imported.instances.len(), 1);
+    let mesh = &imported.meshes[0].resource;
+    assert_eq!(mesh.indices.len(), 36);
+    assert_eq!(mesh.vertices.len(), 36);
+    assert_eq!(mesh.normals.len(), 36);
+    assert_eq!(mesh.tex_coords.len(), 1);
+    let material = &imported.materials[imported.instances[0].material.unwrap()];
+    assert_eq!(material.base_color_texture, Some(0));
+    assert_eq!(material.metallic_roughness_texture, Some(1));
+}
+
+fn bounds(mesh: &MeshResource) -> (Vec3, Vec3) {
+    let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Vec3::new(f32::MIN, f32::MIN, f32::MIN);
+    for v in &mesh.vertices {
+        min = vec3(min.x.min(v.x), min.y.min(v.y), min.z.min(v.z));
+        max = vec3(max.x.max(v.x), max.y.max(v.y), max.z.max(v.z));
+    }
+    (min, max)
+}
+
+// Surface area, how well the vertex normals agree with the winding and the area in UV space
+fn surface_measures(mesh: &MeshResource) -> (f32, f32, f32) {
+    let (mut area, mut facing, mut uv_area) = (0.0, 0.0, 0.0);
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let normal = triangle_area(mesh.vertices[a], mesh.vertices[b], mesh.vertices[c]);
+        area += normal.magnitude();
+        facing += normal.dot(mesh.normals[a] + mesh.normals[b] + mesh.normals[c]) / 3.0;
+        if let Some(uvs) = mesh.tex_coords.first() {
+            let (ab, ac) = (uvs[b] - uvs[a], uvs[c] - uvs[a]);
+            uv_area += (ab.x * ac.y - ab.y * ac.x).abs() * 0.5;
+        }
+    }
+    (area, facing, uv_area)
+}
+
+#[test]
+fn glb_and_data_uris_match_separate_files() {
+    let separate = import::load("assets/Duck/glTF/Duck.gltf").unwrap();
+    for path in [
+        "assets/Duck/glTF-Binary/Duck.glb",
+        "assets/Duck/glTF-Embedded/Duck.gltf",
+    ] {
+        let imported = import::load(path).unwrap();
+        assert_eq!(imported.meshes.len(), separate.meshes.len());
+        let (mesh, expected) = (&imported.meshes[0].resource, &separate.meshes[0].resource);
+        assert_eq!(mesh.indices, expected.indices);
+        assert_eq!(mesh.vertices, expected.vertices);
+        assert_eq!(mesh.tex_coords, expected.tex_coords);
+        assert_eq!(imported.textures.len(), separate.textures.len());
+        for (texture, expected) in imported.textures.iter().zip(&separate.textures) {
+            assert_eq!(
+                (texture.width, texture.height),
+                (expected.width, expected.height)
+            );
+            assert_eq!(texture.pixels, expected.pixels);
+        }
+        assert_eq!(
+            imported.instances[0].transform,
+            separate.instances[0].transform
+        );
+    }
+}
+
+#[test]
+fn draco_meshes_match_the_uncompressed_files() {
+    for (draco, uncompressed) in [
+        (
+            "assets/Duck/glTF-Draco/Duck.gltf",
+            "assets/Duck/glTF/Duck.gltf",
+        ),
+        (
+            "assets/BarramundiFish/glTF-Draco/BarramundiFish.gltf",
+            "assets/BarramundiFish/glTF/BarramundiFish.gltf",
+        ),
+        (
+            "assets/Monster/glTF-Draco/Monster.gltf",
+            "assets/Monster/glTF-Binary/Monster.glb",
+        ),
+    ] {
+        let imported = import::load(draco).unwrap();
+        let expected = import::load(uncompressed).unwrap();
+        assert_eq!(imported.meshes.len(), expected.meshes.len());
+        assert_eq!(imported.instances.len(), expected.instances.len());
+        for (mesh, expected) in imported.meshes.iter().zip(&expected.meshes) {
+            let (mesh, expected) = (&mesh.resource, &expected.resource);
+            assert_eq!(mesh.indices.len(), expected.indices.len(), "{}", draco);
+            assert_eq!(mesh.tex_coords.len(), expected.tex_coords.len());
+
+            // Positions, normals and UVs are quantized
+            let ((min, max), (expected_min, expected_max)) = (bounds(mesh), bounds(expected));
+            let size = (expected_max - expected_min).magnitude();
+            assert!((min - expected_min).magnitude() < size * 1e-3, "{}", draco);
+            assert!((max - expected_max).magnitude() < size * 1e-3, "{}", draco);
+            for normal in &mesh.normals {
+                assert!((normal.magnitude() - 1.0).abs() < 1e-3);
+            }
+            let (area, facing, uv_area) = surface_measures(mesh);
+            let (expected_area, expected_facing, expected_uv_area) = surface_measures(expected);
+            assert!(
+                (area - expected_area).abs() < expected_area * 1e-3,
+                "{}",
+                draco
+            );
+            assert!(facing > 0.0);
+            assert!(
+                (facing - expected_facing).abs() < expected_facing * 1e-2,
+                "{}",
+                draco
+            );
+            assert!(
+                (uv_area - expected_uv_area).abs() <= expected_uv_area * 1e-2,
+                "{}",
+                draco
+            );
+        }
+    }
+}
+
+#[test]
+fn truncated_draco_data_is_an_error() {
+    // The buffer holds nothing but the Draco bitstream of the duck
+    let data = std::fs::read("assets/Duck/glTF-Draco/0.bin").unwrap();
+    assert!(draco::decode(&data).is_ok());
+    // Every length takes minutes in debug builds, a sample and the end are enough
+    for length in (0..data.len())
+        .step_by(17)
+        .chain(data.len() - 64..data.len())
+    {
+        assert!(draco::decode(&data[..length]).is_err(), "{}", length);
+    }
+}
+
+#[test]
+fn draco_meshes_match_their_accessors() {
+    // Covers both edgebreaker traversals and meshes with topology splits
+    let path = "assets/Buggy/glTF-Draco/Buggy.gltf";
+    let json: serde_json::Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    let imported = import::load(path).unwrap();
+    let primitives = json["meshes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|mesh| mesh["primitives"].as_array().unwrap());
+    let mut count = 0;
+    for (primitive, mesh) in primitives.zip(&imported.meshes) {
+        let mesh = &mesh.resource;
+        let accessor =
+            &json["accessors"][primitive["attributes"]["POSITION"].as_u64().unwrap() as usize];
+        let component = |bound: &str, i: usize| accessor[bound][i].as_f64().unwrap() as f32;
+        let (min, max) = bounds(mesh);
+        let size = (0..3)
+            .map(|i| component("max", i) - component("min", i))
+            .fold(0.0, f32::max);
+        for i in 0..3 {
+            assert!(
+                (min[i] - component("min", i)).abs() <= size * 1e-3,
+                "{}",
+                imported.meshes[count].name
+            );
+            assert!(
+                (max[i] - component("max", i)).abs() <= size * 1e-3,
+                "{}",
+                imported.meshes[count].name
+            );
+        }
+        assert!(mesh
+            .indices
+            .iter()
+            .all(|i| (*i as usize) < mesh.vertices.len()));
+        assert_eq!(mesh.normals.len(), mesh.vertices.len());
+        assert!(surface_measures(mesh).1 > 0.0);
+        count += 1;
+    }
+    assert_eq!(count, 148);
+
+    // Meshes without split vertices or removed faces decode to the same points
+    for (path, points, triangles) in [
+        ("assets/Duck/glTF-Draco/Duck.gltf", 2399, 4212),
+        (
+            "assets/BarramundiFish/glTF-Draco/BarramundiFish.gltf",
+            2188,
+            3864,
+        ),
+    ] {
+        let mesh = &import::load(path).unwrap().meshes[0].resource;
+        assert_eq!(mesh.vertices.len(), points);
+        assert_eq!(mesh.indices.len(), triangles * 3);
+    }
+}
+
+#[test]
+fn missing_images_and_unknown_required_extensions() {
+    // Only the normal map of the fish is in the repository
+    let imported = import::load("assets/BarramundiFish/glTF-Draco/BarramundiFish.gltf").unwrap();
+    assert_eq!(imported.textures.len(), 1);
+    let material = &imported.materials[0];
+    assert_eq!(material.base_color_texture, None);
+    assert_eq!(material.normal_texture, Some(0));
+
+    let directory = scratch_directory("extensions");
+    let path = directory.join("scene.gltf");
+    std::fs::write(
+        &path,
+        r#"{"asset": {"version": "2.0"}, "extensionsRequired": ["EXT_unknown"], "extensionsUsed": ["EXT_unknown"]}"#,
+    )
+    .unwrap();
+    let error = import::load(&path).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+}
+
+fn encode(linear: f32) -> u8 {
+    (linear.powf(1.0 / 2.2) * 255.0).round() as u8
+}
+
+#[test]
+fn specular_glossiness_converts_to_metallic_roughness() {
+    let directory = scratch_directory("specular-glossiness");
+    // A dielectric texel and a gold texel
+    let gold = [1.0, 0.766, 0.336];
+    let diffuse = [encode(0.5), encode(0.5), encode(0.5), 255, 0, 0, 0, 255];
+    let specular_glossiness = [
+        encode(0.04),
+        encode(0.04),
+        encode(0.04),
+        64,
+        encode(gold[0]),
+        encode(gold[1]),
+        encode(gold[2]),
+        255,
+    ];
+    let rgba = image::ColorType::Rgba8;
+    image::save_buffer(directory.join("diffuse.png"), &diffuse, 2, 1, rgba).unwrap();
+    let path = directory.join("specular.png");
+    image::save_buffer(path, &specular_glossiness, 2, 1, rgba).unwrap();
+    let path = directory.join("scene.gltf");
+    std::fs::write(
+        &path,
+        r#"{
+            "asset": {"version": "2.0"},
+            "extensionsUsed": ["KHR_materials_pbrSpecularGlossiness", "KHR_texture_transform"],
+            "images": [{"uri": "diffuse.png"}, {"uri": "specular.png"}],
+            "textures": [{"source": 0}, {"source": 1}],
+            "materials": [
+                {"extensions": {"KHR_materials_pbrSpecularGlossiness": {
+                    "diffuseFactor": [0.5, 0.5, 0.5, 0.8],
+                    "specularFactor": [0.04, 0.04, 0.04],
+                    "glossinessFactor": 0.25
+                }}},
+                {"extensions": {"KHR_materials_pbrSpecularGlossiness": {
+                    "diffuseFactor": [1.0, 1.0, 1.0, 0.5],
+                    "diffuseTexture": {"index": 0, "extensions": {
+                        "KHR_texture_transform": {"offset": [0.5, 0.0], "texCoord": 1}
+                    }},
+                    "specularGlossinessTexture": {"index": 1}
+                }}}
+            ]
+        }"#,
+    )
+    .unwrap();
+    let imported = import::load(&path).unwrap();
+
+    let factors = &imported.materials[0];
+    assert_eq!(factors.base_color_texture, None);
+    assert_eq!(factors.metallic_roughness_texture, None);
+    let material = &factors.material;
+    assert!((material.base_color - vec4(0.5, 0.5, 0.5, 0.8)).magnitude() < 1e-3);
+    assert!(material.metallic.abs() < 1e-3);
+    assert!((material.roughness - 0.75).abs() < 1e-3);
+
+    // The source images are followed by the converted textures
+    let textured = &imported.materials[1];
+    assert_eq!(imported.textures.len(), 4);
+    assert_eq!(textured.base_color_texture, Some(2));
+    assert_eq!(textured.metallic_roughness_texture, Some(3));
+    let material = &textured.material;
+    // The diffuse alpha is in the texture, the factor doesn't apply it a second time
+    assert_eq!(material.base_color, vec4(1.0, 1.0, 1.0, 1.0));
+    assert_eq!((material.metallic, material.roughness), (1.0, 1.0));
+    let transform = &material.metallic_roughness_texture_transform;
+    assert_eq!((transform.tex_coord, transform.offset.x), (1, 0.5));
+
+    let texel = |texture: usize, x: u32| imported.textures[texture].texel(x, 0).unwrap() * 255.0;
+    let close = |a: f32, b: u8| (a - b as f32).abs() <= 2.0;
+    let dielectric = texel(2, 0);
+    assert!(close(dielectric.x, encode(0.5)) && close(dielectric.z, encode(0.5)));
+    assert!(close(dielectric.w, 128));
+    let dielectric = texel(3, 0);
+    assert!(close(dielectric.y, 191) && close(dielectric.z, 0));
+    let metal = texel(2, 1);
+    assert!(close(metal.x, encode(gold[0])) && close(metal.y, encode(gold[1])));
+    assert!(close(metal.z, encode(gold[2])));
+    let metal = texel(3, 1);
+    assert!(close(metal.y, 0) && close(metal.z, 255));
+
+    // Without its diffuse and specular images the fish keeps its other maps
+    let path = "assets/BarramundiFish/glTF-pbrSpecularGlossiness/BarramundiFish.gltf";
+    let imported = import::load(path).unwrap();
+    let fish = &imported.materials[0];
+    assert_eq!(fish.base_color_texture, None);
+    assert_eq!(fish.metallic_roughness_texture, None);
+    assert!(fish.normal_texture.is_some() && fish.occlusion_texture.is_some());
+    assert!((fish.material.metallic - 1.0).abs() < 1e-3);
+}
+
+// A unit square in the XY plane as one quad, with a color per corner
+const SQUARE: [[f32; 3]; 4] = [
+    [0.0, 0.0, 0.0],
+    [1.0, 0.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 1.0, 0.0],
+];
+const SQUARE_COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [128, 128, 128]];
+
+fn ply_header(format: &str) -> String {
+    format!(
+        "ply\nformat {} 1.0\ncomment made by hand\nelement vertex 4\nproperty float x\n\
+         property float y\nproperty float z\nproperty uchar red\nproperty uchar green\n\
+         property uchar blue\nelement face 1\nproperty list uchar int vertex_indices\n\
+         element edge 1\nproperty int vertex1\nproperty int vertex2\nend_header\n",
+        format
+    )
+}
+
+fn binary_ply(big_endian: bool) -> Vec<u8> {
+    let format = if big_endian {
+        "binary_big_endian"
+    } else {
+        "binary_little_endian"
+    };
+    let mut bytes = ply_header(format).into_bytes();
+    let ordered = |word: [u8; 4]| {
+        if big_endian {
+            [word[3], word[2], word[1], word[0]]
+        } else {
+            word
+        }
+    };
+    for (position, color) in SQUARE.iter().zip(SQUARE_COLORS) {
+        for value in position {
+            bytes.extend(ordered(value.to_le_bytes()));
+        }
+        bytes.extend(color);
+    }
+    bytes.push(4);
+    for index in [0i32, 1, 2, 3, 0, 1] {
+        bytes.extend(ordered(index.to_le_bytes()));
+    }
+    bytes
+}
+
+#[test]
+fn ply_formats_read_the_same_mesh() {
+    let mut ascii = ply_header("ascii");
+    for (position, color) in SQUARE.iter().zip(SQUARE_COLORS) {
+        ascii += &format!(
+            "{} {} {} {} {} {}\n",
+            position[0], position[1], position[2], color[0], color[1], color[2]
+        );
+    }
+    ascii += "4 0 1 2 3\n0 1\n";
+
+    let meshes = [
+        ply::read(&mut ascii.as_bytes()).unwrap(),
+        ply::read(&mut binary_ply(false).as_slice()).unwrap(),
+        ply::read(&mut binary_ply(true).as_slice()).unwrap(),
+    ];
+    for mesh in &meshes {
+        assert_eq!(mesh.indices.len(), 6);
+        assert_eq!(mesh.indices, meshes[0].indices);
+        assert_eq!(mesh.vertices, meshes[0].vertices);
+        assert_eq!(mesh.vertices[2], vec3(1.0, 1.0, 0.0));
+        // Generated, facing the viewer of the counter clockwise quad
+        assert!((mesh.normals[0] - vec3(0.0, 0.0, 1.0)).magnitude() < 1e-5);
+        assert_eq!(mesh.tangents.len(), 4);
+        let colors = mesh.colors.as_ref().unwrap();
+        assert_eq!(colors[0], cgmath::vec4(1.0, 0.0, 0.0, 1.0));
+        // 8 bit colors are decoded from sRGB
+        assert!((colors[3].x - 0.2158).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn ply_headers_are_validated() {
+    assert!(ply::read(&mut "plx\n".as_bytes()).is_err());
+    assert!(ply::read(&mut "ply\nelement vertex 1\nend_header\n".as_bytes()).is_err());
+    let truncated = "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nend_header\n1\n";
+    assert!(ply::read(&mut truncated.as_bytes()).is_err());
+    // A huge count runs out of data instead of memory
+    let huge =
+        "ply\nformat ascii 1.0\nelement vertex 4000000000\nproperty float x\nend_header\n1\n";
+    assert!(ply::read(&mut huge.as_bytes()).is_err());
+}
+
+// Facets of an axis aligned unit cube, two per side
+fn cube_facets() -> Vec<(Vec3, [Vec3; 3])> {
+    let mut facets = Vec::new();
+    for axis in 0..3 {
+        for side in [0.0, 1.0] {
+            let corner = |u: f32, v: f32| {
+                let mut p = [0.0; 3];
+                p[axis] = side;
+                p[(axis + 1) % 3] = u;
+                p[(axis + 2) % 3] = v;
+                Vec3::from(p)
+            };
+            let mut normal = [0.0; 3];
+            normal[axis] = if side == 0.0 { -1.0 } else { 1.0 };
+            let mut quad = [
+                corner(0.0, 0.0),
+                corner(1.0, 0.0),
+                corner(1.0, 1.0),
+                corner(0.0, 1.0),
+            ];
+            if side == 0.0 {
+                quad.reverse();
+            }
+            facets.push((Vec3::from(normal), [quad[0], quad[1], quad[2]]));
+            facets.push((Vec3::from(normal), [quad[0], quad[2], quad[3]]));
+        }
+    }
+    facets
+}
+
+fn binary_stl(header: &[u8], facets: &[(Vec3, [Vec3; 3])]) -> Vec<u8> {
+    let mut bytes = header.to_vec();
+    bytes.resize(80, b' ');
+    bytes.extend((facets.len() as u32).to_le_bytes());
+    for (normal, corners) in facets {
+        for v in [*normal, corners[0], corners[1], corners[2]] {
+            for value in [v.x, v.y, v.z] {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        bytes.extend([0, 0]);
+    }
+    bytes
+}
+
+fn ascii_stl(facets: &[(Vec3, [Vec3; 3])]) -> String {
+    let mut text = "solid cube\n".to_string();
+    for (n, corners) in facets {
+        text += &format!("  facet normal {} {} {}\n    outer loop\n", n.x, n.y, n.z);
+        for c in corners {
+            text += &format!("      vertex {} {} {}\n", c.x, c.y, c.z);
+        }
+        text += "    endloop\n  endfacet\n";
+    }
+    text + "endsolid cube\n"
+}
+
+#[test]
+fn stl_facets_weld_within_the_crease_angle() {
+    let facets = cube_facets();
+    let meshes = [
+        stl::read(&mut binary_stl(b"binary cube", &facets).as_slice()).unwrap(),
+        // Binary files whose header starts with solid aren't mistaken for text
+        stl::read(&mut binary_stl(b"solid cube", &facets).as_slice()).unwrap(),
+        stl::read(&mut ascii_stl(&facets).as_bytes()).unwrap(),
+    ];
+    for mesh in &meshes {
+        assert_eq!(mesh.indices.len(), 36);
+        // The 90 degree edges stay sharp, 4 welded corners per side
+        assert_eq!(mesh.vertices.len(), 24);
+        for (triangle, (normal, _)) in mesh.indices.chunks_exact(3).zip(&facets) {
+            for index in triangle {
+                assert!((mesh.normals[*index as usize] - normal).magnitude() < 1e-5);
+            }
+        }
+    }
+
+    // A shallow fold welds into shared, averaged normals
+    let fold = [
+        (
+            Vec3::new(0.0, 0.0, 0.0),
+            [
+                vec3(0.0, 0.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+            ],
+        ),
+        (
+            Vec3::new(0.0, 0.0, 0.0),
+            [
+                vec3(1.0, 0.0, 0.0),
+                vec3(1.0, 1.0, 0.2),
+                vec3(0.0, 1.0, 0.0),
+            ],
+        ),
+    ];
+    let mesh = stl::read(&mut binary_stl(b"", &fold).as_slice()).unwrap();
+    assert_eq!(mesh.vertices.len(), 4);
+    let shared = mesh.normals[1];
+    assert!(shared.z > 0.95 && shared.x < 0.0);
+}
+
+#[test]
+fn ply_and_stl_files_load_by_extension() {
+    let directory = scratch_directory("scans");
+    std::fs::write(directory.join("scan.ply"), binary_ply(false)).unwrap();
+    std::fs::write(directory.join("part.STL"), binary_stl(b"", &cube_facets())).unwrap();
+    let scan = import::load(directory.join("scan.ply")).unwrap();
+    assert_eq!(scan.meshes[0].name, "scan");
+    assert_eq!(scan.instances.len(), 1);
+    let part = import::load(directory.join("part.STL")).unwrap();
+    assert_eq!(part.meshes[0].resource.vertices.len(), 24);
+
+    // Neither has UVs, the upload fills in a set for the shaders to read
+    for mesh in [&scan.meshes[0].resource, &part.meshes[0].resource] {
+        assert!(mesh.tex_coords.is_empty());
+        let uploaded = mesh.gpu_tex_coords();
+        assert_eq!(uploaded.len(), 1);
+        assert_eq!(uploaded[0], vec![vec2(0.0, 0.0); mesh.vertices.len()]);
+    }
+}
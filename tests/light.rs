@@ -0,0 +1,99 @@
+use cgmath::{vec3, InnerSpace};
+use renderer::light::{Light, LightType};
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-5
+}
+
+#[test]
+fn lights_convert_to_their_gpu_types() {
+    let white = vec3(1.0, 1.0, 1.0);
+    let types = [
+        Light::point(white, 1.0),
+        Light::spot(white, 1.0, 10.0, 20.0),
+        Light::directional(white, 1.0, 0.5),
+        Light::area(white, 1.0, 2.0, 3.0),
+    ]
+    .map(|light| light.to_gpu()._light_type);
+    assert_eq!(types, [0, 1, 2, 3]);
+
+    // Lights sit at their origin and emit along -Z
+    let mut light = Light::point(white, 1.0);
+    light
+        .translate(&vec3(1.0, 2.0, 3.0))
+        .rotate(&vec3(90.0, 0.0, 0.0));
+    let gpu = light.to_gpu();
+    assert_eq!(gpu._position, vec3(1.0, 2.0, 3.0));
+    assert!((gpu._direction - vec3(0.0, 1.0, 0.0)).magnitude() < 1e-5);
+}
+
+#[test]
+fn intensities_convert_to_radiance() {
+    let color = vec3(1.0, 0.5, 0.25);
+    // Point, spot and directional lights keep their W/sr and W/m²
+    for light in [
+        Light::point(color, 4.0),
+        Light::spot(color, 4.0, 10.0, 20.0),
+        Light::directional(color, 4.0, 0.0),
+    ] {
+        assert_eq!(light.to_gpu()._radiance, color * 4.0);
+    }
+
+    // An area light spreads its power over its area and a Lambertian lobe, scaling the
+    // transform scales the area
+    let mut light = Light::area(color, 6.0, 2.0, 3.0);
+    let gpu = light.to_gpu();
+    assert!(close(gpu._area, 6.0));
+    let expected = color * 6.0 / (std::f32::consts::PI * 6.0);
+    assert!((gpu._radiance - expected).magnitude() < 1e-6);
+    light.set_transform(cgmath::Matrix4::from_scale(2.0));
+    let gpu = light.to_gpu();
+    assert!(close(gpu._area, 24.0));
+    assert!((gpu._radiance - expected / 4.0).magnitude() < 1e-6);
+    // Centered on the light's origin
+    assert!((gpu._position + gpu._edge_u * 0.5 + gpu._edge_v * 0.5).magnitude() < 1e-6);
+}
+
+#[test]
+fn spot_cones_are_half_angles() {
+    let light = Light::spot(vec3(1.0, 1.0, 1.0), 1.0, 10.0, 30.0);
+    let at = |degrees: f32| {
+        let radians = degrees.to_radians();
+        light.falloff(vec3(radians.sin(), 0.0, -radians.cos()))
+    };
+    assert_eq!(at(0.0), 1.0);
+    assert!(close(at(9.9), 1.0));
+    assert!(close(at(30.1), 0.0));
+    assert_eq!(at(90.0), 0.0);
+    // Smooth and decreasing between the cones
+    let mut previous = 1.0;
+    for degrees in 11..30 {
+        let falloff = at(degrees as f32);
+        assert!(falloff < previous && falloff > 0.0);
+        previous = falloff;
+    }
+
+    // Equal angles give a hard edge, an inner cone wider than the outer one is clamped
+    let hard = Light::spot(vec3(1.0, 1.0, 1.0), 1.0, 20.0, 20.0);
+    let gpu = hard.to_gpu();
+    assert!(gpu._cos_inner > gpu._cos_outer);
+    let falloff = |degrees: f32| {
+        let radians = degrees.to_radians();
+        hard.falloff(vec3(radians.sin(), 0.0, -radians.cos()))
+    };
+    assert_eq!((falloff(19.9), falloff(20.1)), (1.0, 0.0));
+    let clamped = Light::spot(vec3(1.0, 1.0, 1.0), 1.0, 40.0, 20.0).to_gpu();
+    assert!(close(clamped._cos_inner, gpu._cos_inner));
+
+    assert_eq!(
+        Light::point(vec3(1.0, 1.0, 1.0), 1.0).falloff(vec3(1.0, 0.0, 0.0)),
+        1.0
+    );
+    assert!(matches!(
+        light.light_type(),
+        LightType::Spot {
+            inner_angle: 10.0,
+            outer_angle: 30.0
+        }
+    ));
+}
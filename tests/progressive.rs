@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use renderer::progressive::{CancelToken, Termination};
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn pass_limit() {
+    let termination = Termination::passes(10);
+    assert!(!termination.is_reached(9, ms(900), None));
+    assert!(termination.is_reached(10, ms(1000), None));
+    assert_eq!(
+        termination.estimate_remaining(4, ms(400), None),
+        Some(ms(600))
+    );
+}
+
+#[test]
+fn time_budget_leaves_room_for_the_next_pass() {
+    let termination = Termination::time(ms(1000));
+    assert!(!termination.is_reached(8, ms(800), None));
+    // Another 100ms pass would end at 1000ms, one more overshoots
+    assert!(termination.is_reached(9, ms(950), None));
+    assert_eq!(
+        termination.estimate_remaining(5, ms(250), None),
+        Some(ms(750))
+    );
+}
+
+#[test]
+fn noise_target() {
+    let termination = Termination::noise(0.01);
+    // Nothing is known before the first estimate
+    assert!(!termination.is_reached(100, ms(1000), None));
+    assert!(!termination.is_reached(100, ms(1000), Some(0.02)));
+    assert!(termination.is_reached(100, ms(1000), Some(0.01)));
+    // Halving the noise takes four times the samples
+    let remaining = termination
+        .estimate_remaining(10, ms(100), Some(0.02))
+        .unwrap();
+    assert!((remaining.as_secs_f64() - 0.3).abs() < 1e-6);
+}
+
+#[test]
+fn closest_limit_wins() {
+    let termination = Termination::passes(1000)
+        .with_time_budget(ms(500))
+        .with_target_noise(0.01);
+    let remaining = termination
+        .estimate_remaining(10, ms(100), Some(0.02))
+        .unwrap();
+    assert!((remaining.as_secs_f64() - 0.3).abs() < 1e-6);
+    assert_eq!(termination.estimate_remaining(0, ms(0), None), None);
+}
+
+#[test]
+fn unbounded_never_terminates() {
+    let termination = Termination::default();
+    assert!(!termination.is_reached(u32::MAX, Duration::MAX, Some(0.0)));
+    assert_eq!(termination.estimate_remaining(10, ms(100), None), None);
+}
+
+#[test]
+fn cancel_tokens_are_shared() {
+    let token = CancelToken::default();
+    let other = token.clone();
+    assert!(!token.is_cancelled());
+    std::thread::spawn(move || other.cancel()).join().unwrap();
+    assert!(token.is_cancelled());
+}
@@ -0,0 +1,61 @@
+use renderer::hdr_image::HdrImage;
+use renderer::math::Vec3;
+use renderer::region::Region;
+
+#[test]
+fn tiles_cover_the_image_once() {
+    let tiles = Region::tiles(100, 70, 32, 32);
+    assert_eq!(tiles.len(), 4 * 3);
+    assert_eq!(tiles[0], Region::new(0, 0, 32, 32));
+    // Cut off at the right and bottom edges
+    assert_eq!(tiles[3], Region::new(96, 0, 4, 32));
+    assert_eq!(tiles[11], Region::new(96, 64, 4, 6));
+
+    let mut covered = vec![0; 100 * 70];
+    for tile in &tiles {
+        assert!(tile.fits_in(100, 70));
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                covered[(y * 100 + x) as usize] += 1;
+            }
+        }
+    }
+    assert!(covered.iter().all(|c| *c == 1));
+    assert_eq!(tiles.iter().map(|t| t.pixel_count()).sum::<u64>(), 7000);
+}
+
+#[test]
+fn single_tile_for_small_images() {
+    assert_eq!(Region::tiles(20, 10, 64, 64), vec![Region::full(20, 10)]);
+}
+
+#[test]
+fn blit_stitches_tiles() {
+    let (width, height) = (10, 7);
+    let mut image = HdrImage::black(width, height);
+    // Tiles come from a framebuffer that can be larger than the tile itself
+    let framebuffer = 4;
+    for (index, tile) in Region::tiles(width, height, framebuffer, framebuffer)
+        .into_iter()
+        .enumerate()
+    {
+        let pixels = (0..framebuffer * framebuffer)
+            .map(|i| {
+                let (x, y) = (i % framebuffer, i / framebuffer);
+                if x < tile.width && y < tile.height {
+                    Vec3::new((tile.x + x) as f32, (tile.y + y) as f32, index as f32)
+                } else {
+                    Vec3::new(-1.0, -1.0, -1.0)
+                }
+            })
+            .collect();
+        image.blit(&HdrImage::new(framebuffer, framebuffer, pixels), tile);
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            let p = image.pixel(x, y);
+            assert_eq!((p.x, p.y), (x as f32, y as f32));
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use renderer::render_settings::RenderSettings;
+
+#[test]
+fn defaults_match_the_previous_constants() {
+    let settings = RenderSettings::default();
+    let constants = settings.push_constants();
+    assert_eq!(&constants[..5], &[15, 16, 16, 16, 4]);
+    // No clamp is pushed as 0
+    assert_eq!(f32::from_bits(constants[5]), 0.0);
+    assert_eq!(f32::from_bits(constants[6]), 0.001);
+    assert_eq!(f32::from_bits(constants[7]), 10000.0);
+    assert_eq!(constants[8], 0);
+}
+
+#[test]
+fn builders() {
+    let settings = RenderSettings::default()
+        .with_max_depth(3)
+        .with_firefly_clamp(10.0)
+        .with_seed(42);
+    let constants = settings.push_constants();
+    assert_eq!(constants[0], 3);
+    assert_eq!(f32::from_bits(constants[5]), 10.0);
+    assert_eq!(constants[8], 42);
+    assert_eq!(
+        RenderSettings {
+            max_diffuse_depth: 2,
+            ..settings
+        }
+        .push_constants()[1],
+        2
+    );
+}
@@ -0,0 +1,175 @@
+use renderer::sampler::{
+    blue_noise_mask, owen_scramble, sobol, sobol_matrices, to_unit_float, SamplerKind,
+    SamplerTables, BLUE_NOISE_SIZE, SOBOL_DIMENSIONS,
+};
+
+// Every interval of width 1 / n holds exactly one of the n values
+fn is_stratified(values: &[f32]) -> bool {
+    let n = values.len();
+    let mut hits = vec![0; n];
+    for value in values {
+        hits[((value * n as f32) as usize).min(n - 1)] += 1;
+    }
+    hits.iter().all(|hits| *hits == 1)
+}
+
+// Star discrepancy of 2D points, exact over the grid of point coordinates
+fn star_discrepancy(points: &[(f32, f32)]) -> f32 {
+    let n = points.len() as f32;
+    let mut xs: Vec<f32> = points.iter().map(|p| p.0).chain([1.0]).collect();
+    let mut ys: Vec<f32> = points.iter().map(|p| p.1).chain([1.0]).collect();
+    xs.sort_by(f32::total_cmp);
+    ys.sort_by(f32::total_cmp);
+    let mut discrepancy = 0.0f32;
+    for x in &xs {
+        for y in &ys {
+            let open = points.iter().filter(|p| p.0 < *x && p.1 < *y).count() as f32;
+            let closed = points.iter().filter(|p| p.0 <= *x && p.1 <= *y).count() as f32;
+            let area = x * y;
+            discrepancy = discrepancy.max(area - open / n).max(closed / n - area);
+        }
+    }
+    discrepancy
+}
+
+fn white_noise(count: usize, seed: u32) -> Vec<f32> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            state = state.wrapping_mul(747796405).wrapping_add(2891336453);
+            to_unit_float(renderer::sampler::hash(state))
+        })
+        .collect()
+}
+
+#[test]
+fn every_dimension_is_stratified() {
+    let matrices = sobol_matrices();
+    assert_eq!(matrices.len(), SOBOL_DIMENSIONS);
+    for matrix in &matrices {
+        for n in [2, 16, 256, 1024] {
+            let values: Vec<f32> = (0..n).map(|i| to_unit_float(sobol(matrix, i))).collect();
+            assert!(is_stratified(&values));
+        }
+    }
+}
+
+#[test]
+fn first_two_dimensions_are_a_02_sequence() {
+    let matrices = sobol_matrices();
+    let k = 8;
+    let points: Vec<(u32, u32)> = (0..1u32 << k)
+        .map(|i| (sobol(&matrices[0], i), sobol(&matrices[1], i)))
+        .collect();
+    // Every elementary interval of area 1 / 2^k holds exactly one point
+    for x_bits in 0..=k {
+        let y_bits = k - x_bits;
+        let mut hits = vec![0; 1 << k];
+        for (x, y) in &points {
+            let cell_x = x.checked_shr(32 - x_bits).unwrap_or(0);
+            let cell_y = y.checked_shr(32 - y_bits).unwrap_or(0);
+            hits[((cell_y << x_bits) | cell_x) as usize] += 1;
+        }
+        assert!(hits.iter().all(|hits| *hits == 1));
+    }
+}
+
+#[test]
+fn owen_scrambling_keeps_the_stratification() {
+    let matrices = sobol_matrices();
+    for seed in [0, 1, 0xdeadbeef] {
+        for matrix in &matrices {
+            let values: Vec<f32> = (0..256)
+                .map(|i| to_unit_float(owen_scramble(sobol(matrix, i), seed)))
+                .collect();
+            assert!(is_stratified(&values));
+        }
+    }
+    // But does change the values
+    let scrambled: Vec<u32> = (0..16)
+        .map(|i| owen_scramble(sobol(&matrices[0], i), 7))
+        .collect();
+    let plain: Vec<u32> = (0..16).map(|i| sobol(&matrices[0], i)).collect();
+    assert_ne!(scrambled, plain);
+}
+
+#[test]
+fn sobol_has_lower_discrepancy_than_random() {
+    let tables = SamplerTables::new();
+    let n = 256;
+    let random = white_noise(2 * n, 3);
+    let random: Vec<(f32, f32)> = random.chunks(2).map(|p| (p[0], p[1])).collect();
+    let random_discrepancy = star_discrepancy(&random);
+
+    for kind in [SamplerKind::Sobol, SamplerKind::BlueNoiseSobol] {
+        // Dimension pairs of the pixel, a bounce and past the matrix count
+        for dimension in [0, 2, 20] {
+            let points: Vec<(f32, f32)> = (0..n as u32)
+                .map(|i| {
+                    let sample = |d| tables.sample(kind, 5, i, (3, 9), d).unwrap();
+                    (sample(dimension), sample(dimension + 1))
+                })
+                .collect();
+            let discrepancy = star_discrepancy(&points);
+            assert!(
+                discrepancy < random_discrepancy * 0.5,
+                "{kind:?} dimension {dimension}: {discrepancy} vs {random_discrepancy}"
+            );
+        }
+    }
+    assert!(tables
+        .sample(SamplerKind::Independent, 5, 0, (3, 9), 0)
+        .is_none());
+}
+
+#[test]
+fn pixels_and_seeds_decorrelate_sobol() {
+    let tables = SamplerTables::new();
+    let sample = |seed, pixel| tables.sample(SamplerKind::Sobol, seed, 1, pixel, 4);
+    assert_ne!(sample(0, (0, 0)), sample(0, (1, 0)));
+    assert_ne!(sample(0, (0, 0)), sample(1, (0, 0)));
+    assert_eq!(sample(2, (5, 5)), sample(2, (5, 5)));
+}
+
+#[test]
+fn blue_noise_mask_is_a_uniform_permutation() {
+    let size = 16;
+    let mask = blue_noise_mask(size, 0);
+    let count = (size * size) as usize;
+    assert_eq!(mask.len(), count);
+    let mut ranks: Vec<usize> = mask.iter().map(|v| (v * count as f32) as usize).collect();
+    ranks.sort();
+    assert_eq!(ranks, (0..count).collect::<Vec<_>>());
+}
+
+#[test]
+fn blue_noise_mask_lacks_low_frequencies() {
+    // Variance of the 4x4 block averages, low frequency energy that white noise has
+    // plenty of and blue noise hardly any
+    let block_variance = |values: &[f32], size: usize| {
+        let blocks = size / 4;
+        let means: Vec<f32> = (0..blocks * blocks)
+            .map(|b| {
+                let (bx, by) = (b % blocks * 4, b / blocks * 4);
+                (0..16)
+                    .map(|i| values[(by + i / 4) * size + bx + i % 4])
+                    .sum::<f32>()
+                    / 16.0
+            })
+            .collect();
+        let mean = means.iter().sum::<f32>() / means.len() as f32;
+        means.iter().map(|m| (m - mean).powi(2)).sum::<f32>() / means.len() as f32
+    };
+    let size = BLUE_NOISE_SIZE as usize;
+    let tables = SamplerTables::new();
+    let blue = block_variance(&tables.blue_noise, size);
+    let white = block_variance(&white_noise(size * size, 11), size);
+    assert!(blue < white * 0.25, "{blue} vs {white}");
+}
+
+#[test]
+fn blue_noise_table_matches_the_generator() {
+    // build.rs stores the mask as ranks, which must decode to the same values
+    let tables = SamplerTables::new();
+    assert_eq!(tables.blue_noise, blue_noise_mask(BLUE_NOISE_SIZE, 0));
+}
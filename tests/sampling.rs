@@ -0,0 +1,67 @@
+use renderer::sampling::AliasTable;
+
+// Fraction of a stratified sweep of u that lands on every entry
+fn sampled_fractions(table: &AliasTable, count: usize) -> Vec<f32> {
+    let mut hits = vec![0; table.len()];
+    for i in 0..count {
+        hits[table.sample((i as f32 + 0.5) / count as f32)] += 1;
+    }
+    hits.into_iter()
+        .map(|hits| hits as f32 / count as f32)
+        .collect()
+}
+
+#[test]
+fn pdfs_are_the_normalized_weights() {
+    let weights = [1.0, 3.0, 0.0, 6.0, 2.0];
+    let table = AliasTable::new(&weights);
+    assert_eq!(table.len(), weights.len());
+    assert_eq!(table.total_weight(), 12.0);
+    let sum: f32 = table.entries().iter().map(|entry| entry.pdf).sum();
+    assert!((sum - 1.0).abs() < 1e-6);
+    for (entry, weight) in table.entries().iter().zip(weights) {
+        assert!((entry.pdf - weight / 12.0).abs() < 1e-6);
+        assert!((0.0..=1.0).contains(&entry.threshold));
+        assert!((entry.alias as usize) < weights.len());
+    }
+}
+
+#[test]
+fn samples_follow_the_weights() {
+    let weights = [1.0, 3.0, 0.0, 6.0, 2.0, 0.5, 0.0, 7.5];
+    let table = AliasTable::new(&weights);
+    let fractions = sampled_fractions(&table, 100_000);
+    for ((fraction, entry), weight) in fractions.iter().zip(table.entries()).zip(weights) {
+        assert!(
+            (fraction - entry.pdf).abs() < 1e-3,
+            "{} {}",
+            fraction,
+            entry.pdf
+        );
+        // Zero weights are never picked, not even through rounding
+        if weight == 0.0 {
+            assert_eq!(*fraction, 0.0);
+        }
+    }
+    // The end of the range stays inside the table
+    assert!(table.sample(0.999_999_9) < weights.len());
+}
+
+#[test]
+fn negative_and_all_zero_weights() {
+    let table = AliasTable::new(&[-1.0, 1.0]);
+    assert_eq!(table.entries()[0].pdf, 0.0);
+    assert_eq!(sampled_fractions(&table, 1000), vec![0.0, 1.0]);
+
+    // Nothing to prefer, so every entry is equally likely
+    let table = AliasTable::new(&[0.0; 4]);
+    assert_eq!(table.total_weight(), 0.0);
+    for entry in table.entries() {
+        assert_eq!(entry.pdf, 0.25);
+    }
+    for fraction in sampled_fractions(&table, 1000) {
+        assert!((fraction - 0.25).abs() < 1e-3);
+    }
+
+    assert!(AliasTable::new(&[]).is_empty());
+}
@@ -0,0 +1,205 @@
+mod common;
+
+use std::path::PathBuf;
+
+use cgmath::{vec2, vec3, vec4, SquareMatrix};
+use common::scratch_directory;
+use renderer::camera::Camera;
+use renderer::ctx::Handle;
+use renderer::image_resource::TextureImageData;
+use renderer::light::LightType;
+use renderer::material::{Material, NormalMapConvention, TextureTransform};
+use renderer::math::Mat4;
+use renderer::mesh_resource::MeshResource;
+use renderer::scene_file::{
+    InstanceDescription, LightDescription, MaterialDescription, SceneDescription,
+    SkyBoxDescription, SkyBoxLayout, SCENE_FORMAT_VERSION,
+};
+use renderer::vk::Format;
+use slotmap::SlotMap;
+
+fn textured_material(texture: Handle) -> Material {
+    let mut material = Material::new();
+    material.base_color = vec4(0.8, 0.2, 0.1, 1.0);
+    material.roughness = 0.3;
+    material.normal_map_convention = NormalMapConvention::DirectX;
+    material.normal_texture = Some(texture);
+    material.normal_texture_transform = TextureTransform {
+        tex_coord: 1,
+        offset: vec2(0.5, 0.25),
+        scale: vec2(2.0, 2.0),
+        rotation: 0.5,
+    };
+    material
+}
+
+fn description() -> SceneDescription {
+    let mut camera = Camera::new(45.0, 0.1, 100.0);
+    camera.translate(vec3(0.0, 1.0, 5.0));
+    let mut description = SceneDescription::new(&camera);
+    description.meshes.push(PathBuf::from("cube.mesh"));
+    description
+        .textures
+        .push(PathBuf::from("textures/normal.png"));
+    let mut keys = SlotMap::<Handle, ()>::new();
+    let texture = keys.insert(());
+    description
+        .materials
+        .push(MaterialDescription::new(&textured_material(texture), |_| 0));
+    description.instances.push(InstanceDescription {
+        mesh: 0,
+        material: 0,
+        transform: Mat4::from_translation(vec3(1.0, 2.0, 3.0)).into(),
+    });
+    description.lights.push(LightDescription {
+        light_type: LightType::Spot {
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+        },
+        color: [1.0, 0.9, 0.8],
+        intensity: 50.0,
+        transform: Mat4::identity().into(),
+    });
+    description.skybox = Some(SkyBoxDescription {
+        file: PathBuf::from("sky.exr"),
+        layout: SkyBoxLayout::Cross,
+        rotation: [0.0, 90.0, 0.0],
+        intensity: 2.0,
+        background_visible: false,
+    });
+    description
+}
+
+#[test]
+fn descriptions_survive_json() {
+    let description = description();
+    let loaded = SceneDescription::from_json(&description.to_json()).unwrap();
+    assert_eq!(loaded, description);
+    assert_eq!(
+        loaded.camera().world_transform(),
+        &Mat4::from_translation(vec3(0.0, 1.0, 5.0))
+    );
+    // Stored like a glTF camera node, where the camera sits in the world
+    assert_eq!(
+        Mat4::from(description.camera.transform),
+        loaded.camera().view_matrix()
+    );
+}
+
+#[test]
+fn materials_map_textures_to_handles() {
+    let mut keys = SlotMap::<Handle, ()>::new();
+    let (saved, loaded) = (keys.insert(()), keys.insert(()));
+    let description = MaterialDescription::new(&textured_material(saved), |texture| {
+        assert_eq!(texture, saved);
+        0
+    });
+    let material = description.to_material(&[loaded]);
+    assert_eq!(material.normal_texture, Some(loaded));
+    assert_eq!(material.base_color_texture, None);
+    assert_eq!(
+        material.normal_texture_transform,
+        textured_material(saved).normal_texture_transform
+    );
+    assert_eq!(material.normal_map_convention, NormalMapConvention::DirectX);
+    assert_eq!(material.roughness, 0.3);
+}
+
+#[test]
+fn missing_fields_take_defaults() {
+    let json = r#"{
+        "version": 1,
+        "camera": { "fov": 60.0, "z_near": 0.01, "z_far": 1000.0,
+                    "transform": [[1,0,0,0],[0,1,0,0],[0,0,1,0],[0,0,0,1]] },
+        "skybox": { "file": "sky.hdr" },
+        "meshes": ["a.mesh"],
+        "materials": [{ "roughness": 0.5 }],
+        "instances": [{ "mesh": 0, "material": 0,
+                        "transform": [[1,0,0,0],[0,1,0,0],[0,0,1,0],[0,0,0,1]] }]
+    }"#;
+    let description = SceneDescription::from_json(json).unwrap();
+    let material = &description.materials[0];
+    assert_eq!(material.roughness, 0.5);
+    let default_color: [f32; 4] = Material::new().base_color.into();
+    assert_eq!(material.base_color, default_color);
+    assert!(description.lights.is_empty());
+    let skybox = description.skybox.unwrap();
+    assert_eq!(skybox.layout, SkyBoxLayout::Equirectangular);
+    assert_eq!(skybox.intensity, 1.0);
+    assert!(skybox.background_visible);
+}
+
+#[test]
+fn textures_round_trip_through_image_files() {
+    let directory = scratch_directory("textures");
+    let pixels = [
+        255, 0, 0, 255, 0, 128, 0, 255, 0, 0, 64, 128, 10, 20, 30, 40,
+    ];
+    let eight_bit = TextureImageData::new(Format::R8G8B8A8_UNORM, 2, 2, &pixels);
+    eight_bit.save(directory.join("eight_bit.png")).unwrap();
+    let loaded = TextureImageData::load(directory.join("eight_bit.png")).unwrap();
+    assert_eq!(loaded.format, Format::R8G8B8A8_UNORM);
+    assert_eq!(loaded.pixels, pixels);
+
+    let floats: Vec<u8> = [0.5f32, 2.0, 100.0, 1.0]
+        .repeat(4)
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let float = TextureImageData::new(Format::R32G32B32A32_SFLOAT, 2, 2, &floats);
+    float.save(directory.join("float.exr")).unwrap();
+    let loaded = TextureImageData::load(directory.join("float.exr")).unwrap();
+    assert_eq!(loaded.format, Format::R32G32B32A32_SFLOAT);
+    assert_eq!(loaded.pixels, floats);
+}
+
+#[test]
+fn newer_versions_and_bad_indices_are_rejected() {
+    let mut description = description();
+    description.version = SCENE_FORMAT_VERSION + 1;
+    assert!(SceneDescription::from_json(&description.to_json()).is_err());
+
+    let mut description = self::description();
+    description.instances[0].mesh = 1;
+    assert!(SceneDescription::from_json(&description.to_json()).is_err());
+
+    let mut description = self::description();
+    description.materials[0]
+        .normal_texture
+        .as_mut()
+        .unwrap()
+        .texture = 3;
+    assert!(SceneDescription::from_json(&description.to_json()).is_err());
+}
+
+#[test]
+fn mesh_files_round_trip() {
+    let mesh = MeshResource::new(
+        vec![0, 1, 2],
+        vec![
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ],
+        vec![vec3(0.0, 0.0, 1.0); 3],
+        vec![vec3(1.0, 0.0, 0.0); 3],
+        vec![
+            vec![vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(0.0, 1.0)],
+            vec![vec2(0.5, 0.5); 3],
+        ],
+    )
+    .with_colors(vec![vec4(1.0, 0.0, 0.0, 1.0); 3]);
+
+    let mut bytes = Vec::new();
+    mesh.write_to(&mut bytes).unwrap();
+    let loaded = MeshResource::read_from(&mut bytes.as_slice()).unwrap();
+    assert_eq!(loaded.indices, mesh.indices);
+    assert_eq!(loaded.vertices, mesh.vertices);
+    assert_eq!(loaded.normals, mesh.normals);
+    assert_eq!(loaded.tangents, mesh.tangents);
+    assert_eq!(loaded.tex_coords, mesh.tex_coords);
+    assert_eq!(loaded.colors, mesh.colors);
+
+    bytes[0] = b'X';
+    assert!(MeshResource::read_from(&mut bytes.as_slice()).is_err());
+}
@@ -0,0 +1,94 @@
+use cgmath::InnerSpace;
+use renderer::emissive::luminance;
+use renderer::environment::spherical_to_direction;
+use renderer::math::{Vec2, Vec3};
+use renderer::sky::PreethamSky;
+
+fn sun_direction(sky: &PreethamSky) -> Vec3 {
+    spherical_to_direction(Vec2::new(
+        (sky.sun_azimuth + 180.0) / 360.0,
+        (90.0 - sky.sun_elevation) / 180.0,
+    ))
+}
+
+// Directions over the whole sphere, including below the horizon
+fn directions() -> impl Iterator<Item = Vec3> {
+    (0..64).flat_map(|y| {
+        (0..128).map(move |x| {
+            spherical_to_direction(Vec2::new((x as f32 + 0.5) / 128.0, (y as f32 + 0.5) / 64.0))
+        })
+    })
+}
+
+#[test]
+fn the_sky_is_brightest_around_the_sun() {
+    for (elevation, azimuth) in [(30.0, 45.0), (60.0, -120.0), (10.0, 170.0)] {
+        let sky = PreethamSky::new(elevation, azimuth, 3.0);
+        let sun = sun_direction(&sky);
+        let brightest = directions()
+            .max_by(|a, b| {
+                let (a, b) = (luminance(sky.radiance(*a)), luminance(sky.radiance(*b)));
+                a.partial_cmp(&b).unwrap()
+            })
+            .unwrap();
+        assert!(
+            brightest.dot(sun) > 10f32.to_radians().cos(),
+            "{:?}",
+            brightest
+        );
+
+        let converted = PreethamSky::from_sun_direction(sun, 3.0);
+        assert!((converted.sun_elevation - elevation).abs() < 1e-3);
+        assert!((converted.sun_azimuth - azimuth).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn turbidity_whitens_the_zenith() {
+    let zenith = spherical_to_direction(Vec2::new(0.5, 0.0));
+    let mut previous_blueness = f32::INFINITY;
+    for turbidity in [2.0, 4.0, 6.0, 10.0] {
+        let radiance = PreethamSky::new(30.0, 0.0, turbidity).radiance(zenith);
+        // Absolute radiance, thousands of cd/m² at the zenith, more in haze
+        let candela = luminance(radiance) * 683.0;
+        assert!(candela > 2000.0 && candela < 20000.0, "{:?}", radiance);
+        // Clear skies have a deep blue zenith, haze scatters all wavelengths alike
+        let blueness = radiance.z / radiance.x;
+        assert!(blueness > 1.0 && blueness < previous_blueness);
+        previous_blueness = blueness;
+    }
+}
+
+#[test]
+fn the_zenith_brightens_as_the_sun_rises() {
+    let zenith = spherical_to_direction(Vec2::new(0.5, 0.0));
+    let mut previous = 0.0;
+    for elevation in [0.0, 10.0, 30.0, 60.0, 85.0] {
+        let zenith_luminance = luminance(PreethamSky::new(elevation, 0.0, 3.0).radiance(zenith));
+        assert!(zenith_luminance > previous, "{}", elevation);
+        previous = zenith_luminance;
+    }
+}
+
+#[test]
+fn radiance_is_finite_and_non_negative_everywhere() {
+    for elevation in [-20.0, -3.0, 0.0, 5.0, 45.0, 90.0] {
+        for turbidity in [1.0, 2.0, 10.0] {
+            let sky = PreethamSky::new(elevation, 30.0, turbidity);
+            for direction in directions() {
+                let radiance = sky.radiance(direction);
+                for c in [radiance.x, radiance.y, radiance.z] {
+                    assert!(c.is_finite() && c >= 0.0, "{} {:?}", elevation, direction);
+                }
+            }
+        }
+    }
+
+    // The ground reflects the horizon, and night falls once the sun is well below it
+    let sky = PreethamSky::new(30.0, 0.0, 3.0);
+    let above = sky.radiance(spherical_to_direction(Vec2::new(0.25, 0.49)));
+    let below = sky.radiance(spherical_to_direction(Vec2::new(0.25, 0.51)));
+    assert!((below - above * sky.ground_albedo).magnitude() < 0.05 * above.magnitude());
+    let night = PreethamSky::new(-10.0, 0.0, 3.0);
+    assert!(directions().all(|direction| night.radiance(direction) == Vec3::new(0.0, 0.0, 0.0)));
+}
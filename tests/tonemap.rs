@@ -0,0 +1,81 @@
+use renderer::math::Vec3;
+use renderer::tonemap::{srgb_eotf, srgb_oetf, ToneMapOperator, ToneMapping};
+
+const OPERATORS: [ToneMapOperator; 5] = [
+    ToneMapOperator::Linear,
+    ToneMapOperator::Reinhard,
+    ToneMapOperator::AcesFilmic,
+    ToneMapOperator::AgX,
+    ToneMapOperator::Hable,
+];
+
+fn grey(v: f32) -> Vec3 {
+    Vec3::new(v, v, v)
+}
+
+#[test]
+fn srgb_round_trips_and_matches_reference_values() {
+    assert_eq!(srgb_oetf(0.0), 0.0);
+    assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-6);
+    assert!((srgb_oetf(0.18) - 0.4613).abs() < 1e-3);
+    // Linear segment
+    assert!((srgb_oetf(0.002) - 0.02584).abs() < 1e-5);
+    for i in 0..=100 {
+        let x = i as f32 / 100.0;
+        assert!((srgb_eotf(srgb_oetf(x)) - x).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn operators_are_monotonic_and_bounded() {
+    for operator in OPERATORS {
+        let tone_mapping = ToneMapping::new(operator);
+        let mut previous = -1.0;
+        for i in 0..200 {
+            let x = (i as f32 * 0.1 - 10.0).exp2();
+            let y = tone_mapping.apply(grey(x)).x;
+            assert!((0.0..=1.0).contains(&y), "{:?} {} {}", operator, x, y);
+            assert!(y >= previous - 1e-4, "{:?} {} {}", operator, x, y);
+            previous = y;
+        }
+    }
+}
+
+#[test]
+fn black_stays_black() {
+    for operator in OPERATORS {
+        let y = ToneMapping::new(operator).apply(grey(0.0));
+        assert!(y.x < 0.01, "{:?} {:?}", operator, y);
+    }
+}
+
+#[test]
+fn default_is_reinhard() {
+    let tone_mapping = ToneMapping::default();
+    assert_eq!(tone_mapping.operator, ToneMapOperator::Reinhard);
+    let y = tone_mapping.tone_map(Vec3::new(1.0, 3.0, 0.0));
+    assert!((y.x - 0.5).abs() < 1e-6 && (y.y - 0.75).abs() < 1e-6 && y.z == 0.0);
+}
+
+#[test]
+fn exposure_is_in_stops() {
+    let linear = ToneMapping::new(ToneMapOperator::Linear);
+    let brighter = linear.with_exposure(1.0);
+    let darker = linear.with_exposure(-2.0);
+    assert!((brighter.tone_map(grey(0.2)).x - 0.4).abs() < 1e-6);
+    assert!((darker.tone_map(grey(0.2)).x - 0.05).abs() < 1e-6);
+    // Linear clips instead of compressing
+    assert_eq!(brighter.tone_map(grey(0.8)).x, 1.0);
+}
+
+#[test]
+fn filmic_curves_saturate_towards_white() {
+    for operator in [
+        ToneMapOperator::AcesFilmic,
+        ToneMapOperator::AgX,
+        ToneMapOperator::Hable,
+    ] {
+        let y = ToneMapping::new(operator).tone_map(grey(1000.0));
+        assert!(y.x > 0.95, "{:?} {:?}", operator, y);
+    }
+}